@@ -0,0 +1,479 @@
+use std::collections::{HashMap, HashSet};
+
+use oxc::{
+    ast::{
+        ast::{BinaryOperator, Expression, LogicalOperator, MemberExpression},
+        AstKind,
+    },
+    semantic::SymbolId,
+};
+
+use crate::{
+    dataflow::{
+        Analysis, Assigned, Dataflow, Definition, Direction, Event, Range, RangeAnalysis, Results,
+    },
+    overflow_analysis::NodeAddress,
+    type_inference::Types,
+};
+
+/// Finds the typed array reads and writes `a[i]` whose index is an integer in `0..a.length`
+/// on every path, so they can skip the bounds check.
+pub fn in_bounds(dataflow: &Dataflow, types: &Types) -> HashSet<NodeAddress> {
+    let semantic = dataflow.semantic();
+    let ranges = dataflow.solve(RangeAnalysis::with_types(types));
+    let lengths = LengthBounds::new(dataflow, types, &ranges);
+    let bounds = dataflow.solve(LengthBounds::new(dataflow, types, &ranges));
+
+    let mut in_bounds = HashSet::new();
+    for node in semantic.nodes().iter() {
+        let AstKind::MemberExpression(MemberExpression::ComputedMemberExpression(access)) =
+            node.kind()
+        else {
+            continue;
+        };
+        if !types.expression(semantic, &access.object).is_typed_array() {
+            continue;
+        }
+        let Some(array) = dataflow
+            .reference_symbol(&access.object)
+            .filter(|array| dataflow.is_tracked(*array))
+        else {
+            continue;
+        };
+
+        let range = ranges.range(&access.expression, node.id());
+        if range.nan || !range.integer || range.min < 0.0 {
+            continue;
+        }
+        let Some((index, offset)) = lengths.linear(dataflow, &access.expression) else {
+            continue;
+        };
+        let mut constraints = bounds.before(node.id());
+        if let Term::Variable(symbol) = index {
+            // what the ranges know about the index at this point, with no path to a length
+            let range = ranges.symbol(symbol, node.id());
+            constraints.bound(Term::Zero, index, &range);
+        }
+        if constraints
+            .closure()
+            .weight(index, Term::Length(array))
+            .is_some_and(|weight| weight > offset)
+        {
+            in_bounds.insert(NodeAddress::of(&**access));
+        }
+    }
+    in_bounds
+}
+
+/// What a difference constraint relates: an integer variable, the length of a typed array
+/// variable, or the constant 0 that relates them to numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Term {
+    Zero,
+    Variable(SymbolId),
+    Length(SymbolId),
+}
+
+/// Difference constraints `x + w <= y` between [`Term`]s, keyed by `(x, y)`. Every term
+/// holds an integer, so `x < y` is `x + 1 <= y`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Constraints(HashMap<(Term, Term), i64>);
+
+impl Constraints {
+    /// The largest `w` with `x + w <= y`, if any.
+    fn weight(&self, x: Term, y: Term) -> Option<i64> {
+        if x == y {
+            return Some(0);
+        }
+        self.0.get(&(x, y)).copied()
+    }
+
+    /// Adds `x + w <= y`.
+    fn add(&mut self, x: Term, y: Term, w: i64) {
+        if x == y {
+            return;
+        }
+        let weight = self.0.entry((x, y)).or_insert(w);
+        *weight = (*weight).max(w);
+    }
+
+    /// Adds `x = y + k`.
+    fn equal(&mut self, x: Term, y: Term, k: i64) {
+        self.add(x, y, -k);
+        self.add(y, x, k);
+    }
+
+    /// Adds what `range` knows about `x`, relative to `zero`.
+    fn bound(&mut self, zero: Term, x: Term, range: &Range) {
+        if range.nan || !range.integer {
+            return;
+        }
+        if range.min.is_finite() {
+            self.add(zero, x, range.min as i64);
+        }
+        if range.max.is_finite() {
+            self.add(x, zero, -range.max as i64);
+        }
+    }
+
+    /// Every constraint that follows from these, by the longest path between each two terms.
+    fn closure(&self) -> Constraints {
+        let mut terms: Vec<Term> = vec![Term::Zero];
+        for (x, y) in self.0.keys() {
+            for term in [x, y] {
+                if !terms.contains(term) {
+                    terms.push(*term);
+                }
+            }
+        }
+        let n = terms.len();
+        let mut weights = vec![None; n * n];
+        for (i, x) in terms.iter().enumerate() {
+            for (j, y) in terms.iter().enumerate() {
+                weights[i * n + j] = self.weight(*x, *y);
+            }
+        }
+        for k in 0..n {
+            for i in 0..n {
+                let Some(first) = weights[i * n + k] else {
+                    continue;
+                };
+                for j in 0..n {
+                    let Some(second) = weights[k * n + j] else {
+                        continue;
+                    };
+                    let through = first.saturating_add(second);
+                    if weights[i * n + j].is_none_or(|weight| weight < through) {
+                        weights[i * n + j] = Some(through);
+                    }
+                }
+            }
+        }
+
+        let mut closure = Constraints::default();
+        for (i, x) in terms.iter().enumerate() {
+            for (j, y) in terms.iter().enumerate() {
+                if let (false, Some(weight)) = (i == j, weights[i * n + j]) {
+                    closure.0.insert((*x, *y), weight);
+                }
+            }
+        }
+        closure
+    }
+
+    /// Gives up on `term`, keeping what the constraints through it imply about the others.
+    fn forget(&mut self, term: Term) {
+        let below: Vec<(Term, i64)> = self
+            .0
+            .iter()
+            .filter(|((_, y), _)| *y == term)
+            .map(|((x, _), w)| (*x, *w))
+            .collect();
+        let above: Vec<(Term, i64)> = self
+            .0
+            .iter()
+            .filter(|((x, _), _)| *x == term)
+            .map(|((_, y), w)| (*y, *w))
+            .collect();
+        self.0.retain(|(x, y), _| *x != term && *y != term);
+        for (x, first) in &below {
+            for (y, second) in &above {
+                self.add(*x, *y, first.saturating_add(*second));
+            }
+        }
+    }
+
+    /// `term` becomes `term + k`.
+    fn shift(&mut self, term: Term, k: i64) {
+        for ((x, y), w) in self.0.iter_mut() {
+            if *x == term {
+                *w -= k;
+            } else if *y == term {
+                *w += k;
+            }
+        }
+    }
+}
+
+/// Bounds integer variables by each other, by constants and by the lengths of typed arrays, to
+/// tell `a[i]` in `for (let i = 0; i < a.length; i++)` or in a loop up to the `n` of
+/// `new Int32Array(n)` from an access that may leave the array.
+///
+/// Comparisons refine both branches, so a counter `j` that is reset with
+/// `if (j == n) j = 0;` after `j++`, like the running values of
+/// [`crate::running_modulo_optimization`], stays below `n`.
+pub struct LengthBounds<'r, 's, 'a, 't> {
+    types: &'t Types,
+    /// What the ranges know about a value, for constraints against 0.
+    ranges: &'r Results<'r, 's, 'a, RangeAnalysis<'t>>,
+    /// Numeric variables only ever assigned whole numbers, `int32_t` or not.
+    integers: HashSet<SymbolId>,
+}
+
+impl<'r, 's, 'a, 't> LengthBounds<'r, 's, 'a, 't> {
+    pub fn new(
+        flow: &Dataflow<'s, 'a>,
+        types: &'t Types,
+        ranges: &'r Results<'r, 's, 'a, RangeAnalysis<'t>>,
+    ) -> Self {
+        // a running counter reset to 0 is a double when the ranges cannot bound its increment
+        let whole = |expr: &Expression, definition: &Definition| {
+            let range = ranges.range(expr, definition.node);
+            range.integer && !range.nan
+        };
+        let integers = flow
+            .semantic()
+            .symbols()
+            .symbol_ids()
+            .filter(|symbol| flow.is_tracked(*symbol))
+            .filter(|symbol| {
+                let t = types.symbol(*symbol);
+                t.is_integer()
+                    || t.is_numeric()
+                        && flow.definitions_of(*symbol).iter().all(|id| {
+                            let definition = flow.definition(*id);
+                            match definition.value {
+                                Assigned::Value(expr) => whole(expr, definition),
+                                Assigned::Compound(
+                                    BinaryOperator::Addition | BinaryOperator::Subtraction,
+                                    expr,
+                                ) => whole(expr, definition),
+                                Assigned::Step(_) => true,
+                                _ => false,
+                            }
+                        })
+            })
+            .collect();
+        Self {
+            types,
+            ranges,
+            integers,
+        }
+    }
+}
+
+impl LengthBounds<'_, '_, '_, '_> {
+    /// The integer variable `symbol` stands for, if it is one.
+    fn variable(&self, symbol: SymbolId) -> Option<Term> {
+        self.integers
+            .contains(&symbol)
+            .then_some(Term::Variable(symbol))
+    }
+
+    /// `expr` as `term + k`.
+    fn linear(&self, flow: &Dataflow, expr: &Expression) -> Option<(Term, i64)> {
+        match expr {
+            Expression::NumericLiteral(literal)
+                if literal.value.fract() == 0.0 && literal.value.abs() <= i32::MAX as f64 =>
+            {
+                Some((Term::Zero, literal.value as i64))
+            }
+            Expression::ParenthesizedExpression(expr) => self.linear(flow, &expr.expression),
+            Expression::Identifier(_) => Some((self.variable(flow.reference_symbol(expr)?)?, 0)),
+            Expression::StaticMemberExpression(member) if member.property.name == "length" => {
+                let array = flow.reference_symbol(&member.object)?;
+                let typed = self
+                    .types
+                    .expression(flow.semantic(), &member.object)
+                    .is_typed_array();
+                (typed && flow.is_tracked(array)).then_some((Term::Length(array), 0))
+            }
+            Expression::BinaryExpression(expr) => {
+                let sign = match expr.operator {
+                    BinaryOperator::Addition => 1,
+                    BinaryOperator::Subtraction => -1,
+                    _ => return None,
+                };
+                match (
+                    self.linear(flow, &expr.left)?,
+                    self.linear(flow, &expr.right)?,
+                ) {
+                    ((term, k), (Term::Zero, c)) => Some((term, k + sign * c)),
+                    ((Term::Zero, c), (term, k)) if sign == 1 => Some((term, k + c)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Adds what the outcome of the comparison `test` implies.
+    fn assume(&self, flow: &Dataflow, test: &Expression, outcome: bool, fact: &mut Constraints) {
+        match test {
+            Expression::ParenthesizedExpression(expr) => {
+                self.assume(flow, &expr.expression, outcome, fact)
+            }
+            Expression::LogicalExpression(expr)
+                if outcome && expr.operator == LogicalOperator::And =>
+            {
+                self.assume(flow, &expr.left, outcome, fact);
+                self.assume(flow, &expr.right, outcome, fact);
+            }
+            Expression::BinaryExpression(expr) => {
+                let (Some((x, k)), Some((y, l))) = (
+                    self.linear(flow, &expr.left),
+                    self.linear(flow, &expr.right),
+                ) else {
+                    return;
+                };
+                // x + k op y + l
+                match (expr.operator, outcome) {
+                    (BinaryOperator::LessThan, true)
+                    | (BinaryOperator::GreaterEqualThan, false) => fact.add(x, y, k - l + 1),
+                    (BinaryOperator::LessEqualThan, true)
+                    | (BinaryOperator::GreaterThan, false) => fact.add(x, y, k - l),
+                    (BinaryOperator::GreaterThan, true)
+                    | (BinaryOperator::LessEqualThan, false) => fact.add(y, x, l - k + 1),
+                    (BinaryOperator::GreaterEqualThan, true)
+                    | (BinaryOperator::LessThan, false) => fact.add(y, x, l - k),
+                    (BinaryOperator::Equality | BinaryOperator::StrictEquality, true)
+                    | (BinaryOperator::Inequality | BinaryOperator::StrictInequality, false) => {
+                        fact.equal(x, y, l - k)
+                    }
+                    (BinaryOperator::Equality | BinaryOperator::StrictEquality, false)
+                    | (BinaryOperator::Inequality | BinaryOperator::StrictInequality, true) => {
+                        // next to `x <= y`, `x != y` makes `x < y`
+                        let closure = fact.closure();
+                        if closure.weight(x, y).is_some_and(|w| w >= k - l) {
+                            fact.add(x, y, k - l + 1);
+                        }
+                        if closure.weight(y, x).is_some_and(|w| w >= l - k) {
+                            fact.add(y, x, l - k + 1);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'a> Analysis<'a> for LengthBounds<'_, '_, 'a, '_> {
+    type Fact = Constraints;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self, _flow: &Dataflow<'_, 'a>) -> Self::Fact {
+        Constraints::default()
+    }
+
+    fn bottom(&self, _flow: &Dataflow<'_, 'a>) -> Self::Fact {
+        Constraints::default()
+    }
+
+    /// Keeps the constraints that follow on both sides, at their weaker weight.
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        let (mine, theirs) = (fact.closure(), other.closure());
+        fact.0 = mine
+            .0
+            .into_iter()
+            .filter_map(|(pair, w)| Some((pair, w.min(*theirs.0.get(&pair)?))))
+            .collect();
+    }
+
+    fn transfer(&self, flow: &Dataflow<'_, 'a>, event: &Event, fact: &mut Self::Fact) {
+        let Event::Def(id) = event else {
+            return;
+        };
+        let definition = flow.definition(*id);
+        let symbol = definition.symbol;
+        let Some(x) = self.variable(symbol) else {
+            // a typed array variable given another array
+            if flow.is_tracked(symbol) {
+                fact.forget(Term::Length(symbol));
+                if let Assigned::Value(Expression::NewExpression(new)) = definition.value {
+                    let length = new
+                        .arguments
+                        .first()
+                        .and_then(|argument| self.linear(flow, argument.as_expression()?));
+                    if let (Some((term, k)), 1) = (length, new.arguments.len()) {
+                        if self.types.symbol(symbol).is_typed_array() {
+                            fact.equal(Term::Length(symbol), term, k);
+                        }
+                    }
+                }
+            }
+            return;
+        };
+
+        let step = match definition.value {
+            Assigned::Step(step) => Some(step as i64),
+            Assigned::Compound(operator, expr) => match (operator, self.linear(flow, expr)) {
+                (BinaryOperator::Addition, Some((Term::Zero, c))) => Some(c),
+                (BinaryOperator::Subtraction, Some((Term::Zero, c))) => Some(-c),
+                _ => None,
+            },
+            Assigned::Value(expr) => match self.linear(flow, expr) {
+                Some((term, k)) if term == x => Some(k),
+                _ => None,
+            },
+            Assigned::Unknown => None,
+        };
+        if let Some(step) = step {
+            fact.shift(x, step);
+            return;
+        }
+
+        let mut derived = Constraints::default();
+        if let Assigned::Value(expr) = definition.value {
+            match self.linear(flow, expr) {
+                Some((term, k)) => derived.equal(x, term, k),
+                None => {
+                    // `y % z` is below a positive `z`
+                    if let Expression::BinaryExpression(remainder) = expr.without_parentheses() {
+                        let divisor = self.linear(flow, &remainder.right);
+                        if let (BinaryOperator::Remainder, Some((term, 0))) =
+                            (remainder.operator, divisor)
+                        {
+                            if fact
+                                .closure()
+                                .weight(Term::Zero, term)
+                                .is_some_and(|w| w >= 1)
+                            {
+                                derived.add(x, term, 1);
+                            }
+                        }
+                    }
+                }
+            }
+            derived.bound(Term::Zero, x, &self.ranges.range(expr, definition.node));
+        }
+        fact.forget(x);
+        for ((y, z), w) in derived.0 {
+            fact.add(y, z, w);
+        }
+        // closed, so a loop head first reached from here knows everything that follows
+        *fact = fact.closure();
+    }
+
+    fn refine(
+        &self,
+        flow: &Dataflow<'_, 'a>,
+        test: &Expression<'a>,
+        outcome: bool,
+        fact: &mut Self::Fact,
+    ) {
+        self.assume(flow, test, outcome, fact);
+    }
+
+    /// Keeps the constraints that followed at the previous visit. One that got weaker falls
+    /// back to `x < y`, then to `x <= y`, the bounds an access needs, before it is dropped.
+    ///
+    /// The previous fact is taken as it is: closing it again would bring back what it dropped,
+    /// derived through constraints that also weaken, and two such constraints can keep weakening
+    /// each other forever.
+    fn widen(&self, _flow: &Dataflow<'_, 'a>, old: &Self::Fact, fact: &mut Self::Fact) {
+        *fact = fact.closure();
+        fact.0.retain(|pair, w| match old.0.get(pair) {
+            Some(old) if *w >= *old => {
+                *w = *old;
+                true
+            }
+            Some(_) if *w >= 0 => {
+                *w = (*w).min(1);
+                true
+            }
+            _ => false,
+        });
+    }
+}
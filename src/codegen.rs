@@ -1,26 +1,385 @@
-use oxc::ast::ast::{AssignmentOperator, Program};
+use oxc::ast::ast::{
+    AssignmentOperator, BinaryExpression, BindingIdentifier, CallExpression,
+    ComputedMemberExpression, Declaration, ExportDefaultDeclarationKind, Function,
+    IdentifierReference, ObjectExpression, ObjectPropertyKind, Program, TSInterfaceDeclaration,
+    UnaryOperator, VariableDeclaration,
+};
+use oxc::ast::{
+    ast::{ArrowFunctionExpression, ReturnStatement},
+    Visit,
+};
+use oxc::semantic::ScopeFlags;
 use oxc::{
     ast::ast::{
-        AssignmentTarget, BinaryOperator, BindingPatternKind, Expression, ForStatementInit,
-        SimpleAssignmentTarget, Statement, UpdateOperator,
+        AssignmentTarget, BinaryOperator, BindingPatternKind, Expression, ForStatement,
+        ForStatementInit, SimpleAssignmentTarget, Statement, UpdateOperator,
     },
-    semantic::Semantic,
+    ast::AstKind,
+    semantic::{Semantic, SymbolId},
+    span::Span,
 };
 
-pub struct Codegen<'a, T>
+use std::collections::{HashMap, HashSet};
+
+use crate::constant_division_analysis::{constant_divisor, magic};
+use crate::counted_loop_analysis::CountedLoop;
+use crate::escape_analysis::StackArray;
+use crate::module_graph::{exports_property, is_module_exports, require_specifier, Linkage};
+use crate::overflow_analysis::{is_math_floor, NodeAddress};
+use crate::profile::{self, Counter, ModuleProfile};
+use crate::type_inference::{is_int32_literal, Type, Types};
+
+pub struct Codegen<'w, 'a, T>
 where
     T: std::io::Write,
 {
-    writer: &'a mut T,
-    semantic: &'a Semantic<'a>,
+    writer: &'w mut T,
+    semantic: &'w Semantic<'a>,
+    linkage: Option<&'w Linkage>,
+    types: Option<&'w Types>,
+    checked_hints: bool,
+    /// Typed array accesses known to stay in bounds; every other one is checked.
+    in_bounds: Option<&'w HashSet<NodeAddress>>,
+    /// Divisions by a literal whose dividend is known to be a whole number in `0..2^31`.
+    unsigned_divisions: Option<&'w HashSet<NodeAddress>>,
+    /// The `for` loops that compile to a counted C++ `for`.
+    counted_loops: Option<&'w HashMap<NodeAddress, CountedLoop>>,
+    /// How many copies of its body an iteration of a counted loop runs.
+    unroll: usize,
+    /// The `__restrict` pointers standing for typed arrays in the loop being printed.
+    restricted: HashMap<SymbolId, &'w str>,
+    /// Typed arrays allocated on the stack.
+    stack_arrays: Option<&'w HashMap<SymbolId, StackArray>>,
+    /// Under `--profile-generate`, the name of the module in the sites of its counters.
+    instrumented: Option<String>,
+    /// Under `--profile-use`, the counts of the module.
+    profile: Option<&'w ModuleProfile>,
+    /// Top-level symbols [`Self::print_declarations`] declared ahead of every module.
+    declared: Option<&'w HashSet<SymbolId>>,
 }
 
-impl<'a, T> Codegen<'a, T>
+/// Under `--profile-use`, an `if` whose test went the same way at least this often, out of at
+/// least [`BRANCH_HINT_RUNS`] runs, tells the C++ compiler so.
+const BRANCH_HINT_RATIO: f64 = 0.9;
+const BRANCH_HINT_RUNS: u64 = 16;
+
+/// Under `--profile-use`, counted loops are unrolled this many times, unless `--unroll` says
+/// otherwise...
+const PROFILE_UNROLL: usize = 4;
+/// ...when they ran at least this many times the unroll factor iterations per entry.
+const PROFILE_UNROLL_TRIPS: f64 = 16.0;
+
+impl<'w, 'a, T> Codegen<'w, 'a, T>
 where
     T: std::io::Write,
 {
-    pub fn new(writer: &'a mut T, semantic: &'a Semantic<'a>) -> Self {
-        Self { writer, semantic }
+    pub fn new(writer: &'w mut T, semantic: &'w Semantic<'a>) -> Self {
+        Self {
+            writer,
+            semantic,
+            linkage: None,
+            types: None,
+            checked_hints: false,
+            in_bounds: None,
+            unsigned_divisions: None,
+            counted_loops: None,
+            unroll: 1,
+            restricted: HashMap::new(),
+            stack_arrays: None,
+            instrumented: None,
+            profile: None,
+            declared: None,
+        }
+    }
+
+    pub fn with_linkage(mut self, linkage: &'w Linkage) -> Self {
+        self.linkage = Some(linkage);
+        self
+    }
+
+    pub fn with_types(mut self, types: &'w Types) -> Self {
+        self.types = Some(types);
+        self
+    }
+
+    /// Verify JSDoc numeric hints at runtime instead of trusting them.
+    pub fn with_checked_hints(mut self, checked_hints: bool) -> Self {
+        self.checked_hints = checked_hints;
+        self
+    }
+
+    pub fn with_in_bounds(mut self, in_bounds: &'w HashSet<NodeAddress>) -> Self {
+        self.in_bounds = Some(in_bounds);
+        self
+    }
+
+    pub fn with_unsigned_divisions(mut self, unsigned_divisions: &'w HashSet<NodeAddress>) -> Self {
+        self.unsigned_divisions = Some(unsigned_divisions);
+        self
+    }
+
+    pub fn with_counted_loops(
+        mut self,
+        counted_loops: &'w HashMap<NodeAddress, CountedLoop>,
+    ) -> Self {
+        self.counted_loops = Some(counted_loops);
+        self
+    }
+
+    pub fn with_stack_arrays(mut self, stack_arrays: &'w HashMap<SymbolId, StackArray>) -> Self {
+        self.stack_arrays = Some(stack_arrays);
+        self
+    }
+
+    /// Copy the body of counted loops `unroll` times per iteration.
+    pub fn with_unroll(mut self, unroll: usize) -> Self {
+        self.unroll = unroll.max(1);
+        self
+    }
+
+    /// Counts how `if` tests go, how many iterations loops run, how often functions of the
+    /// program are called and whether their double arguments hold int32 values, naming the
+    /// sites after the module `file`.
+    pub fn with_instrumentation(mut self, file: Option<String>) -> Self {
+        self.instrumented = file;
+        self
+    }
+
+    /// Hints the likely side of `if` tests and picks which counted loops to unroll from the
+    /// counts of a profile.
+    pub fn with_profile(mut self, profile: Option<&'w ModuleProfile>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Leaves out the declarations of the top-level `declared` symbols, which
+    /// [`Self::print_declarations`] printed ahead of every module.
+    pub fn with_declared(mut self, declared: &'w HashSet<SymbolId>) -> Self {
+        self.declared = Some(declared);
+        self
+    }
+
+    fn is_declared(&self, symbol_id: Option<SymbolId>) -> bool {
+        symbol_id.is_some_and(|symbol_id| {
+            self.declared
+                .is_some_and(|declared| declared.contains(&symbol_id))
+        })
+    }
+
+    /// The counter of `kind` at `span` in an instrumented program, `None` when the program is
+    /// not instrumented or the node was synthesized by a pass.
+    fn counter(&self, kind: Counter, span: Span, index: Option<usize>) -> Option<String> {
+        let file = self.instrumented.as_ref()?;
+        if span.is_unspanned() {
+            return None;
+        }
+        let mut site = profile::site(file, self.semantic.source_text(), span);
+        if let Some(index) = index {
+            site += &format!(" {index}");
+        }
+        Some(format!(
+            "JS_PROFILE_SITE(\"{}\", {:?})",
+            kind.as_str(),
+            site
+        ))
+    }
+
+    /// How many copies of its body an iteration of the counted loop `for_` runs.
+    fn unroll(&self, for_: &ForStatement) -> usize {
+        if self.instrumented.is_some() {
+            return 1;
+        }
+        let trips = self.profile.and_then(|profile| profile.trips(for_.span));
+        let unroll = if self.unroll > 1 {
+            self.unroll
+        } else {
+            PROFILE_UNROLL
+        };
+        match trips {
+            Some(trips) if trips >= PROFILE_UNROLL_TRIPS * unroll as f64 => unroll,
+            Some(_) => 1,
+            None => self.unroll,
+        }
+    }
+
+    /// The runtime function guarding stores into `symbol_id` under `--checked-hints`.
+    fn hint_check(&self, symbol_id: Option<SymbolId>) -> Option<&'static str> {
+        if !self.checked_hints {
+            return None;
+        }
+        match self.types?.hint(symbol_id?)? {
+            Type::Int32 => Some("js_check_int32"),
+            Type::Uint8 => Some("js_check_uint8"),
+            Type::Float32 => Some("js_check_f32"),
+            _ => None,
+        }
+    }
+
+    fn print_checked(
+        &mut self,
+        check: Option<&str>,
+        name: &str,
+        value: &Expression,
+    ) -> Result<(), std::io::Error> {
+        let Some(check) = check else {
+            return self.print_expression(value);
+        };
+        write!(self.writer, "{}(", check)?;
+        self.print_expression(value)?;
+        write!(self.writer, ", \"{}\")", name)
+    }
+
+    fn expression_type(&self, expr: &Expression) -> Type {
+        self.types
+            .map_or(Type::Unknown, |types| types.expression(self.semantic, expr))
+    }
+
+    fn is_integer(&self, expr: &Expression) -> bool {
+        self.expression_type(expr).is_integer()
+    }
+
+    /// Whether `expr` is int32 arithmetic whose result may not be an int32. Its operands may
+    /// have become doubles since the analysis flagged it.
+    fn overflows(&self, expr: &Expression) -> bool {
+        let operands = match expr {
+            Expression::BinaryExpression(expr) => {
+                self.is_integer(&expr.left) && self.is_integer(&expr.right)
+            }
+            Expression::AssignmentExpression(expr) => self.is_integer(&expr.right),
+            _ => true,
+        };
+        operands && self.types.is_some_and(|types| types.overflows(expr))
+    }
+
+    /// Prints an operand of a bitwise operator, converted like ECMAScript ToInt32 unless it
+    /// already is an integer.
+    fn print_int32(&mut self, expr: &Expression) -> Result<(), std::io::Error> {
+        if !self.expression_type(expr).is_numeric() || self.is_integer(expr) {
+            return self.print_expression(expr);
+        }
+        write!(self.writer, "js_to_int32(")?;
+        self.print_expression(expr)?;
+        write!(self.writer, ")")
+    }
+
+    /// Prints `a[i]`, read or stored into. On a typed array it goes through `js_checked` or
+    /// `js_checked_store` unless the access is known to be in bounds, where a double index holds
+    /// a whole number.
+    fn print_element(
+        &mut self,
+        member: &ComputedMemberExpression,
+        store: bool,
+    ) -> Result<(), std::io::Error> {
+        let typed = self.expression_type(&member.object).is_typed_array();
+        let in_bounds = self
+            .in_bounds
+            .is_some_and(|in_bounds| in_bounds.contains(&NodeAddress::of(member)));
+        if typed && !in_bounds {
+            let check = if store {
+                "js_checked_store"
+            } else {
+                "js_checked"
+            };
+            write!(self.writer, "{check}(")?;
+            self.print_expression(&member.object)?;
+            write!(self.writer, ", ")?;
+            self.print_expression(&member.expression)?;
+            return write!(self.writer, ")");
+        }
+        self.print_expression(&member.object)?;
+        write!(self.writer, "[")?;
+        if self.expression_type(&member.expression) == Type::Double {
+            write!(self.writer, "(int32_t)")?;
+        }
+        self.print_expression(&member.expression)?;
+        write!(self.writer, "]")
+    }
+
+    fn is_unsigned(&self, division: &BinaryExpression) -> bool {
+        self.unsigned_divisions
+            .is_some_and(|unsigned| unsigned.contains(&NodeAddress::of(division)))
+    }
+
+    /// `division` by a literal can be computed on integers: its dividend is an int32, or a
+    /// double known to hold a whole number in `0..2^31`.
+    fn is_integer_division(&self, division: &BinaryExpression) -> bool {
+        self.is_unsigned(division) || self.is_integer(&division.left)
+    }
+
+    /// Prints `x / d` rounded down, or `x % d`, for a literal `d` and an `x` known to be a whole
+    /// number in `0..2^31`, as a multiply and a shift by the constants [`magic`] picks for `d`.
+    fn print_unsigned_division(
+        &mut self,
+        division: &BinaryExpression,
+    ) -> Result<(), std::io::Error> {
+        let divisor = constant_divisor(&division.right).unwrap();
+        let (multiplier, shift) = magic(divisor);
+        if division.operator == BinaryOperator::Remainder {
+            write!(self.writer, "js_umod(")?;
+            self.print_expression(&division.left)?;
+            write!(self.writer, ", {}, ", divisor)?;
+        } else {
+            write!(self.writer, "js_udiv(")?;
+            self.print_expression(&division.left)?;
+            write!(self.writer, ", ")?;
+        }
+        write!(self.writer, "{}ull, {})", multiplier, shift)
+    }
+
+    fn print_type(&mut self, t: &Type) -> Result<(), std::io::Error> {
+        match (t, t.cpp_name()) {
+            (Type::Object(symbol_id), _) => write!(self.writer, "{}", self.symbol_name(*symbol_id)),
+            (_, Some(name)) => write!(self.writer, "{}", name),
+            (_, None) => unreachable!(),
+        }
+    }
+
+    fn identifier_type(&self, id: &IdentifierReference) -> Type {
+        match (self.reference_symbol(id), self.types) {
+            (Some(symbol_id), Some(types)) => types.symbol(symbol_id),
+            _ => Type::Unknown,
+        }
+    }
+
+    fn print_declared_type(&mut self, id: &BindingIdentifier) -> Result<(), std::io::Error> {
+        let t = match (id.symbol_id.get(), self.types) {
+            (Some(symbol_id), Some(types)) => types.symbol(symbol_id),
+            _ => Type::Unknown,
+        };
+        self.print_type(&t)
+    }
+
+    fn symbol_name(&self, symbol_id: SymbolId) -> &'w str {
+        if let Some(name) = self.restricted.get(&symbol_id) {
+            return name;
+        }
+        if let Some(name) = self.linkage.and_then(|l| l.bindings.get(&symbol_id)) {
+            return name;
+        }
+        self.semantic.symbols().get_name(symbol_id)
+    }
+
+    fn reference_symbol(&self, id: &IdentifierReference) -> Option<SymbolId> {
+        let reference_id = id.reference_id.get()?;
+        self.semantic
+            .symbols()
+            .get_reference(reference_id)
+            .symbol_id()
+    }
+
+    fn print_identifier(&mut self, id: &IdentifierReference) -> Result<(), std::io::Error> {
+        // identifiers synthesized by optimizations have no reference
+        match self.reference_symbol(id) {
+            Some(symbol_id) => write!(self.writer, "{}", self.symbol_name(symbol_id)),
+            None => write!(self.writer, "{}", id.name),
+        }
+    }
+
+    fn print_binding(&mut self, id: &BindingIdentifier) -> Result<(), std::io::Error> {
+        match id.symbol_id.get() {
+            Some(symbol_id) => write!(self.writer, "{}", self.symbol_name(symbol_id)),
+            None => write!(self.writer, "{}", id.name),
+        }
     }
 
     fn print_operator(&mut self, op: BinaryOperator) -> Result<(), std::io::Error> {
@@ -52,37 +411,187 @@ where
         Ok(())
     }
 
-    //fn print_identifier(&mut self, _identifier: IdentifierReference) {}
-
     fn print_expression(&mut self, node: &Expression) -> Result<(), std::io::Error> {
         match node {
             Expression::NumericLiteral(x) => {
+                if is_int32_literal(x) {
+                    write!(self.writer, "{}", x.value)?;
+                } else {
+                    write!(self.writer, "{:?}", x.value)?;
+                }
+            }
+            Expression::BooleanLiteral(x) => {
                 write!(self.writer, "{}", x.value)?;
             }
+            Expression::StringLiteral(x) => {
+                write!(self.writer, "\"")?;
+                for c in x.value.chars() {
+                    match c {
+                        '"' => write!(self.writer, "\\\"")?,
+                        '\\' => write!(self.writer, "\\\\")?,
+                        '\n' => write!(self.writer, "\\n")?,
+                        '\r' => write!(self.writer, "\\r")?,
+                        '\t' => write!(self.writer, "\\t")?,
+                        c => write!(self.writer, "{}", c)?,
+                    }
+                }
+                write!(self.writer, "\"")?;
+            }
+            Expression::ParenthesizedExpression(expr) => {
+                write!(self.writer, "(")?;
+                self.print_expression(&expr.expression)?;
+                write!(self.writer, ")")?;
+            }
+            Expression::UnaryExpression(expr) if self.overflows(node) => {
+                // neither -(-2147483648) nor -0 is an int32
+                write!(self.writer, "(-(double)")?;
+                self.print_expression(&expr.argument)?;
+                write!(self.writer, ")")?;
+            }
+            Expression::UnaryExpression(expr) if expr.operator == UnaryOperator::BitwiseNot => {
+                write!(self.writer, "(~")?;
+                self.print_int32(&expr.argument)?;
+                write!(self.writer, ")")?;
+            }
+            Expression::UnaryExpression(expr) => {
+                match expr.operator {
+                    UnaryOperator::UnaryNegation => write!(self.writer, "(-")?,
+                    UnaryOperator::UnaryPlus => write!(self.writer, "(+")?,
+                    UnaryOperator::LogicalNot => write!(self.writer, "(!")?,
+                    UnaryOperator::BitwiseNot => write!(self.writer, "(~")?,
+                    _ => panic!("TODO: UnaryOperator {:#?}", expr.operator),
+                }
+                self.print_expression(&expr.argument)?;
+                write!(self.writer, ")")?;
+            }
+            Expression::LogicalExpression(expr) => {
+                write!(self.writer, "(")?;
+                self.print_expression(&expr.left)?;
+                write!(self.writer, " {} ", expr.operator.as_str())?;
+                self.print_expression(&expr.right)?;
+                write!(self.writer, ")")?;
+            }
+            Expression::ConditionalExpression(expr) => {
+                write!(self.writer, "(")?;
+                self.print_expression(&expr.test)?;
+                write!(self.writer, " ? ")?;
+                self.print_expression(&expr.consequent)?;
+                write!(self.writer, " : ")?;
+                self.print_expression(&expr.alternate)?;
+                write!(self.writer, ")")?;
+            }
+            Expression::TSAsExpression(expr) => {
+                self.print_expression(&expr.expression)?;
+            }
+            Expression::TSSatisfiesExpression(expr) => {
+                self.print_expression(&expr.expression)?;
+            }
+            Expression::TSTypeAssertion(expr) => {
+                self.print_expression(&expr.expression)?;
+            }
+            Expression::TSNonNullExpression(expr) => {
+                self.print_expression(&expr.expression)?;
+            }
+            Expression::TSInstantiationExpression(expr) => {
+                self.print_expression(&expr.expression)?;
+            }
+            Expression::BinaryExpression(bexp)
+                if bexp.operator == BinaryOperator::Remainder && self.is_unsigned(bexp) =>
+            {
+                self.print_unsigned_division(bexp)?;
+            }
+            Expression::BinaryExpression(bexp)
+                if truncated_division(bexp)
+                    .is_some_and(|division| self.is_integer_division(division)) =>
+            {
+                // `(x / d) | 0` rounds toward zero like the C++ integer division
+                let division = truncated_division(bexp).unwrap();
+                if self.is_unsigned(division) {
+                    self.print_unsigned_division(division)?;
+                } else {
+                    write!(self.writer, "(")?;
+                    self.print_expression(&division.left)?;
+                    write!(
+                        self.writer,
+                        "/{})",
+                        constant_divisor(&division.right).unwrap()
+                    )?;
+                }
+            }
+            Expression::BinaryExpression(bexp) if self.overflows(node) => {
+                let function = int32_arithmetic(bexp.operator).unwrap();
+                write!(self.writer, "{}(", function)?;
+                self.print_expression(&bexp.left)?;
+                write!(self.writer, ", ")?;
+                self.print_expression(&bexp.right)?;
+                write!(self.writer, ")")?;
+            }
+            Expression::BinaryExpression(bexp)
+                if bexp.operator == BinaryOperator::Remainder
+                    && !(self.is_integer(&bexp.left) && self.is_integer(&bexp.right)) =>
+            {
+                // C++ `%` only takes integers; fmod keeps the sign of the dividend like JS
+                write!(self.writer, "fmod(")?;
+                self.print_expression(&bexp.left)?;
+                write!(self.writer, ", ")?;
+                self.print_expression(&bexp.right)?;
+                write!(self.writer, ")")?;
+            }
+            Expression::BinaryExpression(bexp) if bexp.operator.is_bitwise() => {
+                write!(self.writer, "(")?;
+                self.print_int32(&bexp.left)?;
+                self.print_operator(bexp.operator)?;
+                self.print_int32(&bexp.right)?;
+                write!(self.writer, ")")?;
+            }
             Expression::BinaryExpression(bexp) => {
                 write!(self.writer, "(")?;
+                // JS division is never truncating
+                if bexp.operator == BinaryOperator::Division
+                    && self.expression_type(&bexp.left) == Type::Int32
+                    && self.expression_type(&bexp.right) == Type::Int32
+                {
+                    write!(self.writer, "(double)")?;
+                }
                 self.print_expression(&bexp.left)?;
                 self.print_operator(bexp.operator)?;
                 self.print_expression(&bexp.right)?;
                 write!(self.writer, ")")?;
             }
             Expression::Identifier(x) => {
-                write!(self.writer, "{}", x.name)?;
+                self.print_identifier(x)?;
             }
             Expression::AssignmentExpression(x) => {
                 match &x.left {
                     AssignmentTarget::ComputedMemberExpression(target) => {
-                        // this is also a[i]
-                        //target.object;
-                        //target.expression
-
-                        self.print_expression(&target.object)?;
-                        write!(self.writer, "[")?;
-                        self.print_expression(&target.expression)?;
-                        write!(self.writer, "]")?;
+                        self.print_element(target, true)?;
                     }
                     AssignmentTarget::AssignmentTargetIdentifier(id) => {
-                        write!(self.writer, "{}", id.name)?;
+                        self.print_identifier(id)?;
+                        if let Some(check) = self.hint_check(self.reference_symbol(id)) {
+                            write!(self.writer, " = {}(", check)?;
+                            let operator = x.operator.to_binary_operator();
+                            if let Some(function) = operator
+                                .and_then(int32_arithmetic)
+                                .filter(|_| self.overflows(node))
+                            {
+                                write!(self.writer, "{}(", function)?;
+                                self.print_identifier(id)?;
+                                write!(self.writer, ", ")?;
+                                self.print_expression(&x.right)?;
+                                write!(self.writer, ")")?;
+                            } else if let Some(operator) = operator {
+                                write!(self.writer, "(")?;
+                                self.print_identifier(id)?;
+                                self.print_operator(operator)?;
+                                self.print_expression(&x.right)?;
+                                write!(self.writer, ")")?;
+                            } else {
+                                self.print_expression(&x.right)?;
+                            }
+                            write!(self.writer, ", \"{}\")", id.name)?;
+                            return Ok(());
+                        }
                     }
                     _ => {
                         panic!("Missing AssignmentExpression {:?}", x.left);
@@ -91,6 +600,21 @@ where
                 match x.operator {
                     AssignmentOperator::Assign => {
                         write!(self.writer, " = ")?;
+                        if let Expression::ObjectExpression(object) = &x.right {
+                            let t = match &x.left {
+                                AssignmentTarget::AssignmentTargetIdentifier(id) => {
+                                    self.identifier_type(id)
+                                }
+                                _ => Type::Unknown,
+                            };
+                            return self.print_object_literal(&t, object);
+                        }
+                        // an Int32Array stores ToInt32 of what it is given
+                        if let AssignmentTarget::ComputedMemberExpression(target) = &x.left {
+                            if self.expression_type(&target.object) == Type::Int32Array {
+                                return self.print_int32(&x.right);
+                            }
+                        }
                     }
                     AssignmentOperator::Addition => {
                         write!(self.writer, " += ")?;
@@ -100,25 +624,116 @@ where
                 self.print_expression(&x.right)?;
             }
             Expression::ComputedMemberExpression(expr) => {
-                self.print_expression(&expr.object)?;
-                write!(self.writer, "[")?;
-                self.print_expression(&expr.expression)?;
-                write!(self.writer, "]")?;
+                self.print_element(expr, false)?;
+            }
+            Expression::CallExpression(expr)
+                if floor_division(expr)
+                    .is_some_and(|division| self.is_integer_division(division)) =>
+            {
+                // C++ rounds toward zero, `js_floor_div` toward negative infinity
+                let division = floor_division(expr).unwrap();
+                if self.is_unsigned(division) {
+                    self.print_unsigned_division(division)?;
+                } else {
+                    write!(self.writer, "js_floor_div(")?;
+                    self.print_expression(&division.left)?;
+                    write!(
+                        self.writer,
+                        ", {})",
+                        constant_divisor(&division.right).unwrap()
+                    )?;
+                }
+            }
+            Expression::CallExpression(expr) if is_math_floor(expr) && self.is_integer(node) => {
+                // the runtime floors to a double, range analysis proved this one an int32
+                write!(self.writer, "((int32_t)")?;
+                self.print_expression(&expr.callee)?;
+                write!(self.writer, "(")?;
+                self.print_expression(expr.arguments[0].to_expression())?;
+                write!(self.writer, "))")?;
             }
             Expression::CallExpression(expr) => {
+                // calls to functions of the program count, and so do their double arguments
+                let counted = match &expr.callee {
+                    Expression::Identifier(callee) => {
+                        self.reference_symbol(callee).is_some_and(|symbol_id| {
+                            matches!(
+                                self.semantic.symbol_declaration(symbol_id).kind(),
+                                AstKind::Function(_)
+                            )
+                        })
+                    }
+                    _ => false,
+                };
+                let counter = self
+                    .counter(Counter::Call, expr.span, None)
+                    .filter(|_| counted);
+                if let Some(counter) = &counter {
+                    write!(self.writer, "({}.counts[0]++, ", counter)?;
+                }
                 self.print_expression(&expr.callee)?;
                 write!(self.writer, "(")?;
                 for (i, arg) in expr.arguments.iter().enumerate() {
                     if i > 0 {
                         write!(self.writer, ", ")?;
                     }
-                    self.print_expression(arg.to_expression())?;
+                    let arg = arg.to_expression();
+                    let counter = self
+                        .counter(Counter::Argument, expr.span, Some(i))
+                        .filter(|_| counted && self.expression_type(arg) == Type::Double);
+                    match counter {
+                        Some(counter) => {
+                            write!(self.writer, "js_profile_argument({}, ", counter)?;
+                            self.print_expression(arg)?;
+                            write!(self.writer, ")")?;
+                        }
+                        None => self.print_expression(arg)?,
+                    }
                 }
                 write!(self.writer, ")")?;
+                if counter.is_some() {
+                    write!(self.writer, ")")?;
+                }
             }
             Expression::StaticMemberExpression(expr) => {
+                if self.is_commonjs() {
+                    if let Some(name) = exports_property(self.semantic, expr) {
+                        write!(self.writer, "{}", self.commonjs_export(name))?;
+                        return Ok(());
+                    }
+                    // `require('./m').name`, resolved when linking
+                    if let Some(specifier) = require_specifier(self.semantic, &expr.object) {
+                        let linkage = self.linkage.unwrap();
+                        let name = &linkage.requires[specifier][expr.property.name.as_str()];
+                        write!(self.writer, "{}", name)?;
+                        return Ok(());
+                    }
+                }
+                // `ns.name` on an `import * as ns` namespace
+                if let Expression::Identifier(id) = &expr.object {
+                    let namespace = self
+                        .reference_symbol(id)
+                        .and_then(|symbol_id| self.linkage?.namespaces.get(&symbol_id));
+                    if let Some(namespace) = namespace {
+                        // linking rejects names the namespace lacks
+                        let name = &namespace[expr.property.name.as_str()];
+                        write!(self.writer, "{}", name)?;
+                        return Ok(());
+                    }
+                }
+                if self.expression_type(&expr.object).is_typed_array()
+                    && expr.property.name == "length"
+                {
+                    write!(self.writer, "js_length(")?;
+                    self.print_expression(&expr.object)?;
+                    return write!(self.writer, ")");
+                }
                 self.print_expression(&expr.object)?;
-                write!(self.writer, "::")?;
+                if let Type::Object(_) = self.expression_type(&expr.object) {
+                    write!(self.writer, ".")?;
+                } else {
+                    write!(self.writer, "::")?;
+                }
                 write!(self.writer, "{}", expr.property.name)?;
             }
             Expression::NewExpression(expr) => {
@@ -135,9 +750,27 @@ where
                 write!(self.writer, ")")?;
             }
             Expression::UpdateExpression(expr) => {
+                if let SimpleAssignmentTarget::AssignmentTargetIdentifier(id) = &expr.argument {
+                    if let Some(check) = self.hint_check(self.reference_symbol(id)) {
+                        let (step, undo) = match expr.operator {
+                            UpdateOperator::Increment => ("+", "-"),
+                            UpdateOperator::Decrement => ("-", "+"),
+                        };
+                        write!(self.writer, "((")?;
+                        self.print_identifier(id)?;
+                        write!(self.writer, " = {}(", check)?;
+                        self.print_identifier(id)?;
+                        write!(self.writer, " {} 1.0, \"{}\"))", step, id.name)?;
+                        if !expr.prefix {
+                            write!(self.writer, " {} 1", undo)?;
+                        }
+                        write!(self.writer, ")")?;
+                        return Ok(());
+                    }
+                }
                 match &expr.argument {
                     SimpleAssignmentTarget::AssignmentTargetIdentifier(id) => {
-                        write!(self.writer, "{}", id.name)?;
+                        self.print_identifier(id)?;
                     }
                     _ => panic!("TODO: UpdateOperator {:#?}", expr.argument),
                 }
@@ -158,17 +791,20 @@ where
 
     fn print_statement(&mut self, node: &Statement, indent: usize) -> Result<(), std::io::Error> {
         let indent_str = " ".repeat(indent * 4);
-        let semantic = self.semantic;
         match node {
             Statement::BlockStatement(block) => {
-                block
-                    .body
-                    .iter()
-                    .for_each(|node| self.print_statement(&node, indent + 1).unwrap());
+                writeln!(self.writer, "{}{{", indent_str)?;
+                self.print_statements(&block.body, indent + 1)?;
+                writeln!(self.writer, "{}}}", indent_str)?;
             }
             Statement::ExpressionStatement(expr) => {
                 //println!("{:#?}", expr);
                 //println!("expression statement");
+                if self.is_commonjs()
+                    && self.print_commonjs_export(&expr.expression, &indent_str)?
+                {
+                    return Ok(());
+                }
                 write!(self.writer, "{}", indent_str)?;
                 self.print_expression(&expr.expression)?;
                 writeln!(self.writer, ";")?;
@@ -182,8 +818,13 @@ where
             Statement::WithStatement(_with) => {
                 println!("with statement");
             }
-            Statement::ReturnStatement(_return_) => {
-                println!("return statement");
+            Statement::ReturnStatement(return_) => {
+                write!(self.writer, "{}return", indent_str)?;
+                if let Some(argument) = &return_.argument {
+                    write!(self.writer, " ")?;
+                    self.print_expression(argument)?;
+                }
+                writeln!(self.writer, ";")?;
             }
             Statement::LabeledStatement(_labeled) => {
                 println!("labeled statement");
@@ -196,9 +837,41 @@ where
             }
             Statement::IfStatement(if_) => {
                 write!(self.writer, "{}if (", indent_str)?;
-                self.print_expression(&if_.test)?;
+                let hint = self
+                    .profile
+                    .and_then(|profile| profile.branch(if_.span))
+                    .and_then(|(taken, not_taken)| {
+                        let runs = taken + not_taken;
+                        let ratio = taken as f64 / runs as f64;
+                        if runs < BRANCH_HINT_RUNS {
+                            None
+                        } else if ratio >= BRANCH_HINT_RATIO {
+                            Some("JS_LIKELY")
+                        } else if ratio <= 1.0 - BRANCH_HINT_RATIO {
+                            Some("JS_UNLIKELY")
+                        } else {
+                            None
+                        }
+                    });
+                match (self.counter(Counter::Branch, if_.span, None), hint) {
+                    (Some(counter), _) => {
+                        write!(self.writer, "js_profile_branch({}, ", counter)?;
+                        self.print_expression(&if_.test)?;
+                        write!(self.writer, ")")?;
+                    }
+                    (None, Some(hint)) => {
+                        write!(self.writer, "{}(", hint)?;
+                        self.print_expression(&if_.test)?;
+                        write!(self.writer, ")")?;
+                    }
+                    (None, None) => self.print_expression(&if_.test)?,
+                }
                 writeln!(self.writer, ") {{")?;
-                self.print_statement(&if_.consequent, indent + 1)?;
+                self.print_body(&if_.consequent, indent + 1)?;
+                if let Some(alternate) = &if_.alternate {
+                    writeln!(self.writer, "{}}} else {{", indent_str)?;
+                    self.print_body(alternate, indent + 1)?;
+                }
                 writeln!(self.writer, "{}}}", indent_str)?;
             }
             Statement::SwitchStatement(_switch) => {
@@ -219,27 +892,65 @@ where
             Statement::ForStatement(for_) => {
                 writeln!(self.writer, "{}{{", indent_str)?;
 
-                if let Some(init) = &for_.init {
-                    if let ForStatementInit::VariableDeclaration(var) = init {
-                        //print_statement(&init);
-                        for decl in &var.declarations {
-                            if let BindingPatternKind::BindingIdentifier(x) = &decl.id.kind {
-                                //println!("init {:#?}", decl);
-                                // if let Some(init) = x.symbol_id.get().unwrap().into() {
-                                //     let name = semantic.symbols().get_name(init);
-                                //     write!(self.writer, "{}auto {} = ", indent_str, name)?;
-                                //     self.print_expression(decl.init.as_ref().unwrap())?;
-                                //     writeln!(self.writer, ";")?;
-                                // }
-                                let name = x.name.as_str();
-                                write!(self.writer, "{}auto {} = ", indent_str, name)?;
-                                self.print_expression(decl.init.as_ref().unwrap())?;
-                                writeln!(self.writer, ";")?;
-                            }
+                let counted = self
+                    .counted_loops
+                    .and_then(|loops| loops.get(&NodeAddress::of(&**for_)));
+                // declared first, so that the initializer goes through them too
+                let mut shadowed = vec![];
+                for (array, name) in counted.map_or(&[][..], |counted| &counted.restricted) {
+                    write!(self.writer, "{}", indent_str)?;
+                    self.print_type(&self.types.unwrap().symbol(*array))?;
+                    writeln!(
+                        self.writer,
+                        " __restrict {} = {};",
+                        name,
+                        self.symbol_name(*array)
+                    )?;
+                    shadowed.push((*array, self.restricted.insert(*array, name)));
+                }
+
+                if let Some(ForStatementInit::VariableDeclaration(var)) = &for_.init {
+                    //print_statement(&init);
+                    for decl in &var.declarations {
+                        if let BindingPatternKind::BindingIdentifier(x) = &decl.id.kind {
+                            write!(self.writer, "{}", indent_str)?;
+                            self.print_declared_type(x)?;
+                            write!(self.writer, " ")?;
+                            self.print_binding(x)?;
+                            write!(self.writer, " = ")?;
+                            let check = self.hint_check(x.symbol_id.get());
+                            self.print_checked(check, &x.name, decl.init.as_ref().unwrap())?;
+                            writeln!(self.writer, ";")?;
                         }
                     }
                 }
 
+                let counter = self.counter(Counter::Loop, for_.span, None);
+                if let Some(counter) = &counter {
+                    writeln!(
+                        self.writer,
+                        "{}js_profile_counter& js_profile_loop_{} = {};",
+                        indent_str, for_.span.start, counter
+                    )?;
+                    writeln!(
+                        self.writer,
+                        "{}js_profile_loop_{}.counts[0]++;",
+                        indent_str, for_.span.start
+                    )?;
+                }
+
+                if let Some(counted) = counted {
+                    self.print_counted_loop(for_, counted, indent)?;
+                    for (array, previous) in shadowed {
+                        match previous {
+                            Some(name) => self.restricted.insert(array, name),
+                            None => self.restricted.remove(&array),
+                        };
+                    }
+                    writeln!(self.writer, "{}}}", indent_str)?;
+                    return Ok(());
+                }
+
                 write!(self.writer, "{}while (", indent_str)?;
 
                 if let Some(test) = &for_.test {
@@ -249,10 +960,11 @@ where
                 }
 
                 writeln!(self.writer, ") {{")?;
+                self.print_iteration_count(for_, indent + 1)?;
 
                 //print_statement(&for_.test);
                 //print_statement(&for_.update);
-                self.print_statement(&for_.body, indent + 1)?;
+                self.print_body(&for_.body, indent + 1)?;
 
                 if let Some(update) = &for_.update {
                     self.print_expression(update)?;
@@ -270,68 +982,746 @@ where
                 println!("for of statement");
             }
             Statement::VariableDeclaration(var) => {
-                for decl in &var.declarations {
-                    if let BindingPatternKind::BindingIdentifier(x) = &decl.id.kind {
-                        //println!("variable declaration {:?}", x.symbol_id);
-                        write!(
-                            self.writer,
-                            "auto {}",
-                            semantic
-                                .symbols()
-                                .get_name(x.symbol_id.get().unwrap().into())
-                        )?;
+                self.print_variable_declaration(var)?;
+            }
+            Statement::ClassDeclaration(_class) => {
+                println!("class declaration");
+            }
+            Statement::FunctionDeclaration(function) => {
+                if !self.is_hoisted(function) {
+                    self.print_function(function, indent)?;
+                }
+            }
+            Statement::TSTypeAliasDeclaration(_)
+            | Statement::TSInterfaceDeclaration(_)
+            | Statement::TSModuleDeclaration(_) => {
+                // types only; interfaces are printed as structs by print_module
+            }
+            Statement::TSEnumDeclaration(enum_) => {
+                return Err(unsupported(&format!("enum '{}'", enum_.id.name)));
+            }
+            Statement::ImportDeclaration(_) | Statement::ExportAllDeclaration(_) => {
+                // bindings are resolved through the linkage
+            }
+            Statement::ExportNamedDeclaration(export) => match &export.declaration {
+                Some(Declaration::VariableDeclaration(var)) if var.declare => {}
+                Some(Declaration::VariableDeclaration(var)) => {
+                    self.print_variable_declaration(var)?;
+                }
+                Some(Declaration::FunctionDeclaration(function)) if !self.is_hoisted(function) => {
+                    self.print_function(function, indent)?;
+                }
+                Some(Declaration::ClassDeclaration(_)) => {
+                    return Err(unsupported("exported class"));
+                }
+                _ => {}
+            },
+            Statement::ExportDefaultDeclaration(export) => match &export.declaration {
+                ExportDefaultDeclarationKind::FunctionDeclaration(function) => {
+                    if !self.is_hoisted(function) {
+                        self.print_function(function, indent)?;
                     }
-                    if let Some(init) = &decl.init {
-                        write!(self.writer, " = ")?;
-                        self.print_expression(init)?;
+                    if let Some(id) = &function.id {
+                        let name = self.linkage.map_or("default_export", |l| &l.default_export);
+                        write!(self.writer, "{}auto {} = ", indent_str, name)?;
+                        self.print_binding(id)?;
+                        writeln!(self.writer, ";")?;
+                    }
+                }
+                ExportDefaultDeclarationKind::ClassDeclaration(_) => {
+                    return Err(unsupported("exported class"));
+                }
+                kind => {
+                    if let Some(expr) = kind.as_expression() {
+                        let name = self.linkage.map_or("default_export", |l| &l.default_export);
+                        write!(self.writer, "{}auto {} = ", indent_str, name)?;
+                        self.print_expression(expr)?;
+                        writeln!(self.writer, ";")?;
                     }
+                }
+            },
+            _ => println!("other node"),
+        }
+
+        Ok(())
+    }
+
+    /// Prints the loop of a counted `for` as a C++ `for`. With unrolling, a first loop runs
+    /// several bodies per test while the last of them still passes it, and a second one the
+    /// iterations left.
+    fn print_counted_loop(
+        &mut self,
+        for_: &ForStatement,
+        counted: &CountedLoop,
+        indent: usize,
+    ) -> Result<(), std::io::Error> {
+        let indent_str = " ".repeat(indent * 4);
+        let (Some(test), Some(update)) = (&for_.test, &for_.update) else {
+            unreachable!();
+        };
+        // no iteration depends on the memory another one writes
+        let ivdep = if counted.independent {
+            format!("{}JS_IVDEP\n", indent_str)
+        } else {
+            String::new()
+        };
+
+        let unroll = self.unroll(for_);
+        if unroll > 1 {
+            let Expression::BinaryExpression(test) = test.without_parentheses() else {
+                unreachable!();
+            };
+            let offset = (unroll as i64 - 1) * counted.step;
+            write!(self.writer, "{}{}for (; (", ivdep, indent_str)?;
+            for (i, side) in [&test.left, &test.right].into_iter().enumerate() {
+                if i > 0 {
+                    self.print_operator(test.operator)?;
+                }
+                match side.without_parentheses() {
+                    Expression::Identifier(id)
+                        if self.reference_symbol(id) == Some(counted.counter) =>
+                    {
+                        write!(self.writer, "((int64_t)")?;
+                        self.print_identifier(id)?;
+                        write!(self.writer, "{:+})", offset)?;
+                    }
+                    side => self.print_expression(side)?,
+                }
+            }
+            write!(self.writer, "); ")?;
+            self.print_expression(update)?;
+            writeln!(self.writer, ") {{")?;
+            for copy in 0..unroll {
+                if copy > 0 {
+                    write!(self.writer, "{}    ", indent_str)?;
+                    self.print_expression(update)?;
                     writeln!(self.writer, ";")?;
                 }
+                writeln!(self.writer, "{}    {{", indent_str)?;
+                self.print_body(&for_.body, indent + 2)?;
+                writeln!(self.writer, "{}    }}", indent_str)?;
             }
-            Statement::ClassDeclaration(_class) => {
-                println!("class declaration");
+            writeln!(self.writer, "{}}}", indent_str)?;
+        }
+
+        write!(self.writer, "{}{}for (; ", ivdep, indent_str)?;
+        self.print_expression(test)?;
+        write!(self.writer, "; ")?;
+        self.print_expression(update)?;
+        writeln!(self.writer, ") {{")?;
+        self.print_iteration_count(for_, indent + 1)?;
+        self.print_body(&for_.body, indent + 1)?;
+        writeln!(self.writer, "{}}}", indent_str)
+    }
+
+    /// Counts an iteration of `for_` in an instrumented program.
+    fn print_iteration_count(
+        &mut self,
+        for_: &ForStatement,
+        indent: usize,
+    ) -> Result<(), std::io::Error> {
+        if self.counter(Counter::Loop, for_.span, None).is_none() {
+            return Ok(());
+        }
+        writeln!(
+            self.writer,
+            "{}js_profile_loop_{}.counts[1]++;",
+            " ".repeat(indent * 4),
+            for_.span.start
+        )
+    }
+
+    /// Prints a list of statements. The functions it declares are declared first, so that they
+    /// can be called from anywhere in the list like JS hoists them, and those that capture no
+    /// variable are also defined there.
+    fn print_statements(
+        &mut self,
+        statements: &[Statement],
+        indent: usize,
+    ) -> Result<(), std::io::Error> {
+        let functions: Vec<_> = statements.iter().filter_map(declared_function).collect();
+        for function in &functions {
+            if !self.is_declared(function.id.as_ref().and_then(|id| id.symbol_id.get())) {
+                self.print_function_declaration(function, indent)?;
             }
-            Statement::FunctionDeclaration(_function) => {
-                println!("function declaration");
+        }
+        for function in functions {
+            if self.is_hoisted(function) {
+                self.print_function(function, indent)?;
             }
-            Statement::TSTypeAliasDeclaration(_type_alias) => {
-                println!("ts type alias declaration");
+        }
+
+        for statement in statements {
+            self.print_statement(statement, indent)?;
+        }
+
+        Ok(())
+    }
+
+    /// Declares the `std::function` holding `function`, when its signature is known.
+    fn print_function_declaration(
+        &mut self,
+        function: &Function,
+        indent: usize,
+    ) -> Result<(), std::io::Error> {
+        let Some((params, result)) = self.signature(function) else {
+            return Ok(());
+        };
+        write!(self.writer, "{}std::function<", " ".repeat(indent * 4))?;
+        self.print_result_type(function, result.as_ref())?;
+        write!(self.writer, "(")?;
+        for (i, t) in params.iter().enumerate() {
+            if i > 0 {
+                write!(self.writer, ", ")?;
             }
-            _ => println!("other node"),
+            self.print_type(t)?;
+        }
+        writeln!(self.writer, ")> {};", self.function_name(function))
+    }
+
+    /// Prints the body of an `if` or a loop, whose braces the caller prints.
+    fn print_body(&mut self, node: &Statement, indent: usize) -> Result<(), std::io::Error> {
+        match node {
+            Statement::BlockStatement(block) => self.print_statements(&block.body, indent),
+            node => self.print_statement(node, indent),
+        }
+    }
+
+    fn function_name(&self, function: &Function) -> &'w str {
+        match function.id.as_ref().and_then(|id| id.symbol_id.get()) {
+            Some(symbol_id) => self.symbol_name(symbol_id),
+            None => self.linkage.map_or("default_export", |l| &l.default_export),
+        }
+    }
+
+    /// The C++ types of the parameters and the result of `function`, `None` when one of them is
+    /// unknown. The result is `None` for a function that returns no value.
+    fn signature(&self, function: &Function) -> Option<(Vec<Type>, Option<Type>)> {
+        let types = self.types?;
+        let mut params = vec![];
+        for param in &function.params.items {
+            let BindingPatternKind::BindingIdentifier(id) = &param.pattern.kind else {
+                return None;
+            };
+            match types.symbol(id.symbol_id.get()?) {
+                Type::Unknown => return None,
+                // hinted parameters are checked in the body under `--checked-hints`
+                _ if self.hint_check(id.symbol_id.get()).is_some() => params.push(Type::Double),
+                t => params.push(t),
+            }
+        }
+        if function.params.rest.is_some() {
+            return None;
+        }
+        let result = match function.id.as_ref().and_then(|id| id.symbol_id.get()) {
+            Some(symbol_id) => types.returns(symbol_id).cloned(),
+            None => return None,
+        };
+        if result == Some(Type::Unknown) {
+            return None;
+        }
+        Some((params, result))
+    }
+
+    /// Fails for a `function` that returns a value of a type type inference never found, rather
+    /// than declaring it `void`.
+    fn print_result_type(
+        &mut self,
+        function: &Function,
+        result: Option<&Type>,
+    ) -> Result<(), std::io::Error> {
+        match result {
+            Some(t) => self.print_type(t),
+            None if returns_value(function) => Err(std::io::Error::other(format!(
+                "'{}' returns a value of unknown type",
+                function
+                    .id
+                    .as_ref()
+                    .map_or("default", |id| id.name.as_str())
+            ))),
+            None => write!(self.writer, "void"),
+        }
+    }
+
+    /// Whether `function` is defined where the statement list declaring it starts: its
+    /// `std::function` is declared there, and the lambda captures no variable that might be
+    /// declared later in C++.
+    fn is_hoisted(&self, function: &Function) -> bool {
+        let Some(scope_id) = function.scope_id.get() else {
+            return false;
+        };
+        if self.signature(function).is_none() {
+            return false;
+        }
+        let symbols = self.semantic.symbols();
+        let scopes = self.semantic.scopes();
+        let inside = |scope| scopes.ancestors(scope).any(|ancestor| ancestor == scope_id);
+        !self.semantic.nodes().iter().any(|node| {
+            let AstKind::IdentifierReference(id) = node.kind() else {
+                return false;
+            };
+            // inlined code keeps the positions it was copied from
+            if !inside(node.scope_id()) {
+                return false;
+            }
+            let Some(symbol_id) = self.reference_symbol(id) else {
+                return false;
+            };
+            // function declarations are hoisted themselves
+            let function = matches!(
+                self.semantic.symbol_declaration(symbol_id).kind(),
+                AstKind::Function(_)
+            );
+            symbols.get_flags(symbol_id).is_variable()
+                && !function
+                && !inside(symbols.get_scope_id(symbol_id))
+        })
+    }
+
+    /// Prints `function` as a lambda capturing its environment by reference. With a known
+    /// signature it is stored in the `std::function` [`Self::print_statements`] declared, which
+    /// lets it call itself; otherwise it is a generic lambda in a variable of its own.
+    fn print_function(&mut self, function: &Function, indent: usize) -> Result<(), std::io::Error> {
+        let indent_str = " ".repeat(indent * 4);
+        let name = self.function_name(function);
+        let signature = self.signature(function);
+        if signature.is_some() {
+            write!(self.writer, "{}{} = [&](", indent_str, name)?;
+        } else {
+            write!(self.writer, "{}auto {} = [&](", indent_str, name)?;
+        }
+        for (i, param) in function.params.items.iter().enumerate() {
+            if i > 0 {
+                write!(self.writer, ", ")?;
+            }
+            let BindingPatternKind::BindingIdentifier(id) = &param.pattern.kind else {
+                panic!("TODO: parameter pattern {:#?}", param.pattern);
+            };
+            if self.hint_check(id.symbol_id.get()).is_some() {
+                // checked on entry, see below
+                write!(self.writer, "double ")?;
+                self.print_binding(id)?;
+                write!(self.writer, "_unchecked")?;
+                continue;
+            }
+            if signature.is_some() {
+                self.print_declared_type(id)?;
+            } else {
+                write!(self.writer, "auto")?;
+            }
+            write!(self.writer, " ")?;
+            self.print_binding(id)?;
+        }
+        write!(self.writer, ")")?;
+        if let Some((_, result)) = &signature {
+            write!(self.writer, " -> ")?;
+            self.print_result_type(function, result.as_ref())?;
+        }
+        writeln!(self.writer, " {{")?;
+        for param in &function.params.items {
+            let BindingPatternKind::BindingIdentifier(id) = &param.pattern.kind else {
+                continue;
+            };
+            let Some(check) = self.hint_check(id.symbol_id.get()) else {
+                continue;
+            };
+            write!(self.writer, "{}    ", indent_str)?;
+            self.print_declared_type(id)?;
+            write!(self.writer, " ")?;
+            self.print_binding(id)?;
+            write!(self.writer, " = {}(", check)?;
+            self.print_binding(id)?;
+            writeln!(self.writer, "_unchecked, \"{}\");", id.name)?;
+        }
+        if let Some(body) = &function.body {
+            self.print_statements(&body.statements, indent + 1)?;
+        }
+        writeln!(self.writer, "{}}};", indent_str)
+    }
+
+    fn print_variable_declaration(
+        &mut self,
+        var: &VariableDeclaration,
+    ) -> Result<(), std::io::Error> {
+        if var.declare {
+            return Ok(());
+        }
+        for decl in &var.declarations {
+            if self.is_commonjs()
+                && decl
+                    .init
+                    .as_ref()
+                    .and_then(|init| require_specifier(self.semantic, init))
+                    .is_some()
+            {
+                // bindings are resolved through the linkage
+                continue;
+            }
+            let mut t = Type::Unknown;
+            let mut check = None;
+            let mut name = "";
+            if let BindingPatternKind::BindingIdentifier(x) = &decl.id.kind {
+                let stack = x
+                    .symbol_id
+                    .get()
+                    .and_then(|symbol_id| Some((symbol_id, self.stack_arrays?.get(&symbol_id)?)));
+                if let Some((symbol_id, stack)) = stack {
+                    // the same header as js_typed_array, with the elements zeroed
+                    let element = match self.types.map(|types| types.symbol(symbol_id)) {
+                        Some(Type::Int32Array) => "int32_t",
+                        _ => "double",
+                    };
+                    writeln!(
+                        self.writer,
+                        "js_stack_array<{}, {}> {} = {{{{{}}}}};",
+                        element, stack.length, stack.storage, stack.length
+                    )?;
+                    write!(self.writer, "{}* ", element)?;
+                    self.print_binding(x)?;
+                    writeln!(self.writer, " = {}.elements;", stack.storage)?;
+                    continue;
+                }
+                //println!("variable declaration {:?}", x.symbol_id);
+                if let (Some(symbol_id), Some(types)) = (x.symbol_id.get(), self.types) {
+                    t = types.symbol(symbol_id);
+                }
+                check = self.hint_check(x.symbol_id.get());
+                name = x.name.as_str();
+                if self.is_declared(x.symbol_id.get()) {
+                    // only the initializer is left to assign
+                    if decl.init.is_none() {
+                        continue;
+                    }
+                } else {
+                    self.print_type(&t)?;
+                    write!(self.writer, " ")?;
+                }
+                self.print_binding(x)?;
+            }
+            match &decl.init {
+                Some(Expression::ObjectExpression(object)) => {
+                    write!(self.writer, " = ")?;
+                    self.print_object_literal(&t, object)?;
+                }
+                Some(init) => {
+                    write!(self.writer, " = ")?;
+                    self.print_checked(check, name, init)?;
+                }
+                None => {}
+            }
+            writeln!(self.writer, ";")?;
+        }
+
+        Ok(())
+    }
+
+    /// Object literals only compile when their interface is known, and are built
+    /// field by field in source order so side effects keep their order.
+    fn print_object_literal(
+        &mut self,
+        t: &Type,
+        object: &ObjectExpression,
+    ) -> Result<(), std::io::Error> {
+        let Type::Object(_) = t else {
+            panic!(
+                "TODO: object literal without an interface type {:#?}",
+                object
+            );
+        };
+        write!(self.writer, "[&]() {{ ")?;
+        self.print_type(t)?;
+        write!(self.writer, " o; ")?;
+        for property in &object.properties {
+            let ObjectPropertyKind::ObjectProperty(property) = property else {
+                panic!("TODO: object literal property {:#?}", property);
+            };
+            let Some(key) = property.key.static_name() else {
+                panic!("TODO: computed object literal key {:#?}", property.key);
+            };
+            write!(self.writer, "o.{} = ", key)?;
+            self.print_expression(&property.value)?;
+            write!(self.writer, "; ")?;
+        }
+        write!(self.writer, "return o; }}()")
+    }
+
+    fn print_interface(&mut self, decl: &TSInterfaceDeclaration) -> Result<(), std::io::Error> {
+        let Some(symbol_id) = decl.id.symbol_id.get() else {
+            return Ok(());
+        };
+        let fields = self
+            .types
+            .and_then(|types| types.interface_fields(symbol_id))
+            .unwrap_or_default();
+        writeln!(self.writer, "struct {} {{", self.symbol_name(symbol_id))?;
+        for (name, t) in fields {
+            if *t == Type::Unknown {
+                panic!("TODO: interface field '{}' without a primitive type", name);
+            }
+            write!(self.writer, "    ")?;
+            self.print_type(t)?;
+            writeln!(self.writer, " {};", name)?;
+        }
+        writeln!(self.writer, "}};")?;
+
+        Ok(())
+    }
+
+    fn is_commonjs(&self) -> bool {
+        self.linkage.is_some_and(|l| l.commonjs)
+    }
+
+    fn commonjs_export(&self, name: &str) -> &'w str {
+        match self.linkage.and_then(|l| l.commonjs_exports.get(name)) {
+            Some(name) => name,
+            None => panic!(
+                "exports.{} is read but never assigned at the top level",
+                name
+            ),
+        }
+    }
+
+    /// Prints top-level `require()` calls and `exports` assignments, returning
+    /// false for any other expression statement.
+    fn print_commonjs_export(
+        &mut self,
+        expr: &Expression,
+        indent_str: &str,
+    ) -> Result<bool, std::io::Error> {
+        if require_specifier(self.semantic, expr).is_some() {
+            // evaluated as its own module
+            return Ok(true);
+        }
+
+        let Expression::AssignmentExpression(assign) = expr else {
+            return Ok(false);
+        };
+        let AssignmentTarget::StaticMemberExpression(target) = &assign.left else {
+            return Ok(false);
+        };
+        if assign.operator != AssignmentOperator::Assign {
+            return Ok(false);
+        }
+
+        if let Some(name) = exports_property(self.semantic, target) {
+            write!(
+                self.writer,
+                "{}auto {} = ",
+                indent_str,
+                self.commonjs_export(name)
+            )?;
+            self.print_expression(&assign.right)?;
+            writeln!(self.writer, ";")?;
+        } else if is_module_exports(self.semantic, target) {
+            if let Expression::ObjectExpression(object) = &assign.right {
+                for property in &object.properties {
+                    let ObjectPropertyKind::ObjectProperty(property) = property else {
+                        panic!("TODO: module.exports property {:#?}", property);
+                    };
+                    if let Expression::Identifier(_) = &property.value {
+                        // aliases an existing binding
+                        continue;
+                    }
+                    let key = property.key.static_name().unwrap();
+                    write!(
+                        self.writer,
+                        "{}auto {} = ",
+                        indent_str,
+                        self.commonjs_export(&key)
+                    )?;
+                    self.print_expression(&property.value)?;
+                    writeln!(self.writer, ";")?;
+                }
+            } else {
+                let name = self.linkage.unwrap().default_export.as_str();
+                write!(self.writer, "{}auto {} = ", indent_str, name)?;
+                self.print_expression(&assign.right)?;
+                writeln!(self.writer, ";")?;
+            }
+        } else {
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    fn print_node(&mut self, node: &oxc::semantic::AstNode) -> Result<(), std::io::Error> {
+        if let oxc::ast::AstKind::Program(program) = node.kind() {
+            self.print_interfaces(program)?;
+            self.print_statements(&program.body, 0)?;
         }
 
         Ok(())
     }
 
-    fn print_node(&mut self, node: &oxc::semantic::AstNode) {
-        match node.kind() {
-            oxc::ast::AstKind::Program(program) => {
-                program
-                    .body
-                    .iter()
-                    .for_each(|node| self.print_statement(&node, 0).unwrap());
+    /// Interfaces are hoisted like every other TypeScript type.
+    fn print_interfaces(&mut self, program: &Program) -> Result<(), std::io::Error> {
+        for node in &program.body {
+            match node {
+                Statement::TSInterfaceDeclaration(decl) => self.print_interface(decl)?,
+                Statement::ExportNamedDeclaration(export) => {
+                    if let Some(Declaration::TSInterfaceDeclaration(decl)) = &export.declaration {
+                        self.print_interface(decl)?;
+                    }
+                }
+                _ => {}
             }
-            _ => {} //println!("{:#?}", node.kind());
         }
+
+        Ok(())
     }
 
-    pub fn print_program(&mut self, _program: &Program) -> Result<(), std::io::Error> {
-        writeln!(self.writer, "#include <stdio.h>")?;
-        writeln!(self.writer, "#include <stdlib.h>")?;
-        writeln!(self.writer, "#include <math.h>")?;
-        writeln!(self.writer, "#include <string.h>")?;
-        writeln!(self.writer, "#include <stdbool.h>")?;
-        writeln!(self.writer, "#include <stdint.h>")?;
-        writeln!(self.writer, "#include \"js.h\"")?;
+    pub fn print_header(writer: &mut T) -> Result<(), std::io::Error> {
+        writeln!(writer, "#include <stdio.h>")?;
+        writeln!(writer, "#include <stdlib.h>")?;
+        writeln!(writer, "#include <math.h>")?;
+        writeln!(writer, "#include <string.h>")?;
+        writeln!(writer, "#include <stdbool.h>")?;
+        writeln!(writer, "#include <stdint.h>")?;
+        writeln!(writer, "#include <functional>")?;
+        writeln!(writer, "#include \"js.h\"")?;
 
-        writeln!(self.writer, "int main(int argc, char** argv) {{")?;
-        writeln!(self.writer, "    process::setargs(argc, argv);")?;
-        self.print_node(self.semantic.nodes().root_node().unwrap());
+        writeln!(writer, "int main(int argc, char** argv) {{")?;
+        writeln!(writer, "    process::setargs(argc, argv);")?;
+
+        Ok(())
+    }
 
-        writeln!(self.writer, "return 0;")?;
-        writeln!(self.writer, "}}")?;
-        writeln!(self.writer, "")?;
+    /// Prints the top-level statements of one module into the body of `main`.
+    pub fn print_module(&mut self) -> Result<(), std::io::Error> {
+        self.print_node(self.semantic.nodes().root_node().unwrap())
+    }
+
+    /// Declares the top-level `symbols` of the module ahead of every module, for the functions
+    /// of a module evaluated earlier that read them through a circular import. Returns the name
+    /// of one whose C++ type is not known, which cannot be declared.
+    pub fn print_declarations(
+        &mut self,
+        symbols: &[SymbolId],
+    ) -> Result<Option<String>, std::io::Error> {
+        for &symbol_id in symbols {
+            let name = self.semantic.symbols().get_name(symbol_id).to_string();
+            match self.semantic.symbol_declaration(symbol_id).kind() {
+                AstKind::Function(function) if self.signature(function).is_some() => {
+                    self.print_function_declaration(function, 0)?;
+                }
+                AstKind::VariableDeclarator(_) => {
+                    match self.types.map(|types| types.symbol(symbol_id)) {
+                        Some(Type::Unknown) | None => return Ok(Some(name)),
+                        Some(t) => {
+                            self.print_type(&t)?;
+                            writeln!(self.writer, " {};", self.symbol_name(symbol_id))?;
+                        }
+                    }
+                }
+                _ => return Ok(Some(name)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Makes an instrumented program write its profile to `path` when it exits.
+    pub fn print_profile_output(writer: &mut T, path: &str) -> Result<(), std::io::Error> {
+        writeln!(writer, "    js_profile_open({:?});", path)
+    }
+
+    pub fn print_footer(writer: &mut T) -> Result<(), std::io::Error> {
+        writeln!(writer, "return 0;")?;
+        writeln!(writer, "}}")?;
+        writeln!(writer)?;
 
         Ok(())
     }
 }
+
+/// The error for a construct the code generator cannot lower.
+fn unsupported(construct: &str) -> std::io::Error {
+    std::io::Error::other(format!("{construct} is not supported"))
+}
+
+/// Whether `function` has a `return` with a value, nested functions aside.
+fn returns_value(function: &Function) -> bool {
+    struct Returns(bool);
+
+    impl<'a> Visit<'a> for Returns {
+        fn visit_return_statement(&mut self, statement: &ReturnStatement<'a>) {
+            self.0 |= statement.argument.is_some();
+        }
+
+        fn visit_function(&mut self, _function: &Function<'a>, _flags: ScopeFlags) {}
+
+        fn visit_arrow_function_expression(&mut self, _arrow: &ArrowFunctionExpression<'a>) {}
+    }
+
+    let mut returns = Returns(false);
+    if let Some(body) = &function.body {
+        returns.visit_function_body(body);
+    }
+    returns.0
+}
+
+/// The runtime function computing `operator` on two int32 operands as a double, for results that
+/// may leave the int32 range.
+fn int32_arithmetic(operator: BinaryOperator) -> Option<&'static str> {
+    match operator {
+        BinaryOperator::Addition => Some("js_int32_add"),
+        BinaryOperator::Subtraction => Some("js_int32_sub"),
+        BinaryOperator::Multiplication => Some("js_int32_mul"),
+        BinaryOperator::Remainder => Some("js_int32_mod"),
+        _ => None,
+    }
+}
+
+/// The `x / d` of `Math.floor(x / d)` for a literal `d`.
+fn floor_division<'e, 'a>(call: &'e CallExpression<'a>) -> Option<&'e BinaryExpression<'a>> {
+    let Expression::StaticMemberExpression(callee) = &call.callee else {
+        return None;
+    };
+    if !matches!(&callee.object, Expression::Identifier(object) if object.name == "Math")
+        || callee.property.name != "floor"
+        || call.arguments.len() != 1
+    {
+        return None;
+    }
+    constant_division(call.arguments[0].as_expression()?)
+}
+
+/// The `x / d` of `(x / d) | 0` for a literal `d`.
+fn truncated_division<'e, 'a>(expr: &'e BinaryExpression<'a>) -> Option<&'e BinaryExpression<'a>> {
+    match &expr.right {
+        Expression::NumericLiteral(zero)
+            if expr.operator == BinaryOperator::BitwiseOR && zero.value == 0.0 =>
+        {
+            constant_division(&expr.left)
+        }
+        _ => None,
+    }
+}
+
+fn constant_division<'e, 'a>(expr: &'e Expression<'a>) -> Option<&'e BinaryExpression<'a>> {
+    match expr.without_parentheses() {
+        Expression::BinaryExpression(division)
+            if division.operator == BinaryOperator::Division
+                && constant_divisor(&division.right).is_some() =>
+        {
+            Some(division)
+        }
+        _ => None,
+    }
+}
+
+/// The function `statement` declares.
+fn declared_function<'s, 'a>(statement: &'s Statement<'a>) -> Option<&'s Function<'a>> {
+    match statement {
+        Statement::FunctionDeclaration(function) => Some(function),
+        Statement::ExportNamedDeclaration(export) => match &export.declaration {
+            Some(Declaration::FunctionDeclaration(function)) => Some(function),
+            _ => None,
+        },
+        Statement::ExportDefaultDeclaration(export) => match &export.declaration {
+            ExportDefaultDeclarationKind::FunctionDeclaration(function) => Some(function),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,700 @@
+use std::collections::{HashMap, HashSet};
+
+use oxc::{
+    allocator::{Allocator, Vec as ArenaVec},
+    ast::{
+        ast::{
+            ArrowFunctionExpression, AssignmentExpression, AssignmentTarget, BinaryOperator,
+            BindingIdentifier, CallExpression, ConditionalExpression, DoWhileStatement, Expression,
+            ForInStatement, ForOfStatement, ForStatement, Function, IfStatement, LogicalExpression,
+            Program, SimpleAssignmentTarget, Statement, SwitchStatement, TSTypeAnnotation,
+            TryStatement, UnaryOperator, UpdateExpression, VariableDeclarationKind,
+            VariableDeclarator, WhileStatement,
+        },
+        visit::{walk, walk_mut},
+        AstBuilder, Visit, VisitMut,
+    },
+    semantic::{ScopeFlags, Semantic, SymbolId},
+    span::{GetSpan, Span, SPAN},
+};
+
+use crate::{
+    dataflow::Dataflow, fresh_names::FreshNames, overflow_analysis::NodeAddress, remarks::Remark,
+};
+
+/// Computes an expression once when it occurs again with the same value, keeping the first
+/// occurrence in a variable declared just before its statement:
+///
+/// ```js
+/// a[i] = a[i] + b[i] * b[i];
+/// // becomes
+/// const b_i = b[i];
+/// a[i] = a[i] + b_i * b_i;
+/// ```
+///
+/// The expressions are arithmetic, comparisons, element loads and property reads of variables
+/// and literals. A later occurrence reuses the first one when that one is evaluated on every
+/// path to it: earlier in the same statement list, or before the `if`, loop or block holding
+/// it. Assigning a variable the expression reads makes it compute anew, so does storing an
+/// array element for element loads, storing a property of the same name for property reads,
+/// and calling a function of the program for all of them.
+///
+/// The first occurrence must be evaluated whenever its statement starts: not under `&&`, `||`
+/// or `?:`, nor after a store or a call in the statement, nor in a loop header. A `const`
+/// initialized with the expression is reused as it is. Divisions are left alone: the code
+/// generator turns `Math.floor(a / b)` and `(a / b) | 0` into integer divisions, which a shared
+/// quotient would prevent.
+pub struct CommonSubexpressionOptimization<'a> {
+    ast: AstBuilder<'a>,
+    /// The variable declared for each first occurrence that is reused.
+    first: HashMap<NodeAddress, String>,
+    /// The variable each reused occurrence becomes.
+    reused: HashMap<NodeAddress, String>,
+    /// The declarations to insert before the statement being rewritten.
+    declarations: Vec<Statement<'a>>,
+    pub remarks: Vec<Remark>,
+}
+
+/// What an expression computes from, as far as telling when it changes.
+#[derive(Clone, Default)]
+struct Value {
+    /// The expression with its variables resolved: equal keys compute equal values.
+    key: String,
+    reads: HashSet<SymbolId>,
+    /// It loads an array element.
+    elements: bool,
+    properties: HashSet<String>,
+    /// It computes something, rather than being a variable or a literal.
+    operations: usize,
+}
+
+/// An expression computed on every path to the node being visited.
+#[derive(Clone)]
+struct Available {
+    value: Value,
+    first: NodeAddress,
+    /// The `const` it initializes.
+    variable: Option<String>,
+}
+
+/// What some code may change.
+#[derive(Default)]
+struct Kills {
+    symbols: HashSet<SymbolId>,
+    /// An element, or a property through a computed key, is stored.
+    elements: bool,
+    properties: HashSet<String>,
+    calls: bool,
+}
+
+impl Kills {
+    fn is_empty(&self) -> bool {
+        self.symbols.is_empty() && !self.elements && self.properties.is_empty() && !self.calls
+    }
+
+    /// Why `value` may differ after this code, if it may.
+    fn reason(&self, semantic: &Semantic, value: &Value) -> Option<String> {
+        if self.calls {
+            return Some("a function is called in between".to_string());
+        }
+        if let Some(symbol) = value
+            .reads
+            .iter()
+            .find(|symbol| self.symbols.contains(symbol))
+        {
+            let name = semantic.symbols().get_name(*symbol);
+            return Some(format!("`{name}` is assigned in between"));
+        }
+        if self.elements && (value.elements || !value.properties.is_empty()) {
+            return Some("an element is stored in between".to_string());
+        }
+        let property = value
+            .properties
+            .iter()
+            .find(|property| self.properties.contains(*property));
+        property.map(|property| format!("a `{property}` property is stored in between"))
+    }
+}
+
+/// The address of the node of an expression this pass may reuse.
+fn address(expr: &Expression) -> Option<NodeAddress> {
+    Some(match expr {
+        Expression::BinaryExpression(binary) => NodeAddress::of(&**binary),
+        Expression::UnaryExpression(unary) => NodeAddress::of(&**unary),
+        Expression::ComputedMemberExpression(member) => NodeAddress::of(&**member),
+        Expression::StaticMemberExpression(member) => NodeAddress::of(&**member),
+        _ => return None,
+    })
+}
+
+/// Whether `callee` belongs to the runtime, like `Math.floor` or `console.log`, rather than
+/// to the program.
+fn is_runtime(semantic: &Semantic, callee: &Expression) -> bool {
+    let global = |expr: &Expression| match expr {
+        Expression::Identifier(id) => id.reference_id.get().is_some_and(|reference| {
+            semantic
+                .symbols()
+                .get_reference(reference)
+                .symbol_id()
+                .is_none()
+        }),
+        _ => false,
+    };
+    match callee {
+        Expression::StaticMemberExpression(member) => global(&member.object),
+        callee => global(callee),
+    }
+}
+
+/// The name of the variable holding a reused expression.
+fn base_name(expr: &Expression) -> String {
+    match expr {
+        Expression::ComputedMemberExpression(member) => {
+            match (&member.object, &member.expression) {
+                (Expression::Identifier(array), Expression::Identifier(index)) => {
+                    format!("{}_{}", array.name, index.name)
+                }
+                (Expression::Identifier(array), Expression::NumericLiteral(index))
+                    if index.value.fract() == 0.0 && index.value >= 0.0 =>
+                {
+                    format!("{}_{}", array.name, index.value)
+                }
+                (Expression::Identifier(array), _) => format!("{}_element", array.name),
+                _ => "element".to_string(),
+            }
+        }
+        Expression::StaticMemberExpression(member) => match &member.object {
+            Expression::Identifier(object) => format!("{}_{}", object.name, member.property.name),
+            _ => member.property.name.to_string(),
+        },
+        _ => "common".to_string(),
+    }
+}
+
+/// Collects what some code may change.
+struct Effects<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    kills: Kills,
+}
+
+impl Effects<'_, '_> {
+    fn store(&mut self, target: &SimpleAssignmentTarget) {
+        match target {
+            SimpleAssignmentTarget::AssignmentTargetIdentifier(id) => {
+                let symbol = id.reference_id.get().and_then(|reference| {
+                    self.semantic.symbols().get_reference(reference).symbol_id()
+                });
+                self.kills.symbols.extend(symbol);
+            }
+            SimpleAssignmentTarget::ComputedMemberExpression(_) => self.kills.elements = true,
+            SimpleAssignmentTarget::StaticMemberExpression(member) => {
+                let property = member.property.name.as_str();
+                self.kills.elements |= property == "length";
+                self.kills.properties.insert(property.to_string());
+            }
+            _ => self.kills.calls = true,
+        }
+    }
+}
+
+impl<'a> Visit<'a> for Effects<'_, 'a> {
+    fn visit_assignment_expression(&mut self, assign: &AssignmentExpression<'a>) {
+        match assign.left.as_simple_assignment_target() {
+            Some(target) => self.store(target),
+            None => self.kills.calls = true,
+        }
+        walk::walk_assignment_expression(self, assign);
+    }
+
+    fn visit_update_expression(&mut self, update: &UpdateExpression<'a>) {
+        self.store(&update.argument);
+        walk::walk_update_expression(self, update);
+    }
+
+    fn visit_binding_identifier(&mut self, id: &BindingIdentifier<'a>) {
+        self.kills.symbols.extend(id.symbol_id.get());
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression<'a>) {
+        self.kills.calls |= !is_runtime(self.semantic, &call.callee);
+        walk::walk_call_expression(self, call);
+    }
+
+    // a nested function changes things when it is called
+    fn visit_function(&mut self, _function: &Function<'a>, _flags: ScopeFlags) {}
+
+    fn visit_arrow_function_expression(&mut self, _arrow: &ArrowFunctionExpression<'a>) {}
+}
+
+/// Finds the expressions computed again, walking each function in evaluation order.
+struct Values<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    names: FreshNames,
+    available: HashMap<String, Available>,
+    /// Why each expression that was available no longer is.
+    killed: HashMap<String, String>,
+    /// Where each first occurrence is, and how often it is reused.
+    uses: HashMap<NodeAddress, (Span, usize)>,
+    first: HashMap<NodeAddress, String>,
+    reused: HashMap<NodeAddress, String>,
+    /// A first occurrence here can move in front of the current statement.
+    hoistable: bool,
+    /// The expression being visited initializes this `const`.
+    initializes: Option<(NodeAddress, String)>,
+    remarks: Vec<Remark>,
+}
+
+impl<'a> Values<'_, 'a> {
+    /// The value of `expr` when it is made of variables, literals and the operations this pass
+    /// reuses.
+    fn value(&self, expr: &Expression) -> Option<Value> {
+        let mut value = Value::default();
+        self.describe(expr, &mut value)?;
+        Some(value)
+    }
+
+    fn describe(&self, expr: &Expression, value: &mut Value) -> Option<()> {
+        match expr {
+            Expression::Identifier(id) => {
+                let symbols = self.semantic.symbols();
+                let symbol = symbols.get_reference(id.reference_id.get()?).symbol_id()?;
+                value.reads.insert(symbol);
+                value.key += &format!("{}#{symbol:?}", id.name);
+            }
+            Expression::NumericLiteral(literal) => value.key += &format!("{:?}", literal.value),
+            Expression::BooleanLiteral(literal) => value.key += &literal.value.to_string(),
+            Expression::StringLiteral(literal) => value.key += &format!("{:?}", literal.value),
+            Expression::ParenthesizedExpression(expr) => self.describe(&expr.expression, value)?,
+            Expression::UnaryExpression(unary) => {
+                if matches!(unary.operator, UnaryOperator::Delete | UnaryOperator::Void) {
+                    return None;
+                }
+                value.operations += 1;
+                value.key += unary.operator.as_str();
+                value.key += "(";
+                self.describe(&unary.argument, value)?;
+                value.key += ")";
+            }
+            Expression::BinaryExpression(binary) => {
+                if matches!(
+                    binary.operator,
+                    BinaryOperator::In | BinaryOperator::Instanceof | BinaryOperator::Division
+                ) {
+                    return None;
+                }
+                value.operations += 1;
+                value.key += "(";
+                self.describe(&binary.left, value)?;
+                value.key += binary.operator.as_str();
+                self.describe(&binary.right, value)?;
+                value.key += ")";
+            }
+            Expression::ComputedMemberExpression(member) => {
+                value.operations += 1;
+                value.elements = true;
+                self.describe(&member.object, value)?;
+                value.key += "[";
+                self.describe(&member.expression, value)?;
+                value.key += "]";
+            }
+            Expression::StaticMemberExpression(member) => {
+                value.operations += 1;
+                self.describe(&member.object, value)?;
+                value.key += ".";
+                value.key += &member.property.name;
+                value.properties.insert(member.property.name.to_string());
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn kill(&mut self, kills: &Kills) {
+        if kills.is_empty() {
+            return;
+        }
+        let semantic = self.semantic;
+        let killed = &mut self.killed;
+        self.available.retain(|key, available| {
+            let reason = kills.reason(semantic, &available.value);
+            if let Some(reason) = &reason {
+                killed.insert(key.clone(), reason.clone());
+            }
+            reason.is_none()
+        });
+    }
+
+    fn effects(&self, visit: impl FnOnce(&mut Effects<'_, 'a>)) -> Kills {
+        let mut effects = Effects {
+            semantic: self.semantic,
+            kills: Kills::default(),
+        };
+        visit(&mut effects);
+        effects.kills
+    }
+
+    /// After a statement changed something, its later expressions cannot move in front of it.
+    fn write(&mut self, kills: Kills) {
+        if !kills.is_empty() {
+            self.hoistable = false;
+        }
+        self.kill(&kills);
+    }
+
+    /// Visits code that runs only sometimes, with what is available before it.
+    fn branch(&mut self, visit: impl FnOnce(&mut Self)) {
+        let before = self.available.clone();
+        let hoistable = std::mem::replace(&mut self.hoistable, false);
+        visit(self);
+        self.hoistable = hoistable;
+        self.available = before;
+    }
+
+    /// Visits code that may run any number of times, or be entered from elsewhere than the
+    /// code before it: only what none of it changes is available inside.
+    fn region(&mut self, kills: Kills, visit: impl FnOnce(&mut Self)) {
+        self.kill(&kills);
+        self.branch(visit);
+        self.write(kills);
+    }
+
+    /// Visits a function with nothing available, since it runs when it is called.
+    fn function(&mut self, visit: impl FnOnce(&mut Self)) {
+        let available = std::mem::take(&mut self.available);
+        let killed = std::mem::take(&mut self.killed);
+        let hoistable = std::mem::replace(&mut self.hoistable, false);
+        visit(self);
+        self.available = available;
+        self.killed = killed;
+        self.hoistable = hoistable;
+    }
+}
+
+impl<'a> Visit<'a> for Values<'_, 'a> {
+    fn visit_statements(&mut self, statements: &ArenaVec<'a, Statement<'a>>) {
+        let hoistable = self.hoistable;
+        for statement in statements {
+            self.hoistable = true;
+            self.visit_statement(statement);
+        }
+        self.hoistable = hoistable;
+    }
+
+    fn visit_expression(&mut self, expr: &Expression<'a>) {
+        let value = self
+            .value(expr)
+            .filter(|value| value.operations > 0 && !value.reads.is_empty());
+        let (Some(address), Some(value), false) =
+            (address(expr), value, expr.span().is_unspanned())
+        else {
+            walk::walk_expression(self, expr);
+            return;
+        };
+
+        if let Some(available) = self.available.get(&value.key) {
+            let first = available.first;
+            let name = match &available.variable {
+                Some(variable) => variable.clone(),
+                None => {
+                    let names = &mut self.names;
+                    let name = self
+                        .first
+                        .entry(first)
+                        .or_insert_with(|| names.fresh(&base_name(expr)));
+                    name.clone()
+                }
+            };
+            self.uses.entry(first).or_insert((SPAN, 0)).1 += 1;
+            self.reused.insert(address, name);
+            return;
+        }
+        if let Some(reason) = self.killed.remove(&value.key) {
+            let source = expr.span().source_text(self.semantic.source_text());
+            self.remarks.push(Remark::missed(
+                expr.span(),
+                format!("`{source}` computed again: {reason}"),
+            ));
+        }
+
+        let initializes = self.initializes.take();
+        walk::walk_expression(self, expr);
+        if self.hoistable {
+            let variable = initializes
+                .filter(|(initialized, _)| *initialized == address)
+                .map(|(_, name)| name);
+            self.uses.insert(address, (expr.span(), 0));
+            self.available.insert(
+                value.key.clone(),
+                Available {
+                    value,
+                    first: address,
+                    variable,
+                },
+            );
+        }
+    }
+
+    fn visit_variable_declarator(&mut self, declarator: &VariableDeclarator<'a>) {
+        let kills = self.effects(|effects| effects.visit_binding_pattern(&declarator.id));
+        // the declaration shadows any `const` of the same name holding an expression
+        let symbols = self.semantic.symbols();
+        let declared: HashSet<_> = kills
+            .symbols
+            .iter()
+            .map(|symbol| symbols.get_name(*symbol))
+            .collect();
+        self.available.retain(|_, available| {
+            !matches!(&available.variable, Some(variable) if declared.contains(variable.as_str()))
+        });
+
+        let id = declarator.id.get_binding_identifier();
+        if let (Some(id), Some(init), VariableDeclarationKind::Const) =
+            (id, &declarator.init, declarator.kind)
+        {
+            self.initializes = address(init).map(|address| (address, id.name.to_string()));
+        }
+        if let Some(init) = &declarator.init {
+            self.visit_expression(init);
+        }
+        self.initializes = None;
+        self.kill(&kills);
+        // the next declarators cannot move in front of this one
+        self.hoistable = false;
+    }
+
+    fn visit_assignment_expression(&mut self, assign: &AssignmentExpression<'a>) {
+        // the target is evaluated, then the value, then stored
+        match &assign.left {
+            AssignmentTarget::ComputedMemberExpression(member) => {
+                self.visit_expression(&member.object);
+                self.visit_expression(&member.expression);
+            }
+            AssignmentTarget::StaticMemberExpression(member) => {
+                self.visit_expression(&member.object);
+            }
+            _ => {}
+        }
+        self.visit_expression(&assign.right);
+        let kills = self.effects(|effects| effects.visit_assignment_expression(assign));
+        self.write(kills);
+    }
+
+    fn visit_update_expression(&mut self, update: &UpdateExpression<'a>) {
+        let kills = self.effects(|effects| effects.visit_update_expression(update));
+        self.write(kills);
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression<'a>) {
+        // a method is not read apart from its call
+        match &call.callee {
+            Expression::StaticMemberExpression(member) => self.visit_expression(&member.object),
+            Expression::ComputedMemberExpression(member) => {
+                self.visit_expression(&member.object);
+                self.visit_expression(&member.expression);
+            }
+            callee => self.visit_expression(callee),
+        }
+        self.visit_arguments(&call.arguments);
+        let kills = self.effects(|effects| effects.visit_call_expression(call));
+        self.write(kills);
+    }
+
+    fn visit_logical_expression(&mut self, logical: &LogicalExpression<'a>) {
+        self.visit_expression(&logical.left);
+        self.branch(|values| values.visit_expression(&logical.right));
+        let kills = self.effects(|effects| effects.visit_expression(&logical.right));
+        self.write(kills);
+    }
+
+    fn visit_conditional_expression(&mut self, conditional: &ConditionalExpression<'a>) {
+        self.visit_expression(&conditional.test);
+        self.branch(|values| values.visit_expression(&conditional.consequent));
+        self.branch(|values| values.visit_expression(&conditional.alternate));
+        let kills = self.effects(|effects| {
+            effects.visit_expression(&conditional.consequent);
+            effects.visit_expression(&conditional.alternate);
+        });
+        self.write(kills);
+    }
+
+    fn visit_if_statement(&mut self, if_: &IfStatement<'a>) {
+        // each branch sees what the test and the statements before it computed
+        self.visit_expression(&if_.test);
+        self.branch(|values| values.visit_statement(&if_.consequent));
+        if let Some(alternate) = &if_.alternate {
+            self.branch(|values| values.visit_statement(alternate));
+        }
+        let kills = self.effects(|effects| {
+            effects.visit_statement(&if_.consequent);
+            if let Some(alternate) = &if_.alternate {
+                effects.visit_statement(alternate);
+            }
+        });
+        self.write(kills);
+    }
+
+    fn visit_for_statement(&mut self, for_: &ForStatement<'a>) {
+        let kills = self.effects(|effects| effects.visit_for_statement(for_));
+        self.region(kills, |values| walk::walk_for_statement(values, for_));
+    }
+
+    fn visit_for_in_statement(&mut self, for_in: &ForInStatement<'a>) {
+        let kills = self.effects(|effects| effects.visit_for_in_statement(for_in));
+        self.region(kills, |values| walk::walk_for_in_statement(values, for_in));
+    }
+
+    fn visit_for_of_statement(&mut self, for_of: &ForOfStatement<'a>) {
+        let kills = self.effects(|effects| effects.visit_for_of_statement(for_of));
+        self.region(kills, |values| walk::walk_for_of_statement(values, for_of));
+    }
+
+    fn visit_while_statement(&mut self, while_: &WhileStatement<'a>) {
+        let kills = self.effects(|effects| effects.visit_while_statement(while_));
+        self.region(kills, |values| walk::walk_while_statement(values, while_));
+    }
+
+    fn visit_do_while_statement(&mut self, do_while: &DoWhileStatement<'a>) {
+        let kills = self.effects(|effects| effects.visit_do_while_statement(do_while));
+        self.region(kills, |values| {
+            walk::walk_do_while_statement(values, do_while)
+        });
+    }
+
+    // a case may also be entered by falling through the one before
+    fn visit_switch_statement(&mut self, switch: &SwitchStatement<'a>) {
+        self.visit_expression(&switch.discriminant);
+        let kills = self.effects(|effects| {
+            for case in &switch.cases {
+                effects.visit_switch_case(case);
+            }
+        });
+        self.region(kills, |values| {
+            for case in &switch.cases {
+                values.branch(|values| values.visit_switch_case(case));
+            }
+        });
+    }
+
+    // a `catch` may be entered from anywhere in the `try`
+    fn visit_try_statement(&mut self, try_: &TryStatement<'a>) {
+        let kills = self.effects(|effects| effects.visit_try_statement(try_));
+        self.region(kills, |values| {
+            values.branch(|values| values.visit_block_statement(&try_.block));
+            if let Some(handler) = &try_.handler {
+                values.branch(|values| values.visit_catch_clause(handler));
+            }
+            if let Some(finalizer) = &try_.finalizer {
+                values.branch(|values| values.visit_block_statement(finalizer));
+            }
+        });
+    }
+
+    fn visit_function(&mut self, function: &Function<'a>, flags: ScopeFlags) {
+        self.function(|values| walk::walk_function(values, function, flags));
+    }
+
+    fn visit_arrow_function_expression(&mut self, arrow: &ArrowFunctionExpression<'a>) {
+        self.function(|values| walk::walk_arrow_function_expression(values, arrow));
+    }
+}
+
+impl<'a> CommonSubexpressionOptimization<'a> {
+    pub fn new(
+        dataflow: &Dataflow<'_, 'a>,
+        allocator: &'a Allocator,
+        program: &Program<'a>,
+    ) -> Self {
+        let semantic = dataflow.semantic();
+        let mut values = Values {
+            semantic,
+            names: FreshNames::new(semantic),
+            available: HashMap::new(),
+            killed: HashMap::new(),
+            uses: HashMap::new(),
+            first: HashMap::new(),
+            reused: HashMap::new(),
+            hoistable: false,
+            initializes: None,
+            remarks: vec![],
+        };
+        values.visit_program(program);
+
+        let mut uses: Vec<_> = values
+            .uses
+            .into_values()
+            .filter(|(_, uses)| *uses > 0)
+            .collect();
+        uses.sort_by_key(|(span, _)| span.start);
+        for (span, uses) in uses {
+            let source = span.source_text(semantic.source_text());
+            values.remarks.push(Remark::applied(
+                span,
+                format!(
+                    "`{source}` computed once and reused {uses} time{}",
+                    if uses == 1 { "" } else { "s" }
+                ),
+            ));
+        }
+        Self {
+            ast: AstBuilder::new(allocator),
+            first: values.first,
+            reused: values.reused,
+            declarations: vec![],
+            remarks: values.remarks,
+        }
+    }
+}
+
+impl<'a> VisitMut<'a> for CommonSubexpressionOptimization<'a> {
+    fn visit_statements(&mut self, statements: &mut ArenaVec<'a, Statement<'a>>) {
+        let old = std::mem::replace(statements, self.ast.vec());
+        for mut statement in old {
+            let outer = std::mem::take(&mut self.declarations);
+            self.visit_statement(&mut statement);
+            let declarations = std::mem::replace(&mut self.declarations, outer);
+            statements.extend(declarations);
+            statements.push(statement);
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'a>) {
+        let Some(address) = address(expr) else {
+            walk_mut::walk_expression(self, expr);
+            return;
+        };
+        if let Some(name) = self.reused.get(&address) {
+            *expr = self
+                .ast
+                .expression_identifier_reference(expr.span(), name.as_str());
+            return;
+        }
+        walk_mut::walk_expression(self, expr);
+        let Some(name) = self.first.get(&address) else {
+            return;
+        };
+        let name = self.ast.atom(name);
+        let span = expr.span();
+        let value = self.ast.move_expression(expr);
+        let declarator = self.ast.variable_declarator(
+            SPAN,
+            VariableDeclarationKind::Const,
+            self.ast.binding_pattern(
+                self.ast
+                    .binding_pattern_kind_binding_identifier(SPAN, name.clone()),
+                None::<TSTypeAnnotation>,
+                false,
+            ),
+            Some(value),
+            false,
+        );
+        self.declarations.push(Statement::VariableDeclaration(
+            self.ast.alloc_variable_declaration(
+                SPAN,
+                VariableDeclarationKind::Const,
+                self.ast.vec1(declarator),
+                false,
+            ),
+        ));
+        *expr = self.ast.expression_identifier_reference(span, name);
+    }
+}
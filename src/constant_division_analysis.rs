@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+
+use oxc::ast::{
+    ast::{BinaryOperator, Expression},
+    AstKind,
+};
+
+use crate::{
+    dataflow::{Dataflow, RangeAnalysis},
+    overflow_analysis::NodeAddress,
+    type_inference::Types,
+};
+
+/// Finds the `x / d` and `x % d` by a literal `d` whose dividend is a whole number in
+/// `0..2^31` on every path, so they can be computed with a multiply and a shift instead of
+/// a division or `fmod`.
+pub fn unsigned_divisions(dataflow: &Dataflow, types: &Types) -> HashSet<NodeAddress> {
+    let semantic = dataflow.semantic();
+    let ranges = dataflow.solve(RangeAnalysis::with_types(types));
+
+    let mut unsigned = HashSet::new();
+    for node in semantic.nodes().iter() {
+        let AstKind::BinaryExpression(expr) = node.kind() else {
+            continue;
+        };
+        if !matches!(
+            expr.operator,
+            BinaryOperator::Division | BinaryOperator::Remainder
+        ) || constant_divisor(&expr.right).is_none()
+        {
+            continue;
+        }
+        let dividend = ranges.range(&expr.left, node.id());
+        if dividend.is_int32() && dividend.min >= 0.0 {
+            unsigned.insert(NodeAddress::of(expr));
+        }
+    }
+    unsigned
+}
+
+/// The value of `expr` if it is a literal whole number in `1..2^31`.
+pub fn constant_divisor(expr: &Expression) -> Option<u32> {
+    match expr.without_parentheses() {
+        Expression::NumericLiteral(literal)
+            if literal.value.fract() == 0.0
+                && literal.value >= 1.0
+                && literal.value <= i32::MAX as f64 =>
+        {
+            Some(literal.value as u32)
+        }
+        _ => None,
+    }
+}
+
+/// The `m` and `s` for which `x / d` rounded down is `(x * m) >> s` for every `x` in
+/// `0..2^31`: with `2^l` the smallest power of two not below `d`, `s = 31 + l` and `m` is
+/// `2^s / d` rounded up. Its error `m * d - 2^s` is below `d`, so at most `2^(s - 31)`, which
+/// keeps the product of every such `x` within the same multiple of `2^s`, and it fits in 64 bits.
+pub fn magic(divisor: u32) -> (u64, u32) {
+    let shift = 31 + (32 - (divisor - 1).leading_zeros());
+    let multiplier = (1u64 << shift).div_ceil(divisor as u64);
+    (multiplier, shift)
+}
@@ -0,0 +1,313 @@
+use oxc::{
+    allocator::Allocator,
+    ast::{
+        ast::{BinaryOperator, Expression, NumberBase, ObjectProperty, Program, UnaryOperator},
+        visit::walk_mut,
+        AstBuilder, AstKind, VisitMut,
+    },
+    semantic::Semantic,
+    span::{GetSpan, Span},
+};
+
+use crate::{
+    dataflow::{fold, math, math_function, to_int32, ConstantPropagation, Dataflow, Results},
+    remarks::Remark,
+    type_inference::{Type, TypeInference, Types},
+};
+
+/// Replaces expressions whose value is known at compile time with a literal, including reads of
+/// variables holding a constant:
+///
+/// ```js
+/// const N = 1024;
+/// a[N * 4] = Math.floor(10 / 3);   // a[4096] = 3
+/// ```
+///
+/// Values are computed with the ECMAScript semantics; those C++ has no literal for (NaN and the
+/// infinities) stay as they are, and -0 becomes the double `-0.0`.
+pub struct ConstantFoldingOptimization<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    dataflow: &'s Dataflow<'s, 'a>,
+    constants: Results<'s, 's, 'a, ConstantPropagation>,
+    /// The C++ type of every expression before folding, which a literal must keep. `None` when
+    /// the module does not type check, so nothing is folded.
+    types: Option<Types>,
+    ast: AstBuilder<'a>,
+    pub remarks: Vec<Remark>,
+}
+
+/// A primitive computed at compile time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Value {
+    Number(f64),
+    Boolean(bool),
+}
+
+impl Value {
+    /// ECMAScript ToNumber.
+    fn number(self) -> f64 {
+        match self {
+            Value::Number(value) => value,
+            Value::Boolean(value) => value as u8 as f64,
+        }
+    }
+
+    /// ECMAScript ToBoolean.
+    fn truthy(self) -> bool {
+        match self {
+            Value::Number(value) => value != 0.0 && !value.is_nan(),
+            Value::Boolean(value) => value,
+        }
+    }
+
+    /// The type inferred for a literal of this value written without a decimal point; an int32
+    /// cannot hold -0, whose literal is `-0.0`.
+    fn literal_type(self) -> Type {
+        match self {
+            Value::Number(value) if value == 0.0 && value.is_sign_negative() => Type::Double,
+            Value::Number(value) if is_int32(value) => Type::Int32,
+            Value::Number(_) => Type::Double,
+            Value::Boolean(_) => Type::Boolean,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(value) if value.is_infinite() => {
+                write!(f, "{}Infinity", if *value < 0.0 { "-" } else { "" })
+            }
+            Value::Number(value) => write!(f, "{value}"),
+            Value::Boolean(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+fn is_int32(value: f64) -> bool {
+    value.fract() == 0.0 && value >= i32::MIN as f64 && value <= i32::MAX as f64
+}
+
+impl<'s, 'a> ConstantFoldingOptimization<'s, 'a> {
+    pub fn new(
+        dataflow: &'s Dataflow<'s, 'a>,
+        allocator: &'a Allocator,
+        program: &Program<'a>,
+    ) -> Self {
+        Self {
+            semantic: dataflow.semantic(),
+            dataflow,
+            constants: dataflow.solve(ConstantPropagation),
+            types: TypeInference::new(dataflow.semantic()).infer(program).ok(),
+            ast: AstBuilder::new(allocator),
+            remarks: vec![],
+        }
+    }
+
+    /// The value of `expr`, when it is the same on every path and evaluating it has no effect.
+    fn evaluate(&self, expr: &Expression<'a>) -> Option<Value> {
+        let value = match expr {
+            Expression::NumericLiteral(literal) => Value::Number(literal.value),
+            Expression::BooleanLiteral(literal) => Value::Boolean(literal.value),
+            Expression::ParenthesizedExpression(expr) => self.evaluate(&expr.expression)?,
+            Expression::TSAsExpression(expr) => self.evaluate(&expr.expression)?,
+            Expression::TSSatisfiesExpression(expr) => self.evaluate(&expr.expression)?,
+            Expression::TSNonNullExpression(expr) => self.evaluate(&expr.expression)?,
+            Expression::Identifier(_) => Value::Number(self.variable(expr)?),
+            Expression::UnaryExpression(expr) => {
+                let value = self.evaluate(&expr.argument)?;
+                match expr.operator {
+                    UnaryOperator::UnaryNegation => Value::Number(-value.number()),
+                    UnaryOperator::UnaryPlus => Value::Number(value.number()),
+                    UnaryOperator::BitwiseNot => Value::Number(!to_int32(value.number()) as f64),
+                    UnaryOperator::LogicalNot => Value::Boolean(!value.truthy()),
+                    _ => return None,
+                }
+            }
+            Expression::BinaryExpression(expr) => {
+                let left = self.evaluate(&expr.left)?;
+                let right = self.evaluate(&expr.right)?;
+                compare(expr.operator, left, right).map_or_else(
+                    || fold(expr.operator, left.number(), right.number()).map(Value::Number),
+                    |result| Some(Value::Boolean(result)),
+                )?
+            }
+            Expression::CallExpression(call) => {
+                let name = math_function(self.dataflow, &call.callee)?;
+                let args = call
+                    .arguments
+                    .iter()
+                    .map(|argument| Some(self.evaluate(argument.as_expression()?)?.number()))
+                    .collect::<Option<Vec<_>>>()?;
+                Value::Number(math(name, &args)?)
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// The value the variable `expr` refers to holds where it is read.
+    fn variable(&self, expr: &Expression<'a>) -> Option<f64> {
+        let symbol = self.dataflow.reference_symbol(expr)?;
+        // a hint chose how the variable is stored; reading a literal instead would bypass it
+        if self.types.as_ref()?.hint(symbol).is_some() {
+            return None;
+        }
+        let Expression::Identifier(id) = expr else {
+            return None;
+        };
+        let symbols = self.semantic.symbols();
+        if self.dataflow.is_tracked(symbol) {
+            let node = symbols.get_reference(id.reference_id.get()?).node_id();
+            return self.constants.value(expr, node);
+        }
+
+        // a constant read from a nested function still has the value it was initialized with
+        if !symbols.get_flags(symbol).is_const_variable() {
+            return None;
+        }
+        let declaration = symbols.get_declaration(symbol);
+        let AstKind::VariableDeclarator(declarator) = self.semantic.nodes().kind(declaration)
+        else {
+            return None;
+        };
+        self.constants.value(declarator.init.as_ref()?, declaration)
+    }
+
+    /// The type of the literal replacing `expr` with `value`: the type `expr` had, so the C++
+    /// around it computes the same way.
+    fn check(&self, expr: &Expression<'a>, value: Value) -> Result<Type, String> {
+        if let Value::Number(number) = value {
+            if number.is_nan() || number.is_infinite() {
+                return Err(format!("its value {value} has no C++ literal"));
+            }
+        }
+        let types = self
+            .types
+            .as_ref()
+            .ok_or("the module does not type check")?;
+        let before = types.expression(self.semantic, expr);
+        let natural = value.literal_type();
+        match (&before, value) {
+            (Type::Unknown, _) => Ok(natural),
+            (Type::Double, Value::Number(_)) => Ok(Type::Double),
+            _ if before == natural => Ok(natural),
+            _ => Err(format!(
+                "its value {value} has no {} literal",
+                before.cpp_name().unwrap_or("struct")
+            )),
+        }
+    }
+
+    /// `value` as a literal of type `ty` at `span`; negative numbers are negated literals.
+    fn literal(&self, span: Span, value: Value, ty: &Type) -> Expression<'a> {
+        let number = match value {
+            Value::Boolean(value) => return self.ast.expression_boolean_literal(span, value),
+            Value::Number(value) => value,
+        };
+        // a decimal point keeps a whole number a double
+        let raw = match ty {
+            Type::Double if number.fract() == 0.0 => {
+                Some(self.ast.atom(&format!("{:?}", number.abs())))
+            }
+            _ => None,
+        };
+        if number == i32::MIN as f64 && *ty == Type::Int32 {
+            // 2147483648 itself would be a double
+            let max = self.ast.expression_numeric_literal(
+                span,
+                i32::MAX as f64,
+                None,
+                NumberBase::Decimal,
+            );
+            return self
+                .ast
+                .expression_unary(span, UnaryOperator::BitwiseNot, max);
+        }
+        let literal =
+            self.ast
+                .expression_numeric_literal(span, number.abs(), raw, NumberBase::Decimal);
+        if number.is_sign_negative() {
+            self.ast
+                .expression_unary(span, UnaryOperator::UnaryNegation, literal)
+        } else {
+            literal
+        }
+    }
+}
+
+/// `left op right` for the comparison operators, `None` for the others.
+fn compare(operator: BinaryOperator, left: Value, right: Value) -> Option<bool> {
+    let loose = || left.number() == right.number();
+    let strict = || match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left == right,
+        (Value::Boolean(left), Value::Boolean(right)) => left == right,
+        _ => false,
+    };
+    // every comparison with NaN is false, which f64 comparisons already are
+    let result = match operator {
+        BinaryOperator::Equality => loose(),
+        BinaryOperator::Inequality => !loose(),
+        BinaryOperator::StrictEquality => strict(),
+        BinaryOperator::StrictInequality => !strict(),
+        BinaryOperator::LessThan => left.number() < right.number(),
+        BinaryOperator::LessEqualThan => left.number() <= right.number(),
+        BinaryOperator::GreaterThan => left.number() > right.number(),
+        BinaryOperator::GreaterEqualThan => left.number() >= right.number(),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Whether `expr` is already what folding would produce.
+fn is_literal(expr: &Expression) -> bool {
+    match expr {
+        Expression::NumericLiteral(_) | Expression::BooleanLiteral(_) => true,
+        Expression::UnaryExpression(expr) => match (&expr.operator, &expr.argument) {
+            (UnaryOperator::UnaryNegation, Expression::NumericLiteral(literal)) => {
+                literal.value != 0.0
+            }
+            (UnaryOperator::BitwiseNot, Expression::NumericLiteral(literal)) => {
+                literal.value == i32::MAX as f64
+            }
+            _ => false,
+        },
+        // folding the contents keeps the parentheses
+        Expression::ParenthesizedExpression(_) => true,
+        _ => false,
+    }
+}
+
+impl<'a> VisitMut<'a> for ConstantFoldingOptimization<'_, 'a> {
+    fn visit_expression(&mut self, expr: &mut Expression<'a>) {
+        if !is_literal(expr) {
+            if let Some(value) = self.evaluate(expr) {
+                let span = expr.span();
+                let source = span.source_text(self.semantic.source_text());
+                match self.check(expr, value) {
+                    Ok(ty) => {
+                        self.remarks.push(Remark::applied(
+                            span,
+                            format!("`{source}` folded to {value}"),
+                        ));
+                        *expr = self.literal(span, value, &ty);
+                        return;
+                    }
+                    Err(reason) => {
+                        self.remarks
+                            .push(Remark::missed(span, format!("`{source}` kept: {reason}")));
+                    }
+                }
+            }
+        }
+
+        walk_mut::walk_expression(self, expr);
+    }
+
+    fn visit_object_property(&mut self, property: &mut ObjectProperty<'a>) {
+        // `{ size }` names the variable it reads
+        if !property.shorthand {
+            walk_mut::walk_object_property(self, property);
+        }
+    }
+}
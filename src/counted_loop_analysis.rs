@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+
+use oxc::{
+    ast::{
+        ast::{
+            ArrowFunctionExpression, AssignmentExpression, AssignmentTarget, BinaryOperator,
+            BreakStatement, CallExpression, ComputedMemberExpression, ContinueStatement,
+            Expression, ForStatement, Function, IdentifierReference, ReturnStatement,
+            SimpleAssignmentTarget, UpdateExpression,
+        },
+        visit::walk,
+        AstKind, Visit,
+    },
+    semantic::{ScopeFlags, Semantic, SymbolId},
+};
+
+use crate::{
+    dataflow::{ConstantPropagation, Dataflow},
+    fresh_names::FreshNames,
+    induction_variables::Loop,
+    overflow_analysis::NodeAddress,
+    type_inference::{Type, Types},
+};
+
+/// A `for` loop whose trip count is known when it starts: an int32 counter stepped by a
+/// constant up or down to a bound that the loop does not change, and no way out but the test.
+/// It compiles to a C++ `for`, which the C++ compiler can vectorize and unroll.
+pub struct CountedLoop {
+    pub counter: SymbolId,
+    /// How much the update adds to the counter.
+    pub step: i64,
+    /// The typed arrays the loop uses, each with the name of a `__restrict` pointer to it. They
+    /// are all allocated by a `new` of their own and never assigned again, so no two of them
+    /// share elements; empty when the loop may reach an array some other way.
+    pub restricted: Vec<(SymbolId, String)>,
+    /// No iteration reads or writes an element that another one writes: every array the loop
+    /// writes is only indexed by the counter.
+    pub independent: bool,
+}
+
+/// Finds the counted loops of a module.
+pub fn counted_loops(dataflow: &Dataflow, types: &Types) -> HashMap<NodeAddress, CountedLoop> {
+    let semantic = dataflow.semantic();
+    let constants = dataflow.solve(ConstantPropagation);
+    let mut names = FreshNames::new(semantic);
+
+    let mut counted = HashMap::new();
+    for node in semantic.nodes().iter() {
+        let AstKind::ForStatement(for_) = node.kind() else {
+            continue;
+        };
+        let loop_ = Loop::new(dataflow, for_).with_induction_variables(for_, &constants);
+        let Some((counter, step)) = counter(semantic, types, &loop_, for_) else {
+            continue;
+        };
+
+        let mut body = Body {
+            semantic,
+            dataflow,
+            types,
+            counter,
+            exits: false,
+            calls: false,
+            foreign: false,
+            arrays: HashMap::new(),
+        };
+        if let Some(test) = &for_.test {
+            body.visit_expression(test);
+        }
+        if let Some(update) = &for_.update {
+            body.visit_expression(update);
+        }
+        body.visit_statement(&for_.body);
+        if body.exits {
+            continue;
+        }
+
+        let mut arrays: Vec<_> = body.arrays.into_iter().collect();
+        arrays.sort_by_key(|(array, _)| *array);
+        let (restricted, independent) = if body.calls || body.foreign || arrays.is_empty() {
+            (vec![], false)
+        } else {
+            let independent = arrays
+                .iter()
+                .all(|(_, access)| !access.written || !access.elsewhere);
+            // an array the body allocates has no pointer yet where the loop starts
+            let restricted = arrays
+                .iter()
+                .filter(|(array, _)| !loop_.contains(semantic.symbols().get_declaration(*array)))
+                .map(|(array, _)| {
+                    let name = semantic.symbols().get_name(*array);
+                    (*array, names.fresh(&format!("{name}_restrict")))
+                })
+                .collect();
+            (restricted, independent)
+        };
+        counted.insert(
+            NodeAddress::of(for_),
+            CountedLoop {
+                counter,
+                step,
+                restricted,
+                independent,
+            },
+        );
+    }
+    counted
+}
+
+/// The counter of a loop whose test compares it against a bound it moves toward, with its step.
+fn counter(
+    semantic: &Semantic,
+    types: &Types,
+    loop_: &Loop,
+    for_: &ForStatement,
+) -> Option<(SymbolId, i64)> {
+    let Some(Expression::BinaryExpression(test)) =
+        for_.test.as_ref().map(|test| test.without_parentheses())
+    else {
+        return None;
+    };
+    let symbol = |expr: &Expression| match expr.without_parentheses() {
+        Expression::Identifier(id) => semantic
+            .symbols()
+            .get_reference(id.reference_id.get()?)
+            .symbol_id(),
+        _ => None,
+    };
+    // as if the counter were on the left
+    let (counter, bound, operator) = match symbol(&test.left).filter(|c| loop_.step(*c).is_some()) {
+        Some(counter) => (counter, &test.right, test.operator),
+        None => (
+            symbol(&test.right)?,
+            &test.left,
+            test.operator.compare_inverse_operator()?,
+        ),
+    };
+    let step = loop_.step(counter)?;
+    let toward = match operator {
+        BinaryOperator::LessThan | BinaryOperator::LessEqualThan => step > 0,
+        BinaryOperator::GreaterThan | BinaryOperator::GreaterEqualThan => step < 0,
+        _ => false,
+    };
+    if !toward || !invariant(semantic, types, loop_, bound) {
+        return None;
+    }
+
+    // a counter that may step past the int32 range became a double
+    if types.symbol(counter) != Type::Int32 {
+        return None;
+    }
+    let update = for_.update.as_ref()?;
+    let overflows = match update.without_parentheses() {
+        Expression::AssignmentExpression(assign) => {
+            types.overflows(update) || types.overflows(&assign.right)
+        }
+        update => types.overflows(update),
+    };
+    (!overflows).then_some((counter, step))
+}
+
+/// `bound` is an integer with the same value in every iteration of the loop.
+fn invariant(semantic: &Semantic, types: &Types, loop_: &Loop, bound: &Expression) -> bool {
+    let fixed = |expr: &Expression| match expr {
+        Expression::Identifier(id) => id
+            .reference_id
+            .get()
+            .and_then(|reference| semantic.symbols().get_reference(reference).symbol_id())
+            .is_some_and(|symbol| loop_.is_fixed(symbol)),
+        _ => false,
+    };
+    let integer = types.expression(semantic, bound).is_integer();
+    integer
+        && match bound.without_parentheses() {
+            Expression::NumericLiteral(_) => true,
+            // typed arrays keep their length
+            Expression::StaticMemberExpression(member) => {
+                member.property.name == "length"
+                    && types.expression(semantic, &member.object).is_typed_array()
+                    && fixed(&member.object)
+            }
+            expr => fixed(expr),
+        }
+}
+
+/// How a loop uses one typed array.
+#[derive(Default)]
+struct Access {
+    /// It stores into an element.
+    written: bool,
+    /// It indexes the array with something other than the counter.
+    elsewhere: bool,
+}
+
+/// What the test, update and body of a loop do.
+struct Body<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    dataflow: &'s Dataflow<'s, 'a>,
+    types: &'s Types,
+    counter: SymbolId,
+    /// It may leave the loop before the test fails.
+    exits: bool,
+    /// It calls a function of the program, which may reach any array.
+    calls: bool,
+    /// It uses a typed array that is not a variable holding an allocation of its own.
+    foreign: bool,
+    arrays: HashMap<SymbolId, Access>,
+}
+
+impl Body<'_, '_> {
+    fn symbol(&self, id: &IdentifierReference) -> Option<SymbolId> {
+        self.semantic
+            .symbols()
+            .get_reference(id.reference_id.get()?)
+            .symbol_id()
+    }
+
+    /// `symbol` is initialized with `new Int32Array(...)` or `new Float64Array(...)` and
+    /// never assigned, nor captured by a closure.
+    fn allocated(&self, symbol: SymbolId) -> bool {
+        let symbols = self.semantic.symbols();
+        let AstKind::VariableDeclarator(declarator) =
+            self.semantic.nodes().kind(symbols.get_declaration(symbol))
+        else {
+            return false;
+        };
+        let allocation = matches!(&declarator.init, Some(Expression::NewExpression(new))
+            if matches!(&new.callee, Expression::Identifier(callee)
+                if callee.name == "Int32Array" || callee.name == "Float64Array"));
+        allocation
+            && self.dataflow.is_tracked(symbol)
+            && symbols
+                .get_resolved_reference_ids(symbol)
+                .iter()
+                .all(|reference| !symbols.get_reference(*reference).is_write())
+    }
+
+    /// The array element `target` stores into.
+    fn store(&mut self, target: &ComputedMemberExpression) {
+        if let Expression::Identifier(id) = &target.object {
+            if let Some(symbol) = self.symbol(id) {
+                self.arrays.entry(symbol).or_default().written = true;
+            }
+        }
+    }
+}
+
+impl<'a> Visit<'a> for Body<'_, 'a> {
+    fn visit_identifier_reference(&mut self, id: &IdentifierReference<'a>) {
+        let Some(symbol) = self.symbol(id) else {
+            return;
+        };
+        if !self.types.symbol(symbol).is_typed_array() {
+            return;
+        }
+        if self.allocated(symbol) {
+            self.arrays.entry(symbol).or_default();
+        } else {
+            self.foreign = true;
+        }
+    }
+
+    fn visit_computed_member_expression(&mut self, member: &ComputedMemberExpression<'a>) {
+        if self
+            .types
+            .expression(self.semantic, &member.object)
+            .is_typed_array()
+        {
+            let counter = matches!(member.expression.without_parentheses(), Expression::Identifier(id)
+                if self.symbol(id) == Some(self.counter));
+            match &member.object {
+                Expression::Identifier(id) => {
+                    if let Some(symbol) = self.symbol(id) {
+                        self.arrays.entry(symbol).or_default().elsewhere |= !counter;
+                    }
+                }
+                _ => self.foreign = true,
+            }
+        }
+        walk::walk_computed_member_expression(self, member);
+    }
+
+    fn visit_assignment_expression(&mut self, assign: &AssignmentExpression<'a>) {
+        if let AssignmentTarget::ComputedMemberExpression(target) = &assign.left {
+            self.store(target);
+        }
+        walk::walk_assignment_expression(self, assign);
+    }
+
+    fn visit_update_expression(&mut self, update: &UpdateExpression<'a>) {
+        if let SimpleAssignmentTarget::ComputedMemberExpression(target) = &update.argument {
+            self.store(target);
+        }
+        walk::walk_update_expression(self, update);
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression<'a>) {
+        // the runtime, `Math.floor` or `console.log`, only reads its arguments
+        let callee = match &call.callee {
+            Expression::StaticMemberExpression(member) => &member.object,
+            callee => callee,
+        };
+        self.calls |= !matches!(callee, Expression::Identifier(id) if self.symbol(id).is_none());
+        walk::walk_call_expression(self, call);
+    }
+
+    fn visit_break_statement(&mut self, _: &BreakStatement<'a>) {
+        self.exits = true;
+    }
+
+    fn visit_continue_statement(&mut self, _: &ContinueStatement<'a>) {
+        self.exits = true;
+    }
+
+    fn visit_return_statement(&mut self, _: &ReturnStatement<'a>) {
+        self.exits = true;
+    }
+
+    // a nested function may keep an array and write it whenever it is called
+    fn visit_function(&mut self, _function: &Function<'a>, _flags: ScopeFlags) {
+        self.calls = true;
+    }
+
+    fn visit_arrow_function_expression(&mut self, _arrow: &ArrowFunctionExpression<'a>) {
+        self.calls = true;
+    }
+}
@@ -0,0 +1,1468 @@
+use std::{
+    cell::OnceCell,
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+};
+
+use oxc::{
+    ast::{
+        ast::{
+            AssignmentTarget, BinaryOperator, BindingPatternKind, Expression, LogicalOperator,
+            SimpleAssignmentTarget, UnaryOperator, UpdateOperator, VariableDeclarationKind,
+        },
+        AstKind,
+    },
+    cfg::{
+        graph::{visit::EdgeRef, Direction as EdgeDirection},
+        BlockNodeId, ControlFlowGraph, EdgeType, InstructionKind,
+    },
+    semantic::{NodeId, ScopeId, Semantic, SymbolId},
+};
+
+use crate::{
+    induction_variables::{Lockstep, Loop},
+    overflow_analysis::NodeAddress,
+    type_inference::{Type, Types},
+};
+
+/// A variable read or write, in the order it happens within its basic block.
+#[derive(Clone, Copy, Debug)]
+pub enum Event {
+    /// `symbol` is read.
+    Use(SymbolId),
+    /// `symbol` is given a new value.
+    Def(DefinitionId),
+}
+
+pub type DefinitionId = usize;
+
+/// One place a variable is given a value: a declarator, parameter, assignment or update.
+#[derive(Debug)]
+pub struct Definition<'a> {
+    pub symbol: SymbolId,
+    pub node: NodeId,
+    /// Where the write lands among the events of its block.
+    pub position: NodeId,
+    pub value: Assigned<'a>,
+}
+
+/// What a definition stores into its variable.
+#[derive(Clone, Copy, Debug)]
+pub enum Assigned<'a> {
+    /// `x = e` or `let x = e`
+    Value(&'a Expression<'a>),
+    /// `x op= e`
+    Compound(BinaryOperator, &'a Expression<'a>),
+    /// `x++` (1) or `x--` (-1)
+    Step(f64),
+    /// Parameters, `let x;` and anything else without a known value.
+    Unknown,
+}
+
+/// Events grouped by basic block of the CFG built by `SemanticBuilder::with_cfg`.
+///
+/// Only variables whose reads and writes all sit in the function that declares them, and
+/// whose writes do not straddle blocks (`x = a && b`), are tracked; analyses answer
+/// conservatively for everything else.
+pub struct Dataflow<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    cfg: &'s ControlFlowGraph,
+    blocks: HashMap<BlockNodeId, Vec<(NodeId, Event)>>,
+    definitions: Vec<Definition<'a>>,
+    definitions_of: HashMap<SymbolId, Vec<DefinitionId>>,
+    untracked: HashSet<SymbolId>,
+    /// What each variable is compared with, the bounds worth stopping at when widening.
+    comparisons: HashMap<SymbolId, Vec<&'a Expression<'a>>>,
+    /// The [`Lockstep`] variables of each `for` loop, by the address of its test.
+    locksteps: OnceCell<HashMap<NodeAddress, Vec<Lockstep>>>,
+}
+
+impl<'s, 'a> Dataflow<'s, 'a> {
+    /// Panics if `semantic` was built without a CFG.
+    pub fn new(semantic: &'s Semantic<'a>) -> Self {
+        let cfg = semantic.cfg().expect("semantic built without cfg");
+        let nodes = semantic.nodes();
+
+        // node ids are handed out in pre-order, so a subtree ends at its largest id
+        let ids: Vec<NodeId> = nodes.iter().map(|node| node.id()).collect();
+        let mut last: HashMap<NodeId, NodeId> = ids.iter().map(|id| (*id, *id)).collect();
+        for id in ids.iter().rev() {
+            if let Some(parent) = nodes.parent_id(*id) {
+                if last[id] > last[&parent] {
+                    last.insert(parent, last[id]);
+                }
+            }
+        }
+
+        let mut flow = Self {
+            semantic,
+            cfg,
+            blocks: HashMap::new(),
+            definitions: vec![],
+            definitions_of: HashMap::new(),
+            untracked: HashSet::new(),
+            comparisons: HashMap::new(),
+            locksteps: OnceCell::new(),
+        };
+
+        for node in nodes.iter() {
+            match node.kind() {
+                AstKind::IdentifierReference(id) => {
+                    let Some(reference_id) = id.reference_id.get() else {
+                        continue;
+                    };
+                    let reference = semantic.symbols().get_reference(reference_id);
+                    let Some(symbol) = reference.symbol_id() else {
+                        continue;
+                    };
+                    if flow.function_scope(node.scope_id())
+                        != flow.function_scope(semantic.symbols().get_scope_id(symbol))
+                    {
+                        flow.untracked.insert(symbol);
+                    }
+                    if reference.is_read() {
+                        flow.push(node.cfg_id(), node.id(), Event::Use(symbol));
+                    }
+                    if reference.is_write() {
+                        match flow.written(node.id()) {
+                            Some((defining, value)) => flow.define(symbol, defining, value, &last),
+                            None => {
+                                flow.untracked.insert(symbol);
+                            }
+                        }
+                    }
+                }
+                AstKind::VariableDeclarator(decl) => match &decl.id.kind {
+                    BindingPatternKind::BindingIdentifier(id) => {
+                        let Some(symbol) = id.symbol_id.get() else {
+                            continue;
+                        };
+                        match &decl.init {
+                            Some(init) => {
+                                flow.define(symbol, node.id(), Assigned::Value(init), &last)
+                            }
+                            // `var x;` keeps whatever value x already had
+                            None if decl.kind != VariableDeclarationKind::Var => {
+                                flow.define(symbol, node.id(), Assigned::Unknown, &last)
+                            }
+                            None => {}
+                        }
+                    }
+                    _ => flow.untrack_bindings(node.id()),
+                },
+                AstKind::FormalParameter(param) => match &param.pattern.kind {
+                    BindingPatternKind::BindingIdentifier(id) => {
+                        if let Some(symbol) = id.symbol_id.get() {
+                            flow.define(symbol, node.id(), Assigned::Unknown, &last);
+                        }
+                    }
+                    _ => flow.untrack_bindings(node.id()),
+                },
+                AstKind::BinaryExpression(expr) if expr.operator.is_compare() => {
+                    for (side, other) in [(&expr.left, &expr.right), (&expr.right, &expr.left)] {
+                        if let Some(symbol) = flow.reference_symbol(side) {
+                            flow.comparisons.entry(symbol).or_default().push(other);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for events in flow.blocks.values_mut() {
+            // a write lands after the reads of the expression producing its value
+            events.sort_by_key(|(position, event)| (*position, matches!(event, Event::Def(_))));
+        }
+
+        flow
+    }
+
+    pub fn semantic(&self) -> &'s Semantic<'a> {
+        self.semantic
+    }
+
+    pub fn definition(&self, id: DefinitionId) -> &Definition<'a> {
+        &self.definitions[id]
+    }
+
+    pub fn definitions_of(&self, symbol: SymbolId) -> &[DefinitionId] {
+        self.definitions_of
+            .get(&symbol)
+            .map_or(&[], |definitions| definitions.as_slice())
+    }
+
+    pub fn is_tracked(&self, symbol: SymbolId) -> bool {
+        !self.untracked.contains(&symbol)
+    }
+
+    /// The expressions `symbol` is compared with by `<`, `<=`, `>` or `>=`.
+    pub fn comparisons(&self, symbol: SymbolId) -> &[&'a Expression<'a>] {
+        self.comparisons
+            .get(&symbol)
+            .map_or(&[], |comparisons| comparisons.as_slice())
+    }
+
+    /// The variables stepped in lock step with an induction variable of the `for` loop `test`
+    /// belongs to.
+    pub fn locksteps(&self, test: &Expression) -> &[Lockstep] {
+        let locksteps = self.locksteps.get_or_init(|| {
+            let constants = self.solve(ConstantPropagation);
+            let mut locksteps = HashMap::new();
+            for node in self.semantic.nodes().iter() {
+                let AstKind::ForStatement(for_) = node.kind() else {
+                    continue;
+                };
+                let Some(test) = &for_.test else {
+                    continue;
+                };
+                let found = Loop::new(self, for_)
+                    .with_induction_variables(for_, &constants)
+                    .locksteps(for_, &constants);
+                if !found.is_empty() {
+                    locksteps.insert(NodeAddress::of(test), found);
+                }
+            }
+            locksteps
+        });
+        locksteps
+            .get(&NodeAddress::of(test))
+            .map_or(&[], |locksteps| locksteps.as_slice())
+    }
+
+    /// The symbol an identifier reference resolves to, if any.
+    pub fn reference_symbol(&self, expr: &Expression) -> Option<SymbolId> {
+        let Expression::Identifier(id) = expr else {
+            return None;
+        };
+        self.semantic
+            .symbols()
+            .get_reference(id.reference_id.get()?)
+            .symbol_id()
+    }
+
+    fn push(&mut self, block: BlockNodeId, position: NodeId, event: Event) {
+        self.blocks
+            .entry(block)
+            .or_default()
+            .push((position, event));
+    }
+
+    fn define(
+        &mut self,
+        symbol: SymbolId,
+        node: NodeId,
+        value: Assigned<'a>,
+        last: &HashMap<NodeId, NodeId>,
+    ) {
+        // the write lands once the whole defining expression has been evaluated
+        let position = last[&node];
+        let block = self.semantic.nodes().get_node(node).cfg_id();
+        if self.semantic.nodes().get_node(position).cfg_id() != block {
+            self.untracked.insert(symbol);
+            return;
+        }
+
+        let id = self.definitions.len();
+        self.definitions.push(Definition {
+            symbol,
+            node,
+            position,
+            value,
+        });
+        self.definitions_of.entry(symbol).or_default().push(id);
+        self.push(block, position, Event::Def(id));
+    }
+
+    /// The assignment or update writing through the identifier reference `node`.
+    fn written(&self, node: NodeId) -> Option<(NodeId, Assigned<'a>)> {
+        let nodes = self.semantic.nodes();
+        let defining = nodes.ancestor_ids(node).skip(1).find(|id| {
+            !matches!(
+                nodes.kind(*id),
+                AstKind::SimpleAssignmentTarget(_) | AstKind::AssignmentTarget(_)
+            )
+        })?;
+        let value = match nodes.kind(defining) {
+            AstKind::AssignmentExpression(expr) => {
+                let AssignmentTarget::AssignmentTargetIdentifier(_) = &expr.left else {
+                    return None;
+                };
+                match expr.operator.to_binary_operator() {
+                    _ if expr.operator.is_assign() => Assigned::Value(&expr.right),
+                    Some(operator) => Assigned::Compound(operator, &expr.right),
+                    // `&&=`, `||=` and `??=` only write on one path
+                    None => return None,
+                }
+            }
+            AstKind::UpdateExpression(expr) => {
+                let SimpleAssignmentTarget::AssignmentTargetIdentifier(_) = &expr.argument else {
+                    return None;
+                };
+                match expr.operator {
+                    UpdateOperator::Increment => Assigned::Step(1.0),
+                    UpdateOperator::Decrement => Assigned::Step(-1.0),
+                }
+            }
+            _ => return None,
+        };
+        Some((defining, value))
+    }
+
+    /// Destructuring declares several symbols at once; give up on all of them.
+    fn untrack_bindings(&mut self, node: NodeId) {
+        let scope = self.semantic.nodes().get_node(node).scope_id();
+        let bindings: Vec<SymbolId> = self.semantic.scopes().iter_bindings_in(scope).collect();
+        for symbol in bindings {
+            let declaration = self.semantic.symbols().get_declaration(symbol);
+            if self
+                .semantic
+                .nodes()
+                .ancestor_ids(declaration)
+                .any(|id| id == node)
+            {
+                self.untracked.insert(symbol);
+            }
+        }
+    }
+
+    fn function_scope(&self, scope: ScopeId) -> ScopeId {
+        let scopes = self.semantic.scopes();
+        scopes
+            .ancestors(scope)
+            .find(|id| {
+                let flags = scopes.get_flags(*id);
+                flags.is_function() || flags.is_top()
+            })
+            .unwrap_or(scope)
+    }
+
+    fn edges(
+        &self,
+        block: BlockNodeId,
+        direction: EdgeDirection,
+    ) -> impl Iterator<Item = (BlockNodeId, &EdgeType)> + '_ {
+        self.cfg
+            .graph()
+            .edges_directed(block, direction)
+            .filter(|edge| !matches!(edge.weight(), EdgeType::NewFunction | EdgeType::Unreachable))
+            .map(move |edge| match direction {
+                EdgeDirection::Outgoing => (edge.target(), edge.weight()),
+                EdgeDirection::Incoming => (edge.source(), edge.weight()),
+            })
+    }
+
+    /// The test of the `if`, `for` or `while` a block ends with; its `Jump` edge is taken
+    /// when the test is true.
+    ///
+    /// oxc records the test in the block it starts in. When `&&`, `||` or `?:` split the test
+    /// into several blocks, the block it ends in has no instructions, and neither have those in
+    /// between.
+    fn condition(&self, block: BlockNodeId) -> Option<&'a Expression<'a>> {
+        let mut queue = VecDeque::from([block]);
+        let mut seen = HashSet::from([block]);
+        while let Some(block) = queue.pop_front() {
+            if let Some(instruction) = self.cfg.basic_block(block).instructions().last() {
+                if instruction.kind != InstructionKind::Condition {
+                    return None;
+                }
+                return match self.semantic.nodes().parent_kind(instruction.node_id?)? {
+                    AstKind::IfStatement(stmt) => Some(&stmt.test),
+                    AstKind::WhileStatement(stmt) => Some(&stmt.test),
+                    AstKind::ForStatement(stmt) => stmt.test.as_ref(),
+                    _ => None,
+                };
+            }
+            for (neighbour, edge) in self.edges(block, EdgeDirection::Incoming) {
+                if matches!(edge, EdgeType::Normal) && seen.insert(neighbour) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        None
+    }
+
+    /// The test deciding whether the edge of kind `edge` from `block` to `target` is taken, and
+    /// the outcome it takes it on.
+    fn branch(
+        &self,
+        block: BlockNodeId,
+        target: BlockNodeId,
+        edge: &EdgeType,
+    ) -> Option<(&'a Expression<'a>, bool)> {
+        match edge {
+            EdgeType::Jump => Some((self.condition(block)?, true)),
+            // the other way out of a test; a `while` also leaves the block it tests in towards
+            // the blocks `&&`, `||` and `?:` split its test into
+            EdgeType::Normal
+                if self
+                    .edges(block, EdgeDirection::Outgoing)
+                    .any(|(_, edge)| matches!(edge, EdgeType::Jump)) =>
+            {
+                let test = self.condition(block)?;
+                (!branches(test)).then_some((test, false))
+            }
+            // an `if` reaches its `else` or what follows it from the block before its test
+            EdgeType::Normal => {
+                let test = self
+                    .edges(block, EdgeDirection::Outgoing)
+                    .filter(|(next, edge)| *next != target && matches!(edge, EdgeType::Normal))
+                    .find_map(|(next, _)| {
+                        let instruction = self.cfg.basic_block(next).instructions().last()?;
+                        if instruction.kind != InstructionKind::Condition {
+                            return None;
+                        }
+                        match self.semantic.nodes().parent_kind(instruction.node_id?)? {
+                            AstKind::IfStatement(stmt) => Some(&stmt.test),
+                            _ => None,
+                        }
+                    })?;
+                (!branches(test)).then_some((test, false))
+            }
+            _ => None,
+        }
+    }
+
+    /// Runs `analysis` over every block to a fixpoint.
+    pub fn solve<A: Analysis<'a>>(&self, analysis: A) -> Results<'_, 's, 'a, A> {
+        let blocks: Vec<BlockNodeId> = self.cfg.graph().node_indices().collect();
+        let mut entry: HashMap<BlockNodeId, A::Fact> = HashMap::new();
+        let mut exit: HashMap<BlockNodeId, A::Fact> = HashMap::new();
+        let (inward, outward) = match A::DIRECTION {
+            Direction::Forward => (EdgeDirection::Incoming, EdgeDirection::Outgoing),
+            Direction::Backward => (EdgeDirection::Outgoing, EdgeDirection::Incoming),
+        };
+
+        let mut worklist: Vec<BlockNodeId> = blocks.iter().rev().copied().collect();
+        let mut queued: HashSet<BlockNodeId> = blocks.iter().copied().collect();
+        while let Some(block) = worklist.pop() {
+            queued.remove(&block);
+
+            // the fact flowing into the block, in the direction of the analysis
+            let mut fact = None;
+            for (neighbour, edge) in self.edges(block, inward) {
+                let Some(flowing) = exit.get(&neighbour) else {
+                    continue;
+                };
+                let mut flowing = flowing.clone();
+                if A::DIRECTION == Direction::Forward {
+                    if let Some((test, outcome)) = self.branch(neighbour, block, edge) {
+                        analysis.refine(self, test, outcome, &mut flowing);
+                    }
+                }
+                match &mut fact {
+                    None => fact = Some(flowing),
+                    Some(fact) => analysis.join(fact, &flowing),
+                }
+            }
+            let mut fact = match fact {
+                Some(fact) => fact,
+                None if self.edges(block, inward).next().is_none() => analysis.boundary(self),
+                // not reached yet: a forward fact made up from nothing would flow around loops
+                // and widen them for good
+                None if A::DIRECTION == Direction::Forward => continue,
+                None => analysis.bottom(self),
+            };
+            if let Some(old) = entry.get(&block) {
+                if self.is_loop_head(block) {
+                    analysis.widen(self, old, &mut fact);
+                }
+            }
+
+            let mut out = fact.clone();
+            self.apply(&analysis, block, None, &mut out);
+            entry.insert(block, fact);
+            if exit.get(&block) != Some(&out) {
+                exit.insert(block, out);
+                for (neighbour, _) in self.edges(block, outward) {
+                    if queued.insert(neighbour) {
+                        worklist.push(neighbour);
+                    }
+                }
+            }
+        }
+
+        Results {
+            flow: self,
+            analysis,
+            entry,
+        }
+    }
+
+    /// Every cycle enters one of these through a `Backedge`. The blocks a back edge leaves from
+    /// are not: oxc also marks the edges from the body of a `for` to its update as back edges, and
+    /// widening the body or the update would throw away what the condition of the loop proved.
+    fn is_loop_head(&self, block: BlockNodeId) -> bool {
+        let backedge = |(_, edge): (BlockNodeId, &EdgeType)| matches!(edge, EdgeType::Backedge);
+        self.edges(block, EdgeDirection::Incoming).any(backedge)
+            && !self.edges(block, EdgeDirection::Outgoing).any(backedge)
+    }
+
+    /// Applies the events of `block` to `fact`, stopping before `until` when given.
+    fn apply<A: Analysis<'a>>(
+        &self,
+        analysis: &A,
+        block: BlockNodeId,
+        until: Option<NodeId>,
+        fact: &mut A::Fact,
+    ) {
+        let Some(events) = self.blocks.get(&block) else {
+            return;
+        };
+        match A::DIRECTION {
+            Direction::Forward => {
+                for (position, event) in events {
+                    if until.is_some_and(|until| *position >= until) {
+                        break;
+                    }
+                    analysis.transfer(self, event, fact);
+                }
+            }
+            Direction::Backward => {
+                for (position, event) in events.iter().rev() {
+                    if until.is_some_and(|until| *position < until) {
+                        break;
+                    }
+                    analysis.transfer(self, event, fact);
+                }
+            }
+        }
+    }
+}
+
+/// Whether evaluating `expr` branches, so that oxc splits it over several blocks.
+fn branches(expr: &Expression) -> bool {
+    match expr {
+        Expression::NumericLiteral(_)
+        | Expression::BooleanLiteral(_)
+        | Expression::StringLiteral(_)
+        | Expression::Identifier(_) => false,
+        Expression::ParenthesizedExpression(expr) => branches(&expr.expression),
+        Expression::UnaryExpression(expr) => branches(&expr.argument),
+        Expression::BinaryExpression(expr) => branches(&expr.left) || branches(&expr.right),
+        Expression::StaticMemberExpression(expr) => branches(&expr.object),
+        Expression::ComputedMemberExpression(expr) => {
+            branches(&expr.object) || branches(&expr.expression)
+        }
+        _ => true,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// A monotone dataflow problem over `Event`s.
+pub trait Analysis<'a> {
+    type Fact: Clone + PartialEq;
+    const DIRECTION: Direction;
+
+    /// The fact at the entry of a function (forward) or at its exits (backward).
+    fn boundary(&self, flow: &Dataflow<'_, 'a>) -> Self::Fact;
+
+    /// The fact for a block no path has reached yet; the identity of `join`.
+    fn bottom(&self, flow: &Dataflow<'_, 'a>) -> Self::Fact;
+
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact);
+
+    fn transfer(&self, flow: &Dataflow<'_, 'a>, event: &Event, fact: &mut Self::Fact);
+
+    /// Narrows a forward fact along the edge taken when `test` evaluates to `outcome`.
+    fn refine(
+        &self,
+        _flow: &Dataflow<'_, 'a>,
+        _test: &Expression<'a>,
+        _outcome: bool,
+        _fact: &mut Self::Fact,
+    ) {
+    }
+
+    /// Called at loop heads so lattices of unbounded height still converge.
+    fn widen(&self, _flow: &Dataflow<'_, 'a>, _old: &Self::Fact, _fact: &mut Self::Fact) {}
+}
+
+/// The solution of an analysis at the boundaries of every block.
+pub struct Results<'f, 's, 'a, A: Analysis<'a>> {
+    flow: &'f Dataflow<'s, 'a>,
+    analysis: A,
+    entry: HashMap<BlockNodeId, A::Fact>,
+}
+
+impl<'a, A: Analysis<'a>> Results<'_, '_, 'a, A> {
+    /// The fact holding just before `node` is evaluated.
+    pub fn before(&self, node: NodeId) -> A::Fact {
+        // `entry` is where the analysis enters the block: its end for backward problems
+        let block = self.flow.semantic.nodes().get_node(node).cfg_id();
+        // blocks no path reaches have no entry
+        let mut fact = match self.entry.get(&block) {
+            Some(fact) => fact.clone(),
+            None => self.analysis.bottom(self.flow),
+        };
+        self.flow
+            .apply(&self.analysis, block, Some(node), &mut fact);
+        fact
+    }
+}
+
+/// Which definitions may have produced the value a variable holds.
+pub struct ReachingDefinitions;
+
+impl<'a> Analysis<'a> for ReachingDefinitions {
+    type Fact = BTreeSet<DefinitionId>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self, _flow: &Dataflow<'_, 'a>) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn bottom(&self, _flow: &Dataflow<'_, 'a>) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.extend(other);
+    }
+
+    fn transfer(&self, flow: &Dataflow<'_, 'a>, event: &Event, fact: &mut Self::Fact) {
+        if let Event::Def(id) = event {
+            for killed in flow.definitions_of(flow.definition(*id).symbol) {
+                fact.remove(killed);
+            }
+            fact.insert(*id);
+        }
+    }
+}
+
+impl<'a> Results<'_, '_, 'a, ReachingDefinitions> {
+    /// The definitions of `symbol` that may reach `node`, or `None` when it is not tracked.
+    pub fn reaching(&self, symbol: SymbolId, node: NodeId) -> Option<Vec<DefinitionId>> {
+        if !self.flow.is_tracked(symbol) {
+            return None;
+        }
+        Some(
+            self.before(node)
+                .into_iter()
+                .filter(|id| self.flow.definition(*id).symbol == symbol)
+                .collect(),
+        )
+    }
+}
+
+/// Which variables may still be read before they are written again.
+pub struct Liveness;
+
+impl<'a> Analysis<'a> for Liveness {
+    type Fact = BTreeSet<SymbolId>;
+    const DIRECTION: Direction = Direction::Backward;
+
+    fn boundary(&self, _flow: &Dataflow<'_, 'a>) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn bottom(&self, _flow: &Dataflow<'_, 'a>) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.extend(other);
+    }
+
+    fn transfer(&self, flow: &Dataflow<'_, 'a>, event: &Event, fact: &mut Self::Fact) {
+        match event {
+            Event::Use(symbol) => {
+                fact.insert(*symbol);
+            }
+            Event::Def(id) => {
+                fact.remove(&flow.definition(*id).symbol);
+            }
+        }
+    }
+}
+
+impl<'a> Results<'_, '_, 'a, Liveness> {
+    /// Whether the value `symbol` holds just before `node` may be read later.
+    #[allow(dead_code)]
+    pub fn is_live(&self, symbol: SymbolId, node: NodeId) -> bool {
+        !self.flow.is_tracked(symbol) || self.before(node).contains(&symbol)
+    }
+
+    /// Whether the value stored by `definition` may be read later.
+    pub fn is_read_after(&self, definition: DefinitionId) -> bool {
+        let definition = self.flow.definition(definition);
+        if !self.flow.is_tracked(definition.symbol) {
+            return true;
+        }
+        let block = self
+            .flow
+            .semantic
+            .nodes()
+            .get_node(definition.position)
+            .cfg_id();
+        let mut fact = self.entry[&block].clone();
+        if let Some(events) = self.flow.blocks.get(&block) {
+            // back to the write, which comes after the reads of its own value
+            for (position, event) in events.iter().rev() {
+                if *position <= definition.position {
+                    break;
+                }
+                self.analysis.transfer(self.flow, event, &mut fact);
+            }
+        }
+        fact.contains(&definition.symbol)
+    }
+}
+
+/// A variable's value when every path agrees on it.
+#[derive(Clone, Copy, Debug)]
+pub enum Constant {
+    Number(f64),
+    Varying,
+}
+
+impl PartialEq for Constant {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            // NaN is a perfectly good constant
+            (Constant::Number(a), Constant::Number(b)) => a.to_bits() == b.to_bits(),
+            (Constant::Varying, Constant::Varying) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Numeric constant propagation. A variable missing from the fact has no definition
+/// reaching yet.
+pub struct ConstantPropagation;
+
+impl ConstantPropagation {
+    pub fn evaluate(
+        flow: &Dataflow,
+        fact: &HashMap<SymbolId, Constant>,
+        expr: &Expression,
+    ) -> Option<f64> {
+        let evaluate = |expr| Self::evaluate(flow, fact, expr);
+        match expr {
+            Expression::NumericLiteral(literal) => Some(literal.value),
+            Expression::ParenthesizedExpression(expr) => evaluate(&expr.expression),
+            Expression::TSAsExpression(expr) => evaluate(&expr.expression),
+            Expression::TSNonNullExpression(expr) => evaluate(&expr.expression),
+            Expression::Identifier(_) => {
+                let symbol = flow.reference_symbol(expr)?;
+                if !flow.is_tracked(symbol) {
+                    return None;
+                }
+                match fact.get(&symbol)? {
+                    Constant::Number(value) => Some(*value),
+                    Constant::Varying => None,
+                }
+            }
+            Expression::UnaryExpression(expr) => {
+                let value = evaluate(&expr.argument)?;
+                match expr.operator {
+                    UnaryOperator::UnaryNegation => Some(-value),
+                    UnaryOperator::UnaryPlus => Some(value),
+                    UnaryOperator::BitwiseNot => Some(!to_int32(value) as f64),
+                    _ => None,
+                }
+            }
+            Expression::BinaryExpression(expr) => {
+                fold(expr.operator, evaluate(&expr.left)?, evaluate(&expr.right)?)
+            }
+            Expression::CallExpression(call) => {
+                let name = math_function(flow, &call.callee)?;
+                let args = call
+                    .arguments
+                    .iter()
+                    .map(|argument| evaluate(argument.as_expression()?))
+                    .collect::<Option<Vec<_>>>()?;
+                math(name, &args)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The name of the function when `callee` is `Math.name` and `Math` is the global.
+pub fn math_function<'e>(flow: &Dataflow, callee: &'e Expression) -> Option<&'e str> {
+    let Expression::StaticMemberExpression(member) = callee else {
+        return None;
+    };
+    match &member.object {
+        Expression::Identifier(id) if id.name == "Math" => {}
+        _ => return None,
+    }
+    if flow.reference_symbol(&member.object).is_some() {
+        return None;
+    }
+    Some(member.property.name.as_str())
+}
+
+/// `Math.name(...args)` for the functions of `Math` that only compute with their arguments.
+pub fn math(name: &str, args: &[f64]) -> Option<f64> {
+    let value = match (name, args) {
+        ("abs", [x]) => x.abs(),
+        ("ceil", [x]) => x.ceil(),
+        ("floor", [x]) => x.floor(),
+        ("trunc", [x]) => x.trunc(),
+        ("sqrt", [x]) => x.sqrt(),
+        // keeps NaN, 0 and -0
+        ("sign", [x]) if x.is_nan() || *x == 0.0 => *x,
+        ("sign", [x]) => x.signum(),
+        ("round", [x]) => {
+            // x - floor(x) is exact, unlike the x + 0.5 of the usual formula
+            let floor = x.floor();
+            let rounded = if x - floor >= 0.5 { floor + 1.0 } else { floor };
+            if rounded == 0.0 && x.is_sign_negative() {
+                -0.0
+            } else {
+                rounded
+            }
+        }
+        ("min", args) => args.iter().fold(f64::INFINITY, |min, x| {
+            if min.is_nan() || x.is_nan() {
+                f64::NAN
+            } else if *x < min || (*x == 0.0 && min == 0.0 && x.is_sign_negative()) {
+                *x
+            } else {
+                min
+            }
+        }),
+        ("max", args) => args.iter().fold(f64::NEG_INFINITY, |max, x| {
+            if max.is_nan() || x.is_nan() {
+                f64::NAN
+            } else if *x > max || (*x == 0.0 && max == 0.0 && x.is_sign_positive()) {
+                *x
+            } else {
+                max
+            }
+        }),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// `left op right` for the numeric operators.
+pub fn fold(operator: BinaryOperator, left: f64, right: f64) -> Option<f64> {
+    let value = match operator {
+        BinaryOperator::Addition => left + right,
+        BinaryOperator::Subtraction => left - right,
+        BinaryOperator::Multiplication => left * right,
+        BinaryOperator::Division => left / right,
+        BinaryOperator::Remainder => left % right,
+        // IEEE pow gives 1 for `1 ** NaN` and `1 ** Infinity`, ECMAScript gives NaN
+        BinaryOperator::Exponential if right.is_nan() => f64::NAN,
+        BinaryOperator::Exponential if left.abs() == 1.0 && right.is_infinite() => f64::NAN,
+        BinaryOperator::Exponential => left.powf(right),
+        BinaryOperator::BitwiseAnd => (to_int32(left) & to_int32(right)) as f64,
+        BinaryOperator::BitwiseOR => (to_int32(left) | to_int32(right)) as f64,
+        BinaryOperator::BitwiseXOR => (to_int32(left) ^ to_int32(right)) as f64,
+        BinaryOperator::ShiftLeft => to_int32(left).wrapping_shl(to_int32(right) as u32) as f64,
+        BinaryOperator::ShiftRight => to_int32(left).wrapping_shr(to_int32(right) as u32) as f64,
+        BinaryOperator::ShiftRightZeroFill => {
+            (to_int32(left) as u32).wrapping_shr(to_int32(right) as u32) as f64
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// The ECMAScript ToInt32 conversion.
+pub fn to_int32(value: f64) -> i32 {
+    if !value.is_finite() {
+        return 0;
+    }
+    value.trunc().rem_euclid(4294967296.0) as u32 as i32
+}
+
+impl<'a> Analysis<'a> for ConstantPropagation {
+    type Fact = HashMap<SymbolId, Constant>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self, _flow: &Dataflow<'_, 'a>) -> Self::Fact {
+        HashMap::new()
+    }
+
+    fn bottom(&self, _flow: &Dataflow<'_, 'a>) -> Self::Fact {
+        HashMap::new()
+    }
+
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        for (symbol, value) in other {
+            match fact.get(symbol) {
+                Some(old) if old != value => {
+                    fact.insert(*symbol, Constant::Varying);
+                }
+                Some(_) => {}
+                None => {
+                    fact.insert(*symbol, *value);
+                }
+            }
+        }
+    }
+
+    fn transfer(&self, flow: &Dataflow<'_, 'a>, event: &Event, fact: &mut Self::Fact) {
+        let Event::Def(id) = event else {
+            return;
+        };
+        let definition = flow.definition(*id);
+        let current = match fact.get(&definition.symbol) {
+            Some(Constant::Number(value)) => Some(*value),
+            _ => None,
+        };
+        let value = match definition.value {
+            Assigned::Value(expr) => Self::evaluate(flow, fact, expr),
+            Assigned::Compound(operator, expr) => {
+                let right = Self::evaluate(flow, fact, expr);
+                current
+                    .zip(right)
+                    .and_then(|(left, right)| fold(operator, left, right))
+            }
+            Assigned::Step(step) => current.map(|value| value + step),
+            Assigned::Unknown => None,
+        };
+        fact.insert(
+            definition.symbol,
+            value.map_or(Constant::Varying, Constant::Number),
+        );
+    }
+}
+
+impl<'a> Results<'_, '_, 'a, ConstantPropagation> {
+    /// The value of `expr` when it is the same constant on every path to it.
+    pub fn value(&self, expr: &Expression, node: NodeId) -> Option<f64> {
+        ConstantPropagation::evaluate(self.flow, &self.before(node), expr)
+    }
+}
+
+/// The values a variable may hold: every `v` with `min <= v <= max`, plus NaN when `nan` and
+/// -0 when `negative_zero`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub min: f64,
+    pub max: f64,
+    /// Every finite value is a whole number.
+    pub integer: bool,
+    pub nan: bool,
+    /// -0 is possible, which only a double holds.
+    pub negative_zero: bool,
+}
+
+impl Range {
+    pub const UNKNOWN: Range = Range {
+        min: f64::NEG_INFINITY,
+        max: f64::INFINITY,
+        integer: false,
+        nan: true,
+        negative_zero: true,
+    };
+    const INT32: Range = Range {
+        min: i32::MIN as f64,
+        max: i32::MAX as f64,
+        integer: true,
+        nan: false,
+        negative_zero: false,
+    };
+
+    /// The values a C++ variable of type `t` can hold.
+    pub fn of_type(t: &Type) -> Self {
+        match t {
+            Type::Int32 => Range::INT32,
+            Type::Uint8 => Range {
+                min: 0.0,
+                max: u8::MAX as f64,
+                integer: true,
+                nan: false,
+                negative_zero: false,
+            },
+            _ => Range::UNKNOWN,
+        }
+    }
+
+    pub fn point(value: f64) -> Self {
+        if value.is_nan() {
+            return Self::UNKNOWN;
+        }
+        Range {
+            min: value,
+            max: value,
+            integer: value.fract() == 0.0,
+            nan: false,
+            negative_zero: value == 0.0 && value.is_sign_negative(),
+        }
+    }
+
+    fn union(&self, other: &Range) -> Range {
+        Range {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+            integer: self.integer && other.integer,
+            nan: self.nan || other.nan,
+            negative_zero: self.negative_zero || other.negative_zero,
+        }
+    }
+
+    fn intersect(&self, other: &Range) -> Range {
+        Range {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+            integer: self.integer || other.integer,
+            nan: self.nan && other.nan,
+            negative_zero: self.negative_zero && other.negative_zero,
+        }
+    }
+
+    /// Whether every value is an int32.
+    pub fn is_int32(&self) -> bool {
+        self.integer
+            && !self.nan
+            && !self.negative_zero
+            && self.min >= i32::MIN as f64
+            && self.max <= i32::MAX as f64
+    }
+
+    /// Whether +0 or -0 is possible.
+    fn has_zero(&self) -> bool {
+        self.negative_zero || (self.min <= 0.0 && self.max >= 0.0)
+    }
+
+    /// The range of `-x` for every `x` in `self`: -0 where `x` may be +0.
+    pub fn negate(&self) -> Range {
+        Range {
+            min: -self.max,
+            max: -self.min,
+            negative_zero: self.min <= 0.0 && self.max >= 0.0,
+            ..*self
+        }
+    }
+
+    /// The range of `Math.floor(x)` for every `x` in `self`. Only an `x` of -0 floors to -0; an
+    /// `x` reaching into `(-1, 0]` from below is counted as well.
+    pub fn floor(&self) -> Range {
+        Range {
+            min: self.min.floor(),
+            max: self.max.floor(),
+            integer: true,
+            nan: self.nan,
+            negative_zero: self.negative_zero || (self.min < 0.0 && self.max > -1.0),
+        }
+    }
+
+    /// The smallest range holding `f(a, b)` for every `a` in `self` and `b` in `other`,
+    /// for an `f` monotone in each argument, which may give -0 when `negative_zero`.
+    fn corners(&self, other: &Range, negative_zero: bool, f: impl Fn(f64, f64) -> f64) -> Range {
+        let values = [
+            f(self.min, other.min),
+            f(self.min, other.max),
+            f(self.max, other.min),
+            f(self.max, other.max),
+        ];
+        if values.iter().any(|value| value.is_nan()) {
+            // inf - inf, 0 * inf and friends
+            return Range::UNKNOWN;
+        }
+        Range {
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            integer: self.integer && other.integer,
+            nan: self.nan || other.nan,
+            negative_zero,
+        }
+    }
+
+    pub fn binary(&self, operator: BinaryOperator, other: &Range) -> Range {
+        match operator {
+            // -0 + -0 and -0 - 0
+            BinaryOperator::Addition => {
+                let negative_zero = self.negative_zero && other.negative_zero;
+                self.corners(other, negative_zero, |a, b| a + b)
+            }
+            BinaryOperator::Subtraction => {
+                let negative_zero = self.negative_zero && other.has_zero();
+                self.corners(other, negative_zero, |a, b| a - b)
+            }
+            // a zero times a number of the other sign
+            BinaryOperator::Multiplication => {
+                let negative_zero = (self.has_zero() && (other.min < 0.0 || self.negative_zero))
+                    || (other.has_zero() && (self.min < 0.0 || other.negative_zero));
+                self.corners(other, negative_zero, |a, b| a * b)
+            }
+            // a divisor that cannot be 0 keeps the quotient between the corners; it is -0 for a
+            // zero or an underflow over a divisor of the other sign
+            BinaryOperator::Division if other.min > 0.0 || other.max < 0.0 => {
+                let negative_zero = self.negative_zero
+                    || (self.min <= 0.0 && other.max > 0.0)
+                    || (self.max >= 0.0 && other.min < 0.0);
+                Range {
+                    integer: false,
+                    ..self.corners(other, negative_zero, |a, b| a / b)
+                }
+            }
+            BinaryOperator::Remainder => {
+                // |a % b| < |b| and the sign follows a; NaN when b is 0 or a is infinite
+                let bound = other.min.abs().max(other.max.abs());
+                if self.min.is_infinite()
+                    || self.max.is_infinite()
+                    || (other.min <= 0.0 && other.max >= 0.0)
+                {
+                    return Range::UNKNOWN;
+                }
+                let bound = if self.integer && other.integer {
+                    bound - 1.0
+                } else {
+                    bound
+                };
+                Range {
+                    min: if self.min >= 0.0 {
+                        0.0
+                    } else {
+                        self.min.max(-bound)
+                    },
+                    max: if self.max <= 0.0 {
+                        0.0
+                    } else {
+                        self.max.min(bound)
+                    },
+                    integer: self.integer && other.integer,
+                    nan: self.nan || other.nan,
+                    // a negative multiple of `b`, or -0 itself
+                    negative_zero: self.min < 0.0 || self.negative_zero,
+                }
+            }
+            BinaryOperator::BitwiseAnd if other.min >= 0.0 && other.max <= i32::MAX as f64 => {
+                Range {
+                    min: 0.0,
+                    max: other.max,
+                    integer: true,
+                    nan: false,
+                    negative_zero: false,
+                }
+            }
+            BinaryOperator::BitwiseAnd
+            | BinaryOperator::BitwiseOR
+            | BinaryOperator::BitwiseXOR
+            | BinaryOperator::ShiftLeft
+            | BinaryOperator::ShiftRight => Range::INT32,
+            BinaryOperator::ShiftRightZeroFill => Range {
+                min: 0.0,
+                max: u32::MAX as f64,
+                integer: true,
+                nan: false,
+                negative_zero: false,
+            },
+            _ => Range::UNKNOWN,
+        }
+    }
+}
+
+/// Interval analysis of numeric variables, narrowed by loop and `if` conditions.
+///
+/// Given the module's [`Types`], a value is also known to fit the C++ type it is computed in:
+/// an `int32_t` variable, an `Int32Array` element or a `Number()` call is in the int32 range even
+/// where nothing else bounds it. That holds as long as the arithmetic producing int32 values
+/// cannot overflow, which is what [`crate::overflow_analysis`] checks with these ranges.
+#[derive(Default)]
+pub struct RangeAnalysis<'t> {
+    types: Option<&'t Types>,
+}
+
+impl<'t> RangeAnalysis<'t> {
+    pub fn with_types(types: &'t Types) -> Self {
+        Self { types: Some(types) }
+    }
+
+    /// The values `expr` may evaluate to in the states `fact` describes, narrowed to its C++ type
+    /// when there are types.
+    fn evaluate(
+        &self,
+        flow: &Dataflow,
+        fact: &HashMap<SymbolId, Range>,
+        expr: &Expression,
+    ) -> Range {
+        let range = self.evaluate_operation(flow, fact, expr);
+        match self.types {
+            Some(types) => {
+                range.intersect(&Range::of_type(&types.expression(flow.semantic(), expr)))
+            }
+            None => range,
+        }
+    }
+
+    fn evaluate_operation(
+        &self,
+        flow: &Dataflow,
+        fact: &HashMap<SymbolId, Range>,
+        expr: &Expression,
+    ) -> Range {
+        let evaluate = |expr| self.evaluate(flow, fact, expr);
+        match expr {
+            Expression::NumericLiteral(literal) => Range::point(literal.value),
+            Expression::ParenthesizedExpression(expr) => evaluate(&expr.expression),
+            Expression::TSAsExpression(expr) => evaluate(&expr.expression),
+            Expression::TSNonNullExpression(expr) => evaluate(&expr.expression),
+            Expression::Identifier(_) => match flow.reference_symbol(expr) {
+                Some(symbol) if flow.is_tracked(symbol) => {
+                    fact.get(&symbol).copied().unwrap_or(Range::UNKNOWN)
+                }
+                _ => Range::UNKNOWN,
+            },
+            Expression::UnaryExpression(expr) => match expr.operator {
+                UnaryOperator::UnaryNegation => evaluate(&expr.argument).negate(),
+                UnaryOperator::BitwiseNot => Range::INT32,
+                _ => Range::UNKNOWN,
+            },
+            Expression::BinaryExpression(expr) => {
+                evaluate(&expr.left).binary(expr.operator, &evaluate(&expr.right))
+            }
+            Expression::CallExpression(call) => {
+                let is_floor = matches!(&call.callee, Expression::StaticMemberExpression(member)
+                    if member.property.name == "floor"
+                        && matches!(&member.object, Expression::Identifier(id) if id.name == "Math"));
+                match call
+                    .arguments
+                    .first()
+                    .and_then(|argument| argument.as_expression())
+                {
+                    Some(argument) if is_floor && call.arguments.len() == 1 => {
+                        evaluate(argument).floor()
+                    }
+                    _ => Range::UNKNOWN,
+                }
+            }
+            Expression::StaticMemberExpression(member)
+                if member.property.name == "length"
+                    && self.types.is_some_and(|types| {
+                        types
+                            .expression(flow.semantic(), &member.object)
+                            .is_typed_array()
+                    }) =>
+            {
+                Range {
+                    min: 0.0,
+                    ..Range::INT32
+                }
+            }
+            _ => Range::UNKNOWN,
+        }
+    }
+
+    /// Narrows `fact` to the states where `test` evaluates to `outcome`.
+    fn assume(
+        &self,
+        flow: &Dataflow,
+        test: &Expression,
+        outcome: bool,
+        fact: &mut HashMap<SymbolId, Range>,
+    ) {
+        match test {
+            Expression::ParenthesizedExpression(expr) => {
+                self.assume(flow, &expr.expression, outcome, fact)
+            }
+            Expression::UnaryExpression(expr) if expr.operator == UnaryOperator::LogicalNot => {
+                self.assume(flow, &expr.argument, !outcome, fact)
+            }
+            // both sides of a true `&&` hold, neither side of a false `||` does
+            Expression::LogicalExpression(expr)
+                if (outcome && expr.operator == LogicalOperator::And)
+                    || (!outcome && expr.operator == LogicalOperator::Or) =>
+            {
+                self.assume(flow, &expr.left, outcome, fact);
+                self.assume(flow, &expr.right, outcome, fact);
+            }
+            Expression::BinaryExpression(expr) => {
+                // normalize to `low < high` or `low <= high`
+                let (low, high, inclusive) = match (expr.operator, outcome) {
+                    (BinaryOperator::LessThan, true)
+                    | (BinaryOperator::GreaterEqualThan, false) => (&expr.left, &expr.right, false),
+                    (BinaryOperator::LessEqualThan, true)
+                    | (BinaryOperator::GreaterThan, false) => (&expr.left, &expr.right, true),
+                    (BinaryOperator::GreaterThan, true)
+                    | (BinaryOperator::LessEqualThan, false) => (&expr.right, &expr.left, false),
+                    (BinaryOperator::GreaterEqualThan, true)
+                    | (BinaryOperator::LessThan, false) => (&expr.right, &expr.left, true),
+                    _ => return,
+                };
+                let low_range = self.evaluate(flow, fact, low);
+                let high_range = self.evaluate(flow, fact, high);
+                // a comparison with NaN is false without the opposite one being true
+                if !outcome && (low_range.nan || high_range.nan) {
+                    return;
+                }
+                Self::bound(flow, low, fact, None, Some((high_range.max, inclusive)));
+                Self::bound(flow, high, fact, Some((low_range.min, inclusive)), None);
+            }
+            _ => {}
+        }
+    }
+
+    /// Clamps a variable to `min`/`max`, each given as `(bound, inclusive)`.
+    fn bound(
+        flow: &Dataflow,
+        expr: &Expression,
+        fact: &mut HashMap<SymbolId, Range>,
+        min: Option<(f64, bool)>,
+        max: Option<(f64, bool)>,
+    ) {
+        let Some(symbol) = flow.reference_symbol(expr) else {
+            return;
+        };
+        if !flow.is_tracked(symbol) {
+            return;
+        }
+        let mut range = fact.get(&symbol).copied().unwrap_or(Range::UNKNOWN);
+        // a comparison is false for NaN
+        range.nan = false;
+        if let Some((bound, inclusive)) = min {
+            let bound = if !inclusive && range.integer {
+                bound.floor() + 1.0
+            } else {
+                bound
+            };
+            range.min = range.min.max(bound);
+        }
+        if let Some((bound, inclusive)) = max {
+            let bound = if !inclusive && range.integer {
+                bound.ceil() - 1.0
+            } else {
+                bound
+            };
+            range.max = range.max.min(bound);
+        }
+        fact.insert(symbol, range);
+    }
+}
+
+impl<'a> Analysis<'a> for RangeAnalysis<'_> {
+    type Fact = HashMap<SymbolId, Range>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self, _flow: &Dataflow<'_, 'a>) -> Self::Fact {
+        HashMap::new()
+    }
+
+    fn bottom(&self, _flow: &Dataflow<'_, 'a>) -> Self::Fact {
+        HashMap::new()
+    }
+
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        for (symbol, range) in other {
+            let joined = match fact.get(symbol) {
+                Some(old) => old.union(range),
+                None => *range,
+            };
+            fact.insert(*symbol, joined);
+        }
+    }
+
+    fn transfer(&self, flow: &Dataflow<'_, 'a>, event: &Event, fact: &mut Self::Fact) {
+        let Event::Def(id) = event else {
+            return;
+        };
+        let definition = flow.definition(*id);
+        let current = fact
+            .get(&definition.symbol)
+            .copied()
+            .unwrap_or(Range::UNKNOWN);
+        let evaluate = |expr| self.evaluate(flow, fact, expr);
+        let range = match definition.value {
+            Assigned::Value(expr) => evaluate(expr),
+            Assigned::Compound(operator, expr) => current.binary(operator, &evaluate(expr)),
+            Assigned::Step(step) => current.binary(BinaryOperator::Addition, &Range::point(step)),
+            Assigned::Unknown => Range::UNKNOWN,
+        };
+        let range = match self.types {
+            Some(types) => range.intersect(&Range::of_type(&types.symbol(definition.symbol))),
+            None => range,
+        };
+        fact.insert(definition.symbol, range);
+    }
+
+    fn refine(
+        &self,
+        flow: &Dataflow<'_, 'a>,
+        test: &Expression<'a>,
+        outcome: bool,
+        fact: &mut Self::Fact,
+    ) {
+        self.assume(flow, test, outcome, fact);
+        if !outcome {
+            return;
+        }
+        // the loop test runs with each lock-step variable at its spot on the counter's line
+        for lockstep in flow.locksteps(test) {
+            let Some(counter) = fact.get(&lockstep.counter) else {
+                continue;
+            };
+            let line = Range::point(lockstep.scale)
+                .binary(BinaryOperator::Multiplication, counter)
+                .binary(BinaryOperator::Addition, &Range::point(lockstep.offset));
+            let range = fact
+                .get(&lockstep.symbol)
+                .copied()
+                .unwrap_or(Range::UNKNOWN)
+                .intersect(&line);
+            fact.insert(lockstep.symbol, range);
+        }
+    }
+
+    /// A bound that moved jumps to the next bound of what the variable is compared with, so
+    /// `i < n` or a reset at `i >= n` can still stop it, and past those to infinity.
+    fn widen(&self, flow: &Dataflow<'_, 'a>, old: &Self::Fact, fact: &mut Self::Fact) {
+        for (symbol, range) in fact.iter_mut() {
+            let Some(old) = old.get(symbol) else {
+                continue;
+            };
+            // compared with their C++ types only: the thresholds stay put while the ranges
+            // grow, so widening still ends
+            let thresholds: Vec<f64> = flow
+                .comparisons(*symbol)
+                .iter()
+                .map(|expr| self.evaluate(flow, &HashMap::new(), expr))
+                .flat_map(|bound| [bound.min - 1.0, bound.min, bound.max, bound.max + 1.0])
+                .filter(|threshold| threshold.is_finite())
+                .collect();
+            // a bound only narrows back from infinity: a finite one creeping inward, as when
+            // the C++ type clamps a widened variable, would take a round per step
+            if range.min < old.min {
+                range.min = thresholds
+                    .iter()
+                    .copied()
+                    .filter(|threshold| *threshold <= range.min)
+                    .fold(f64::NEG_INFINITY, f64::max);
+            } else if old.min.is_finite() {
+                range.min = old.min;
+            }
+            if range.max > old.max {
+                range.max = thresholds
+                    .iter()
+                    .copied()
+                    .filter(|threshold| *threshold >= range.max)
+                    .fold(f64::INFINITY, f64::min);
+            } else if old.max.is_finite() {
+                range.max = old.max;
+            }
+        }
+    }
+}
+
+impl<'a> Results<'_, '_, 'a, RangeAnalysis<'_>> {
+    /// The values `expr` may evaluate to just before `node`.
+    pub fn range(&self, expr: &Expression, node: NodeId) -> Range {
+        self.analysis.evaluate(self.flow, &self.before(node), expr)
+    }
+
+    /// The values `symbol` may hold just before `node`.
+    pub fn symbol(&self, symbol: SymbolId, node: NodeId) -> Range {
+        let range = if self.flow.is_tracked(symbol) {
+            self.before(node)
+                .get(&symbol)
+                .copied()
+                .unwrap_or(Range::UNKNOWN)
+        } else {
+            Range::UNKNOWN
+        };
+        match self.analysis.types {
+            Some(types) => range.intersect(&Range::of_type(&types.symbol(symbol))),
+            None => range,
+        }
+    }
+}
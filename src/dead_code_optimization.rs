@@ -0,0 +1,368 @@
+use std::collections::HashSet;
+
+use oxc::{
+    allocator::{self, Allocator},
+    ast::{
+        ast::{
+            AssignmentTarget, BindingPatternKind, Expression, SimpleAssignmentTarget, Statement,
+        },
+        visit::walk_mut,
+        AstBuilder, AstKind, VisitMut,
+    },
+    semantic::{ReferenceId, Semantic, SymbolId},
+    span::{GetSpan, Span},
+};
+
+use crate::{
+    dataflow::{Dataflow, Liveness},
+    purity::is_pure,
+    remarks::Remark,
+};
+
+/// Removes what cannot affect the output of the program:
+///
+/// - declarations of variables and functions nothing reads, with the stores into them,
+/// - stores whose value is overwritten or goes out of scope before it is read,
+/// - statements the control flow graph never reaches, like those after `return` or `break`,
+/// - the branch an `if` with a constant condition never takes,
+/// - expression statements without side effects, according to [`crate::purity`].
+pub struct DeadCodeOptimization<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    ast: AstBuilder<'a>,
+    /// Variables and functions that are only ever assigned.
+    unused: HashSet<SymbolId>,
+    /// The targets of statements `x = e;`, `x op= e;` and `x++;` whose value is never read.
+    dead_stores: HashSet<ReferenceId>,
+    /// Where the nodes of unreachable basic blocks are.
+    unreachable: HashSet<Span>,
+    pub remarks: Vec<Remark>,
+}
+
+impl<'s, 'a> DeadCodeOptimization<'s, 'a> {
+    pub fn new(dataflow: &'s Dataflow<'s, 'a>, allocator: &'a Allocator) -> Self {
+        let semantic = dataflow.semantic();
+        let symbols = semantic.symbols();
+        let nodes = semantic.nodes();
+        let liveness = dataflow.solve(Liveness);
+
+        let mut unused = HashSet::new();
+        let mut dead_stores = HashSet::new();
+        for symbol in symbols.symbol_ids() {
+            let declaration = symbols.get_declaration(symbol);
+            // other modules read what is exported
+            let exported = nodes
+                .ancestor_kinds(declaration)
+                .skip(1)
+                .take(2)
+                .any(|kind| {
+                    matches!(
+                        kind,
+                        AstKind::ExportNamedDeclaration(_) | AstKind::ExportDefaultDeclaration(_)
+                    )
+                });
+            if exported {
+                continue;
+            }
+
+            let declared = match nodes.kind(declaration) {
+                AstKind::VariableDeclarator(_) => true,
+                AstKind::Function(function) => function.is_declaration(),
+                _ => false,
+            };
+            let only_assigned = symbols
+                .get_resolved_reference_ids(symbol)
+                .iter()
+                .all(|reference| is_simple_assignment(semantic, *reference));
+            if declared && only_assigned {
+                unused.insert(symbol);
+                continue;
+            }
+
+            for definition in dataflow.definitions_of(symbol) {
+                let node = dataflow.definition(*definition).node;
+                if !matches!(
+                    nodes.parent_kind(node),
+                    Some(AstKind::ExpressionStatement(_))
+                ) {
+                    continue;
+                }
+                let target = match nodes.kind(node) {
+                    AstKind::AssignmentExpression(assignment) => match &assignment.left {
+                        AssignmentTarget::AssignmentTargetIdentifier(id) => id.reference_id.get(),
+                        _ => None,
+                    },
+                    AstKind::UpdateExpression(update) => match &update.argument {
+                        SimpleAssignmentTarget::AssignmentTargetIdentifier(id) => {
+                            id.reference_id.get()
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if let Some(target) = target {
+                    if !liveness.is_read_after(*definition) {
+                        dead_stores.insert(target);
+                    }
+                }
+            }
+        }
+
+        let cfg = semantic.cfg().expect("semantic built without cfg");
+        let unreachable = nodes
+            .iter()
+            .filter(|node| cfg.basic_block(node.cfg_id()).is_unreachable())
+            .map(|node| node.kind().span())
+            // nodes synthesized by other passes have no place in the source
+            .filter(|span| !span.is_unspanned())
+            .collect();
+
+        Self {
+            semantic,
+            ast: AstBuilder::new(allocator),
+            unused,
+            dead_stores,
+            unreachable,
+            remarks: vec![],
+        }
+    }
+
+    fn symbol(&self, reference: Option<ReferenceId>) -> Option<SymbolId> {
+        self.semantic
+            .symbols()
+            .get_reference(reference?)
+            .symbol_id()
+    }
+
+    fn source(&self, span: Span) -> &'a str {
+        let text = span.source_text(self.semantic.source_text());
+        text.lines().next().unwrap_or_default()
+    }
+
+    /// Whether `statement` stays in its list, after rewriting it into the part that still has to
+    /// run. `previous_kept` tells whether the statement before stayed, so a run of unreachable
+    /// statements gets a single remark.
+    fn keep(&mut self, statement: &mut Statement<'a>, previous_kept: bool) -> bool {
+        let span = statement.span();
+        let hoisted = matches!(statement, Statement::FunctionDeclaration(_))
+            || matches!(statement, Statement::VariableDeclaration(declaration) if declaration.kind.is_var());
+        if self.unreachable.contains(&span) && !hoisted {
+            if previous_kept {
+                self.remarks.push(Remark::applied(
+                    span,
+                    "unreachable code removed".to_string(),
+                ));
+            }
+            return false;
+        }
+
+        match statement {
+            Statement::ExpressionStatement(statement) => {
+                if let Some(name) = self.dead_store(&statement.expression) {
+                    self.remarks.push(Remark::applied(
+                        span,
+                        format!("store to `{name}` removed: the value is never read"),
+                    ));
+                    // what is left of `x op= e` is `e`, and nothing of `x++`
+                    let Expression::AssignmentExpression(assignment) = &mut statement.expression
+                    else {
+                        return false;
+                    };
+                    statement.expression = self.ast.move_expression(&mut assignment.right);
+                    return !is_pure(self.semantic, &statement.expression);
+                }
+                if is_pure(self.semantic, &statement.expression) {
+                    self.remarks.push(Remark::applied(
+                        span,
+                        format!("`{}` removed: no side effects", self.source(span)),
+                    ));
+                    return false;
+                }
+            }
+            Statement::VariableDeclaration(declaration) => {
+                let mut removed = vec![];
+                declaration.declarations.retain(|declarator| {
+                    let BindingPatternKind::BindingIdentifier(id) = &declarator.id.kind else {
+                        return true;
+                    };
+                    if !id
+                        .symbol_id
+                        .get()
+                        .is_some_and(|symbol| self.unused.contains(&symbol))
+                    {
+                        return true;
+                    }
+                    let pure = declarator
+                        .init
+                        .as_ref()
+                        .is_none_or(|init| is_pure(self.semantic, init));
+                    removed.push((declarator.span, id.name.to_string(), pure));
+                    !pure
+                });
+                for (span, name, pure) in removed {
+                    self.remarks.push(if pure {
+                        Remark::applied(span, format!("`{name}` removed: never read"))
+                    } else {
+                        Remark::missed(
+                            span,
+                            format!("`{name}` kept: its initializer may have side effects"),
+                        )
+                    });
+                }
+                return !declaration.declarations.is_empty();
+            }
+            Statement::FunctionDeclaration(function) => {
+                let symbol = function.id.as_ref().and_then(|id| id.symbol_id.get());
+                if let (Some(symbol), Some(id)) = (symbol, &function.id) {
+                    if self.unused.contains(&symbol) {
+                        self.remarks.push(Remark::applied(
+                            span,
+                            format!("`{}` removed: never called", id.name),
+                        ));
+                        return false;
+                    }
+                }
+            }
+            Statement::IfStatement(if_) => {
+                let Expression::BooleanLiteral(test) = &if_.test else {
+                    return true;
+                };
+                let value = test.value;
+                let taken = if value {
+                    Some(&mut if_.consequent)
+                } else {
+                    if_.alternate.as_mut()
+                };
+                let Some(taken) = taken else {
+                    self.remarks.push(Remark::applied(
+                        span,
+                        "`if (false)` removed: the condition is always false".to_string(),
+                    ));
+                    return false;
+                };
+                if declares(taken) {
+                    // without its own block the branch's declarations would leak
+                    if value && if_.alternate.is_some() {
+                        if_.alternate = None;
+                        self.remarks.push(Remark::applied(
+                            span,
+                            "`else` removed: the condition is always true".to_string(),
+                        ));
+                    }
+                    return true;
+                }
+                self.remarks.push(Remark::applied(
+                    span,
+                    format!("`if ({value})` replaced by the branch it always takes"),
+                ));
+                *statement = self.ast.move_statement(taken);
+            }
+            Statement::WhileStatement(while_) => {
+                if matches!(&while_.test, Expression::BooleanLiteral(test) if !test.value) {
+                    self.remarks.push(Remark::applied(
+                        span,
+                        "`while (false)` removed: the loop never runs".to_string(),
+                    ));
+                    return false;
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// The variable `expr` stores into, when it is `x = e`, `x op= e` or `x++` and nothing reads
+    /// the value it stores.
+    fn dead_store(&self, expr: &Expression<'a>) -> Option<&'a str> {
+        let id = match expr {
+            Expression::AssignmentExpression(assignment) => match &assignment.left {
+                AssignmentTarget::AssignmentTargetIdentifier(id) => id,
+                _ => return None,
+            },
+            Expression::UpdateExpression(update) => match &update.argument {
+                SimpleAssignmentTarget::AssignmentTargetIdentifier(id) => id,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let reference = id.reference_id.get()?;
+        self.dead_stores
+            .contains(&reference)
+            .then(|| id.name.as_str())
+    }
+}
+
+/// Whether `reference` is only the target of an `x = e`.
+fn is_simple_assignment(semantic: &Semantic, reference: ReferenceId) -> bool {
+    let nodes = semantic.nodes();
+    let reference = semantic.symbols().get_reference(reference);
+    if reference.is_read() || !reference.is_write() {
+        return false;
+    }
+    let parent = nodes.ancestor_ids(reference.node_id()).skip(1).find(|id| {
+        !matches!(
+            nodes.kind(*id),
+            AstKind::SimpleAssignmentTarget(_) | AstKind::AssignmentTarget(_)
+        )
+    });
+    parent.is_some_and(|parent| {
+        matches!(
+            nodes.kind(parent),
+            AstKind::AssignmentExpression(assignment) if assignment.operator.is_assign()
+                && matches!(assignment.left, AssignmentTarget::AssignmentTargetIdentifier(_))
+        )
+    })
+}
+
+/// Whether `statement` is a block declaring something scoped to it.
+fn declares(statement: &Statement) -> bool {
+    let Statement::BlockStatement(block) = statement else {
+        return statement.is_declaration();
+    };
+    block.body.iter().any(|statement| match statement {
+        Statement::VariableDeclaration(declaration) => declaration.kind.is_lexical(),
+        statement => statement.is_declaration(),
+    })
+}
+
+impl<'a> VisitMut<'a> for DeadCodeOptimization<'_, 'a> {
+    fn visit_statements(&mut self, statements: &mut allocator::Vec<'a, Statement<'a>>) {
+        walk_mut::walk_statements(self, statements);
+
+        let mut previous_kept = true;
+        statements.retain_mut(|statement| {
+            previous_kept = self.keep(statement, previous_kept);
+            previous_kept
+        });
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'a>) {
+        if let Expression::AssignmentExpression(assignment) = expr {
+            let target = match &assignment.left {
+                AssignmentTarget::AssignmentTargetIdentifier(id)
+                    if assignment.operator.is_assign() =>
+                {
+                    Some(id)
+                }
+                _ => None,
+            };
+            if let Some(id) = target {
+                if self
+                    .symbol(id.reference_id.get())
+                    .is_some_and(|symbol| self.unused.contains(&symbol))
+                {
+                    self.remarks.push(Remark::applied(
+                        assignment.span,
+                        format!(
+                            "store to `{}` removed: `{}` is never read",
+                            id.name, id.name
+                        ),
+                    ));
+                    *expr = self.ast.move_expression(&mut assignment.right);
+                    return self.visit_expression(expr);
+                }
+            }
+        }
+
+        walk_mut::walk_expression(self, expr);
+    }
+}
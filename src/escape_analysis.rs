@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use oxc::{
+    ast::{
+        ast::{Expression, MemberExpression, UnaryOperator},
+        AstKind,
+    },
+    semantic::{NodeId, Semantic, SymbolId},
+};
+
+use crate::{
+    dataflow::{ConstantPropagation, Dataflow},
+    fresh_names::FreshNames,
+    type_inference::Types,
+};
+
+/// The longest `new Int32Array(n)` or `new Float64Array(n)` that [`stack_arrays`] moves to the
+/// stack.
+pub const MAX_STACK_LENGTH: usize = 1024;
+
+/// The member expressions `symbol` is the object of, each with its node, when that is all the
+/// variable is used for. The allocation it holds is then reachable through nothing else: it is
+/// never passed on, stored, returned, captured or exported, and dies with the variable.
+pub fn members<'a>(
+    semantic: &Semantic<'a>,
+    dataflow: &Dataflow,
+    symbol: SymbolId,
+) -> Result<Vec<(NodeId, &'a MemberExpression<'a>)>, &'static str> {
+    let symbols = semantic.symbols();
+    let nodes = semantic.nodes();
+    if !dataflow.is_tracked(symbol) {
+        return Err("a closure captures it");
+    }
+    let declaration = symbols.get_declaration(symbol);
+    // above the declarator and its declaration
+    if nodes
+        .ancestor_kinds(declaration)
+        .nth(2)
+        .is_some_and(|kind| matches!(kind, AstKind::ExportNamedDeclaration(_)))
+    {
+        return Err("it is exported");
+    }
+
+    let mut members = vec![];
+    for reference_id in symbols.get_resolved_reference_ids(symbol) {
+        let reference = symbols.get_reference(*reference_id);
+        if reference.is_write() {
+            return Err("it is assigned again");
+        }
+        let member = nodes.parent_id(reference.node_id()).ok_or("it escapes")?;
+        let AstKind::MemberExpression(access) = nodes.kind(member) else {
+            return Err("it escapes");
+        };
+        if !matches!(access.object(), Expression::Identifier(id) if id.reference_id.get() == Some(*reference_id))
+        {
+            return Err("it escapes");
+        }
+        match nodes.parent_kind(member) {
+            // the method would see the object as `this`
+            Some(AstKind::CallExpression(call)) if matches!(call.callee.as_member_expression(), Some(callee) if std::ptr::eq(callee, access)) =>
+            {
+                return Err("a method of it is called");
+            }
+            Some(AstKind::UnaryExpression(unary)) if unary.operator == UnaryOperator::Delete => {
+                return Err("a property of it is deleted");
+            }
+            _ => {}
+        }
+        members.push((member, access));
+    }
+    Ok(members)
+}
+
+/// A typed array that lives on the stack of the function creating it.
+pub struct StackArray {
+    /// The C++ variable holding its length and elements.
+    pub storage: String,
+    pub length: usize,
+}
+
+/// Finds the variables initialized with `new Int32Array(n)` or `new Float64Array(n)` for a
+/// constant `n` up to [`MAX_STACK_LENGTH`] that only ever index the array or read its length.
+pub fn stack_arrays(dataflow: &Dataflow, types: &Types) -> HashMap<SymbolId, StackArray> {
+    let semantic = dataflow.semantic();
+    let nodes = semantic.nodes();
+    let constants = dataflow.solve(ConstantPropagation);
+    let mut names = FreshNames::new(semantic);
+
+    let mut arrays = HashMap::new();
+    for node in nodes.iter() {
+        let AstKind::VariableDeclarator(declarator) = node.kind() else {
+            continue;
+        };
+        let (Some(symbol), Some(Expression::NewExpression(new))) = (
+            declarator
+                .id
+                .get_binding_identifier()
+                .and_then(|id| id.symbol_id.get()),
+            &declarator.init,
+        ) else {
+            continue;
+        };
+        // the initializer of a `for` is printed on its own
+        let in_for = nodes
+            .ancestor_ids(node.id())
+            .nth(2)
+            .is_some_and(|init| matches!(nodes.kind(init), AstKind::ForStatementInit(_)));
+        if in_for || !types.symbol(symbol).is_typed_array() || new.arguments.len() != 1 {
+            continue;
+        }
+        let length = new.arguments[0]
+            .as_expression()
+            .and_then(|length| constants.value(length, node.id()))
+            .filter(|length| {
+                length.fract() == 0.0 && *length >= 1.0 && *length <= MAX_STACK_LENGTH as f64
+            });
+        let Some(length) = length else {
+            continue;
+        };
+        let Ok(members) = members(semantic, dataflow, symbol) else {
+            continue;
+        };
+        let indexed = members.iter().all(|(_, member)| match member {
+            MemberExpression::ComputedMemberExpression(_) => true,
+            MemberExpression::StaticMemberExpression(member) => member.property.name == "length",
+            MemberExpression::PrivateFieldExpression(_) => false,
+        });
+        if indexed {
+            let name = semantic.symbols().get_name(symbol);
+            arrays.insert(
+                symbol,
+                StackArray {
+                    storage: names.fresh(&format!("{name}_storage")),
+                    length: length as usize,
+                },
+            );
+        }
+    }
+    arrays
+}
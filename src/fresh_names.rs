@@ -0,0 +1,39 @@
+use std::collections::HashSet;
+
+use oxc::semantic::Semantic;
+
+/// Hands out identifiers for synthesized variables that cannot collide with a binding,
+/// a global the program refers to, or an earlier fresh name.
+pub struct FreshNames {
+    taken: HashSet<String>,
+}
+
+impl FreshNames {
+    pub fn new(semantic: &Semantic) -> Self {
+        let symbols = semantic.symbols();
+        let mut taken: HashSet<String> = symbols
+            .symbol_ids()
+            .map(|symbol_id| symbols.get_name(symbol_id).to_string())
+            .collect();
+        taken.extend(
+            semantic
+                .scopes()
+                .root_unresolved_references()
+                .keys()
+                .map(|name| name.to_string()),
+        );
+        Self { taken }
+    }
+
+    /// `base`, or `base_2`, `base_3`, ... if that is taken.
+    pub fn fresh(&mut self, base: &str) -> String {
+        let mut name = base.to_string();
+        let mut suffix = 1;
+        while self.taken.contains(&name) {
+            suffix += 1;
+            name = format!("{base}_{suffix}");
+        }
+        self.taken.insert(name.clone());
+        name
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+use oxc::{
+    ast::{
+        ast::{
+            AssignmentOperator, AssignmentTarget, BinaryOperator, ContinueStatement, Expression,
+            ForStatement, Function, SimpleAssignmentTarget, Statement, UpdateOperator,
+        },
+        AstKind, Visit,
+    },
+    semantic::{NodeId, ReferenceId, ScopeFlags, Semantic, SymbolId},
+};
+
+use crate::dataflow::{ConstantPropagation, Dataflow, Results};
+
+/// The variables of a `for` loop: which ones keep their value through it, and which ones its
+/// update steps by a constant once per iteration, its basic induction variables.
+pub struct Loop<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    dataflow: &'s Dataflow<'s, 'a>,
+    node: NodeId,
+    /// The step of each basic induction variable.
+    steps: HashMap<SymbolId, i64>,
+}
+
+/// A variable that a statement of a loop body steps by a constant once per iteration, as the
+/// update steps `counter`: whenever the loop test runs, it holds `offset + scale * counter`.
+#[derive(Debug)]
+pub struct Lockstep {
+    pub symbol: SymbolId,
+    pub counter: SymbolId,
+    pub offset: f64,
+    pub scale: f64,
+}
+
+impl<'s, 'a> Loop<'s, 'a> {
+    pub fn new(dataflow: &'s Dataflow<'s, 'a>, for_: &ForStatement<'a>) -> Self {
+        let semantic = dataflow.semantic();
+        Self {
+            semantic,
+            dataflow,
+            node: semantic.scopes().get_node_id(for_.scope_id()),
+            steps: HashMap::new(),
+        }
+    }
+
+    /// Also finds the basic induction variables, with steps given by `constants` where they
+    /// are not literals.
+    pub fn with_induction_variables(
+        mut self,
+        for_: &ForStatement<'a>,
+        constants: &Results<'_, 's, 'a, ConstantPropagation>,
+    ) -> Self {
+        let mut updates = vec![];
+        if let Some(update) = &for_.update {
+            self.steps_of(update, constants, &mut updates);
+        }
+        let symbols = self.semantic.symbols();
+        for (reference, step) in updates {
+            let Some(symbol) = symbols.get_reference(reference).symbol_id() else {
+                continue;
+            };
+            let declaration = symbols.get_declaration(symbol);
+            if self.only_write(symbol, reference)
+                && self.dataflow.is_tracked(symbol)
+                && (!self.contains(declaration) || self.in_initializer(declaration))
+            {
+                self.steps.insert(symbol, step);
+            }
+        }
+        self
+    }
+
+    /// The variables of the initializer that a statement of the body steps in lock step with a
+    /// basic induction variable also declared there, starting from constants or from
+    /// `counter * scale`. A body with `continue` may skip the statement.
+    pub fn locksteps(
+        &self,
+        for_: &ForStatement<'a>,
+        constants: &Results<'_, 's, 'a, ConstantPropagation>,
+    ) -> Vec<Lockstep> {
+        let Statement::BlockStatement(body) = &for_.body else {
+            return vec![];
+        };
+        let mut continues = Continues(false);
+        continues.visit_statement(&for_.body);
+        if continues.0 {
+            return vec![];
+        }
+        let mut updates = vec![];
+        for statement in &body.body {
+            if let Statement::ExpressionStatement(statement) = statement {
+                self.steps_of(&statement.expression, constants, &mut updates);
+            }
+        }
+
+        let symbols = self.semantic.symbols();
+        let nodes = self.semantic.nodes();
+        let init = |declaration: NodeId| match nodes.kind(declaration) {
+            AstKind::VariableDeclarator(declarator) => declarator.init.as_ref(),
+            _ => None,
+        };
+        let mut counters: Vec<(SymbolId, i64)> = self
+            .steps
+            .iter()
+            .map(|(symbol, step)| (*symbol, *step))
+            .filter(|(symbol, _)| self.in_initializer(symbols.get_declaration(*symbol)))
+            .collect();
+        counters.sort();
+
+        let mut locksteps = vec![];
+        for (reference, step) in updates {
+            let Some(symbol) = symbols.get_reference(reference).symbol_id() else {
+                continue;
+            };
+            let declaration = symbols.get_declaration(symbol);
+            if !self.only_write(symbol, reference)
+                || !self.dataflow.is_tracked(symbol)
+                || !self.in_initializer(declaration)
+            {
+                continue;
+            }
+            let Some(start) = init(declaration) else {
+                continue;
+            };
+            for &(counter, counter_step) in &counters {
+                let scale = step as f64 / counter_step as f64;
+                let counter_declaration = symbols.get_declaration(counter);
+                let counter_start = init(counter_declaration)
+                    .and_then(|init| constants.value(init, counter_declaration));
+                let offset = match (constants.value(start, declaration), counter_start) {
+                    (Some(start), Some(counter_start)) => start - scale * counter_start,
+                    _ if self.is_product(start, counter, scale, constants, declaration) => 0.0,
+                    _ => continue,
+                };
+                locksteps.push(Lockstep {
+                    symbol,
+                    counter,
+                    offset,
+                    scale,
+                });
+                break;
+            }
+        }
+        locksteps
+    }
+
+    /// `expr` is `counter * scale` or `scale * counter`, evaluated at `node`.
+    fn is_product(
+        &self,
+        expr: &Expression,
+        counter: SymbolId,
+        scale: f64,
+        constants: &Results<'_, 's, 'a, ConstantPropagation>,
+        node: NodeId,
+    ) -> bool {
+        let Expression::BinaryExpression(product) = expr.without_parentheses() else {
+            return false;
+        };
+        let is = |variable: &Expression, factor: &Expression| {
+            self.dataflow.reference_symbol(variable) == Some(counter)
+                && constants.value(factor, node) == Some(scale)
+        };
+        product.operator == BinaryOperator::Multiplication
+            && (is(&product.left, &product.right) || is(&product.right, &product.left))
+    }
+
+    /// `reference` is the only write to `symbol` within the loop.
+    fn only_write(&self, symbol: SymbolId, reference: ReferenceId) -> bool {
+        let symbols = self.semantic.symbols();
+        symbols
+            .get_resolved_reference_ids(symbol)
+            .iter()
+            .filter(|other| symbols.get_reference(**other).is_write())
+            .filter(|other| self.contains(symbols.get_reference(**other).node_id()))
+            .all(|other| *other == reference)
+    }
+
+    /// The `x++`, `x -= c` or `x = x + c` in `update`, as the written reference and the step.
+    fn steps_of(
+        &self,
+        update: &Expression<'a>,
+        constants: &Results<'_, 's, 'a, ConstantPropagation>,
+        steps: &mut Vec<(ReferenceId, i64)>,
+    ) {
+        let constant = |expr: &Expression, reference: ReferenceId| {
+            let node = self.semantic.symbols().get_reference(reference).node_id();
+            constants
+                .value(expr, node)
+                .filter(|value| value.fract() == 0.0 && value.abs() <= i32::MAX as f64)
+                .map(|value| value as i64)
+        };
+        let reference = |target: &SimpleAssignmentTarget| match target {
+            SimpleAssignmentTarget::AssignmentTargetIdentifier(id) => id.reference_id.get(),
+            _ => None,
+        };
+        match update {
+            Expression::SequenceExpression(sequence) => {
+                for expr in &sequence.expressions {
+                    self.steps_of(expr, constants, steps);
+                }
+            }
+            Expression::ParenthesizedExpression(expr) => {
+                self.steps_of(&expr.expression, constants, steps)
+            }
+            Expression::UpdateExpression(update) => {
+                if let Some(reference) = reference(&update.argument) {
+                    let step = match update.operator {
+                        UpdateOperator::Increment => 1,
+                        UpdateOperator::Decrement => -1,
+                    };
+                    steps.push((reference, step));
+                }
+            }
+            Expression::AssignmentExpression(assign) => {
+                let AssignmentTarget::AssignmentTargetIdentifier(id) = &assign.left else {
+                    return;
+                };
+                let Some(reference) = id.reference_id.get() else {
+                    return;
+                };
+                let step = match (assign.operator, &assign.right) {
+                    (AssignmentOperator::Addition, right) => constant(right, reference),
+                    (AssignmentOperator::Subtraction, right) => {
+                        constant(right, reference).map(|step| -step)
+                    }
+                    (AssignmentOperator::Assign, Expression::BinaryExpression(right)) => {
+                        let same = |expr: &Expression| matches!(expr, Expression::Identifier(other) if other.name == id.name);
+                        match right.operator {
+                            BinaryOperator::Addition if same(&right.left) => {
+                                constant(&right.right, reference)
+                            }
+                            BinaryOperator::Addition if same(&right.right) => {
+                                constant(&right.left, reference)
+                            }
+                            BinaryOperator::Subtraction if same(&right.left) => {
+                                constant(&right.right, reference).map(|step| -step)
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(step) = step {
+                    steps.push((reference, step));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// `node` is part of the loop, its initializer included.
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.semantic
+            .nodes()
+            .ancestor_ids(node)
+            .any(|id| id == self.node)
+    }
+
+    /// `declaration` is one of the declarators of the loop initializer.
+    fn in_initializer(&self, declaration: NodeId) -> bool {
+        let nodes = self.semantic.nodes();
+        // above the declarator and its declaration
+        let mut ancestors = nodes.ancestor_ids(declaration).skip(2);
+        ancestors
+            .next()
+            .is_some_and(|init| matches!(nodes.kind(init), AstKind::ForStatementInit(_)))
+            && ancestors.next() == Some(self.node)
+    }
+
+    /// `symbol` keeps its value for the whole loop: it is declared before the loop or by its
+    /// initializer, and nothing in the loop writes it, nested functions included.
+    pub fn is_fixed(&self, symbol: SymbolId) -> bool {
+        let symbols = self.semantic.symbols();
+        let declaration = symbols.get_declaration(symbol);
+        if self.contains(declaration) && !self.in_initializer(declaration) {
+            return false;
+        }
+        let mut writes = symbols
+            .get_resolved_reference_ids(symbol)
+            .iter()
+            .map(|reference| symbols.get_reference(*reference))
+            .filter(|reference| reference.is_write())
+            .peekable();
+        // functions called from the loop may write what they can see
+        if writes.peek().is_some() && !self.dataflow.is_tracked(symbol) {
+            return false;
+        }
+        writes.all(|reference| !self.contains(reference.node_id()))
+    }
+
+    /// How much the update adds to `symbol` per iteration, if it is a basic induction variable.
+    pub fn step(&self, symbol: SymbolId) -> Option<i64> {
+        self.steps.get(&symbol).copied()
+    }
+}
+
+/// Whether a statement contains `continue`.
+pub struct Continues(pub bool);
+
+impl<'a> Visit<'a> for Continues {
+    fn visit_continue_statement(&mut self, _continue: &ContinueStatement<'a>) {
+        self.0 = true;
+    }
+
+    fn visit_function(&mut self, _function: &Function<'a>, _flags: ScopeFlags) {}
+}
@@ -0,0 +1,689 @@
+use std::collections::{HashMap, HashSet};
+
+use oxc::{
+    allocator::{self, Allocator, CloneIn},
+    ast::{
+        ast::{
+            Argument, ArrowFunctionExpression, BindingPatternKind, CallExpression, Expression,
+            Function, FunctionType, IdentifierReference, ObjectProperty, ReturnStatement,
+            Statement, ThisExpression, VariableDeclaration, VariableDeclarationKind,
+        },
+        visit::{walk, walk_mut},
+        AstBuilder, AstKind, Visit, VisitMut,
+    },
+    semantic::{NodeId, ScopeFlags, ScopeId, Semantic, SymbolId},
+    span::{Atom, GetSpan, Span, SPAN},
+};
+
+use crate::{
+    dataflow::Dataflow, fresh_names::FreshNames, profile::ModuleProfile, purity::is_pure,
+    remarks::Remark,
+};
+
+/// How often a call must have run in the profile to be inlined under a larger threshold.
+pub const HOT_CALLS: u64 = 1000;
+
+/// Replaces direct calls to small function declarations with their body, so the passes after it
+/// see the loops and arithmetic the function hid:
+///
+/// ```js
+/// function wrap(i, n) { return i % n; }
+/// s += wrap(i, k);   // s += i % k
+/// update(a, i);      // { let a_2 = a; let i_2 = i; { a_2[i_2] = 0; } }
+/// ```
+///
+/// A function whose body is a single `return` is inlined into any expression, when evaluating its
+/// arguments has no side effects that moving them into the body could reorder. A function that
+/// returns nothing is inlined into a call statement as a block binding its parameters.
+///
+/// The body is copied only when its cost, the number of AST nodes it adds, is at most the
+/// threshold, or twice the threshold for calls inside a loop. Under `--profile-use`, a call
+/// that ran at least [`HOT_CALLS`] times may cost four times the threshold. Recursive functions, functions
+/// defining closures and functions using `this` or `arguments` are never inlined. Calls in an
+/// inlined body are inlined by the next round of the pipeline.
+pub struct InliningOptimization<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    allocator: &'a Allocator,
+    ast: AstBuilder<'a>,
+    names: FreshNames,
+    threshold: usize,
+    /// The position of every call the profile counts as hot.
+    hot: HashSet<u32>,
+    /// Every function declaration, with what a call needs to inline it or why it cannot be.
+    candidates: HashMap<SymbolId, Result<Candidate<'a>, &'static str>>,
+    pub remarks: Vec<Remark>,
+}
+
+/// A function declaration that can be inlined.
+struct Candidate<'a> {
+    params: Vec<SymbolId>,
+    /// A copy of the body taken before any rewrite.
+    body: Body<'a>,
+    /// AST nodes in the body.
+    size: usize,
+    /// How often each parameter is read.
+    uses: HashMap<SymbolId, usize>,
+    /// The parameter each parameter reference in the body refers to, by position, since the copy
+    /// of the body is not resolved.
+    references: HashMap<Span, SymbolId>,
+    /// Whether evaluating the returned expression has no side effects.
+    pure: bool,
+    /// The names the body refers to outside of it, and the symbol each resolves to; `None` for
+    /// globals.
+    captures: Vec<(String, Option<SymbolId>)>,
+}
+
+enum Body<'a> {
+    /// The `e` of a body that is only `return e;`.
+    Expression(Expression<'a>),
+    /// The statements of a body without `return`.
+    Statements(allocator::Vec<'a, Statement<'a>>),
+}
+
+/// What the body of a function contains, as far as inlining cares.
+struct Scan<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    scope: ScopeId,
+    params: &'s [SymbolId],
+    size: usize,
+    closures: bool,
+    this: bool,
+    arguments: bool,
+    var: bool,
+    returns: usize,
+    uses: HashMap<SymbolId, usize>,
+    references: HashMap<Span, SymbolId>,
+    /// A parameter reference synthesized by another pass, which has no position.
+    unspanned: bool,
+    written_params: bool,
+    captures: Vec<(String, Option<SymbolId>)>,
+}
+
+impl<'a> Visit<'a> for Scan<'_, 'a> {
+    fn enter_node(&mut self, _kind: AstKind<'a>) {
+        self.size += 1;
+    }
+
+    fn visit_function(&mut self, _function: &Function<'a>, _flags: ScopeFlags) {
+        self.closures = true;
+    }
+
+    fn visit_arrow_function_expression(&mut self, _function: &ArrowFunctionExpression<'a>) {
+        self.closures = true;
+    }
+
+    fn visit_this_expression(&mut self, _this: &ThisExpression) {
+        self.this = true;
+    }
+
+    fn visit_variable_declaration(&mut self, declaration: &VariableDeclaration<'a>) {
+        self.var |= declaration.kind.is_var();
+        walk::walk_variable_declaration(self, declaration);
+    }
+
+    fn visit_return_statement(&mut self, statement: &ReturnStatement<'a>) {
+        self.returns += 1;
+        walk::walk_return_statement(self, statement);
+    }
+
+    fn visit_identifier_reference(&mut self, id: &IdentifierReference<'a>) {
+        self.size += 1;
+        let Some(reference) = id.reference_id.get() else {
+            return;
+        };
+        let symbols = self.semantic.symbols();
+        let reference = symbols.get_reference(reference);
+        let Some(symbol) = reference.symbol_id() else {
+            self.arguments |= id.name == "arguments";
+            self.captures.push((id.name.to_string(), None));
+            return;
+        };
+        if self.params.contains(&symbol) {
+            self.references.insert(id.span, symbol);
+            self.unspanned |= id.span.is_unspanned();
+            if reference.is_write() {
+                self.written_params = true;
+            }
+            if reference.is_read() {
+                *self.uses.entry(symbol).or_default() += 1;
+            }
+        }
+        let local = self
+            .semantic
+            .scopes()
+            .ancestors(symbols.get_scope_id(symbol))
+            .any(|scope| scope == self.scope);
+        if !local {
+            self.captures.push((id.name.to_string(), Some(symbol)));
+        }
+    }
+}
+
+impl<'s, 'a> InliningOptimization<'s, 'a> {
+    pub fn new(
+        dataflow: &'s Dataflow<'s, 'a>,
+        allocator: &'a Allocator,
+        threshold: usize,
+        profile: Option<&ModuleProfile>,
+    ) -> Self {
+        let semantic = dataflow.semantic();
+        let hot = profile
+            .map(|profile| {
+                semantic
+                    .nodes()
+                    .iter()
+                    .filter_map(|node| match node.kind() {
+                        AstKind::CallExpression(call)
+                            if profile
+                                .calls(call.span)
+                                .is_some_and(|calls| calls >= HOT_CALLS) =>
+                        {
+                            Some(call.span.start)
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut pass = Self {
+            semantic,
+            allocator,
+            ast: AstBuilder::new(allocator),
+            names: FreshNames::new(semantic),
+            threshold,
+            hot,
+            candidates: HashMap::new(),
+            remarks: vec![],
+        };
+
+        let calls = pass.call_graph();
+        for node in semantic.nodes().iter() {
+            let AstKind::Function(function) = node.kind() else {
+                continue;
+            };
+            let Some(symbol) = function.id.as_ref().and_then(|id| id.symbol_id.get()) else {
+                continue;
+            };
+            if function.r#type != FunctionType::FunctionDeclaration {
+                continue;
+            }
+            let candidate = if reaches(&calls, symbol, symbol) {
+                Err("it is recursive")
+            } else {
+                pass.candidate(node.id(), function)
+            };
+            pass.candidates.insert(symbol, candidate);
+        }
+        pass
+    }
+
+    /// For every function declaration, the function declarations its body refers to.
+    fn call_graph(&self) -> HashMap<SymbolId, HashSet<SymbolId>> {
+        let symbols = self.semantic.symbols();
+        let nodes = self.semantic.nodes();
+        let mut calls: HashMap<SymbolId, HashSet<SymbolId>> = HashMap::new();
+        for callee in symbols.symbol_ids() {
+            if !matches!(
+                nodes.kind(symbols.get_declaration(callee)),
+                AstKind::Function(_)
+            ) {
+                continue;
+            }
+            for reference in symbols.get_resolved_references(callee) {
+                let caller = nodes
+                    .ancestor_kinds(reference.node_id())
+                    .find_map(|kind| match kind {
+                        AstKind::Function(function) => Some(function.id.as_ref()),
+                        AstKind::ArrowFunctionExpression(_) => Some(None),
+                        _ => None,
+                    })
+                    .flatten()
+                    .and_then(|id| id.symbol_id.get());
+                if let Some(caller) = caller {
+                    calls.entry(caller).or_default().insert(callee);
+                }
+            }
+        }
+        calls
+    }
+
+    /// What a call needs to inline `function`, declared at `node`, or why it cannot.
+    fn candidate(
+        &self,
+        node: NodeId,
+        function: &Function<'a>,
+    ) -> Result<Candidate<'a>, &'static str> {
+        if function.r#async || function.generator {
+            return Err("it is async or a generator");
+        }
+        let mut params = vec![];
+        for param in &function.params.items {
+            match &param.pattern.kind {
+                BindingPatternKind::BindingIdentifier(id) => params.push(
+                    id.symbol_id
+                        .get()
+                        .ok_or("its parameters are not plain names")?,
+                ),
+                _ => return Err("its parameters are not plain names"),
+            }
+        }
+        if function.params.rest.is_some() {
+            return Err("its parameters are not plain names");
+        }
+        // a `@param {int32} n` hint converts the argument, a plain `let` would not
+        let hinted = self
+            .semantic
+            .jsdoc()
+            .get_all_by_node(self.semantic.nodes().get_node(node))
+            .unwrap_or_default()
+            .iter()
+            .any(|jsdoc| jsdoc.tags().iter().any(|tag| tag.kind.parsed() == "param"));
+        if hinted {
+            return Err("its parameters have type hints");
+        }
+        let body = function.body.as_ref().ok_or("it has no body")?;
+
+        let mut scan = Scan {
+            semantic: self.semantic,
+            scope: function.scope_id.get().ok_or("it has no scope")?,
+            params: &params,
+            size: 0,
+            closures: false,
+            this: false,
+            arguments: false,
+            var: false,
+            returns: 0,
+            uses: HashMap::new(),
+            references: HashMap::new(),
+            unspanned: false,
+            written_params: false,
+            captures: vec![],
+        };
+        scan.visit_function_body(body);
+        if scan.closures {
+            return Err("it defines closures");
+        }
+        if scan.this {
+            return Err("it uses `this`");
+        }
+        if scan.arguments {
+            return Err("it uses `arguments`");
+        }
+        if scan.unspanned {
+            return Err("another pass rewrote it");
+        }
+
+        let (body, pure) = match body.statements.as_slice() {
+            [Statement::ReturnStatement(statement)] if statement.argument.is_some() => {
+                if scan.written_params {
+                    return Err("it assigns its parameters");
+                }
+                let expr = statement.argument.as_ref().unwrap();
+                (
+                    Body::Expression(expr.clone_in(self.allocator)),
+                    is_pure(self.semantic, expr),
+                )
+            }
+            _ if scan.returns > 0 => return Err("it has statements besides its `return`"),
+            _ if scan.var => return Err("it declares `var` variables"),
+            _ => (
+                Body::Statements(body.statements.clone_in(self.allocator)),
+                false,
+            ),
+        };
+
+        Ok(Candidate {
+            params: params.clone(),
+            body,
+            size: scan.size,
+            uses: scan.uses,
+            references: scan.references,
+            pure,
+            captures: scan.captures,
+        })
+    }
+
+    /// The inlined candidate `call` refers to, checked against this call site.
+    fn check(
+        &self,
+        call: &CallExpression<'a>,
+        candidate: &Candidate<'a>,
+        statement: bool,
+    ) -> Result<(), String> {
+        if call.arguments.len() != candidate.params.len()
+            || call.arguments.iter().any(Argument::is_spread)
+        {
+            return Err(format!(
+                "it is called with {} arguments for {} parameters",
+                call.arguments.len(),
+                candidate.params.len()
+            ));
+        }
+        let Expression::Identifier(callee) = &call.callee else {
+            unreachable!();
+        };
+        let reference = callee.reference_id.get().ok_or("the call is synthesized")?;
+        let node = self.semantic.symbols().get_reference(reference).node_id();
+        let scope = self.semantic.nodes().get_node(node).scope_id();
+        for (name, symbol) in &candidate.captures {
+            if self.semantic.scopes().find_binding(scope, name) != *symbol {
+                return Err(format!("`{name}` means something else here"));
+            }
+        }
+
+        let mut cost = candidate.size;
+        match &candidate.body {
+            Body::Expression(_) => {
+                for (param, argument) in candidate.params.iter().zip(&call.arguments) {
+                    let argument = argument.to_expression();
+                    let literal = matches!(
+                        argument,
+                        Expression::NumericLiteral(_)
+                            | Expression::BooleanLiteral(_)
+                            | Expression::StringLiteral(_)
+                    );
+                    if !(literal || candidate.pure && is_pure(self.semantic, argument)) {
+                        return Err("its arguments would be evaluated out of order".to_string());
+                    }
+                    let uses = candidate.uses.get(param).copied().unwrap_or(0);
+                    cost += size(argument) * uses.saturating_sub(1);
+                }
+            }
+            Body::Statements(_) if !statement => {
+                return Err("it returns nothing but its result is used".to_string());
+            }
+            Body::Statements(_) => {}
+        }
+
+        let threshold = if !call.span.is_unspanned() && self.hot.contains(&call.span.start) {
+            self.threshold * 4
+        } else if self.in_loop(node) {
+            self.threshold * 2
+        } else {
+            self.threshold
+        };
+        if cost > threshold {
+            return Err(format!(
+                "its cost {cost} is above the threshold of {threshold}"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether `node` runs in a loop of its function.
+    fn in_loop(&self, node: NodeId) -> bool {
+        self.semantic
+            .nodes()
+            .ancestor_kinds(node)
+            .take_while(|kind| {
+                !matches!(
+                    kind,
+                    AstKind::Function(_) | AstKind::ArrowFunctionExpression(_)
+                )
+            })
+            .any(|kind| {
+                matches!(
+                    kind,
+                    AstKind::ForStatement(_)
+                        | AstKind::WhileStatement(_)
+                        | AstKind::DoWhileStatement(_)
+                        | AstKind::ForInStatement(_)
+                        | AstKind::ForOfStatement(_)
+                )
+            })
+    }
+
+    /// The function declaration `call` calls directly, if it is one.
+    fn callee(&self, call: &CallExpression<'a>) -> Option<SymbolId> {
+        let Expression::Identifier(callee) = &call.callee else {
+            return None;
+        };
+        let symbol = self
+            .semantic
+            .symbols()
+            .get_reference(callee.reference_id.get()?)
+            .symbol_id()?;
+        self.candidates.contains_key(&symbol).then_some(symbol)
+    }
+
+    /// Whether `call` can be inlined, recording why when it cannot.
+    fn accept(&mut self, call: &CallExpression<'a>, statement: bool) -> Option<SymbolId> {
+        let symbol = self.callee(call)?;
+        let name = self.semantic.symbols().get_name(symbol);
+        let result = match &self.candidates[&symbol] {
+            Ok(candidate) => self.check(call, candidate, statement),
+            Err(reason) => Err(reason.to_string()),
+        };
+        match result {
+            Ok(()) => {
+                self.remarks.push(Remark::applied(
+                    call.span,
+                    format!("call to `{name}` inlined"),
+                ));
+                Some(symbol)
+            }
+            Err(reason) => {
+                self.remarks.push(Remark::missed(
+                    call.span,
+                    format!("call to `{name}` not inlined: {reason}"),
+                ));
+                None
+            }
+        }
+    }
+
+    /// The returned expression of `function` with its parameters replaced by `arguments`.
+    fn inline_expression(
+        &mut self,
+        function: SymbolId,
+        arguments: &mut allocator::Vec<'a, Argument<'a>>,
+    ) -> Expression<'a> {
+        let Ok(candidate) = &self.candidates[&function] else {
+            unreachable!();
+        };
+        let Body::Expression(body) = &candidate.body else {
+            unreachable!();
+        };
+        let mut body = body.clone_in(self.allocator);
+        let arguments = candidate
+            .params
+            .iter()
+            .zip(arguments.iter_mut())
+            .map(|(param, argument)| {
+                (
+                    *param,
+                    self.ast.move_expression(argument.to_expression_mut()),
+                )
+            })
+            .collect();
+        Substitute {
+            allocator: self.allocator,
+            references: &candidate.references,
+            arguments,
+        }
+        .visit_expression(&mut body);
+        body
+    }
+
+    /// A block binding the parameters of `function` to `arguments`, around its body.
+    fn inline_statements(
+        &mut self,
+        span: Span,
+        function: SymbolId,
+        arguments: &mut allocator::Vec<'a, Argument<'a>>,
+    ) -> Statement<'a> {
+        let Ok(candidate) = &self.candidates[&function] else {
+            unreachable!();
+        };
+        let Body::Statements(body) = &candidate.body else {
+            unreachable!();
+        };
+        let mut body = body.clone_in(self.allocator);
+        let params = candidate.params.clone();
+
+        let mut names = HashMap::new();
+        let mut block = self.ast.vec();
+        for (param, argument) in params.iter().zip(arguments.iter_mut()) {
+            let name = self.names.fresh(self.semantic.symbols().get_name(*param));
+            let init = self.ast.move_expression(argument.to_expression_mut());
+            // where the parameter gets its value
+            let span = init.span();
+            let declarator = self.ast.variable_declarator(
+                span,
+                VariableDeclarationKind::Let,
+                self.ast.binding_pattern(
+                    self.ast
+                        .binding_pattern_kind_binding_identifier(SPAN, name.clone()),
+                    None::<allocator::Box<_>>,
+                    false,
+                ),
+                Some(init),
+                false,
+            );
+            block.push(Statement::VariableDeclaration(
+                self.ast.alloc_variable_declaration(
+                    span,
+                    VariableDeclarationKind::Let,
+                    self.ast.vec1(declarator),
+                    false,
+                ),
+            ));
+            names.insert(*param, self.ast.atom(&name));
+        }
+
+        Rename {
+            references: &candidate.references,
+            names,
+        }
+        .visit_statements(&mut body);
+        // the body's own declarations must not shadow the arguments
+        block.push(self.ast.statement_block(SPAN, body));
+        self.ast.statement_block(span, block)
+    }
+}
+
+/// Whether `to` can be reached from `from` in `calls`, in at least one step.
+fn reaches(calls: &HashMap<SymbolId, HashSet<SymbolId>>, from: SymbolId, to: SymbolId) -> bool {
+    let mut seen = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(symbol) = stack.pop() {
+        for callee in calls.get(&symbol).into_iter().flatten() {
+            if *callee == to {
+                return true;
+            }
+            if seen.insert(*callee) {
+                stack.push(*callee);
+            }
+        }
+    }
+    false
+}
+
+/// The number of AST nodes in `expr`.
+fn size(expr: &Expression) -> usize {
+    struct Count(usize);
+    impl<'a> Visit<'a> for Count {
+        fn enter_node(&mut self, _kind: AstKind<'a>) {
+            self.0 += 1;
+        }
+    }
+    let mut count = Count(0);
+    count.visit_expression(expr);
+    count.0
+}
+
+/// Replaces the reads of parameters with their arguments.
+struct Substitute<'c, 'a> {
+    allocator: &'a Allocator,
+    references: &'c HashMap<Span, SymbolId>,
+    arguments: HashMap<SymbolId, Expression<'a>>,
+}
+
+impl<'a> VisitMut<'a> for Substitute<'_, 'a> {
+    fn visit_expression(&mut self, expr: &mut Expression<'a>) {
+        if let Expression::Identifier(id) = expr {
+            let argument = self
+                .references
+                .get(&id.span)
+                .and_then(|param| self.arguments.get(param));
+            if let Some(argument) = argument {
+                *expr = argument.clone_in(self.allocator);
+                return;
+            }
+        }
+        walk_mut::walk_expression(self, expr);
+    }
+
+    fn visit_object_property(&mut self, property: &mut ObjectProperty<'a>) {
+        // `{ n }` would name the argument
+        property.shorthand = false;
+        walk_mut::walk_object_property(self, property);
+    }
+}
+
+/// Renames the references to parameters.
+struct Rename<'c, 'a> {
+    references: &'c HashMap<Span, SymbolId>,
+    names: HashMap<SymbolId, Atom<'a>>,
+}
+
+impl<'a> VisitMut<'a> for Rename<'_, 'a> {
+    fn visit_identifier_reference(&mut self, id: &mut IdentifierReference<'a>) {
+        let name = self
+            .references
+            .get(&id.span)
+            .and_then(|param| self.names.get(param));
+        if let Some(name) = name {
+            id.name = name.clone();
+        }
+    }
+
+    fn visit_object_property(&mut self, property: &mut ObjectProperty<'a>) {
+        property.shorthand = false;
+        walk_mut::walk_object_property(self, property);
+    }
+}
+
+impl<'a> VisitMut<'a> for InliningOptimization<'_, 'a> {
+    fn visit_statement(&mut self, statement: &mut Statement<'a>) {
+        if let Statement::ExpressionStatement(expression) = statement {
+            if let Expression::CallExpression(call) = &mut expression.expression {
+                let statements = self.callee(call).is_some_and(|symbol| {
+                    matches!(
+                        &self.candidates[&symbol],
+                        Ok(Candidate {
+                            body: Body::Statements(_),
+                            ..
+                        })
+                    )
+                });
+                if statements {
+                    walk_mut::walk_arguments(self, &mut call.arguments);
+                    if let Some(function) = self.accept(call, true) {
+                        let span = expression.span;
+                        let Statement::ExpressionStatement(expression) = statement else {
+                            unreachable!();
+                        };
+                        let Expression::CallExpression(call) = &mut expression.expression else {
+                            unreachable!();
+                        };
+                        *statement = self.inline_statements(span, function, &mut call.arguments);
+                    }
+                    return;
+                }
+            }
+        }
+
+        walk_mut::walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'a>) {
+        walk_mut::walk_expression(self, expr);
+
+        if let Expression::CallExpression(call) = expr {
+            if let Some(function) = self.accept(call, false) {
+                *expr = self.inline_expression(function, &mut call.arguments);
+            }
+        }
+    }
+}
@@ -0,0 +1,617 @@
+//! A typed SSA representation of a module: every function is a graph of basic blocks whose
+//! values are assigned once, with phi nodes where control flow merges.
+//!
+//! [`crate::ir_builder`] lowers the AST into it; [`Module::verify`] checks the SSA invariants.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use oxc::syntax::operator::{BinaryOperator, UnaryOperator};
+
+use crate::type_inference::Type;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ValueId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32);
+
+impl fmt::Display for ValueId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+    Number(f64),
+    Boolean(bool),
+    String(String),
+    Undefined,
+}
+
+#[derive(Clone, Debug)]
+pub enum Op {
+    Const(Constant),
+    /// The function's parameter at this index.
+    Param(usize),
+    /// The value flowing in from each predecessor block.
+    Phi(Vec<(BlockId, ValueId)>),
+    /// A binding this function does not own: a global, an import or a function.
+    Global(String),
+    /// A variable shared with a closure or another module, which stays in memory.
+    LoadVar(String),
+    StoreVar(String, ValueId),
+    Unary(UnaryOperator, ValueId),
+    Binary(BinaryOperator, ValueId, ValueId),
+    /// `object.name`
+    Property(ValueId, String),
+    /// `object.name = value`
+    SetProperty(ValueId, String, ValueId),
+    /// `object[index]`
+    Load(ValueId, ValueId),
+    /// `object[index] = value`
+    Store(ValueId, ValueId, ValueId),
+    /// A call of a global function or of a static path into a global, like `Math.floor`.
+    Call(String, Vec<ValueId>),
+    /// `receiver.name(args)`
+    CallMethod(ValueId, String, Vec<ValueId>),
+    /// `new name(args)`
+    New(String, Vec<ValueId>),
+}
+
+impl Op {
+    /// Whether the operation produces a value other instructions can use.
+    pub fn has_result(&self) -> bool {
+        !matches!(self, Op::StoreVar(..) | Op::SetProperty(..) | Op::Store(..))
+    }
+
+    pub fn operands(&self) -> Vec<ValueId> {
+        match self {
+            Op::Const(_) | Op::Param(_) | Op::Global(_) | Op::LoadVar(_) => vec![],
+            Op::Phi(incoming) => incoming.iter().map(|(_, value)| *value).collect(),
+            Op::StoreVar(_, value) | Op::Unary(_, value) | Op::Property(value, _) => vec![*value],
+            Op::Binary(_, left, right) => vec![*left, *right],
+            Op::SetProperty(object, _, value) | Op::Load(object, value) => vec![*object, *value],
+            Op::Store(object, index, value) => vec![*object, *index, *value],
+            Op::Call(_, args) | Op::New(_, args) => args.clone(),
+            Op::CallMethod(receiver, _, args) => {
+                [*receiver].into_iter().chain(args.clone()).collect()
+            }
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut ValueId> {
+        match self {
+            Op::Const(_) | Op::Param(_) | Op::Global(_) | Op::LoadVar(_) => vec![],
+            Op::Phi(incoming) => incoming.iter_mut().map(|(_, value)| value).collect(),
+            Op::StoreVar(_, value) | Op::Unary(_, value) | Op::Property(value, _) => vec![value],
+            Op::Binary(_, left, right) => vec![left, right],
+            Op::SetProperty(object, _, value) | Op::Load(object, value) => vec![object, value],
+            Op::Store(object, index, value) => vec![object, index, value],
+            Op::Call(_, args) | Op::New(_, args) => args.iter_mut().collect(),
+            Op::CallMethod(receiver, _, args) => [receiver].into_iter().chain(args).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Value {
+    pub op: Op,
+    pub ty: Type,
+    pub block: BlockId,
+}
+
+#[derive(Clone, Debug)]
+pub enum Terminator {
+    Jump(BlockId),
+    /// Goes to the first block when the condition is truthy.
+    Branch(ValueId, BlockId, BlockId),
+    Return(Option<ValueId>),
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch(_, then, otherwise) => vec![*then, *otherwise],
+            Terminator::Return(_) => vec![],
+        }
+    }
+
+    fn operands_mut(&mut self) -> Vec<&mut ValueId> {
+        match self {
+            Terminator::Branch(condition, ..) => vec![condition],
+            Terminator::Return(Some(value)) => vec![value],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub phis: Vec<ValueId>,
+    pub body: Vec<ValueId>,
+    pub terminator: Terminator,
+}
+
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    /// The first block is the entry.
+    pub blocks: Vec<Block>,
+    pub values: Vec<Value>,
+}
+
+/// The top-level code of a module, as `main`, followed by its function declarations.
+#[derive(Clone, Debug, Default)]
+pub struct Module {
+    pub functions: Vec<Function>,
+}
+
+impl Function {
+    pub fn value(&self, id: ValueId) -> &Value {
+        &self.values[id.0 as usize]
+    }
+
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.0 as usize]
+    }
+
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![vec![]; self.blocks.len()];
+        for (index, block) in self.blocks.iter().enumerate() {
+            for successor in block.terminator.successors() {
+                predecessors[successor.0 as usize].push(BlockId(index as u32));
+            }
+        }
+        predecessors
+    }
+
+    /// The immediate dominator of every block reachable from the entry, by the iterative
+    /// algorithm of Cooper, Harvey and Kennedy.
+    pub fn dominators(&self) -> Vec<Option<BlockId>> {
+        let predecessors = self.predecessors();
+
+        // reverse postorder
+        let mut order = vec![];
+        let mut visited = vec![false; self.blocks.len()];
+        let mut stack = vec![(BlockId(0), 0)];
+        visited[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            let successors = self.block(block).terminator.successors();
+            if let Some(successor) = successors.get(next) {
+                stack.push((block, next + 1));
+                if !visited[successor.0 as usize] {
+                    visited[successor.0 as usize] = true;
+                    stack.push((*successor, 0));
+                }
+            } else {
+                order.push(block);
+            }
+        }
+        order.reverse();
+        let mut rank = vec![usize::MAX; self.blocks.len()];
+        for (index, block) in order.iter().enumerate() {
+            rank[block.0 as usize] = index;
+        }
+
+        let mut idom: Vec<Option<BlockId>> = vec![None; self.blocks.len()];
+        idom[0] = Some(BlockId(0));
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in order.iter().skip(1) {
+                let mut new_idom: Option<BlockId> = None;
+                for predecessor in &predecessors[block.0 as usize] {
+                    if idom[predecessor.0 as usize].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => *predecessor,
+                        Some(mut other) => {
+                            let mut finger = *predecessor;
+                            while finger != other {
+                                while rank[finger.0 as usize] > rank[other.0 as usize] {
+                                    finger = idom[finger.0 as usize].unwrap();
+                                }
+                                while rank[other.0 as usize] > rank[finger.0 as usize] {
+                                    other = idom[other.0 as usize].unwrap();
+                                }
+                            }
+                            finger
+                        }
+                    });
+                }
+                if idom[block.0 as usize] != new_idom {
+                    idom[block.0 as usize] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        idom
+    }
+
+    /// Whether every path from the entry to `block` goes through `dominator`.
+    fn dominates(idom: &[Option<BlockId>], dominator: BlockId, mut block: BlockId) -> bool {
+        loop {
+            if block == dominator {
+                return true;
+            }
+            match idom[block.0 as usize] {
+                Some(parent) if parent != block => block = parent,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Replaces every use of `from` with `to`.
+    pub fn replace_uses(&mut self, from: ValueId, to: ValueId) {
+        let blocks = &mut self.blocks;
+        let operands = self
+            .values
+            .iter_mut()
+            .flat_map(|value| value.op.operands_mut())
+            .chain(
+                blocks
+                    .iter_mut()
+                    .flat_map(|block| block.terminator.operands_mut()),
+            );
+        for operand in operands {
+            if *operand == from {
+                *operand = to;
+            }
+        }
+    }
+
+    /// Drops the blocks the entry cannot reach and numbers blocks and values in order.
+    pub fn compact(&mut self) {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![BlockId(0)];
+        reachable[0] = true;
+        while let Some(block) = stack.pop() {
+            for successor in self.block(block).terminator.successors() {
+                if !reachable[successor.0 as usize] {
+                    reachable[successor.0 as usize] = true;
+                    stack.push(successor);
+                }
+            }
+        }
+
+        let mut block_ids = HashMap::new();
+        for (index, _) in reachable.iter().enumerate().filter(|(_, live)| **live) {
+            block_ids.insert(BlockId(index as u32), BlockId(block_ids.len() as u32));
+        }
+        let mut value_ids = HashMap::new();
+        for (index, block) in self.blocks.iter().enumerate() {
+            if reachable[index] {
+                for value in block.phis.iter().chain(&block.body) {
+                    value_ids.insert(*value, ValueId(value_ids.len() as u32));
+                }
+            }
+        }
+
+        let mut values: Vec<Option<Value>> = vec![None; value_ids.len()];
+        for (old, new) in &value_ids {
+            let mut value = self.values[old.0 as usize].clone();
+            value.block = block_ids[&value.block];
+            if let Op::Phi(incoming) = &mut value.op {
+                incoming.retain(|(block, _)| block_ids.contains_key(block));
+                for (block, _) in incoming.iter_mut() {
+                    *block = block_ids[block];
+                }
+            }
+            for operand in value.op.operands_mut() {
+                *operand = value_ids[operand];
+            }
+            values[new.0 as usize] = Some(value);
+        }
+
+        let blocks = std::mem::take(&mut self.blocks);
+        self.blocks = blocks
+            .into_iter()
+            .zip(reachable)
+            .filter(|(_, live)| *live)
+            .map(|(mut block, _)| {
+                for value in block.phis.iter_mut().chain(&mut block.body) {
+                    *value = value_ids[value];
+                }
+                for operand in block.terminator.operands_mut() {
+                    *operand = value_ids[operand];
+                }
+                block.terminator = match block.terminator {
+                    Terminator::Jump(target) => Terminator::Jump(block_ids[&target]),
+                    Terminator::Branch(condition, then, otherwise) => {
+                        Terminator::Branch(condition, block_ids[&then], block_ids[&otherwise])
+                    }
+                    terminator => terminator,
+                };
+                block
+            })
+            .collect();
+        self.values = values.into_iter().map(Option::unwrap).collect();
+    }
+
+    /// Checks that every phi has one operand per predecessor, that definitions dominate their
+    /// uses and that types agree with the operations.
+    pub fn verify(&self) -> Result<(), String> {
+        let error = |message: String| Err(format!("{}: {message}", self.name));
+        if self.blocks.is_empty() {
+            return error("no entry block".into());
+        }
+
+        let predecessors = self.predecessors();
+        if !predecessors[0].is_empty() {
+            return error("the entry block has predecessors".into());
+        }
+        let idom = self.dominators();
+
+        // where every value is defined: its block and its position in it
+        let mut defined = HashMap::new();
+        for (index, block) in self.blocks.iter().enumerate() {
+            let block_id = BlockId(index as u32);
+            if idom[index].is_none() {
+                return error(format!("{block_id} is unreachable"));
+            }
+            for (position, value) in block.phis.iter().chain(&block.body).enumerate() {
+                let Some(data) = self.values.get(value.0 as usize) else {
+                    return error(format!("{value} does not exist"));
+                };
+                if data.block != block_id {
+                    return error(format!(
+                        "{value} is listed in {block_id} but belongs to {}",
+                        data.block
+                    ));
+                }
+                if defined.insert(*value, (block_id, position)).is_some() {
+                    return error(format!("{value} is defined twice"));
+                }
+            }
+        }
+        if defined.len() != self.values.len() {
+            return error("a value is not in any block".into());
+        }
+
+        let available = |value: &ValueId, block: BlockId, position: usize| match defined.get(value)
+        {
+            None => false,
+            Some((def_block, def_position)) if *def_block == block => *def_position < position,
+            Some((def_block, _)) => Self::dominates(&idom, *def_block, block),
+        };
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            let block_id = BlockId(index as u32);
+            let preds: HashSet<_> = predecessors[index].iter().copied().collect();
+
+            for (position, value) in block.phis.iter().chain(&block.body).enumerate() {
+                let data = self.value(*value);
+                let is_phi = position < block.phis.len();
+                match (&data.op, is_phi) {
+                    (Op::Phi(incoming), true) => {
+                        let sources: HashSet<_> =
+                            incoming.iter().map(|(block, _)| *block).collect();
+                        if sources != preds || incoming.len() != preds.len() {
+                            return error(format!(
+                                "{value} does not have one operand per predecessor of {block_id}"
+                            ));
+                        }
+                        for (pred, operand) in incoming {
+                            // the operand must be available at the end of the predecessor
+                            if !available(operand, *pred, usize::MAX) {
+                                return error(format!(
+                                    "{operand} does not dominate its use in {value}"
+                                ));
+                            }
+                            if !compatible(&data.ty, &self.value(*operand).ty) {
+                                return error(format!(
+                                    "{value} is {} but {operand} is {}",
+                                    data.ty,
+                                    self.value(*operand).ty
+                                ));
+                            }
+                        }
+                    }
+                    (Op::Phi(_), false) => {
+                        return error(format!("{value} is a phi in the body of {block_id}"))
+                    }
+                    (_, true) => return error(format!("{value} is listed as a phi of {block_id}")),
+                    (op, false) => {
+                        for operand in op.operands() {
+                            if !available(&operand, block_id, position) {
+                                return error(format!(
+                                    "{operand} does not dominate its use in {value}"
+                                ));
+                            }
+                            if !self.value(operand).op.has_result() {
+                                return error(format!(
+                                    "{value} uses {operand}, which has no result"
+                                ));
+                            }
+                        }
+                        self.check_type(*value)
+                            .or_else(|message| error(format!("{value}: {message}")))?;
+                    }
+                }
+            }
+
+            let mut terminator = block.terminator.clone();
+            for operand in terminator.operands_mut() {
+                if !available(operand, block_id, usize::MAX) {
+                    return error(format!("{operand} does not dominate the end of {block_id}"));
+                }
+            }
+            for successor in block.terminator.successors() {
+                if successor.0 as usize >= self.blocks.len() {
+                    return error(format!("{block_id} jumps to the missing {successor}"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_type(&self, value: ValueId) -> Result<(), String> {
+        let data = self.value(value);
+        let ty = &data.ty;
+        let expect = |ok: bool, what: &str| {
+            if ok || *ty == Type::Unknown {
+                Ok(())
+            } else {
+                Err(format!("{what} cannot be {ty}"))
+            }
+        };
+        match &data.op {
+            Op::Const(Constant::Number(_)) => expect(ty.is_numeric(), "a number"),
+            Op::Const(Constant::Boolean(_)) => expect(*ty == Type::Boolean, "a boolean"),
+            Op::Const(Constant::String(_)) => expect(*ty == Type::String, "a string"),
+            Op::Binary(operator, ..) if operator.is_compare() || operator.is_equality() => {
+                expect(*ty == Type::Boolean, "a comparison")
+            }
+            Op::Binary(operator, ..)
+                if operator.is_arithmetic() && *operator != BinaryOperator::Addition
+                    || operator.is_bitwise() =>
+            {
+                expect(ty.is_numeric(), "arithmetic")
+            }
+            Op::Unary(UnaryOperator::LogicalNot, _) => expect(*ty == Type::Boolean, "a negation"),
+            Op::Store(array, _, stored) => {
+                let array = &self.value(*array).ty;
+                let stored = &self.value(*stored).ty;
+                if matches!(array, Type::Int32Array | Type::Float64Array)
+                    && !(stored.is_numeric() || *stored == Type::Unknown)
+                {
+                    return Err(format!("stores a {stored} into an {array}"));
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether a value of type `from` may flow into a phi of type `to`.
+fn compatible(to: &Type, from: &Type) -> bool {
+    to == from
+        || *to == Type::Unknown
+        || *from == Type::Unknown
+        || (to.is_numeric() && from.is_numeric())
+}
+
+impl Module {
+    pub fn verify(&self) -> Result<(), String> {
+        self.functions.iter().try_for_each(Function::verify)
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Number(value) => write!(f, "{value}"),
+            Constant::Boolean(value) => write!(f, "{value}"),
+            Constant::String(value) => write!(f, "{value:?}"),
+            Constant::Undefined => write!(f, "undefined"),
+        }
+    }
+}
+
+/// `v1, v2, v3`
+struct List<'v>(&'v [ValueId]);
+
+impl fmt::Display for List<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, value) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Const(constant) => write!(f, "const {constant}"),
+            Op::Param(index) => write!(f, "param {index}"),
+            Op::Phi(incoming) => {
+                write!(f, "phi ")?;
+                for (index, (block, value)) in incoming.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "[{block}: {value}]")?;
+                }
+                Ok(())
+            }
+            Op::Global(name) => write!(f, "global {name}"),
+            Op::LoadVar(name) => write!(f, "load_var {name}"),
+            Op::StoreVar(name, value) => write!(f, "store_var {name}, {value}"),
+            Op::Unary(operator, value) => write!(f, "{} {value}", operator.as_str()),
+            Op::Binary(operator, left, right) => {
+                write!(f, "{left} {} {right}", operator.as_str())
+            }
+            Op::Property(object, name) => write!(f, "{object}.{name}"),
+            Op::SetProperty(object, name, value) => write!(f, "{object}.{name} = {value}"),
+            Op::Load(object, index) => write!(f, "{object}[{index}]"),
+            Op::Store(object, index, value) => write!(f, "{object}[{index}] = {value}"),
+            Op::Call(callee, args) => write!(f, "call {callee}({})", List(args)),
+            Op::CallMethod(receiver, name, args) => {
+                write!(f, "call {receiver}.{name}({})", List(args))
+            }
+            Op::New(callee, args) => write!(f, "new {callee}({})", List(args)),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "function {}({}) {{", self.name, self.params.join(", "))?;
+        let predecessors = self.predecessors();
+        for (index, block) in self.blocks.iter().enumerate() {
+            write!(f, "{}:", BlockId(index as u32))?;
+            if !predecessors[index].is_empty() {
+                let names: Vec<_> = predecessors[index].iter().map(|p| p.to_string()).collect();
+                write!(f, " ; preds {}", names.join(", "))?;
+            }
+            writeln!(f)?;
+            for value in block.phis.iter().chain(&block.body) {
+                let data = self.value(*value);
+                if data.op.has_result() {
+                    writeln!(f, "    {value}: {} = {}", data.ty, data.op)?;
+                } else {
+                    writeln!(f, "    {}", data.op)?;
+                }
+            }
+            match &block.terminator {
+                Terminator::Jump(target) => writeln!(f, "    jump {target}")?,
+                Terminator::Branch(condition, then, otherwise) => {
+                    writeln!(f, "    br {condition}, {then}, {otherwise}")?
+                }
+                Terminator::Return(Some(value)) => writeln!(f, "    return {value}")?,
+                Terminator::Return(None) => writeln!(f, "    return")?,
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{function}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,934 @@
+use std::collections::HashMap;
+
+use oxc::{
+    ast::{
+        ast::{
+            Argument, AssignmentExpression, AssignmentOperator, AssignmentTarget, BinaryOperator,
+            BindingPatternKind, Declaration, ExportDefaultDeclarationKind, Expression,
+            ForStatementInit, Function as FunctionNode, LogicalOperator, Program,
+            SimpleAssignmentTarget, Statement, UnaryOperator, UpdateExpression, UpdateOperator,
+            VariableDeclaration, VariableDeclarationKind,
+        },
+        AstKind,
+    },
+    cfg::{
+        graph::{visit::EdgeRef, Direction as EdgeDirection},
+        BlockNodeId, ControlFlowGraph, EdgeType,
+    },
+    semantic::{ScopeId, Semantic, SymbolId},
+    span::{GetSpan, Span},
+};
+
+use crate::{
+    ir::{Block, BlockId, Constant, Function, Module, Op, Terminator, Value, ValueId},
+    type_inference::{Type, Types},
+};
+
+/// Lowers a module's AST to [`crate::ir`], building SSA form with the algorithm of Braun et al.
+/// ("Simple and Efficient Construction of Static Single Assignment Form").
+///
+/// The blocks are the basic blocks of `semantic.cfg()`, the CFG [`crate::dataflow`] analyzes, and
+/// their edges are its edges, so both agree on control flow. The builder walks the AST to lower
+/// the expressions in each block, and follows the CFG edges wherever oxc starts a new block.
+/// oxc leaves the test of an `if` or `while` from the block the test starts in, and the update of
+/// a `for` from the block the update starts in; the builder moves those edges to the blocks the
+/// test and the update end in, where their values are known.
+///
+/// Variables that only the function declaring them uses become SSA values; variables a closure
+/// or another module can see stay in memory behind `load_var`/`store_var`.
+pub struct IrBuilder<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    cfg: &'s ControlFlowGraph,
+    types: &'s Types,
+}
+
+impl<'s, 'a> IrBuilder<'s, 'a> {
+    /// Panics if `semantic` was built without a CFG.
+    pub fn new(semantic: &'s Semantic<'a>, types: &'s Types) -> Self {
+        let cfg = semantic.cfg().expect("semantic built without cfg");
+        Self {
+            semantic,
+            cfg,
+            types,
+        }
+    }
+
+    /// The module's top-level code as `main`, then every function declaration in it, or why
+    /// part of the module cannot be lowered.
+    pub fn build(&self, program: &Program<'a>) -> Result<Module, String> {
+        let mut module = Module::default();
+
+        let root = self.semantic.nodes().root_node().unwrap();
+        let mut main = FunctionBuilder::new(
+            self,
+            "main".into(),
+            self.semantic.scopes().root_scope_id(),
+            root.cfg_id(),
+        );
+        main.statements(&program.body)?;
+        module.functions.push(main.finish());
+
+        for node in self.semantic.nodes().iter() {
+            if let AstKind::Function(function) = node.kind() {
+                if function.is_declaration() {
+                    module
+                        .functions
+                        .push(self.function(function, node.cfg_id())?);
+                }
+            }
+        }
+
+        Ok(module)
+    }
+
+    fn function(
+        &self,
+        function: &FunctionNode<'a>,
+        entry: BlockNodeId,
+    ) -> Result<Function, String> {
+        let name = function
+            .id
+            .as_ref()
+            .map_or("default".into(), |id| id.name.to_string());
+        let scope = function.scope_id.get().unwrap();
+        let mut builder = FunctionBuilder::new(self, name, scope, entry);
+
+        for (index, param) in function.params.items.iter().enumerate() {
+            let BindingPatternKind::BindingIdentifier(id) = &param.pattern.kind else {
+                return Err(self.unsupported("destructured parameters", param.span));
+            };
+            let symbol = id.symbol_id.get().unwrap();
+            let value = builder.emit(Op::Param(index), self.types.symbol(symbol));
+            builder.function.params.push(id.name.to_string());
+            builder.assign(symbol, value);
+        }
+        if function.params.rest.is_some() {
+            return Err(self.unsupported("rest parameters", function.params.span));
+        }
+
+        if let Some(body) = &function.body {
+            builder.statements(&body.statements)?;
+        }
+        Ok(builder.finish())
+    }
+
+    /// The successors of `block` along the edges `follows` accepts, in the order oxc created
+    /// them.
+    fn successors(
+        &self,
+        block: BlockNodeId,
+        follows: impl Fn(&EdgeType) -> bool,
+    ) -> Vec<BlockNodeId> {
+        let mut successors: Vec<BlockNodeId> = self
+            .cfg
+            .graph()
+            .edges_directed(block, EdgeDirection::Outgoing)
+            .filter(|edge| follows(edge.weight()))
+            .map(|edge| edge.target())
+            .collect();
+        successors.sort();
+        successors
+    }
+
+    fn unsupported(&self, what: &str, span: Span) -> String {
+        let source_text = self.semantic.source_text();
+        let line = source_text[..span.start as usize].matches('\n').count() + 1;
+        format!("line {line}: {what} are not supported")
+    }
+
+    /// The innermost function scope, or the module scope, containing `scope`.
+    fn function_scope(&self, scope: ScopeId) -> ScopeId {
+        let scopes = self.semantic.scopes();
+        scopes
+            .ancestors(scope)
+            .find(|scope| {
+                let flags = scopes.get_flags(*scope);
+                flags.is_function() || flags.is_top()
+            })
+            .unwrap()
+    }
+
+    fn reference_symbol(&self, expr: &Expression) -> Option<SymbolId> {
+        let Expression::Identifier(id) = expr else {
+            return None;
+        };
+        self.semantic
+            .symbols()
+            .get_reference(id.reference_id.get()?)
+            .symbol_id()
+    }
+
+    /// `Math.floor` for a static member path starting at a global.
+    fn global_path(&self, expr: &Expression) -> Option<String> {
+        match expr {
+            Expression::Identifier(id) if self.reference_symbol(expr).is_none() => {
+                Some(id.name.to_string())
+            }
+            Expression::StaticMemberExpression(member) => Some(format!(
+                "{}.{}",
+                self.global_path(&member.object)?,
+                member.property.name
+            )),
+            _ => None,
+        }
+    }
+
+    fn element_type(array: &Type) -> Type {
+        match array {
+            Type::Int32Array => Type::Int32,
+            Type::Float64Array => Type::Double,
+            _ => Type::Unknown,
+        }
+    }
+}
+
+struct FunctionBuilder<'b, 's, 'a> {
+    builder: &'b IrBuilder<'s, 'a>,
+    function: Function,
+    scope: ScopeId,
+    current: BlockId,
+    /// The IR block of each basic block of the CFG, and the other way around.
+    blocks: HashMap<BlockNodeId, BlockId>,
+    basic_blocks: Vec<BlockNodeId>,
+    predecessors: Vec<Vec<BlockId>>,
+    sealed: Vec<bool>,
+    /// The value of each SSA variable at the end of each block, as far as it is known.
+    definitions: HashMap<(SymbolId, BlockId), ValueId>,
+    /// Phis of blocks whose predecessors are not all known yet.
+    incomplete: HashMap<BlockId, Vec<(SymbolId, ValueId)>>,
+    /// Whether each symbol seen so far is an SSA variable of this function.
+    locals: HashMap<SymbolId, bool>,
+}
+
+impl<'b, 's, 'a> FunctionBuilder<'b, 's, 'a> {
+    fn new(
+        builder: &'b IrBuilder<'s, 'a>,
+        name: String,
+        scope: ScopeId,
+        entry: BlockNodeId,
+    ) -> Self {
+        let mut this = Self {
+            builder,
+            function: Function {
+                name,
+                params: vec![],
+                blocks: vec![],
+                values: vec![],
+            },
+            scope,
+            current: BlockId(0),
+            blocks: HashMap::new(),
+            basic_blocks: vec![],
+            predecessors: vec![],
+            sealed: vec![],
+            definitions: HashMap::new(),
+            incomplete: HashMap::new(),
+            locals: HashMap::new(),
+        };
+        // nothing jumps back to the start of a function
+        let entry = this.block(entry);
+        this.seal(entry);
+        this
+    }
+
+    /// Seals every block: the CFG only tells where a block's predecessors come from, so the
+    /// builder waits until they are all lowered.
+    fn finish(mut self) -> Function {
+        self.predecessors = self.function.predecessors();
+        for index in 0..self.function.blocks.len() {
+            if !self.sealed[index] {
+                self.seal(BlockId(index as u32));
+            }
+        }
+        // dropping unreachable predecessors can leave more phis trivial
+        self.function.compact();
+        self.remove_trivial_phis();
+        self.function.compact();
+        self.function
+    }
+
+    /// The IR block of the basic block `node`. Blocks end in `return` until they are terminated.
+    fn block(&mut self, node: BlockNodeId) -> BlockId {
+        if let Some(block) = self.blocks.get(&node) {
+            return *block;
+        }
+        self.function.blocks.push(Block {
+            phis: vec![],
+            body: vec![],
+            terminator: Terminator::Return(None),
+        });
+        self.predecessors.push(vec![]);
+        self.sealed.push(false);
+        self.basic_blocks.push(node);
+        let block = BlockId(self.function.blocks.len() as u32 - 1);
+        self.blocks.insert(node, block);
+        block
+    }
+
+    /// The first successor oxc created for `block` along the edges `follows` accepts.
+    fn successor_of(&mut self, block: BlockId, follows: fn(&EdgeType) -> bool) -> BlockId {
+        let successors = self
+            .builder
+            .successors(self.basic_blocks[block.0 as usize], follows);
+        self.block(
+            *successors
+                .first()
+                .expect("a basic block is missing an edge"),
+        )
+    }
+
+    fn successor(&mut self, follows: fn(&EdgeType) -> bool) -> BlockId {
+        self.successor_of(self.current, follows)
+    }
+
+    /// The last successor oxc created for `block` along the edges `follows` accepts.
+    fn last_successor_of(&mut self, block: BlockId, follows: fn(&EdgeType) -> bool) -> BlockId {
+        let successors = self
+            .builder
+            .successors(self.basic_blocks[block.0 as usize], follows);
+        self.block(*successors.last().expect("a basic block is missing an edge"))
+    }
+
+    /// Continues in the block oxc starts next, like the test of a loop or the code after a
+    /// function declaration.
+    fn fall_through(&mut self) {
+        let next = self.successor(is_normal);
+        self.jump(next);
+        self.current = next;
+    }
+
+    fn emit(&mut self, op: Op, ty: Type) -> ValueId {
+        self.emit_in(self.current, op, ty)
+    }
+
+    fn emit_in(&mut self, block: BlockId, op: Op, ty: Type) -> ValueId {
+        let id = ValueId(self.function.values.len() as u32);
+        let is_phi = matches!(op, Op::Phi(_));
+        self.function.values.push(Value { op, ty, block });
+        let block = &mut self.function.blocks[block.0 as usize];
+        if is_phi {
+            block.phis.push(id);
+        } else {
+            block.body.push(id);
+        }
+        id
+    }
+
+    /// Ends the current block. Code after it, like the statements after a `return`, goes to the
+    /// block oxc marks unreachable, which [`Function::compact`] drops.
+    fn terminate(&mut self, terminator: Terminator) {
+        self.function.blocks[self.current.0 as usize].terminator = terminator;
+    }
+
+    fn jump(&mut self, target: BlockId) {
+        self.terminate(Terminator::Jump(target));
+    }
+
+    fn branch(&mut self, condition: ValueId, then: BlockId, otherwise: BlockId) {
+        self.terminate(Terminator::Branch(condition, then, otherwise));
+    }
+
+    /// Declares that every predecessor of `block` is known.
+    fn seal(&mut self, block: BlockId) {
+        for (symbol, phi) in self.incomplete.remove(&block).unwrap_or_default() {
+            self.add_phi_operands(symbol, phi);
+        }
+        self.sealed[block.0 as usize] = true;
+    }
+
+    fn assign(&mut self, symbol: SymbolId, value: ValueId) {
+        if self.is_local(symbol) {
+            self.definitions.insert((symbol, self.current), value);
+        } else {
+            let name = self.builder.semantic.symbols().get_name(symbol).to_string();
+            self.emit(Op::StoreVar(name, value), Type::Unknown);
+        }
+    }
+
+    fn read(&mut self, symbol: SymbolId) -> ValueId {
+        if self.is_local(symbol) {
+            self.read_in(symbol, self.current)
+        } else {
+            let name = self.builder.semantic.symbols().get_name(symbol).to_string();
+            self.emit(Op::LoadVar(name), self.builder.types.symbol(symbol))
+        }
+    }
+
+    fn read_in(&mut self, symbol: SymbolId, block: BlockId) -> ValueId {
+        if let Some(value) = self.definitions.get(&(symbol, block)) {
+            return *value;
+        }
+
+        let ty = self.builder.types.symbol(symbol);
+        let predecessors = self.predecessors[block.0 as usize].clone();
+        let value = if !self.sealed[block.0 as usize] {
+            let phi = self.emit_in(block, Op::Phi(vec![]), ty);
+            self.incomplete
+                .entry(block)
+                .or_default()
+                .push((symbol, phi));
+            phi
+        } else if let [predecessor] = predecessors.as_slice() {
+            self.read_in(symbol, *predecessor)
+        } else if predecessors.is_empty() {
+            // read before any assignment, like a hoisted `var`
+            self.emit_in(block, Op::Const(Constant::Undefined), Type::Unknown)
+        } else {
+            // the phi breaks cycles through loops
+            let phi = self.emit_in(block, Op::Phi(vec![]), ty);
+            self.definitions.insert((symbol, block), phi);
+            self.add_phi_operands(symbol, phi);
+            phi
+        };
+        self.definitions.insert((symbol, block), value);
+        value
+    }
+
+    fn add_phi_operands(&mut self, symbol: SymbolId, phi: ValueId) {
+        let block = self.function.value(phi).block;
+        for predecessor in self.predecessors[block.0 as usize].clone() {
+            let value = self.read_in(symbol, predecessor);
+            if let Op::Phi(incoming) = &mut self.function.values[phi.0 as usize].op {
+                incoming.push((predecessor, value));
+            }
+        }
+    }
+
+    /// Replaces phis whose operands are all one value, besides the phi itself, with that value.
+    fn remove_trivial_phis(&mut self) {
+        loop {
+            let trivial = self
+                .function
+                .blocks
+                .iter()
+                .flat_map(|block| &block.phis)
+                .find_map(|phi| {
+                    let Op::Phi(incoming) = &self.function.value(*phi).op else {
+                        return None;
+                    };
+                    let mut operands = incoming
+                        .iter()
+                        .map(|(_, value)| *value)
+                        .filter(|value| value != phi);
+                    let first = operands.next()?;
+                    operands
+                        .all(|value| value == first)
+                        .then_some((*phi, first))
+                });
+            let Some((phi, value)) = trivial else {
+                break;
+            };
+            let block = self.function.value(phi).block;
+            self.function.blocks[block.0 as usize]
+                .phis
+                .retain(|other| *other != phi);
+            self.function.replace_uses(phi, value);
+        }
+    }
+
+    /// Whether `symbol` is a variable only this function reads and writes.
+    fn is_local(&mut self, symbol: SymbolId) -> bool {
+        if let Some(local) = self.locals.get(&symbol) {
+            return *local;
+        }
+        let semantic = self.builder.semantic;
+        let symbols = semantic.symbols();
+        let nodes = semantic.nodes();
+
+        let owner = self.builder.function_scope(symbols.get_scope_id(symbol));
+        let exported = nodes
+            .ancestors(symbols.get_declaration(symbol))
+            .any(|node| matches!(node.kind(), AstKind::ExportNamedDeclaration(_)));
+        let local = symbols.get_flags(symbol).is_variable()
+            && owner == self.scope
+            && !exported
+            && symbols
+                .get_resolved_reference_ids(symbol)
+                .iter()
+                .all(|reference| {
+                    let node = symbols.get_reference(*reference).node_id();
+                    !matches!(nodes.parent_kind(node), Some(AstKind::ExportSpecifier(_)))
+                        && self.builder.function_scope(nodes.get_node(node).scope_id()) == owner
+                });
+        self.locals.insert(symbol, local);
+        local
+    }
+
+    fn statements(&mut self, statements: &[Statement<'a>]) -> Result<(), String> {
+        statements
+            .iter()
+            .try_for_each(|statement| self.statement(statement))
+    }
+
+    fn statement(&mut self, statement: &Statement<'a>) -> Result<(), String> {
+        match statement {
+            Statement::VariableDeclaration(declaration) => self.declaration(declaration)?,
+            Statement::ExpressionStatement(statement) => {
+                self.expression(&statement.expression)?;
+            }
+            Statement::BlockStatement(block) => self.statements(&block.body)?,
+            Statement::IfStatement(if_) => {
+                // oxc leaves for the alternate from the block before the test
+                let otherwise = self.last_successor_of(self.current, is_normal);
+                self.fall_through();
+                let condition = self.expression(&if_.test)?;
+                let then = self.successor(is_jump);
+                self.branch(condition, then, otherwise);
+
+                self.current = then;
+                self.statement(&if_.consequent)?;
+                let join = self.successor(is_normal);
+                self.jump(join);
+                if let Some(alternate) = &if_.alternate {
+                    self.current = otherwise;
+                    self.statement(alternate)?;
+                    self.jump(join);
+                }
+                self.current = join;
+            }
+            Statement::WhileStatement(while_) => {
+                self.fall_through();
+                let header = self.current;
+                let condition = self.expression(&while_.test)?;
+                // oxc leaves the test from the block it starts in
+                let body = self.successor_of(header, is_jump);
+                let exit = self.last_successor_of(header, is_normal);
+                self.branch(condition, body, exit);
+
+                self.current = body;
+                self.statement(&while_.body)?;
+                let header = self.successor(is_backedge);
+                self.jump(header);
+                self.current = exit;
+            }
+            Statement::DoWhileStatement(do_while) => {
+                self.fall_through();
+                let body = self.current;
+                self.statement(&do_while.body)?;
+
+                self.fall_through();
+                let condition = self.expression(&do_while.test)?;
+                let exit = self.successor(is_normal);
+                self.branch(condition, body, exit);
+                self.current = exit;
+            }
+            Statement::ForStatement(for_) => {
+                match &for_.init {
+                    Some(ForStatementInit::VariableDeclaration(declaration)) => {
+                        self.declaration(declaration)?
+                    }
+                    Some(init) => {
+                        self.expression(init.to_expression())?;
+                    }
+                    None => {}
+                }
+                self.fall_through();
+                let header = self.current;
+                let condition = match &for_.test {
+                    Some(test) => Some(self.expression(test)?),
+                    None => None,
+                };
+                let body = self.successor(is_jump);
+                let exit = self.successor(is_normal);
+                match condition {
+                    Some(condition) => self.branch(condition, body, exit),
+                    None => self.jump(body),
+                }
+
+                self.current = body;
+                self.statement(&for_.body)?;
+                let update = self.successor(is_backedge);
+                self.jump(update);
+
+                self.current = update;
+                if let Some(update) = &for_.update {
+                    self.expression(update)?;
+                }
+                self.jump(header);
+                self.current = exit;
+            }
+            Statement::BreakStatement(break_) if break_.label.is_none() => self.leave(),
+            Statement::ContinueStatement(continue_) if continue_.label.is_none() => self.leave(),
+            Statement::ReturnStatement(return_) => {
+                let value = match &return_.argument {
+                    Some(argument) => Some(self.expression(argument)?),
+                    None => None,
+                };
+                self.terminate(Terminator::Return(value));
+                self.current = self.successor(is_unreachable);
+            }
+            // lowered as functions of their own, after which oxc starts a new block
+            Statement::FunctionDeclaration(_) => self.fall_through(),
+            Statement::EmptyStatement(_)
+            | Statement::ImportDeclaration(_)
+            | Statement::TSInterfaceDeclaration(_)
+            | Statement::TSTypeAliasDeclaration(_) => {}
+            Statement::ExportNamedDeclaration(export) => match &export.declaration {
+                Some(Declaration::VariableDeclaration(declaration)) => {
+                    self.declaration(declaration)?
+                }
+                Some(Declaration::FunctionDeclaration(_)) => self.fall_through(),
+                _ => {}
+            },
+            Statement::ExportDefaultDeclaration(export) => {
+                if let Some(expression) = export.declaration.as_expression() {
+                    let value = self.expression(expression)?;
+                    self.emit(Op::StoreVar("default".into(), value), Type::Unknown);
+                } else if let ExportDefaultDeclarationKind::FunctionDeclaration(_) =
+                    export.declaration
+                {
+                    self.fall_through();
+                } else if !matches!(
+                    export.declaration,
+                    ExportDefaultDeclarationKind::TSInterfaceDeclaration(_)
+                ) {
+                    return Err(self
+                        .builder
+                        .unsupported("default exported classes", export.span));
+                }
+            }
+            Statement::BreakStatement(_) | Statement::ContinueStatement(_) => {
+                return Err(self.builder.unsupported("labeled jumps", statement.span()))
+            }
+            _ => {
+                let what = match statement {
+                    Statement::SwitchStatement(_) => "switch statements",
+                    Statement::ThrowStatement(_) => "throw statements",
+                    Statement::TryStatement(_) => "try statements",
+                    Statement::ForInStatement(_) | Statement::ForOfStatement(_) => {
+                        "for-in and for-of loops"
+                    }
+                    Statement::LabeledStatement(_) => "labeled statements",
+                    Statement::ClassDeclaration(_) => "classes",
+                    _ => "statements like this",
+                };
+                return Err(self.builder.unsupported(what, statement.span()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Lowers `break` and `continue`, which oxc resolves to jumps out of the current block.
+    fn leave(&mut self) {
+        let target = self.successor(is_jump);
+        self.jump(target);
+        self.current = self.successor(is_unreachable);
+    }
+
+    fn declaration(&mut self, declaration: &VariableDeclaration<'a>) -> Result<(), String> {
+        for declarator in &declaration.declarations {
+            let BindingPatternKind::BindingIdentifier(id) = &declarator.id.kind else {
+                return Err(self
+                    .builder
+                    .unsupported("destructuring declarations", declarator.span));
+            };
+            let symbol = id.symbol_id.get().unwrap();
+            let value = match &declarator.init {
+                Some(init) => self.expression(init)?,
+                // `var x;` keeps the value of an earlier `var x = ...`
+                None if declaration.kind == VariableDeclarationKind::Var => continue,
+                None => self.emit(Op::Const(Constant::Undefined), Type::Unknown),
+            };
+            self.assign(symbol, value);
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expression<'a>) -> Result<ValueId, String> {
+        let builder = self.builder;
+        let ty = builder.types.expression(builder.semantic, expr);
+        let value = match expr {
+            Expression::NumericLiteral(literal) => {
+                self.emit(Op::Const(Constant::Number(literal.value)), ty)
+            }
+            Expression::BooleanLiteral(literal) => {
+                self.emit(Op::Const(Constant::Boolean(literal.value)), ty)
+            }
+            Expression::StringLiteral(literal) => {
+                self.emit(Op::Const(Constant::String(literal.value.to_string())), ty)
+            }
+            Expression::Identifier(id) => match builder.reference_symbol(expr) {
+                Some(symbol) if builder.semantic.symbols().get_flags(symbol).is_variable() => {
+                    self.read(symbol)
+                }
+                None if id.name == "undefined" => {
+                    self.emit(Op::Const(Constant::Undefined), Type::Unknown)
+                }
+                _ => self.emit(Op::Global(id.name.to_string()), ty),
+            },
+            Expression::ParenthesizedExpression(expr) => self.expression(&expr.expression)?,
+            Expression::TSAsExpression(expr) => self.expression(&expr.expression)?,
+            Expression::TSNonNullExpression(expr) => self.expression(&expr.expression)?,
+            Expression::TSSatisfiesExpression(expr) => self.expression(&expr.expression)?,
+            Expression::SequenceExpression(sequence) => {
+                let mut last = None;
+                for expr in &sequence.expressions {
+                    last = Some(self.expression(expr)?);
+                }
+                last.unwrap()
+            }
+            Expression::UnaryExpression(unary) => match unary.operator {
+                UnaryOperator::Void => {
+                    self.expression(&unary.argument)?;
+                    self.emit(Op::Const(Constant::Undefined), Type::Unknown)
+                }
+                UnaryOperator::Typeof | UnaryOperator::Delete => {
+                    return Err(builder.unsupported("typeof and delete", unary.span))
+                }
+                operator => {
+                    let argument = self.expression(&unary.argument)?;
+                    self.emit(Op::Unary(operator, argument), ty)
+                }
+            },
+            Expression::BinaryExpression(binary) => {
+                if binary.operator.is_relational() {
+                    return Err(builder.unsupported("in and instanceof", binary.span));
+                }
+                let left = self.expression(&binary.left)?;
+                let right = self.expression(&binary.right)?;
+                self.emit(Op::Binary(binary.operator, left, right), ty)
+            }
+            Expression::LogicalExpression(logical) => {
+                if logical.operator == LogicalOperator::Coalesce {
+                    return Err(builder.unsupported("?? expressions", logical.span));
+                }
+                let left = self.expression(&logical.left)?;
+                let left_end = self.current;
+                let right_block = self.successor(is_normal);
+
+                self.current = right_block;
+                let right = self.expression(&logical.right)?;
+                let right_end = self.current;
+                let join = self.successor(is_normal);
+                self.jump(join);
+
+                self.current = left_end;
+                if logical.operator == LogicalOperator::And {
+                    self.branch(left, right_block, join);
+                } else {
+                    self.branch(left, join, right_block);
+                }
+
+                self.current = join;
+                self.emit(Op::Phi(vec![(left_end, left), (right_end, right)]), ty)
+            }
+            Expression::ConditionalExpression(conditional) => {
+                self.fall_through();
+                let condition = self.expression(&conditional.test)?;
+                let then = self.successor(is_jump);
+                let otherwise = self.successor(is_normal);
+                self.branch(condition, then, otherwise);
+
+                self.current = then;
+                let consequent = self.expression(&conditional.consequent)?;
+                let then_end = self.current;
+                let join = self.successor(is_normal);
+                self.jump(join);
+
+                self.current = otherwise;
+                let alternate = self.expression(&conditional.alternate)?;
+                let otherwise_end = self.current;
+                self.jump(join);
+
+                self.current = join;
+                self.emit(
+                    Op::Phi(vec![(then_end, consequent), (otherwise_end, alternate)]),
+                    ty,
+                )
+            }
+            Expression::AssignmentExpression(assignment) => self.assignment(assignment)?,
+            Expression::UpdateExpression(update) => self.update(update)?,
+            Expression::StaticMemberExpression(member) => {
+                let object = self.expression(&member.object)?;
+                self.emit(Op::Property(object, member.property.name.to_string()), ty)
+            }
+            Expression::ComputedMemberExpression(member) => {
+                let object = self.expression(&member.object)?;
+                let index = self.expression(&member.expression)?;
+                self.emit(Op::Load(object, index), ty)
+            }
+            Expression::CallExpression(call) => {
+                if call.optional {
+                    return Err(builder.unsupported("optional calls", call.span));
+                }
+                if let Some(path) = builder.global_path(&call.callee) {
+                    let args = self.arguments(&call.arguments)?;
+                    self.emit(Op::Call(path, args), ty)
+                } else if let Expression::Identifier(id) = &call.callee {
+                    let args = self.arguments(&call.arguments)?;
+                    self.emit(Op::Call(id.name.to_string(), args), ty)
+                } else if let Expression::StaticMemberExpression(member) = &call.callee {
+                    let receiver = self.expression(&member.object)?;
+                    let args = self.arguments(&call.arguments)?;
+                    self.emit(
+                        Op::CallMethod(receiver, member.property.name.to_string(), args),
+                        ty,
+                    )
+                } else {
+                    return Err(builder.unsupported("calls of computed functions", call.span));
+                }
+            }
+            Expression::NewExpression(new) => {
+                let Expression::Identifier(callee) = &new.callee else {
+                    return Err(builder.unsupported("computed constructors", new.span));
+                };
+                let args = self.arguments(&new.arguments)?;
+                self.emit(Op::New(callee.name.to_string(), args), ty)
+            }
+            _ => {
+                let what = match expr {
+                    Expression::ObjectExpression(_) => "object literals",
+                    Expression::ArrayExpression(_) => "array literals",
+                    Expression::FunctionExpression(_) | Expression::ArrowFunctionExpression(_) => {
+                        "function expressions"
+                    }
+                    Expression::TemplateLiteral(_) => "template literals",
+                    Expression::NullLiteral(_) => "null literals",
+                    Expression::ThisExpression(_) => "`this` expressions",
+                    _ => "expressions like this",
+                };
+                return Err(builder.unsupported(what, expr.span()));
+            }
+        };
+        Ok(value)
+    }
+
+    fn arguments(&mut self, arguments: &[Argument<'a>]) -> Result<Vec<ValueId>, String> {
+        arguments
+            .iter()
+            .map(|argument| match argument {
+                Argument::SpreadElement(spread) => {
+                    Err(self.builder.unsupported("spread arguments", spread.span))
+                }
+                argument => self.expression(argument.to_expression()),
+            })
+            .collect()
+    }
+
+    fn assignment(&mut self, assignment: &AssignmentExpression<'a>) -> Result<ValueId, String> {
+        let builder = self.builder;
+        let operator = match assignment.operator {
+            AssignmentOperator::Assign => None,
+            operator => Some(
+                operator
+                    .to_binary_operator()
+                    .ok_or_else(|| builder.unsupported("logical assignments", assignment.span))?,
+            ),
+        };
+
+        match &assignment.left {
+            AssignmentTarget::AssignmentTargetIdentifier(id) => {
+                let symbol = id.reference_id.get().and_then(|reference| {
+                    builder
+                        .semantic
+                        .symbols()
+                        .get_reference(reference)
+                        .symbol_id()
+                });
+                let Some(symbol) = symbol else {
+                    return Err(builder.unsupported("assignments to globals", id.span));
+                };
+                let ty = builder.types.symbol(symbol);
+                // the old value is read before the right side is evaluated
+                let old = operator.map(|_| self.read(symbol));
+                let mut value = self.expression(&assignment.right)?;
+                if let (Some(operator), Some(old)) = (operator, old) {
+                    value = self.emit(Op::Binary(operator, old, value), ty);
+                }
+                self.assign(symbol, value);
+                Ok(value)
+            }
+            AssignmentTarget::ComputedMemberExpression(member) => {
+                let object = self.expression(&member.object)?;
+                let index = self.expression(&member.expression)?;
+                let ty = IrBuilder::element_type(&self.function.value(object).ty);
+                let old = operator.map(|_| self.emit(Op::Load(object, index), ty.clone()));
+                let mut value = self.expression(&assignment.right)?;
+                if let (Some(operator), Some(old)) = (operator, old) {
+                    value = self.emit(Op::Binary(operator, old, value), ty);
+                }
+                self.emit(Op::Store(object, index, value), Type::Unknown);
+                Ok(value)
+            }
+            AssignmentTarget::StaticMemberExpression(member) => {
+                let object = self.expression(&member.object)?;
+                let name = member.property.name.to_string();
+                let old =
+                    operator.map(|_| self.emit(Op::Property(object, name.clone()), Type::Unknown));
+                let mut value = self.expression(&assignment.right)?;
+                if let (Some(operator), Some(old)) = (operator, old) {
+                    value = self.emit(Op::Binary(operator, old, value), Type::Unknown);
+                }
+                self.emit(Op::SetProperty(object, name, value), Type::Unknown);
+                Ok(value)
+            }
+            target => Err(builder.unsupported("destructuring assignments", target.span())),
+        }
+    }
+
+    fn update(&mut self, update: &UpdateExpression<'a>) -> Result<ValueId, String> {
+        let builder = self.builder;
+        let operator = match update.operator {
+            UpdateOperator::Increment => BinaryOperator::Addition,
+            UpdateOperator::Decrement => BinaryOperator::Subtraction,
+        };
+
+        let (old, new) = match &update.argument {
+            SimpleAssignmentTarget::AssignmentTargetIdentifier(id) => {
+                let symbol = id.reference_id.get().and_then(|reference| {
+                    builder
+                        .semantic
+                        .symbols()
+                        .get_reference(reference)
+                        .symbol_id()
+                });
+                let Some(symbol) = symbol else {
+                    return Err(builder.unsupported("updates of globals", id.span));
+                };
+                let ty = builder.types.symbol(symbol);
+                let old = self.read(symbol);
+                let one = self.emit(Op::Const(Constant::Number(1.0)), Type::Int32);
+                let new = self.emit(Op::Binary(operator, old, one), ty);
+                self.assign(symbol, new);
+                (old, new)
+            }
+            SimpleAssignmentTarget::ComputedMemberExpression(member) => {
+                let object = self.expression(&member.object)?;
+                let index = self.expression(&member.expression)?;
+                let ty = IrBuilder::element_type(&self.function.value(object).ty);
+                let old = self.emit(Op::Load(object, index), ty.clone());
+                let one = self.emit(Op::Const(Constant::Number(1.0)), Type::Int32);
+                let new = self.emit(Op::Binary(operator, old, one), ty);
+                self.emit(Op::Store(object, index, new), Type::Unknown);
+                (old, new)
+            }
+            SimpleAssignmentTarget::StaticMemberExpression(member) => {
+                let object = self.expression(&member.object)?;
+                let name = member.property.name.to_string();
+                let old = self.emit(Op::Property(object, name.clone()), Type::Unknown);
+                let one = self.emit(Op::Const(Constant::Number(1.0)), Type::Int32);
+                let new = self.emit(Op::Binary(operator, old, one), Type::Unknown);
+                self.emit(Op::SetProperty(object, name, new), Type::Unknown);
+                (old, new)
+            }
+            target => return Err(builder.unsupported("updates like this", target.span())),
+        };
+        Ok(if update.prefix { new } else { old })
+    }
+}
+
+fn is_normal(edge: &EdgeType) -> bool {
+    matches!(edge, EdgeType::Normal)
+}
+
+fn is_jump(edge: &EdgeType) -> bool {
+    matches!(edge, EdgeType::Jump)
+}
+
+fn is_backedge(edge: &EdgeType) -> bool {
+    matches!(edge, EdgeType::Backedge)
+}
+
+fn is_unreachable(edge: &EdgeType) -> bool {
+    matches!(edge, EdgeType::Unreachable)
+}
@@ -0,0 +1,368 @@
+use std::collections::{HashMap, HashSet};
+
+use oxc::{
+    allocator::{Allocator, Box},
+    ast::{
+        ast::{
+            ArrowFunctionExpression, BinaryOperator, CallExpression, Expression, ForStatement,
+            ForStatementInit, Function, IdentifierReference, SimpleAssignmentTarget,
+            TSTypeAnnotation, VariableDeclarationKind,
+        },
+        visit::{walk, walk_mut},
+        AstBuilder, AstKind, Visit, VisitMut,
+    },
+    semantic::{ScopeFlags, Semantic},
+    span::{GetSpan, SPAN},
+};
+
+use crate::{
+    dataflow::Dataflow, fresh_names::FreshNames, induction_variables::Loop, profile::ModuleProfile,
+    purity::is_pure, remarks::Remark,
+};
+
+/// Moves computations whose value is the same in every iteration of a `for` loop, like
+/// `a.length`, `n * m` or `Math.sqrt(k)`, into a variable declared by the loop initializer:
+///
+/// ```js
+/// for (let i = 0; i < a.length; i++) s += Math.sqrt(k) * i;
+/// // becomes
+/// for (let i = 0, a_length = a.length, sqrt_value = Math.sqrt(k); i < a_length; i++)
+///     s += sqrt_value * i;
+/// ```
+///
+/// Only expressions without side effects, according to [`crate::purity`], are hoisted, and
+/// only when every variable they read is written nowhere in the loop. They then give the same
+/// value whether the loop runs them once, every iteration or not at all, except for
+/// an int32 `%` by zero, which C++ does not allow and is left where it is.
+///
+/// Under `--profile-use`, a loop that was entered but never iterated keeps its invariants, which
+/// hoisting would compute for nothing.
+pub struct LoopInvariantCodeMotionOptimization<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    dataflow: &'s Dataflow<'s, 'a>,
+    ast: AstBuilder<'a>,
+    names: FreshNames,
+    /// The variables introduced for hoisted expressions, fixed wherever they are visible.
+    hoisted: HashSet<String>,
+    /// The position of every loop the profile saw entered but never iterating.
+    cold: HashSet<u32>,
+    pub remarks: Vec<Remark>,
+}
+
+impl<'s, 'a> LoopInvariantCodeMotionOptimization<'s, 'a> {
+    pub fn new(
+        dataflow: &'s Dataflow<'s, 'a>,
+        allocator: &'a Allocator,
+        profile: Option<&ModuleProfile>,
+    ) -> Self {
+        let cold = profile
+            .map(|profile| {
+                dataflow
+                    .semantic()
+                    .nodes()
+                    .iter()
+                    .filter_map(|node| match node.kind() {
+                        AstKind::ForStatement(for_) if profile.trips(for_.span) == Some(0.0) => {
+                            Some(for_.span.start)
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            semantic: dataflow.semantic(),
+            dataflow,
+            ast: AstBuilder::new(allocator),
+            names: FreshNames::new(dataflow.semantic()),
+            hoisted: HashSet::new(),
+            cold,
+            remarks: vec![],
+        }
+    }
+
+    fn source(&self, expr: &Expression) -> &'a str {
+        let text = expr.span().source_text(self.semantic.source_text());
+        text.lines().next().unwrap_or_default()
+    }
+
+    /// What to call the variable holding `expr`.
+    fn name(&mut self, expr: &Expression) -> String {
+        let base = match expr.without_parentheses() {
+            Expression::StaticMemberExpression(member) => match &member.object {
+                Expression::Identifier(object) => {
+                    format!("{}_{}", object.name, member.property.name)
+                }
+                _ => member.property.name.to_string(),
+            },
+            // not `sqrt`, which would hide the C++ function
+            Expression::CallExpression(call) => match &call.callee {
+                Expression::StaticMemberExpression(callee) => {
+                    format!("{}_value", callee.property.name)
+                }
+                Expression::Identifier(callee) => format!("{}_value", callee.name.to_lowercase()),
+                _ => "invariant".to_string(),
+            },
+            _ => "invariant".to_string(),
+        };
+        self.names.fresh(&base)
+    }
+}
+
+impl<'a> VisitMut<'a> for LoopInvariantCodeMotionOptimization<'_, 'a> {
+    fn visit_for_statement(&mut self, for_: &mut ForStatement<'a>) {
+        let loop_ = Loop::new(self.dataflow, for_);
+        let mut effects = Effects {
+            semantic: self.semantic,
+            writes_length: false,
+            calls: false,
+        };
+        effects.visit_for_statement(for_);
+        let (writes_length, calls) = (effects.writes_length, effects.calls);
+
+        // count first: a loop whose initializer is an expression has nowhere to put them
+        let declares = !matches!(&for_.init, Some(init) if init.is_expression());
+        for dry_run in [true, false] {
+            if !dry_run && !declares {
+                break;
+            }
+            let mut invariants = Invariants {
+                pass: self,
+                loop_: &loop_,
+                lengths: !writes_length && !calls,
+                dry_run,
+                count: 0,
+                found: vec![],
+                names: HashMap::new(),
+            };
+            if let Some(test) = &mut for_.test {
+                invariants.visit_expression(test);
+            }
+            if let Some(update) = &mut for_.update {
+                invariants.visit_expression(update);
+            }
+            invariants.visit_statement(&mut for_.body);
+            let (count, found) = (invariants.count, invariants.found);
+
+            if count == 0 {
+                break;
+            }
+            if !for_.span.is_unspanned() && self.cold.contains(&for_.span.start) {
+                self.remarks.push(Remark::missed(
+                    for_.span,
+                    format!(
+                        "{count} invariant expression{} kept: the loop never iterated in the profile",
+                        if count == 1 { "" } else { "s" }
+                    ),
+                ));
+                break;
+            }
+            if !declares {
+                self.remarks.push(Remark::missed(
+                    for_.span,
+                    format!(
+                        "{count} invariant expression{} kept: the loop initializer is not a declaration",
+                        if count == 1 { "" } else { "s" }
+                    ),
+                ));
+                break;
+            }
+            if dry_run {
+                continue;
+            }
+
+            let init = for_.init.get_or_insert_with(|| {
+                ForStatementInit::VariableDeclaration(self.ast.alloc_variable_declaration(
+                    SPAN,
+                    VariableDeclarationKind::Let,
+                    self.ast.vec(),
+                    false,
+                ))
+            });
+            let ForStatementInit::VariableDeclaration(init) = init else {
+                unreachable!();
+            };
+            let kind = init.kind;
+            for (name, expr) in found {
+                self.remarks.push(Remark::applied(
+                    expr.span(),
+                    format!("`{}` hoisted out of the loop", self.source(&expr)),
+                ));
+                init.declarations.push(self.ast.variable_declarator(
+                    SPAN,
+                    kind,
+                    self.ast.binding_pattern(
+                        self.ast.binding_pattern_kind_binding_identifier(SPAN, name),
+                        None::<Box<TSTypeAnnotation>>,
+                        false,
+                    ),
+                    Some(expr),
+                    false,
+                ));
+            }
+        }
+
+        walk_mut::walk_for_statement(self, for_);
+    }
+}
+
+/// What a loop does that may change the length of an object it reads.
+struct Effects<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    /// It assigns some `x.length`.
+    writes_length: bool,
+    /// It calls a function of the program, which may assign anything it sees.
+    calls: bool,
+}
+
+impl<'a> Visit<'a> for Effects<'_, 'a> {
+    fn visit_simple_assignment_target(&mut self, target: &SimpleAssignmentTarget<'a>) {
+        if let SimpleAssignmentTarget::StaticMemberExpression(member) = target {
+            self.writes_length |= member.property.name == "length";
+        }
+        walk::walk_simple_assignment_target(self, target);
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression<'a>) {
+        // the runtime, `Math.floor` or `console.log`, only reads its arguments
+        let global = |expr: &Expression| match expr {
+            Expression::Identifier(id) => id.reference_id.get().is_some_and(|reference| {
+                self.semantic
+                    .symbols()
+                    .get_reference(reference)
+                    .symbol_id()
+                    .is_none()
+            }),
+            _ => false,
+        };
+        self.calls |= match &call.callee {
+            Expression::StaticMemberExpression(member) => !global(&member.object),
+            callee => !global(callee),
+        };
+        walk::walk_call_expression(self, call);
+    }
+}
+
+/// Finds the largest invariant expressions of one loop and, unless `dry_run`, replaces each
+/// with a fresh variable, the same one for the same source text.
+struct Invariants<'p, 's, 'a> {
+    pass: &'p mut LoopInvariantCodeMotionOptimization<'s, 'a>,
+    loop_: &'p Loop<'s, 'a>,
+    /// `x.length` keeps its value through the loop.
+    lengths: bool,
+    dry_run: bool,
+    count: usize,
+    /// The fresh variables, with the expression each one is initialized with.
+    found: Vec<(String, Expression<'a>)>,
+    /// The variable of each hoisted source text.
+    names: HashMap<&'a str, String>,
+}
+
+impl<'a> Invariants<'_, '_, 'a> {
+    /// `expr` evaluates to the same value in every iteration of the loop.
+    fn invariant(&self, expr: &Expression) -> bool {
+        let invariant = |expr: &Expression| self.invariant(expr);
+        match expr {
+            Expression::NumericLiteral(_)
+            | Expression::BooleanLiteral(_)
+            | Expression::StringLiteral(_)
+            | Expression::NullLiteral(_) => true,
+            Expression::Identifier(id) => match id.reference_id.get() {
+                Some(reference) => self
+                    .pass
+                    .semantic
+                    .symbols()
+                    .get_reference(reference)
+                    .symbol_id()
+                    .is_some_and(|symbol| self.loop_.is_fixed(symbol)),
+                // hoisted out of an enclosing loop
+                None => self.pass.hoisted.contains(id.name.as_str()),
+            },
+            Expression::ParenthesizedExpression(expr) => invariant(&expr.expression),
+            Expression::UnaryExpression(expr) => invariant(&expr.argument),
+            Expression::BinaryExpression(expr) => {
+                let divisor = expr.operator != BinaryOperator::Remainder
+                    || matches!(&expr.right, Expression::NumericLiteral(literal) if literal.value != 0.0);
+                divisor && invariant(&expr.left) && invariant(&expr.right)
+            }
+            Expression::LogicalExpression(expr) => invariant(&expr.left) && invariant(&expr.right),
+            Expression::ConditionalExpression(expr) => {
+                invariant(&expr.test) && invariant(&expr.consequent) && invariant(&expr.alternate)
+            }
+            Expression::StaticMemberExpression(member) => {
+                self.lengths && member.property.name == "length" && invariant(&member.object)
+            }
+            Expression::CallExpression(call) => call
+                .arguments
+                .iter()
+                .all(|argument| argument.as_expression().is_some_and(invariant)),
+            _ => false,
+        }
+    }
+}
+
+/// `expr` computes something from variables, rather than naming one or being a constant.
+fn worth_hoisting(expr: &Expression) -> bool {
+    match expr {
+        Expression::UnaryExpression(unary) => worth_hoisting(&unary.argument),
+        Expression::BinaryExpression(_)
+        | Expression::LogicalExpression(_)
+        | Expression::ConditionalExpression(_)
+        | Expression::StaticMemberExpression(_)
+        | Expression::CallExpression(_) => {
+            let mut reads = Reads(false);
+            reads.visit_expression(expr);
+            reads.0
+        }
+        _ => false,
+    }
+}
+
+impl<'a> VisitMut<'a> for Invariants<'_, '_, 'a> {
+    fn visit_expression(&mut self, expr: &mut Expression<'a>) {
+        if !worth_hoisting(expr) || !is_pure(self.pass.semantic, expr) || !self.invariant(expr) {
+            walk_mut::walk_expression(self, expr);
+            return;
+        }
+
+        let key = (!expr.span().is_unspanned())
+            .then(|| expr.span().source_text(self.pass.semantic.source_text()));
+        let name = match key.and_then(|key| self.names.get(key)) {
+            Some(name) => name.clone(),
+            None => {
+                self.count += 1;
+                let name = if self.dry_run {
+                    String::new()
+                } else {
+                    self.pass.name(expr)
+                };
+                if let Some(key) = key {
+                    self.names.insert(key, name.clone());
+                }
+                if !self.dry_run {
+                    let hoisted = self.pass.ast.move_expression(expr);
+                    self.found.push((name.clone(), hoisted));
+                    self.pass.hoisted.insert(name.clone());
+                }
+                name
+            }
+        };
+        if !self.dry_run {
+            *expr = self.pass.ast.expression_identifier_reference(SPAN, name);
+        }
+    }
+
+    // a nested function runs whenever it is called
+    fn visit_function(&mut self, _function: &mut Function<'a>, _flags: ScopeFlags) {}
+
+    fn visit_arrow_function_expression(&mut self, _arrow: &mut ArrowFunctionExpression<'a>) {}
+}
+
+/// Whether an expression reads a variable.
+struct Reads(bool);
+
+impl<'a> Visit<'a> for Reads {
+    fn visit_identifier_reference(&mut self, _id: &IdentifierReference<'a>) {
+        self.0 = true;
+    }
+}
@@ -1,25 +1,52 @@
 #![allow(clippy::print_stdout)]
 #[allow(unused_imports)]
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
+use std::path::Path;
 use std::process::Command;
-use std::{fs, path::Path};
 
-use clap::arg;
 use clap::Parser as ClapParser;
 use codegen::Codegen;
+use dataflow::Dataflow;
+use ir_builder::IrBuilder;
+use module_graph::{Linkage, ModuleFormat, ModuleGraph};
 use oxc::{
     allocator::Allocator,
-    ast::VisitMut,
-    parser::{ParseOptions, Parser, ParserReturn},
-    semantic::{SemanticBuilder, SemanticBuilderReturn},
-    span::SourceType,
+    semantic::{Semantic, SemanticBuilderReturn, SymbolId},
 };
-use running_modulo_optimization::RunningModuloOptimization;
-use slotted_array_read_optimization::SlottedArrayReadOptimization;
+use pass_manager::PassManager;
+use profile::Profile;
+use remarks::ReportFormat;
+use type_inference::{Type, TypeInference, Types};
 
+mod bounds_check_analysis;
 mod codegen;
+mod common_subexpression_optimization;
+mod constant_division_analysis;
+mod constant_folding_optimization;
+mod counted_loop_analysis;
+mod dataflow;
+mod dead_code_optimization;
+mod escape_analysis;
+mod fresh_names;
+mod induction_variables;
+mod inlining_optimization;
+mod ir;
+mod ir_builder;
+mod loop_invariant_code_motion_optimization;
+mod module_graph;
+mod overflow_analysis;
+mod pass_manager;
+mod profile;
+mod purity;
+mod remarks;
 mod running_modulo_optimization;
+mod scalar_replacement_optimization;
 mod slotted_array_read_optimization;
+mod specialization_optimization;
+mod strength_reduction_optimization;
+mod tail_call_optimization;
+mod type_inference;
 
 static OUTPUT_FILE: &str = "tmp/out.cpp";
 
@@ -30,79 +57,320 @@ struct Args {
 
     #[arg(short, long, default_value_t = String::from("out.exe"))]
     output: String,
+
+    /// How modules refer to each other
+    #[arg(long, value_enum, default_value_t = ModuleFormat::Esm)]
+    module_format: ModuleFormat,
+
+    /// Check JSDoc numeric hints (int32, uint8, f32) at runtime
+    #[arg(long)]
+    checked_hints: bool,
+
+    /// Only write the C++ source, without invoking the C++ compiler
+    #[arg(long)]
+    no_build: bool,
+
+    /// Optimization level: 0 runs no passes, 3 also iterates them to a fixed point
+    #[arg(
+        short = 'O',
+        default_value_t = 2,
+        overrides_with = "opt_level",
+        value_parser = clap::value_parser!(u8).range(0..=3)
+    )]
+    opt_level: u8,
+
+    /// Run a pass that the optimization level leaves out
+    #[arg(long, value_name = "PASS")]
+    enable_pass: Vec<String>,
+
+    /// Skip a pass that the optimization level would run
+    #[arg(long, value_name = "PASS")]
+    disable_pass: Vec<String>,
+
+    /// Largest function body, in AST nodes, that the inline pass copies into a call site
+    #[arg(long, value_name = "NODES", default_value_t = pass_manager::DEFAULT_INLINE_THRESHOLD)]
+    inline_threshold: usize,
+
+    /// Also turn recursion like `return n * f(n - 1)` into a loop, which reorders the
+    /// additions or multiplications
+    #[arg(long)]
+    tail_call_accumulator: bool,
+
+    /// Copy the body of counted loops this many times per iteration
+    #[arg(long, value_name = "FACTOR", default_value_t = 1)]
+    unroll: usize,
+
+    /// Repeat the passes until none of them changes the program
+    #[arg(long)]
+    fixed_point: bool,
+
+    /// Print how often each pass ran, what it rewrote and how long it took
+    #[arg(long)]
+    pass_stats: bool,
+
+    /// Print the SSA intermediate representation of every module
+    #[arg(long)]
+    emit_ir: bool,
+
+    /// Print what each pass transformed or rejected, and why
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        default_missing_value = "human"
+    )]
+    opt_report: Option<ReportFormat>,
+
+    /// Build a program that counts branches, loop trips, calls and argument types, and writes
+    /// them to FILE when it exits
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        default_missing_value = "crushscript.profile",
+        conflicts_with = "profile_use"
+    )]
+    profile_generate: Option<String>,
+
+    /// Optimize with the counts a program built with --profile-generate wrote
+    #[arg(long, value_name = "FILE")]
+    profile_use: Option<String>,
 }
 
 #[allow(unused)]
 fn main() -> Result<(), String> {
     let args = Args::parse();
 
-    // read source file
-    let name = args.input;
-    let path = Path::new(&name);
-    let source_text = fs::read_to_string(path).map_err(|_| format!("Missing '{name}'"))?;
-    let source_type = SourceType::from_path(path).unwrap();
-
-    // parse source code into AST
+    // read and parse the entry file and every module it imports
     let allocator = Allocator::default();
-    let mut errors = Vec::new();
-
-    let ParserReturn {
-        mut program,
-        errors: parser_errors,
-        panicked,
-        ..
-    } = Parser::new(&allocator, &source_text, source_type)
-        .with_options(ParseOptions {
-            parse_regular_expression: true,
-            ..ParseOptions::default()
-        })
-        .parse();
-    errors.extend(parser_errors);
-
-    let SemanticBuilderReturn {
-        mut semantic,
-        errors: semantic_errors,
-    } = SemanticBuilder::new()
-        .with_check_syntax_error(true)
-        .with_build_jsdoc(true)
-        .with_cfg(true)
-        .build(&program);
-    errors.extend(semantic_errors);
-
-    if panicked {
+    let mut graph = ModuleGraph::load(&allocator, Path::new(&args.input), args.module_format)?;
+
+    let profile = match &args.profile_use {
+        Some(path) => Some(Profile::load(Path::new(path))?),
+        None => None,
+    };
+
+    let mut passes = PassManager::new(&allocator)
+        .with_level(args.opt_level)
+        .with_passes(&args.enable_pass, &args.disable_pass)?
+        .with_fixed_point(args.fixed_point)
+        .with_inline_threshold(args.inline_threshold)
+        .with_tail_call_accumulator(args.tail_call_accumulator)
+        .with_profile(profile.clone());
+
+    // optimize, link and type every module before printing any: the functions of a module may
+    // read the bindings of one evaluated after it
+    let order = graph.evaluation_order.clone();
+    let mut semantics: Vec<_> = graph.modules.iter().map(|_| None).collect();
+    for &index in &order {
+        let mut errors = Vec::new();
+
+        let SemanticBuilderReturn {
+            semantic,
+            errors: semantic_errors,
+        } = pass_manager::analyze(&graph.modules[index].program);
+        errors.extend(semantic_errors);
+
         for error in &errors {
             eprintln!("{error:?}");
-            panic!("Parsing failed");
+        }
+
+        // apply optimizations
+        let module = &mut graph.modules[index];
+        semantics[index] =
+            Some(passes.run(&module.path, &mut module.program, semantic, errors.len()));
+    }
+    let semantics: Vec<Semantic> = semantics.into_iter().map(Option::unwrap).collect();
+
+    let mut linkages = Vec::new();
+    for (index, semantic) in semantics.iter().enumerate() {
+        linkages.push(graph.link(index, semantic)?);
+    }
+
+    // the module defining each top-level binding, by its C++ name
+    let mut definitions = HashMap::new();
+    for &index in &order {
+        for (symbol_id, name) in top_level(&semantics[index], &linkages[index], false) {
+            definitions.insert(name, (index, symbol_id));
         }
     }
+    let position = |index: usize| order.iter().position(|&i| i == index);
 
-    // apply optimizations
-    SlottedArrayReadOptimization::new(&allocator).visit_program(&mut program);
-    RunningModuloOptimization::new(&semantic, &allocator).visit_program(&mut program);
+    // imports take the types of the bindings they import; a module importing from one evaluated
+    // after it is inferred again once that one is
+    let mut exported: HashMap<&str, Type> = HashMap::new();
+    let mut types: Vec<Option<Types>> = graph.modules.iter().map(|_| None).collect();
+    let mut again = vec![];
+    for round in 0..2 {
+        let modules = if round == 0 {
+            order.clone()
+        } else {
+            std::mem::take(&mut again)
+        };
+        for index in modules {
+            let semantic = &semantics[index];
+            let mut imports = HashMap::new();
+            for (symbol_id, name) in top_level(semantic, &linkages[index], true) {
+                match exported.get(name) {
+                    Some(t) => {
+                        imports.insert(symbol_id, t.clone());
+                    }
+                    None if round == 0 && definitions.contains_key(name) => again.push(index),
+                    None => {}
+                }
+            }
+            let module_types = TypeInference::new(semantic)
+                .with_imports(imports)
+                .infer(&graph.modules[index].program)?;
+            for (symbol_id, name) in top_level(semantic, &linkages[index], false) {
+                match module_types.symbol(symbol_id) {
+                    Type::Unknown | Type::Object(_) => {}
+                    t => {
+                        exported.insert(name, t);
+                    }
+                }
+            }
+            types[index] = Some(module_types);
+        }
+        again.dedup();
+    }
+    let types: Vec<Types> = types.into_iter().map(Option::unwrap).collect();
+
+    // the bindings read by a module evaluated before theirs, declared ahead of every module
+    let mut declared: Vec<Vec<SymbolId>> = graph.modules.iter().map(|_| vec![]).collect();
+    for &index in &order {
+        for (_, name) in top_level(&semantics[index], &linkages[index], true) {
+            if let Some(&(module, symbol_id)) = definitions.get(name) {
+                if position(module) > position(index) && !declared[module].contains(&symbol_id) {
+                    declared[module].push(symbol_id);
+                }
+            }
+        }
+    }
 
-    // output source code
     let mut writer = File::create(OUTPUT_FILE).unwrap();
-    let mut codegen = Codegen::new(&mut writer, &semantic);
-    codegen.print_program(&program);
+    Codegen::print_header(&mut writer).unwrap();
+    if let Some(path) = &args.profile_generate {
+        Codegen::print_profile_output(&mut writer, path).unwrap();
+    }
+    for &index in &order {
+        let unknown = Codegen::new(&mut writer, &semantics[index])
+            .with_linkage(&linkages[index])
+            .with_types(&types[index])
+            .print_declarations(&declared[index])
+            .map_err(|error| format!("{}: {error}", graph.modules[index].path.display()))?;
+        if let Some(name) = unknown {
+            return Err(format!(
+                "{}: '{name}' is read through a circular import, but its type is unknown",
+                graph.modules[index].path.display()
+            ));
+        }
+    }
+
+    for &index in &order {
+        let semantic = &semantics[index];
+        let linkage = &linkages[index];
+        let types = &types[index];
+        let declared: HashSet<SymbolId> = declared[index].iter().copied().collect();
+        let program = &graph.modules[index].program;
+
+        let dataflow = Dataflow::new(semantic);
+        let in_bounds = bounds_check_analysis::in_bounds(&dataflow, types);
+        let unsigned_divisions = constant_division_analysis::unsigned_divisions(&dataflow, types);
+        let counted_loops = counted_loop_analysis::counted_loops(&dataflow, types);
+        let stack_arrays = escape_analysis::stack_arrays(&dataflow, types);
+        let module_path = &graph.modules[index].path;
+        let module_profile = profile
+            .as_ref()
+            .map(|profile| profile.module(module_path, program.source_text));
+
+        if args.emit_ir {
+            let path = graph.modules[index].path.display();
+            match IrBuilder::new(semantic, types).build(program) {
+                Ok(module) => {
+                    module
+                        .verify()
+                        .map_err(|error| format!("{path}: invalid IR: {error}"))?;
+                    println!("; {path}\n{module}");
+                }
+                Err(error) => println!("; {path}: not lowered: {error}\n"),
+            }
+        }
+
+        // output source code
+        Codegen::new(&mut writer, semantic)
+            .with_linkage(linkage)
+            .with_types(types)
+            .with_checked_hints(args.checked_hints)
+            .with_in_bounds(&in_bounds)
+            .with_unsigned_divisions(&unsigned_divisions)
+            .with_counted_loops(&counted_loops)
+            .with_stack_arrays(&stack_arrays)
+            .with_unroll(args.unroll)
+            .with_instrumentation(
+                args.profile_generate
+                    .is_some()
+                    .then(|| remarks::file_name(module_path)),
+            )
+            .with_profile(module_profile.as_ref())
+            .with_declared(&declared)
+            .print_module()
+            .map_err(|error| format!("{}: {error}", module_path.display()))?;
+    }
+
+    Codegen::print_footer(&mut writer).unwrap();
     drop(writer);
 
+    if args.pass_stats {
+        passes.print_statistics(&mut std::io::stderr()).unwrap();
+    }
+    if let Some(format) = args.opt_report {
+        passes.report.print(&mut std::io::stdout(), format).unwrap();
+    }
+
     // build program to executable
-    build_program(&args.output);
+    if !args.no_build {
+        let pgo = if args.profile_generate.is_some() {
+            Some("/GENPROFILE")
+        } else if args.profile_use.is_some() {
+            Some("/USEPROFILE")
+        } else {
+            None
+        };
+        build_program(&args.output, pgo);
+    }
 
     Ok(())
 }
 
-fn build_program(output: &str) {
-    let hello = Command::new("cl")
-        .args([
-            OUTPUT_FILE,
-            "/O2",
-            "/arch:SSE2",
-            "/Istatic",
-            "/link",
-            &format!("/out:{}", output),
-        ])
-        .output()
-        .expect("failed to execute process");
+/// The symbols of the top-level scope of a module, either its imports or the ones it declares,
+/// with their C++ names.
+fn top_level<'l>(
+    semantic: &Semantic,
+    linkage: &'l Linkage,
+    imports: bool,
+) -> Vec<(SymbolId, &'l str)> {
+    let scopes = semantic.scopes();
+    scopes
+        .iter_bindings_in(scopes.root_scope_id())
+        .filter(|&symbol_id| semantic.symbols().get_flags(symbol_id).is_import() == imports)
+        .filter_map(|symbol_id| Some((symbol_id, linkage.bindings.get(&symbol_id)?.as_str())))
+        .collect()
+}
+
+/// Compiles the C++ source with MSVC; `pgo` is the linker option of its own profile-guided
+/// optimization, which needs whole program optimization.
+fn build_program(output: &str, pgo: Option<&str>) {
+    let mut command = Command::new("cl");
+    command.args([OUTPUT_FILE, "/O2", "/arch:SSE2", "/Istatic"]);
+    if pgo.is_some() {
+        command.arg("/GL");
+    }
+    command.args(["/link", &format!("/out:{}", output)]);
+    if let Some(pgo) = pgo {
+        command.args(["/LTCG", pgo]);
+    }
+    let hello = command.output().expect("failed to execute process");
     println!("{}", String::from_utf8_lossy(&hello.stdout));
 }
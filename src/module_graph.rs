@@ -0,0 +1,855 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use oxc::{
+    allocator::Allocator,
+    ast::{
+        ast::{
+            Argument, AssignmentOperator, AssignmentTarget, BindingPatternKind, CallExpression,
+            Expression, IdentifierReference, MemberExpression, ObjectPropertyKind, Program,
+            Statement, StaticMemberExpression,
+        },
+        visit::walk,
+        AstKind, Visit,
+    },
+    parser::{ParseOptions, Parser, ParserReturn},
+    semantic::{Reference, Semantic, SymbolId},
+    span::{SourceType, Span},
+    syntax::module_record::{
+        ExportExportName, ExportImportName, ExportLocalName, ImportImportName, ModuleRecord,
+    },
+};
+
+use crate::{fresh_names::FreshNames, pass_manager::analyze};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ModuleFormat {
+    /// `import`/`export` only
+    Esm,
+    /// additionally resolve literal `require()` calls and `module.exports` assignments
+    #[value(name = "commonjs")]
+    CommonJs,
+}
+
+/// Extensions tried, in order, when an import specifier does not name a file directly.
+static EXTENSIONS: [&str; 4] = ["js", "mjs", "ts", "mts"];
+
+pub struct Module<'a> {
+    pub path: PathBuf,
+    pub program: Program<'a>,
+    module_record: ModuleRecord<'a>,
+    /// Requested specifiers in source order, with the index of the module they resolved to.
+    dependencies: Vec<(String, usize)>,
+    /// Names assigned through `exports.name` or `module.exports = { ... }`.
+    commonjs_exports: HashMap<String, String>,
+    /// `module.exports = { name }` properties whose value is itself bound by
+    /// `require()`, as the specifier and export name it reads.
+    commonjs_aliases: HashMap<String, (String, String)>,
+}
+
+/// Every module reachable from the entry file through relative imports.
+pub struct ModuleGraph<'a> {
+    format: ModuleFormat,
+    pub modules: Vec<Module<'a>>,
+    /// Indices into `modules`, dependencies before dependents.
+    pub evaluation_order: Vec<usize>,
+}
+
+/// How a module's symbols are named in the generated C++.
+#[derive(Default)]
+pub struct Linkage {
+    /// Top-level declarations and named/default imports.
+    pub bindings: HashMap<SymbolId, String>,
+    /// `import * as ns` bindings, mapping each export name of the target module.
+    pub namespaces: HashMap<SymbolId, HashMap<String, String>>,
+    /// Variable holding the value of `export default <expression>` or `module.exports = <expression>`.
+    pub default_export: String,
+    /// Whether `require()` calls and `exports` assignments are resolved.
+    pub commonjs: bool,
+    /// This module's own `exports.name` properties.
+    pub commonjs_exports: HashMap<String, String>,
+    /// The exports read as `require('./m').name`, by specifier.
+    pub requires: HashMap<String, HashMap<String, String>>,
+}
+
+enum Export {
+    Binding(String),
+    Namespace(usize),
+}
+
+impl<'a> ModuleGraph<'a> {
+    pub fn load(
+        allocator: &'a Allocator,
+        entry: &Path,
+        format: ModuleFormat,
+    ) -> Result<Self, String> {
+        let mut graph = Self {
+            format,
+            modules: vec![],
+            evaluation_order: vec![],
+        };
+        let mut indices = HashMap::new();
+        let entry = canonicalize(entry)?;
+        graph.load_module(allocator, entry, &mut indices)?;
+
+        // named once every module is loaded, since the prefixes depend on their count
+        if graph.format == ModuleFormat::CommonJs {
+            for index in 0..graph.modules.len() {
+                let (exports, aliases) =
+                    graph.commonjs_exports(index, &graph.modules[index].program);
+                graph.modules[index].commonjs_exports = exports;
+                graph.modules[index].commonjs_aliases = aliases;
+            }
+        }
+
+        let mut visiting = HashSet::new();
+        graph.order_from(0, &mut visiting);
+
+        Ok(graph)
+    }
+
+    fn load_module(
+        &mut self,
+        allocator: &'a Allocator,
+        path: PathBuf,
+        indices: &mut HashMap<PathBuf, usize>,
+    ) -> Result<usize, String> {
+        if let Some(index) = indices.get(&path) {
+            return Ok(*index);
+        }
+
+        let name = path.display().to_string();
+        let source_text = fs::read_to_string(&path).map_err(|_| format!("Missing '{name}'"))?;
+        let source_text = allocator.alloc_str(&source_text);
+        let source_type = SourceType::from_path(&path).map_err(|e| format!("{name}: {e}"))?;
+
+        let ParserReturn {
+            program,
+            module_record,
+            errors,
+            panicked,
+            ..
+        } = Parser::new(allocator, source_text, source_type)
+            .with_options(ParseOptions {
+                parse_regular_expression: true,
+                ..ParseOptions::default()
+            })
+            .parse();
+
+        if panicked {
+            for error in &errors {
+                eprintln!("{error:?}");
+            }
+            return Err(format!("Parsing '{name}' failed"));
+        }
+
+        let mut requests: Vec<_> = module_record
+            .requested_modules
+            .iter()
+            .filter(|(_, requests)| requests.iter().any(|request| !request.is_type))
+            .map(|(specifier, requests)| {
+                let start = requests.iter().map(|r| r.span.start).min().unwrap();
+                (start, specifier.to_string())
+            })
+            .collect();
+        let index = self.modules.len();
+        if self.format == ModuleFormat::CommonJs {
+            let semantic = analyze(&program).semantic;
+            let mut find_requires = FindRequires {
+                semantic: &semantic,
+                requests: vec![],
+                dynamic: None,
+            };
+            find_requires.visit_program(&program);
+            if let Some(span) = find_requires.dynamic {
+                return Err(format!(
+                    "{name}: only require() of a string literal is supported ({})",
+                    &source_text[span.start as usize..span.end as usize]
+                ));
+            }
+            requests.extend(find_requires.requests);
+        }
+        requests.sort();
+        let mut seen = HashSet::new();
+        requests.retain(|(_, specifier)| seen.insert(specifier.clone()));
+
+        indices.insert(path.clone(), index);
+        self.modules.push(Module {
+            path,
+            program,
+            module_record,
+            dependencies: vec![],
+            commonjs_exports: HashMap::new(),
+            commonjs_aliases: HashMap::new(),
+        });
+
+        for (_, specifier) in requests {
+            let resolved = resolve(&self.modules[index].path, &specifier)?;
+            let dependency = self.load_module(allocator, resolved, indices)?;
+            self.modules[index]
+                .dependencies
+                .push((specifier, dependency));
+        }
+
+        Ok(index)
+    }
+
+    /// Post-order DFS, as in ECMAScript's InnerModuleEvaluation: a module that is
+    /// already on the stack is part of a cycle and evaluates after the module
+    /// that requested it.
+    fn order_from(&mut self, index: usize, visiting: &mut HashSet<usize>) {
+        if !visiting.insert(index) {
+            return;
+        }
+        let dependencies: Vec<usize> = self.modules[index]
+            .dependencies
+            .iter()
+            .map(|(_, dependency)| *dependency)
+            .collect();
+        for dependency in dependencies {
+            self.order_from(dependency, visiting);
+        }
+        self.evaluation_order.push(index);
+    }
+
+    fn prefix(&self, index: usize) -> String {
+        if self.modules.len() == 1 {
+            String::new()
+        } else {
+            format!("m{index}_")
+        }
+    }
+
+    fn dependency(&self, index: usize, specifier: &str) -> usize {
+        self.modules[index]
+            .dependencies
+            .iter()
+            .find(|(s, _)| s == specifier)
+            .map(|(_, dependency)| *dependency)
+            .unwrap()
+    }
+
+    /// Resolves `export_name` of module `index` to the C++ name of the binding
+    /// it ultimately refers to, following re-exports.
+    fn resolve_export(
+        &self,
+        index: usize,
+        export_name: &str,
+        seen: &mut HashSet<(usize, String)>,
+    ) -> Option<Export> {
+        if !seen.insert((index, export_name.to_string())) {
+            return None;
+        }
+        if let Some(binding) = self.modules[index].commonjs_exports.get(export_name) {
+            return Some(Export::Binding(binding.clone()));
+        }
+        if let Some((specifier, name)) = self.modules[index].commonjs_aliases.get(export_name) {
+            let dependency = self.dependency(index, specifier);
+            return match self.resolve_export(dependency, name, seen) {
+                None if name == "default" => Some(Export::Namespace(dependency)),
+                export => export,
+            };
+        }
+        let record = &self.modules[index].module_record;
+
+        for entry in &record.local_export_entries {
+            if export_name_matches(&entry.export_name, export_name) {
+                return Some(Export::Binding(match &entry.local_name {
+                    ExportLocalName::Name(name) | ExportLocalName::Default(name) => {
+                        format!("{}{}", self.prefix(index), name.name)
+                    }
+                    ExportLocalName::Null => self.default_export_name(index),
+                }));
+            }
+        }
+
+        for entry in &record.indirect_export_entries {
+            if export_name_matches(&entry.export_name, export_name) {
+                let dependency = self.dependency(index, &entry.module_request.as_ref()?.name);
+                return match &entry.import_name {
+                    ExportImportName::Name(name) => {
+                        self.resolve_export(dependency, &name.name, seen)
+                    }
+                    ExportImportName::All | ExportImportName::AllButDefault => {
+                        Some(Export::Namespace(dependency))
+                    }
+                    ExportImportName::Null => None,
+                };
+            }
+        }
+
+        if export_name == "default" {
+            return None;
+        }
+        for entry in &record.star_export_entries {
+            let dependency = self.dependency(index, &entry.module_request.as_ref()?.name);
+            if let Some(export) = self.resolve_export(dependency, export_name, seen) {
+                return Some(export);
+            }
+        }
+
+        None
+    }
+
+    fn export_names(&self, index: usize, names: &mut Vec<String>, seen: &mut HashSet<usize>) {
+        if !seen.insert(index) {
+            return;
+        }
+        names.extend(self.modules[index].commonjs_exports.keys().cloned());
+        names.extend(self.modules[index].commonjs_aliases.keys().cloned());
+        let record = &self.modules[index].module_record;
+        for entry in record
+            .local_export_entries
+            .iter()
+            .chain(record.indirect_export_entries.iter())
+        {
+            match &entry.export_name {
+                ExportExportName::Name(name) => names.push(name.name.to_string()),
+                ExportExportName::Default(_) => names.push("default".to_string()),
+                ExportExportName::Null => {}
+            }
+        }
+        for entry in &record.star_export_entries {
+            if let Some(request) = &entry.module_request {
+                let dependency = self.dependency(index, &request.name);
+                self.export_names(dependency, names, seen);
+            }
+        }
+    }
+
+    /// Maps each export name of module `index` to the C++ name of its binding.
+    /// Re-exported namespaces cannot be expressed as a single name and are skipped.
+    fn namespace(&self, index: usize) -> HashMap<String, String> {
+        let mut names = vec![];
+        self.export_names(index, &mut names, &mut HashSet::new());
+
+        names
+            .into_iter()
+            .filter_map(
+                |name| match self.resolve_export(index, &name, &mut HashSet::new()) {
+                    Some(Export::Binding(binding)) => Some((name, binding)),
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+
+    /// Names every top-level symbol of module `index` and binds its imports to
+    /// the exports they refer to.
+    pub fn link(&self, index: usize, semantic: &Semantic) -> Result<Linkage, String> {
+        let module = &self.modules[index];
+        let mut linkage = Linkage {
+            default_export: self.default_export_name(index),
+            commonjs: self.format == ModuleFormat::CommonJs,
+            commonjs_exports: module.commonjs_exports.clone(),
+            ..Linkage::default()
+        };
+        let root_scope = semantic.scopes().root_scope_id();
+
+        let prefix = self.prefix(index);
+        for symbol_id in semantic.scopes().iter_bindings_in(root_scope) {
+            let name = semantic.symbols().get_name(symbol_id);
+            linkage
+                .bindings
+                .insert(symbol_id, format!("{prefix}{name}"));
+        }
+
+        let position = |i: usize| self.evaluation_order.iter().position(|x| *x == i);
+        for entry in &module.module_record.import_entries {
+            if entry.is_type {
+                continue;
+            }
+            let local_name = entry.local_name.name.as_str();
+            let Some(symbol_id) = semantic.scopes().get_binding(root_scope, local_name) else {
+                continue;
+            };
+            let specifier = entry.module_request.name.as_str();
+            let dependency = self.dependency(index, specifier);
+
+            // A reference at the top level runs during module evaluation, so reading
+            // a binding from a module that has not been evaluated yet is a TDZ error.
+            // One in a function body only runs when the function is called, which
+            // may well be after the dependency is evaluated.
+            let top_level = |reference: &Reference| {
+                let scope = semantic.nodes().get_node(reference.node_id()).scope_id();
+                !semantic
+                    .scopes()
+                    .ancestors(scope)
+                    .any(|scope| semantic.scopes().get_flags(scope).is_function())
+            };
+            if position(dependency) > position(index)
+                && semantic.symbol_references(symbol_id).any(top_level)
+            {
+                return Err(format!(
+                    "{}: cannot access '{local_name}' before initialization (circular import of '{specifier}')",
+                    module.path.display()
+                ));
+            }
+
+            let import_name = match &entry.import_name {
+                ImportImportName::Name(name) => name.name.as_str(),
+                ImportImportName::Default(_) => "default",
+                ImportImportName::NamespaceObject => {
+                    linkage
+                        .namespaces
+                        .insert(symbol_id, self.namespace(dependency));
+                    linkage.bindings.remove(&symbol_id);
+                    continue;
+                }
+            };
+
+            match self.resolve_export(dependency, import_name, &mut HashSet::new()) {
+                Some(Export::Binding(binding)) => {
+                    linkage.bindings.insert(symbol_id, binding);
+                }
+                Some(Export::Namespace(target)) => {
+                    linkage.namespaces.insert(symbol_id, self.namespace(target));
+                    linkage.bindings.remove(&symbol_id);
+                }
+                None => {
+                    return Err(format!(
+                        "{}: '{specifier}' does not provide an export named '{import_name}'",
+                        module.path.display()
+                    ));
+                }
+            }
+        }
+
+        if linkage.commonjs {
+            self.link_requires(index, semantic, &mut linkage)?;
+            for key in module.commonjs_aliases.keys() {
+                if let Some(Export::Binding(binding)) =
+                    self.resolve_export(index, key, &mut HashSet::new())
+                {
+                    linkage.commonjs_exports.insert(key.clone(), binding);
+                }
+            }
+        }
+
+        // `ns.name` reads of a namespace are resolved by codegen, which needs every name to exist
+        for (symbol_id, names) in &linkage.namespaces {
+            for reference in semantic.symbol_references(*symbol_id) {
+                let Some(AstKind::MemberExpression(MemberExpression::StaticMemberExpression(
+                    member,
+                ))) = semantic.nodes().parent_kind(reference.node_id())
+                else {
+                    continue;
+                };
+                if !names.contains_key(member.property.name.as_str()) {
+                    return Err(format!(
+                        "{}: '{}' has no export '{}'",
+                        module.path.display(),
+                        semantic.symbols().get_name(*symbol_id),
+                        member.property.name
+                    ));
+                }
+            }
+        }
+
+        Ok(linkage)
+    }
+
+    /// Binds `const x = require('./m')` and `const { a, b: c } = require('./m')`
+    /// at the top level of module `index`.
+    fn link_requires(
+        &self,
+        index: usize,
+        semantic: &Semantic,
+        linkage: &mut Linkage,
+    ) -> Result<(), String> {
+        let module = &self.modules[index];
+        let position = |i: usize| self.evaluation_order.iter().position(|x| *x == i);
+
+        for statement in &module.program.body {
+            let Statement::VariableDeclaration(var) = statement else {
+                continue;
+            };
+            for decl in &var.declarations {
+                let Some(specifier) = decl
+                    .init
+                    .as_ref()
+                    .and_then(|init| require_specifier(semantic, init))
+                else {
+                    continue;
+                };
+                let dependency = self.dependency(index, specifier);
+                let cyclic = position(dependency) > position(index);
+
+                let mut bind = |symbol_id: SymbolId, export: Option<Export>, name: &str| {
+                    if cyclic && semantic.symbol_references(symbol_id).next().is_some() {
+                        return Err(format!(
+                            "{}: cannot access '{name}' before initialization (circular require of '{specifier}')",
+                            module.path.display()
+                        ));
+                    }
+                    match export {
+                        Some(Export::Binding(binding)) => {
+                            linkage.bindings.insert(symbol_id, binding);
+                        }
+                        Some(Export::Namespace(target)) => {
+                            linkage.namespaces.insert(symbol_id, self.namespace(target));
+                            linkage.bindings.remove(&symbol_id);
+                        }
+                        None => {
+                            return Err(format!(
+                                "{}: '{specifier}' does not export '{name}'",
+                                module.path.display()
+                            ));
+                        }
+                    }
+                    Ok(())
+                };
+
+                match &decl.id.kind {
+                    BindingPatternKind::BindingIdentifier(id) => {
+                        let export =
+                            match self.resolve_export(dependency, "default", &mut HashSet::new()) {
+                                Some(export) => export,
+                                None => Export::Namespace(dependency),
+                            };
+                        bind(id.symbol_id.get().unwrap(), Some(export), &id.name)?;
+                    }
+                    BindingPatternKind::ObjectPattern(pattern) => {
+                        for property in &pattern.properties {
+                            let (Some(key), BindingPatternKind::BindingIdentifier(id)) =
+                                (property.key.static_name(), &property.value.kind)
+                            else {
+                                return Err(format!(
+                                    "{}: unsupported destructuring of require('{specifier}')",
+                                    module.path.display()
+                                ));
+                            };
+                            let export = self.resolve_export(dependency, &key, &mut HashSet::new());
+                            bind(id.symbol_id.get().unwrap(), export, &key)?;
+                        }
+                    }
+                    _ => {
+                        return Err(format!(
+                            "{}: unsupported binding of require('{specifier}')",
+                            module.path.display()
+                        ));
+                    }
+                }
+            }
+        }
+
+        // `require('./m').name` anywhere else reads the export directly
+        let mut uses = RequireUses {
+            semantic,
+            members: vec![],
+            unsupported: None,
+        };
+        for statement in &module.program.body {
+            match statement {
+                Statement::ExpressionStatement(statement)
+                    if require_specifier(semantic, &statement.expression).is_some() => {}
+                Statement::VariableDeclaration(var) => {
+                    for decl in &var.declarations {
+                        match &decl.init {
+                            Some(init) if require_specifier(semantic, init).is_some() => {}
+                            Some(init) => uses.visit_expression(init),
+                            None => {}
+                        }
+                    }
+                }
+                _ => uses.visit_statement(statement),
+            }
+        }
+        if let Some(specifier) = uses.unsupported {
+            return Err(format!(
+                "{}: require('{specifier}') is only supported as a statement, as the initializer \
+                 of a top-level declaration or as require('{specifier}').name",
+                module.path.display()
+            ));
+        }
+        for (specifier, name) in uses.members {
+            let dependency = self.dependency(index, &specifier);
+            if position(dependency) > position(index) {
+                return Err(format!(
+                    "{}: cannot access '{name}' before initialization (circular require of '{specifier}')",
+                    module.path.display()
+                ));
+            }
+            let binding = match self.resolve_export(dependency, &name, &mut HashSet::new()) {
+                Some(Export::Binding(binding)) => binding,
+                Some(Export::Namespace(_)) => {
+                    return Err(format!(
+                        "{}: require('{specifier}').{name} is a namespace, bind it with const instead",
+                        module.path.display()
+                    ));
+                }
+                None => {
+                    return Err(format!(
+                        "{}: '{specifier}' does not export '{name}'",
+                        module.path.display()
+                    ));
+                }
+            };
+            linkage
+                .requires
+                .entry(specifier)
+                .or_default()
+                .insert(name, binding);
+        }
+
+        Ok(())
+    }
+
+    /// Collects the names a CommonJS module assigns at its top level. Properties
+    /// that alias a top-level binding reuse its name, except for `require()`
+    /// bindings, which are returned separately as the (specifier, export name)
+    /// they read. Everything else gets a variable of its own.
+    fn commonjs_exports(
+        &self,
+        index: usize,
+        program: &Program,
+    ) -> (HashMap<String, String>, HashMap<String, (String, String)>) {
+        let semantic = analyze(program).semantic;
+        let mut names = FreshNames::new(&semantic);
+        let prefix = self.prefix(index);
+        let mut variable =
+            |name: &str| format!("{prefix}{}", names.fresh(&format!("exports_{name}")));
+
+        let mut required = HashMap::new();
+        for statement in &program.body {
+            let Statement::VariableDeclaration(var) = statement else {
+                continue;
+            };
+            for decl in &var.declarations {
+                let Some(specifier) = decl
+                    .init
+                    .as_ref()
+                    .and_then(|init| require_specifier(&semantic, init))
+                else {
+                    continue;
+                };
+                match &decl.id.kind {
+                    BindingPatternKind::BindingIdentifier(id) => {
+                        required.insert(
+                            id.name.to_string(),
+                            (specifier.to_string(), "default".to_string()),
+                        );
+                    }
+                    BindingPatternKind::ObjectPattern(pattern) => {
+                        for property in &pattern.properties {
+                            if let (Some(key), BindingPatternKind::BindingIdentifier(id)) =
+                                (property.key.static_name(), &property.value.kind)
+                            {
+                                required.insert(
+                                    id.name.to_string(),
+                                    (specifier.to_string(), key.to_string()),
+                                );
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut exports = HashMap::new();
+        let mut aliases = HashMap::new();
+        for statement in &program.body {
+            let Statement::ExpressionStatement(statement) = statement else {
+                continue;
+            };
+            let Expression::AssignmentExpression(assign) = &statement.expression else {
+                continue;
+            };
+            if assign.operator != AssignmentOperator::Assign {
+                continue;
+            }
+            let AssignmentTarget::StaticMemberExpression(target) = &assign.left else {
+                continue;
+            };
+
+            if let Some(name) = exports_property(&semantic, target) {
+                exports.insert(name.to_string(), variable(name));
+            } else if is_module_exports(&semantic, target) {
+                let Expression::ObjectExpression(object) = &assign.right else {
+                    // the whole value, as seen by `const x = require(...)`
+                    exports.insert("default".to_string(), self.default_export_name(index));
+                    continue;
+                };
+                for property in &object.properties {
+                    let ObjectPropertyKind::ObjectProperty(property) = property else {
+                        continue;
+                    };
+                    let Some(key) = property.key.static_name() else {
+                        continue;
+                    };
+                    match &property.value {
+                        Expression::Identifier(id) => match required.get(id.name.as_str()) {
+                            Some(import) => {
+                                aliases.insert(key.to_string(), import.clone());
+                            }
+                            None => {
+                                exports.insert(key.to_string(), format!("{prefix}{}", id.name));
+                            }
+                        },
+                        _ => {
+                            exports.insert(key.to_string(), variable(&key));
+                        }
+                    }
+                }
+            }
+        }
+
+        (exports, aliases)
+    }
+
+    fn default_export_name(&self, index: usize) -> String {
+        format!("m{index}_default")
+    }
+}
+
+fn export_name_matches(export: &ExportExportName, name: &str) -> bool {
+    match export {
+        ExportExportName::Name(export) => export.name == name,
+        ExportExportName::Default(_) => name == "default",
+        ExportExportName::Null => false,
+    }
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, String> {
+    fs::canonicalize(path).map_err(|_| format!("Missing '{}'", path.display()))
+}
+
+fn resolve(importer: &Path, specifier: &str) -> Result<PathBuf, String> {
+    if !(specifier.starts_with("./") || specifier.starts_with("../") || specifier.starts_with('/'))
+    {
+        return Err(format!(
+            "{}: cannot resolve '{specifier}', only relative imports are supported",
+            importer.display()
+        ));
+    }
+
+    let base = importer.parent().unwrap().join(specifier);
+    let mut candidates = vec![base.clone()];
+    for extension in EXTENSIONS {
+        let mut file = base.clone().into_os_string();
+        file.push(".");
+        file.push(extension);
+        candidates.push(file.into());
+    }
+    for extension in EXTENSIONS {
+        candidates.push(base.join(format!("index.{extension}")));
+    }
+
+    candidates
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+        .map(|candidate| canonicalize(&candidate))
+        .unwrap_or_else(|| {
+            Err(format!(
+                "{}: cannot resolve '{specifier}'",
+                importer.display()
+            ))
+        })
+}
+
+/// Whether `id` refers to the CommonJS global `name` rather than a binding of the module.
+fn is_global(semantic: &Semantic, id: &IdentifierReference, name: &str) -> bool {
+    id.name == name
+        && id.reference_id.get().is_none_or(|reference_id| {
+            semantic
+                .symbols()
+                .get_reference(reference_id)
+                .symbol_id()
+                .is_none()
+        })
+}
+
+/// `module.exports`
+pub fn is_module_exports(semantic: &Semantic, member: &StaticMemberExpression) -> bool {
+    matches!(&member.object, Expression::Identifier(id) if is_global(semantic, id, "module"))
+        && member.property.name == "exports"
+}
+
+/// The property name of `exports.name` or `module.exports.name`.
+pub fn exports_property<'b>(
+    semantic: &Semantic,
+    member: &'b StaticMemberExpression,
+) -> Option<&'b str> {
+    let exports = match &member.object {
+        Expression::Identifier(id) => is_global(semantic, id, "exports"),
+        Expression::StaticMemberExpression(object) => is_module_exports(semantic, object),
+        _ => false,
+    };
+    exports.then_some(member.property.name.as_str())
+}
+
+/// The specifier of a `require("...")` call.
+pub fn require_specifier<'b>(semantic: &Semantic, expr: &'b Expression) -> Option<&'b str> {
+    let Expression::CallExpression(call) = expr else {
+        return None;
+    };
+    call_specifier(semantic, call)
+}
+
+fn call_specifier<'b>(semantic: &Semantic, call: &'b CallExpression) -> Option<&'b str> {
+    if !is_require(semantic, call) {
+        return None;
+    }
+    match call.arguments.first() {
+        Some(Argument::StringLiteral(literal)) if call.arguments.len() == 1 => {
+            Some(literal.value.as_str())
+        }
+        _ => None,
+    }
+}
+
+fn is_require(semantic: &Semantic, call: &CallExpression) -> bool {
+    matches!(&call.callee, Expression::Identifier(id) if is_global(semantic, id, "require"))
+}
+
+/// The `require('./m').name` reads of a module, and the first `require()` used any other way.
+struct RequireUses<'s, 'b> {
+    semantic: &'s Semantic<'b>,
+    members: Vec<(String, String)>,
+    unsupported: Option<String>,
+}
+
+impl<'a> Visit<'a> for RequireUses<'_, '_> {
+    fn visit_static_member_expression(&mut self, member: &StaticMemberExpression<'a>) {
+        if let Some(specifier) = require_specifier(self.semantic, &member.object) {
+            self.members
+                .push((specifier.to_string(), member.property.name.to_string()));
+            return;
+        }
+        walk::walk_static_member_expression(self, member);
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression<'a>) {
+        if let Some(specifier) = call_specifier(self.semantic, call) {
+            self.unsupported.get_or_insert(specifier.to_string());
+        }
+        walk::walk_call_expression(self, call);
+    }
+}
+
+struct FindRequires<'s, 'b> {
+    semantic: &'s Semantic<'b>,
+    requests: Vec<(u32, String)>,
+    /// First `require()` whose argument is not a string literal.
+    dynamic: Option<Span>,
+}
+
+impl<'a> Visit<'a> for FindRequires<'_, '_> {
+    fn visit_call_expression(&mut self, call: &CallExpression<'a>) {
+        if is_require(self.semantic, call) {
+            match call.arguments.first() {
+                Some(Argument::StringLiteral(literal)) if call.arguments.len() == 1 => {
+                    self.requests
+                        .push((call.span.start, literal.value.to_string()));
+                }
+                _ => {
+                    self.dynamic.get_or_insert(call.span);
+                }
+            }
+        }
+
+        walk::walk_call_expression(self, call);
+    }
+}
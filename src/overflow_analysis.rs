@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+
+use oxc::{
+    ast::{
+        ast::{
+            AssignmentTarget, BinaryOperator, CallExpression, Expression, IdentifierReference,
+            SimpleAssignmentTarget, UnaryOperator, UpdateOperator,
+        },
+        AstKind,
+    },
+    semantic::{Semantic, SymbolId},
+};
+
+use crate::{
+    dataflow::{Dataflow, Range, RangeAnalysis},
+    type_inference::Types,
+};
+
+/// An arithmetic node of the AST, told apart by its address: its span is shared with the copies
+/// the inline pass makes and with every node a pass synthesizes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeAddress(usize);
+
+impl NodeAddress {
+    pub fn of<T>(node: &T) -> Self {
+        Self(node as *const T as usize)
+    }
+}
+
+/// The operators whose int32 result may not be an int32: JS computes them on doubles, where C++
+/// would wrap around, or for `%`, give `-0` and NaN.
+pub fn may_overflow(operator: BinaryOperator) -> bool {
+    matches!(
+        operator,
+        BinaryOperator::Addition
+            | BinaryOperator::Subtraction
+            | BinaryOperator::Multiplication
+            | BinaryOperator::Remainder
+    )
+}
+
+/// The values JS computes for `left operator right` on int32 operands.
+fn int32_result(left: &Range, operator: BinaryOperator, right: &Range) -> Range {
+    let result = left.binary(operator, right);
+    // -0 is a double: a zero times a negative number, or a negative multiple of the divisor
+    if result.negative_zero {
+        return Range::UNKNOWN;
+    }
+    result
+}
+
+/// Finds the additions, subtractions, multiplications, remainders, negations, `x op= e` and `x++` whose
+/// operands are int32 but whose result [`RangeAnalysis`] cannot keep in the int32 range, the
+/// negations of an int32 that may be zero, which make `-0`, and the `Math.floor(x)` that may not
+/// be an int32.
+pub fn overflows(dataflow: &Dataflow, types: &Types) -> HashSet<NodeAddress> {
+    let semantic = dataflow.semantic();
+    let ranges = dataflow.solve(RangeAnalysis::with_types(types));
+    let integer = |expr| types.expression(semantic, expr).is_integer();
+    let symbol = |id| reference_symbol(semantic, id);
+
+    let mut overflows = HashSet::new();
+    for node in semantic.nodes().iter() {
+        let (address, range) = match node.kind() {
+            AstKind::BinaryExpression(expr)
+                if may_overflow(expr.operator) && integer(&expr.left) && integer(&expr.right) =>
+            {
+                let left = ranges.range(&expr.left, node.id());
+                let right = ranges.range(&expr.right, node.id());
+                (
+                    NodeAddress::of(expr),
+                    int32_result(&left, expr.operator, &right),
+                )
+            }
+            AstKind::UnaryExpression(expr)
+                if expr.operator == UnaryOperator::UnaryNegation && integer(&expr.argument) =>
+            {
+                // -0 is a double
+                let range = ranges.range(&expr.argument, node.id()).negate();
+                (NodeAddress::of(expr), range)
+            }
+            AstKind::CallExpression(call) if is_math_floor(call) => {
+                let Some(argument) = call.arguments[0].as_expression() else {
+                    continue;
+                };
+                (
+                    NodeAddress::of(call),
+                    ranges.range(argument, node.id()).floor(),
+                )
+            }
+            AstKind::AssignmentExpression(expr) => {
+                let (AssignmentTarget::AssignmentTargetIdentifier(id), Some(operator)) =
+                    (&expr.left, expr.operator.to_binary_operator())
+                else {
+                    continue;
+                };
+                let Some(target) = symbol(id) else {
+                    continue;
+                };
+                if !may_overflow(operator)
+                    || !types.symbol(target).is_integer()
+                    || !integer(&expr.right)
+                {
+                    continue;
+                }
+                let left = ranges.symbol(target, node.id());
+                let right = ranges.range(&expr.right, node.id());
+                (NodeAddress::of(expr), int32_result(&left, operator, &right))
+            }
+            AstKind::UpdateExpression(expr) => {
+                let SimpleAssignmentTarget::AssignmentTargetIdentifier(id) = &expr.argument else {
+                    continue;
+                };
+                let Some(target) = symbol(id) else {
+                    continue;
+                };
+                if !types.symbol(target).is_integer() {
+                    continue;
+                }
+                let step = Range::point(match expr.operator {
+                    UpdateOperator::Increment => 1.0,
+                    UpdateOperator::Decrement => -1.0,
+                });
+                let current = ranges.symbol(target, node.id());
+                (
+                    NodeAddress::of(expr),
+                    current.binary(BinaryOperator::Addition, &step),
+                )
+            }
+            _ => continue,
+        };
+        if !range.is_int32() {
+            overflows.insert(address);
+        }
+    }
+    overflows
+}
+
+fn reference_symbol(semantic: &Semantic, id: &IdentifierReference) -> Option<SymbolId> {
+    semantic
+        .symbols()
+        .get_reference(id.reference_id.get()?)
+        .symbol_id()
+}
+
+/// `Math.floor(x)`
+pub fn is_math_floor(call: &CallExpression) -> bool {
+    matches!(&call.callee, Expression::StaticMemberExpression(member)
+        if member.property.name == "floor"
+            && matches!(&member.object, Expression::Identifier(id) if id.name == "Math"))
+        && call.arguments.len() == 1
+}
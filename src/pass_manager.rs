@@ -0,0 +1,421 @@
+use std::{
+    io::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use oxc::{
+    allocator::Allocator,
+    ast::{
+        ast::{BindingIdentifier, IdentifierReference, Program},
+        visit::walk,
+        Visit, VisitMut,
+    },
+    semantic::{Semantic, SemanticBuilder, SemanticBuilderReturn},
+};
+
+use crate::{
+    common_subexpression_optimization::CommonSubexpressionOptimization,
+    constant_folding_optimization::ConstantFoldingOptimization,
+    dataflow::Dataflow,
+    dead_code_optimization::DeadCodeOptimization,
+    inlining_optimization::InliningOptimization,
+    loop_invariant_code_motion_optimization::LoopInvariantCodeMotionOptimization,
+    profile::{ModuleProfile, Profile},
+    remarks::{Remark, Report},
+    running_modulo_optimization::RunningModuloOptimization,
+    scalar_replacement_optimization::ScalarReplacementOptimization,
+    slotted_array_read_optimization::SlottedArrayReadOptimization,
+    specialization_optimization::SpecializationOptimization,
+    strength_reduction_optimization::StrengthReductionOptimization,
+    tail_call_optimization::TailCallOptimization,
+    type_inference::TypeInference,
+};
+
+/// An optimization that rewrites a module, given the analyses of its current AST.
+pub struct Pass {
+    pub name: &'static str,
+    /// The lowest `-O` level that runs this pass.
+    pub level: u8,
+    /// Returns what the pass rewrote and what it had to leave alone.
+    run: for<'s, 'a> fn(
+        &'s Dataflow<'s, 'a>,
+        &'a Allocator,
+        &mut Program<'a>,
+        &PassOptions,
+    ) -> Vec<Remark>,
+}
+
+/// The default of [`PassOptions::inline_threshold`].
+pub const DEFAULT_INLINE_THRESHOLD: usize = 40;
+
+/// Knobs of the passes that have some.
+pub struct PassOptions {
+    /// The largest function body, in AST nodes, `inline` copies into a call site.
+    pub inline_threshold: usize,
+    /// `tail-call` also rewrites calls under `+` and `*` with an accumulator.
+    pub tail_call_accumulator: bool,
+    /// Under `--profile-use`, the counts of the module being optimized.
+    pub profile: Option<ModuleProfile>,
+}
+
+impl Default for PassOptions {
+    fn default() -> Self {
+        Self {
+            inline_threshold: DEFAULT_INLINE_THRESHOLD,
+            tail_call_accumulator: false,
+            profile: None,
+        }
+    }
+}
+
+/// Every pass, in the order they run.
+pub static PASSES: [Pass; 11] = [
+    Pass {
+        name: "tail-call",
+        level: 2,
+        run: |dataflow, allocator, program, options| {
+            let types = TypeInference::new(dataflow.semantic()).infer(program).ok();
+            let mut pass = TailCallOptimization::new(
+                dataflow,
+                allocator,
+                types.as_ref(),
+                options.tail_call_accumulator,
+            );
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+    Pass {
+        name: "inline",
+        level: 2,
+        run: |dataflow, allocator, program, options| {
+            let mut pass = InliningOptimization::new(
+                dataflow,
+                allocator,
+                options.inline_threshold,
+                options.profile.as_ref(),
+            );
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+    Pass {
+        name: "specialize",
+        level: 2,
+        run: |dataflow, allocator, program, options| {
+            let mut pass =
+                SpecializationOptimization::new(dataflow, allocator, options.profile.as_ref());
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+    Pass {
+        name: "scalar-replacement",
+        level: 2,
+        run: |dataflow, allocator, program, _options| {
+            let mut pass = ScalarReplacementOptimization::new(dataflow, allocator);
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+    Pass {
+        name: "constant-folding",
+        level: 1,
+        run: |dataflow, allocator, program, _options| {
+            let mut pass = ConstantFoldingOptimization::new(dataflow, allocator, program);
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+    Pass {
+        name: "dead-code",
+        level: 1,
+        run: |dataflow, allocator, program, _options| {
+            let mut pass = DeadCodeOptimization::new(dataflow, allocator);
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+    Pass {
+        name: "slotted-array-read",
+        level: 2,
+        run: |dataflow, allocator, program, _options| {
+            let mut pass = SlottedArrayReadOptimization::new(dataflow, allocator);
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+    Pass {
+        name: "running-modulo",
+        level: 1,
+        run: |dataflow, allocator, program, _options| {
+            let mut pass = RunningModuloOptimization::new(dataflow, allocator);
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+    Pass {
+        name: "strength-reduction",
+        level: 2,
+        run: |dataflow, allocator, program, _options| {
+            let types = TypeInference::new(dataflow.semantic()).infer(program).ok();
+            let mut pass = StrengthReductionOptimization::new(dataflow, allocator, types.as_ref());
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+    Pass {
+        name: "licm",
+        level: 2,
+        run: |dataflow, allocator, program, options| {
+            let mut pass = LoopInvariantCodeMotionOptimization::new(
+                dataflow,
+                allocator,
+                options.profile.as_ref(),
+            );
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+    Pass {
+        name: "cse",
+        level: 2,
+        run: |dataflow, allocator, program, _options| {
+            let mut pass = CommonSubexpressionOptimization::new(dataflow, allocator, program);
+            pass.visit_program(program);
+            pass.remarks
+        },
+    },
+];
+
+/// The pipeline stops iterating to a fixed point after this many rounds.
+const MAX_ROUNDS: usize = 8;
+
+/// What one pass did over every module.
+#[derive(Default)]
+struct Statistics {
+    runs: usize,
+    rewrites: usize,
+    /// Including the semantic analysis rebuilt after the pass.
+    time: Duration,
+}
+
+/// Runs passes over a module, rebuilding its semantic analysis after each one that changed the
+/// AST so the next pass and codegen see symbols and references for the nodes it synthesized.
+pub struct PassManager<'a> {
+    allocator: &'a Allocator,
+    /// Indices into [`PASSES`].
+    passes: Vec<usize>,
+    fixed_point: bool,
+    options: PassOptions,
+    /// Under `--profile-use`, the counts of every module.
+    profile: Option<Profile>,
+    statistics: Vec<Statistics>,
+    pub report: Report,
+}
+
+impl<'a> PassManager<'a> {
+    /// A pipeline of the passes enabled at `-O2`.
+    pub fn new(allocator: &'a Allocator) -> Self {
+        Self {
+            allocator,
+            passes: vec![],
+            fixed_point: false,
+            options: PassOptions::default(),
+            profile: None,
+            statistics: PASSES.iter().map(|_| Statistics::default()).collect(),
+            report: Report::default(),
+        }
+        .with_level(2)
+    }
+
+    /// Runs the passes enabled at `level`; `-O3` also iterates them to a fixed point.
+    pub fn with_level(mut self, level: u8) -> Self {
+        self.passes = (0..PASSES.len())
+            .filter(|index| PASSES[*index].level <= level)
+            .collect();
+        self.fixed_point = level >= 3;
+        self
+    }
+
+    /// Adds the passes named in `enable` and removes the ones named in `disable`.
+    pub fn with_passes(mut self, enable: &[String], disable: &[String]) -> Result<Self, String> {
+        let find = |name: &String| {
+            PASSES
+                .iter()
+                .position(|pass| pass.name == name)
+                .ok_or_else(|| {
+                    let names: Vec<_> = PASSES.iter().map(|pass| pass.name).collect();
+                    format!(
+                        "Unknown pass '{name}', expected one of {}",
+                        names.join(", ")
+                    )
+                })
+        };
+        for name in enable {
+            let index = find(name)?;
+            if !self.passes.contains(&index) {
+                self.passes.push(index);
+            }
+        }
+        for name in disable {
+            let index = find(name)?;
+            self.passes.retain(|pass| *pass != index);
+        }
+        // passes always run in the order of `PASSES`
+        self.passes.sort();
+        Ok(self)
+    }
+
+    /// Repeats the pipeline until a round makes no rewrite.
+    pub fn with_fixed_point(mut self, fixed_point: bool) -> Self {
+        self.fixed_point |= fixed_point;
+        self
+    }
+
+    /// Inlines functions whose body has at most `threshold` AST nodes.
+    pub fn with_inline_threshold(mut self, threshold: usize) -> Self {
+        self.options.inline_threshold = threshold;
+        self
+    }
+
+    /// Lets `tail-call` carry the other operand of `return n * f(n - 1)` in an accumulator.
+    pub fn with_tail_call_accumulator(mut self, accumulate: bool) -> Self {
+        self.options.tail_call_accumulator = accumulate;
+        self
+    }
+
+    /// Lets `inline`, `specialize` and `licm` decide from the counts of a profile.
+    pub fn with_profile(mut self, profile: Option<Profile>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Optimizes `program`, the module at `path` whose analysis is `semantic` with `errors`
+    /// diagnostics, and returns the analysis of the result.
+    pub fn run(
+        &mut self,
+        path: &Path,
+        program: &mut Program<'a>,
+        mut semantic: Semantic<'a>,
+        errors: usize,
+    ) -> Semantic<'a> {
+        self.options.profile = self
+            .profile
+            .as_ref()
+            .map(|profile| profile.module(path, program.source_text));
+        for _ in 0..MAX_ROUNDS {
+            let mut changed = false;
+            for index in &self.passes {
+                let pass = &PASSES[*index];
+                let start = Instant::now();
+
+                let dataflow = Dataflow::new(&semantic);
+                let remarks = (pass.run)(&dataflow, self.allocator, program, &self.options);
+                drop(dataflow);
+                let rewrites = remarks.iter().filter(|remark| remark.applied).count();
+                self.report
+                    .add(pass.name, path, program.source_text, remarks);
+
+                if rewrites > 0 {
+                    let SemanticBuilderReturn {
+                        semantic: rebuilt,
+                        errors: rebuilt_errors,
+                    } = analyze(program);
+                    if cfg!(debug_assertions) {
+                        assert!(
+                            rebuilt_errors.len() <= errors,
+                            "{} produced invalid code: {rebuilt_errors:?}",
+                            pass.name
+                        );
+                        check_consistency(pass.name, program, &rebuilt);
+                    }
+                    semantic = rebuilt;
+                    changed = true;
+                }
+
+                let statistics = &mut self.statistics[*index];
+                statistics.runs += 1;
+                statistics.rewrites += rewrites;
+                statistics.time += start.elapsed();
+            }
+
+            if !self.fixed_point || !changed {
+                break;
+            }
+        }
+        semantic
+    }
+
+    /// Writes how often each pass ran, what it rewrote and how long it took.
+    pub fn print_statistics(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(
+            out,
+            "{:<24}{:>6}{:>10}{:>12}",
+            "pass", "runs", "rewrites", "time"
+        )?;
+        for (pass, statistics) in PASSES.iter().zip(&self.statistics) {
+            writeln!(
+                out,
+                "{:<24}{:>6}{:>10}{:>10.3}ms",
+                pass.name,
+                statistics.runs,
+                statistics.rewrites,
+                statistics.time.as_secs_f64() * 1000.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Scopes, symbols, JSDoc and the control flow graph of `program`.
+pub fn analyze<'a>(program: &Program<'a>) -> SemanticBuilderReturn<'a> {
+    SemanticBuilder::new()
+        .with_check_syntax_error(true)
+        .with_build_jsdoc(true)
+        .with_cfg(true)
+        .build(program)
+}
+
+/// Panics unless every identifier in `program` is bound to the symbol of the same name.
+fn check_consistency<'a>(pass: &str, program: &Program<'a>, semantic: &Semantic<'a>) {
+    struct Check<'s, 'a> {
+        pass: &'s str,
+        semantic: &'s Semantic<'a>,
+    }
+
+    impl<'a> Visit<'a> for Check<'_, 'a> {
+        fn visit_identifier_reference(&mut self, id: &IdentifierReference<'a>) {
+            let reference = id
+                .reference_id
+                .get()
+                .unwrap_or_else(|| panic!("{}: `{}` has no reference", self.pass, id.name));
+            let symbols = self.semantic.symbols();
+            if let Some(symbol) = symbols.get_reference(reference).symbol_id() {
+                assert_eq!(
+                    symbols.get_name(symbol),
+                    id.name.as_str(),
+                    "{}: reference resolves to another name",
+                    self.pass
+                );
+            }
+        }
+
+        fn visit_binding_identifier(&mut self, id: &BindingIdentifier<'a>) {
+            let symbol = id
+                .symbol_id
+                .get()
+                .unwrap_or_else(|| panic!("{}: `{}` has no symbol", self.pass, id.name));
+            assert_eq!(
+                self.semantic.symbols().get_name(symbol),
+                id.name.as_str(),
+                "{}: binding declares another name",
+                self.pass
+            );
+            walk::walk_binding_identifier(self, id);
+        }
+    }
+
+    Check { pass, semantic }.visit_program(program);
+}
@@ -0,0 +1,182 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use oxc::span::Span;
+
+use crate::remarks::{file_name, line_column};
+
+/// What a counter of an instrumented program counts at its site.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Counter {
+    /// How often an `if` test was true, then false.
+    Branch,
+    /// How often a loop was entered, then how many iterations it ran in all.
+    Loop,
+    /// How often a call to a function of the program ran.
+    Call,
+    /// How often an argument whose C++ type is a double held an int32 value, then another
+    /// number.
+    Argument,
+}
+
+impl Counter {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Counter::Branch => "branch",
+            Counter::Loop => "loop",
+            Counter::Call => "call",
+            Counter::Argument => "argument",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        Some(match kind {
+            "branch" => Counter::Branch,
+            "loop" => Counter::Loop,
+            "call" => Counter::Call,
+            "argument" => Counter::Argument,
+            _ => return None,
+        })
+    }
+}
+
+/// Where a counter is, as a profile names it: `file:line:column`.
+pub fn site(file: &str, source_text: &str, span: Span) -> String {
+    let (line, column) = line_column(source_text, span.start);
+    format!("{file}:{line}:{column}")
+}
+
+/// A counter of a profile: its kind, site and argument index.
+type Key = (Counter, String, usize);
+
+/// The counts a program built with `--profile-generate` wrote when it exited, one counter per
+/// line:
+///
+/// ```text
+/// branch main.js:4:5 990 10
+/// loop main.js:3:1 1 1000
+/// call main.js:9:13 1000 0
+/// argument main.js:9:13 0 1000 0
+/// ```
+///
+/// Lines of the same counter add up: a site copied by inlining counts in each copy.
+#[derive(Clone, Default)]
+pub struct Profile {
+    counts: HashMap<Key, [u64; 2]>,
+}
+
+impl Profile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("cannot read profile {}: {error}", path.display()))?;
+        Self::parse(&text).map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut profile = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = || format!("line {}: invalid counter `{line}`", number + 1);
+            let (kind, rest) = line.split_once(' ').ok_or_else(invalid)?;
+            let kind = Counter::parse(kind).ok_or_else(invalid)?;
+            let mut fields = rest.rsplitn(3, ' ');
+            let (Some(second), Some(first), Some(site)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid());
+            };
+            let (site, index) = match kind {
+                Counter::Argument => {
+                    let (site, index) = site.rsplit_once(' ').ok_or_else(invalid)?;
+                    (site, index.parse().map_err(|_| invalid())?)
+                }
+                _ => (site, 0),
+            };
+            let first: u64 = first.parse().map_err(|_| invalid())?;
+            let second: u64 = second.parse().map_err(|_| invalid())?;
+            let counts = profile
+                .counts
+                .entry((kind, site.to_string(), index))
+                .or_default();
+            counts[0] += first;
+            counts[1] += second;
+        }
+        Ok(profile)
+    }
+
+    /// The counters of the module at `path`, whose source is `source_text`, by the offset of
+    /// their site.
+    pub fn module(&self, path: &Path, source_text: &str) -> ModuleProfile {
+        let file = file_name(path);
+        let mut offsets = HashMap::new();
+        let mut line = 1;
+        let mut column = 1;
+        for (offset, c) in source_text.char_indices() {
+            offsets.insert((line, column), offset as u32);
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+
+        let mut counts = HashMap::new();
+        for ((kind, site, index), count) in &self.counts {
+            let mut parts = site.rsplitn(3, ':');
+            let (Some(column), Some(line), Some(site_file)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            let (Ok(line), Ok(column)) = (line.parse::<usize>(), column.parse::<usize>()) else {
+                continue;
+            };
+            if site_file != file {
+                continue;
+            }
+            if let Some(offset) = offsets.get(&(line, column)) {
+                counts.insert((*kind, *offset, *index), *count);
+            }
+        }
+        ModuleProfile { counts }
+    }
+}
+
+/// The counters of a [`Profile`] in one module.
+#[derive(Clone, Default)]
+pub struct ModuleProfile {
+    counts: HashMap<(Counter, u32, usize), [u64; 2]>,
+}
+
+impl ModuleProfile {
+    fn get(&self, kind: Counter, span: Span, index: usize) -> Option<[u64; 2]> {
+        self.counts.get(&(kind, span.start, index)).copied()
+    }
+
+    /// How often the test of the `if` at `span` was true, then false.
+    pub fn branch(&self, span: Span) -> Option<(u64, u64)> {
+        self.get(Counter::Branch, span, 0)
+            .map(|[taken, not_taken]| (taken, not_taken))
+    }
+
+    /// The iterations the loop at `span` ran each time it was entered, on average.
+    pub fn trips(&self, span: Span) -> Option<f64> {
+        match self.get(Counter::Loop, span, 0)? {
+            [0, _] => None,
+            [entries, iterations] => Some(iterations as f64 / entries as f64),
+        }
+    }
+
+    /// How often the call at `span` ran.
+    pub fn calls(&self, span: Span) -> Option<u64> {
+        self.get(Counter::Call, span, 0).map(|[calls, _]| calls)
+    }
+
+    /// How often argument `index` of the call at `span` was an int32, then another number.
+    pub fn argument(&self, span: Span, index: usize) -> Option<(u64, u64)> {
+        self.get(Counter::Argument, span, index)
+            .map(|[int32, other]| (int32, other))
+    }
+}
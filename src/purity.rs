@@ -0,0 +1,88 @@
+use oxc::{
+    ast::ast::{Argument, Expression, UnaryOperator},
+    semantic::Semantic,
+};
+
+/// Runtime functions that only compute their result from their arguments: a call can be
+/// dropped when its result is unused. `Math.random` is not one, it advances the generator.
+pub static PURE_FUNCTIONS: [&str; 10] = [
+    "Math.abs",
+    "Math.ceil",
+    "Math.floor",
+    "Math.max",
+    "Math.min",
+    "Math.round",
+    "Math.sign",
+    "Math.sqrt",
+    "Math.trunc",
+    "Number",
+];
+
+/// Runtime constructors that only allocate.
+pub static PURE_CONSTRUCTORS: [&str; 2] = ["Int32Array", "Float64Array"];
+
+/// Evaluating `expr` has no side effects.
+pub fn is_pure(semantic: &Semantic, expr: &Expression) -> bool {
+    let pure = |expr: &Expression| is_pure(semantic, expr);
+    let pure_arguments = |arguments: &[Argument]| {
+        arguments.iter().all(|argument| match argument {
+            Argument::SpreadElement(_) => false,
+            argument => is_pure(semantic, argument.to_expression()),
+        })
+    };
+    match expr {
+        Expression::NumericLiteral(_)
+        | Expression::BooleanLiteral(_)
+        | Expression::StringLiteral(_)
+        | Expression::NullLiteral(_)
+        | Expression::Identifier(_) => true,
+        Expression::ParenthesizedExpression(expr) => pure(&expr.expression),
+        Expression::TSAsExpression(expr) => pure(&expr.expression),
+        Expression::TSNonNullExpression(expr) => pure(&expr.expression),
+        Expression::UnaryExpression(expr) => {
+            expr.operator != UnaryOperator::Delete && pure(&expr.argument)
+        }
+        Expression::BinaryExpression(expr) => pure(&expr.left) && pure(&expr.right),
+        Expression::LogicalExpression(expr) => pure(&expr.left) && pure(&expr.right),
+        Expression::ConditionalExpression(expr) => {
+            pure(&expr.test) && pure(&expr.consequent) && pure(&expr.alternate)
+        }
+        Expression::StaticMemberExpression(expr) => pure(&expr.object),
+        Expression::ComputedMemberExpression(expr) => pure(&expr.object) && pure(&expr.expression),
+        Expression::CallExpression(call) => {
+            global_name(semantic, &call.callee)
+                .is_some_and(|name| PURE_FUNCTIONS.contains(&name.as_str()))
+                && pure_arguments(&call.arguments)
+        }
+        Expression::NewExpression(new) => {
+            global_name(semantic, &new.callee)
+                .is_some_and(|name| PURE_CONSTRUCTORS.contains(&name.as_str()))
+                && pure_arguments(&new.arguments)
+        }
+        _ => false,
+    }
+}
+
+/// `name` or `object.name` when `callee` refers to a global the program does not shadow.
+fn global_name(semantic: &Semantic, callee: &Expression) -> Option<String> {
+    let is_global = |expr: &Expression| match expr {
+        Expression::Identifier(id) => id.reference_id.get().is_some_and(|reference| {
+            semantic
+                .symbols()
+                .get_reference(reference)
+                .symbol_id()
+                .is_none()
+        }),
+        _ => false,
+    };
+    match callee {
+        Expression::Identifier(id) if is_global(callee) => Some(id.name.to_string()),
+        Expression::StaticMemberExpression(member) if is_global(&member.object) => {
+            let Expression::Identifier(object) = &member.object else {
+                return None;
+            };
+            Some(format!("{}.{}", object.name, member.property.name))
+        }
+        _ => None,
+    }
+}
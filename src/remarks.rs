@@ -0,0 +1,147 @@
+use std::{io::Write, path::Path};
+
+use oxc::span::Span;
+use serde_json::json;
+
+/// What a pass did at one place in the source, or why it left it alone.
+pub struct Remark {
+    pub span: Span,
+    pub applied: bool,
+    pub message: String,
+}
+
+impl Remark {
+    pub fn applied(span: Span, message: String) -> Self {
+        Self {
+            span,
+            applied: true,
+            message,
+        }
+    }
+
+    pub fn missed(span: Span, message: String) -> Self {
+        Self {
+            span,
+            applied: false,
+            message,
+        }
+    }
+}
+
+/// How remarks and profiles name a module: relative to the working directory, which is usually
+/// where the user looks, when it is under it.
+pub fn file_name(path: &Path) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    path.strip_prefix(cwd).unwrap_or(path).display().to_string()
+}
+
+/// The 1-based line and column, in characters, of the byte `offset` of `source_text`.
+pub fn line_column(source_text: &str, offset: u32) -> (usize, usize) {
+    let before = &source_text[..offset as usize];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// one `file:line:column: pass: message` line per remark
+    Human,
+    /// an array of objects with `pass`, `file`, `line`, `column`, `applied` and `message`
+    Json,
+}
+
+/// A remark located in a module.
+struct Entry {
+    pass: &'static str,
+    file: String,
+    line: usize,
+    column: usize,
+    applied: bool,
+    message: String,
+}
+
+/// The remarks of every pass over every module.
+#[derive(Default)]
+pub struct Report {
+    entries: Vec<Entry>,
+}
+
+impl Report {
+    /// Adds the remarks `pass` made about the module at `path` with source `source_text`.
+    ///
+    /// A pass that runs more than once sees the same code again; only its last word on each
+    /// place is kept, unless an earlier run transformed it.
+    pub fn add(
+        &mut self,
+        pass: &'static str,
+        path: &Path,
+        source_text: &str,
+        remarks: Vec<Remark>,
+    ) {
+        let file = file_name(path);
+        for remark in remarks {
+            let (line, column) = line_column(source_text, remark.span.start);
+
+            let earlier = self.entries.iter().position(|entry| {
+                entry.pass == pass
+                    && entry.file == file
+                    && entry.line == line
+                    && entry.column == column
+            });
+            match earlier {
+                Some(index) if self.entries[index].applied => continue,
+                Some(index) => {
+                    self.entries.remove(index);
+                }
+                None => {}
+            }
+
+            self.entries.push(Entry {
+                pass,
+                file: file.clone(),
+                line,
+                column,
+                applied: remark.applied,
+                message: remark.message,
+            });
+        }
+    }
+
+    pub fn print(&self, out: &mut impl Write, format: ReportFormat) -> std::io::Result<()> {
+        match format {
+            ReportFormat::Human => {
+                for entry in &self.entries {
+                    writeln!(
+                        out,
+                        "{}:{}:{}: {}: {}{}",
+                        entry.file,
+                        entry.line,
+                        entry.column,
+                        entry.pass,
+                        if entry.applied { "" } else { "missed: " },
+                        entry.message
+                    )?;
+                }
+            }
+            ReportFormat::Json => {
+                let entries: Vec<_> = self
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        json!({
+                            "pass": entry.pass,
+                            "file": entry.file,
+                            "line": entry.line,
+                            "column": entry.column,
+                            "applied": entry.applied,
+                            "message": entry.message,
+                        })
+                    })
+                    .collect();
+                writeln!(out, "{}", serde_json::to_string_pretty(&entries)?)?;
+            }
+        }
+        Ok(())
+    }
+}
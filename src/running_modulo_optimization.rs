@@ -5,69 +5,225 @@ use oxc::{
     ast::{
         ast::{
             AssignmentOperator, AssignmentTarget, BinaryExpression, BinaryOperator,
-            BindingPatternKind, Expression, ForStatement, ForStatementInit, NumberBase, Statement,
-            TSType, UpdateOperator,
+            BindingPatternKind, ContinueStatement, Expression, ForStatement, ForStatementInit,
+            IdentifierReference, NumberBase, SimpleAssignmentTarget, Statement, TSType,
+            UpdateOperator,
         },
         visit::{walk, walk_mut},
-        AstBuilder, Visit, VisitMut,
+        AstBuilder, AstKind, Visit, VisitMut,
     },
-    semantic::{Semantic, SymbolId},
+    semantic::{NodeId, ReferenceId, Semantic, SymbolId},
     span::SPAN,
 };
 
-pub struct RunningModuloOptimization<'a> {
-    semantic: &'a Semantic<'a>,
+use crate::{
+    dataflow::{ConstantPropagation, Dataflow, RangeAnalysis, ReachingDefinitions, Results},
+    fresh_names::FreshNames,
+    remarks::Remark,
+};
+
+pub struct RunningModuloOptimization<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    dataflow: &'s Dataflow<'s, 'a>,
+    constants: Results<'s, 's, 'a, ConstantPropagation>,
+    ranges: Results<'s, 's, 'a, RangeAnalysis<'s>>,
+    reaching: Results<'s, 's, 'a, ReachingDefinitions>,
     ast: AstBuilder<'a>,
-    replacements: HashMap<(String, String), String>,
+    names: FreshNames,
+    /// The numerator reference of each `i % n` to replace, and its running variable.
+    replacements: HashMap<ReferenceId, String>,
+    pub remarks: Vec<Remark>,
 }
 
-impl<'a> RunningModuloOptimization<'a> {
-    pub fn new(semantic: &'a Semantic<'a>, allocator: &'a Allocator) -> Self {
+impl<'s, 'a> RunningModuloOptimization<'s, 'a> {
+    pub fn new(dataflow: &'s Dataflow<'s, 'a>, allocator: &'a Allocator) -> Self {
         let ast = AstBuilder::new(allocator);
         Self {
-            semantic,
+            semantic: dataflow.semantic(),
+            dataflow,
+            constants: dataflow.solve(ConstantPropagation),
+            ranges: dataflow.solve(RangeAnalysis::default()),
+            reaching: dataflow.solve(ReachingDefinitions),
             ast,
+            names: FreshNames::new(dataflow.semantic()),
             replacements: HashMap::new(),
+            remarks: vec![],
+        }
+    }
+
+    fn describe(&self, denominator: Denominator) -> String {
+        match denominator {
+            Denominator::Variable(symbol) => self.semantic.symbols().get_name(symbol).to_string(),
+            Denominator::Literal(value) => value.to_string(),
+        }
+    }
+
+    /// `i % n` can only be kept as a running value when `i` counts 0, 1, 2, ... one step
+    /// per iteration and `n` is a fixed positive integer for the whole loop.
+    fn check(
+        &self,
+        for_: &ForStatement<'a>,
+        counter: SymbolId,
+        denominator: Denominator,
+        modulos: &[(ReferenceId, Option<ReferenceId>)],
+    ) -> Result<(), &'static str> {
+        let symbols = self.semantic.symbols();
+        let declaration = symbols.get_declaration(counter);
+        let AstKind::VariableDeclarator(declarator) = self.semantic.nodes().kind(declaration)
+        else {
+            return Err("counter is not a variable");
+        };
+        if !self.dataflow.is_tracked(counter) {
+            return Err("counter is used in a nested function");
+        }
+        let init = declarator
+            .init
+            .as_ref()
+            .ok_or("counter has no initial value")?;
+        if self.constants.value(init, declaration) != Some(0.0) {
+            return Err("counter does not start at 0");
+        }
+
+        // the update is the only write to the counter, and adds exactly 1
+        let update = match &for_.update {
+            Some(Expression::UpdateExpression(update))
+                if update.operator == UpdateOperator::Increment =>
+            {
+                match &update.argument {
+                    SimpleAssignmentTarget::AssignmentTargetIdentifier(id) => id.reference_id.get(),
+                    _ => None,
+                }
+            }
+            Some(Expression::AssignmentExpression(assign))
+                if assign.operator == AssignmentOperator::Addition
+                    && matches!(&assign.right, Expression::NumericLiteral(one) if one.value == 1.0) =>
+            {
+                match &assign.left {
+                    AssignmentTarget::AssignmentTargetIdentifier(id) => id.reference_id.get(),
+                    _ => None,
+                }
+            }
+            _ => None,
         }
+        .ok_or("loop update is not counter++")?;
+        if symbols.get_reference(update).symbol_id() != Some(counter) {
+            return Err("loop update is not counter++");
+        }
+        if symbols
+            .get_resolved_reference_ids(counter)
+            .iter()
+            .any(|reference| *reference != update && symbols.get_reference(*reference).is_write())
+        {
+            return Err("counter is written outside the loop update");
+        }
+
+        let for_node = self
+            .semantic
+            .nodes()
+            .ancestors(declaration)
+            .find(|node| matches!(node.kind(), AstKind::ForStatement(_)))
+            .ok_or("counter is not declared by the loop")?
+            .id();
+        let inside = |node: NodeId| {
+            self.semantic
+                .nodes()
+                .ancestor_ids(node)
+                .any(|id| id == for_node)
+        };
+
+        let Statement::BlockStatement(body) = &for_.body else {
+            return Err("loop body is not a block");
+        };
+        let denominator = match denominator {
+            Denominator::Variable(symbol) => symbol,
+            Denominator::Literal(value) if value.fract() == 0.0 && value >= 1.0 => return Ok(()),
+            Denominator::Literal(_) => {
+                return Err("denominator is not known to be a positive integer")
+            }
+        };
+
+        // the reset at the end of the body names the denominator again
+        let name = symbols.get_name(denominator);
+        if self.semantic.scopes().find_binding(body.scope_id(), name) != Some(denominator) {
+            return Err("denominator is shadowed in the loop body");
+        }
+
+        for reference in modulos.iter().filter_map(|(_, reference)| *reference) {
+            let node = symbols.get_reference(reference).node_id();
+            let definitions = self
+                .reaching
+                .reaching(denominator, node)
+                .ok_or("denominator is used in a nested function")?;
+            if definitions
+                .iter()
+                .any(|id| inside(self.dataflow.definition(*id).node))
+            {
+                return Err("denominator changes inside the loop");
+            }
+
+            let range = self.ranges.symbol(denominator, node);
+            if range.nan || !range.integer || range.min < 1.0 {
+                return Err("denominator is not known to be a positive integer");
+            }
+        }
+
+        Ok(())
     }
 }
 
-impl<'a> VisitMut<'a> for RunningModuloOptimization<'a> {
+impl<'a> VisitMut<'a> for RunningModuloOptimization<'_, 'a> {
     fn visit_for_statement(&mut self, for_: &mut ForStatement<'a>) {
         let mut simple_incr_variable: Option<SymbolId> = None;
-        if let Some(init) = &for_.init {
-            if let ForStatementInit::VariableDeclaration(var) = init {
-                if let Some(init) = &var.declarations.first() {
-                    if let BindingPatternKind::BindingIdentifier(x) = &init.id.kind {
-                        if let Some(symbol_id) = x.symbol_id.get() {
-                            simple_incr_variable = Some(symbol_id.clone());
-                        }
-                    }
+        if let Some(ForStatementInit::VariableDeclaration(var)) = &for_.init {
+            if let Some(init) = &var.declarations.first() {
+                if let BindingPatternKind::BindingIdentifier(x) = &init.id.kind {
+                    simple_incr_variable = x.symbol_id.get();
                 }
-            };
+            }
         }
 
         if let Some(simple_incr_variable) = simple_incr_variable {
-            let mut find_modulo_var =
-                FindModuloVar::new(self.semantic.symbols().get_name(simple_incr_variable));
+            let mut find_modulo_var = FindModuloVar::new(self.semantic, simple_incr_variable);
             find_modulo_var.visit_for_statement(for_);
 
-            if let Some(denominator) = find_modulo_var.denominator {
-                let modulo_var_name = format!(
+            let modulos = find_modulo_var
+                .denominator
+                .map(|denominator| (denominator, find_modulo_var.modulos(denominator)));
+            let legal = match &modulos {
+                // the running value is bumped at the end of the body, which `continue` skips
+                Some(_) if find_modulo_var.continues => Err("loop body contains continue"),
+                Some((denominator, modulos)) => {
+                    self.check(for_, simple_incr_variable, *denominator, modulos)
+                }
+                None => Err("no modulo of the counter"),
+            };
+            if let (Some((denominator, _)), Err(reason)) = (&modulos, legal) {
+                self.remarks.push(Remark::missed(
+                    for_.span,
+                    format!(
+                        "`{} % {}` kept: {reason}",
+                        self.semantic.symbols().get_name(simple_incr_variable),
+                        self.describe(*denominator)
+                    ),
+                ));
+            }
+            if let (Some((denominator, modulos)), Ok(())) = (modulos, legal) {
+                let modulo_var_name = self.names.fresh(&format!(
                     "{}_modulo",
                     self.semantic.symbols().get_name(simple_incr_variable)
-                );
-
-                self.replacements.insert(
-                    (
-                        self.semantic
-                            .symbols()
-                            .get_name(simple_incr_variable)
-                            .to_string(),
-                        denominator.clone(),
+                ));
+                self.remarks.push(Remark::applied(
+                    for_.span,
+                    format!(
+                        "`{} % {}` replaced by the running `{modulo_var_name}`",
+                        self.semantic.symbols().get_name(simple_incr_variable),
+                        self.describe(denominator)
                     ),
-                    modulo_var_name.clone(),
-                );
+                ));
+
+                for (numerator, _) in modulos {
+                    self.replacements.insert(numerator, modulo_var_name.clone());
+                }
 
                 if let Some(ForStatementInit::VariableDeclaration(init)) = &mut for_.init {
                     init.declarations.push(
@@ -113,7 +269,8 @@ impl<'a> VisitMut<'a> for RunningModuloOptimization<'a> {
                         ),
                     ));
 
-                    // if (modulo_var_name == n) modulo_var_name = 0;
+                    // if (modulo_var_name >= n) modulo_var_name = 0;
+                    // `>=` rather than `==` lets range analysis bound it by `n` on the false edge
                     block.body.push(Statement::IfStatement(
                         self.ast.alloc_if_statement(
                             SPAN,
@@ -126,11 +283,23 @@ impl<'a> VisitMut<'a> for RunningModuloOptimization<'a> {
                                             modulo_var_name.clone(),
                                         ),
                                     ),
-                                    BinaryOperator::Equality,
-                                    Expression::Identifier(
-                                        self.ast
-                                            .alloc_identifier_reference(SPAN, denominator.clone()),
-                                    ),
+                                    BinaryOperator::GreaterEqualThan,
+                                    match denominator {
+                                        Denominator::Variable(symbol) => Expression::Identifier(
+                                            self.ast.alloc_identifier_reference(
+                                                SPAN,
+                                                self.semantic.symbols().get_name(symbol),
+                                            ),
+                                        ),
+                                        Denominator::Literal(value) => Expression::NumericLiteral(
+                                            self.ast.alloc_numeric_literal(
+                                                SPAN,
+                                                value,
+                                                None,
+                                                NumberBase::Decimal,
+                                            ),
+                                        ),
+                                    },
                                 ),
                             ),
                             Statement::ExpressionStatement(self.ast.alloc_expression_statement(
@@ -168,16 +337,15 @@ impl<'a> VisitMut<'a> for RunningModuloOptimization<'a> {
         if let Expression::BinaryExpression(expr) = expr_base {
             if let BinaryOperator::Remainder = expr.operator {
                 if let Expression::Identifier(left) = &expr.left {
-                    if let Expression::Identifier(right) = &expr.right {
-                        if let Some(replacement) = self.replacements.get(&(
-                            left.name.as_str().to_string(),
-                            right.name.as_str().to_string(),
-                        )) {
-                            *expr_base = Expression::Identifier(
-                                self.ast
-                                    .alloc_identifier_reference(SPAN, replacement.clone()),
-                            );
-                        }
+                    let replacement = left
+                        .reference_id
+                        .get()
+                        .and_then(|reference| self.replacements.get(&reference));
+                    if let Some(replacement) = replacement {
+                        *expr_base = Expression::Identifier(
+                            self.ast
+                                .alloc_identifier_reference(SPAN, replacement.clone()),
+                        );
                     }
                 }
             }
@@ -187,39 +355,91 @@ impl<'a> VisitMut<'a> for RunningModuloOptimization<'a> {
     }
 }
 
-struct FindModuloVar {
-    numerator: String,
-    denominator: Option<String>,
+/// The right side of `i % n`: a variable, or a literal once constants are folded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Denominator {
+    Variable(SymbolId),
+    Literal(f64),
+}
+
+struct FindModuloVar<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    numerator: SymbolId,
+    denominator: Option<Denominator>,
+    /// Every `numerator % x`, as the references of both sides (none for a literal `x`) and `x`.
+    found: Vec<(ReferenceId, Option<ReferenceId>, Denominator)>,
+    continues: bool,
 }
 
-impl FindModuloVar {
-    fn new(numerator: &str) -> Self {
+impl<'s, 'a> FindModuloVar<'s, 'a> {
+    fn new(semantic: &'s Semantic<'a>, numerator: SymbolId) -> Self {
         Self {
-            numerator: numerator.to_string(),
+            semantic,
+            numerator,
             denominator: None,
+            found: Vec::new(),
+            continues: false,
         }
     }
+
+    fn symbol(&self, id: &IdentifierReference) -> Option<(ReferenceId, SymbolId)> {
+        let reference = id.reference_id.get()?;
+        let symbol = self
+            .semantic
+            .symbols()
+            .get_reference(reference)
+            .symbol_id()?;
+        Some((reference, symbol))
+    }
+
+    /// The references of every `numerator % denominator` that would be rewritten.
+    fn modulos(&self, denominator: Denominator) -> Vec<(ReferenceId, Option<ReferenceId>)> {
+        self.found
+            .iter()
+            .filter(|(_, _, found)| *found == denominator)
+            .map(|(left, right, _)| (*left, *right))
+            .collect()
+    }
 }
 
-impl<'a> Visit<'a> for FindModuloVar {
+impl<'a> Visit<'a> for FindModuloVar<'_, 'a> {
     fn visit_binary_expression(&mut self, expr: &BinaryExpression<'a>) {
         if let BinaryOperator::Remainder = expr.operator {
-            let mut good = false;
+            let mut good = None;
             if let Expression::Identifier(id) = &expr.left {
-                if id.name == self.numerator {
-                    good = true;
-                }
+                good = self
+                    .symbol(id)
+                    .filter(|(_, symbol)| *symbol == self.numerator);
             }
 
-            if !good {
+            let Some((numerator, _)) = good else {
                 return;
-            }
-
-            if let Expression::Identifier(id) = &expr.right {
-                self.denominator = Some(id.name.as_str().to_string());
             };
+
+            match &expr.right {
+                Expression::Identifier(id) => {
+                    if let Some((reference, symbol)) = self.symbol(id) {
+                        self.denominator = Some(Denominator::Variable(symbol));
+                        self.found.push((
+                            numerator,
+                            Some(reference),
+                            Denominator::Variable(symbol),
+                        ));
+                    }
+                }
+                Expression::NumericLiteral(literal) => {
+                    self.denominator = Some(Denominator::Literal(literal.value));
+                    self.found
+                        .push((numerator, None, Denominator::Literal(literal.value)));
+                }
+                _ => {}
+            }
         }
 
         walk::walk_binary_expression(self, expr);
     }
+
+    fn visit_continue_statement(&mut self, _: &ContinueStatement<'a>) {
+        self.continues = true;
+    }
 }
@@ -0,0 +1,314 @@
+use std::collections::{HashMap, HashSet};
+
+use oxc::{
+    allocator::Allocator,
+    ast::{
+        ast::{
+            ArrayExpressionElement, BindingPatternKind, Expression, MemberExpression, NumberBase,
+            ObjectPropertyKind, PropertyKind, SimpleAssignmentTarget, TSTypeAnnotation,
+            VariableDeclaration, VariableDeclarationKind,
+        },
+        visit::walk_mut,
+        AstBuilder, AstKind, VisitMut,
+    },
+    semantic::{Semantic, SymbolId},
+    span::{GetSpan, SPAN},
+};
+
+use crate::{
+    dataflow::{ConstantPropagation, Dataflow},
+    escape_analysis,
+    fresh_names::FreshNames,
+    overflow_analysis::NodeAddress,
+    remarks::Remark,
+};
+
+/// Replaces an object literal or an array literal held by a variable that is only used to
+/// read and write its fields, according to [`escape_analysis::members`], with one variable
+/// per field:
+///
+/// ```js
+/// const p = { x: a, y: b };   const v = [1, 2];
+/// s += p.x * p.y + v[1];
+/// // becomes
+/// const p_x = a, p_y = b;     const v_0 = 1, v_1 = 2;
+/// s += p_x * p_y + v_1;
+/// ```
+///
+/// Every field read must be one the literal has, and arrays must be indexed with constants in
+/// bounds, so that no read gives `undefined`.
+pub struct ScalarReplacementOptimization<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    ast: AstBuilder<'a>,
+    /// The variable of each field of a replaced literal, in source order.
+    fields: HashMap<SymbolId, Vec<String>>,
+    /// Replaced literals that have a field written.
+    written: HashSet<SymbolId>,
+    /// What each member expression of a replaced literal becomes.
+    members: HashMap<NodeAddress, Replacement>,
+    pub remarks: Vec<Remark>,
+}
+
+enum Replacement {
+    Field(String),
+    /// The `length` of an array literal.
+    Length(usize),
+}
+
+impl<'s, 'a> ScalarReplacementOptimization<'s, 'a> {
+    pub fn new(dataflow: &'s Dataflow<'s, 'a>, allocator: &'a Allocator) -> Self {
+        let mut pass = Self {
+            semantic: dataflow.semantic(),
+            ast: AstBuilder::new(allocator),
+            fields: HashMap::new(),
+            written: HashSet::new(),
+            members: HashMap::new(),
+            remarks: vec![],
+        };
+        let constants = dataflow.solve(ConstantPropagation);
+        let mut names = FreshNames::new(pass.semantic);
+        for node in pass.semantic.nodes().iter() {
+            let AstKind::VariableDeclarator(declarator) = node.kind() else {
+                continue;
+            };
+            let BindingPatternKind::BindingIdentifier(id) = &declarator.id.kind else {
+                continue;
+            };
+            let (Some(symbol), Some(init)) = (id.symbol_id.get(), &declarator.init) else {
+                continue;
+            };
+            let keys = match init {
+                Expression::ObjectExpression(object) => keys(object.properties.iter()),
+                Expression::ArrayExpression(array) => array
+                    .elements
+                    .iter()
+                    .all(ArrayExpressionElement::is_expression)
+                    .then(|| (0..array.elements.len()).map(|i| i.to_string()).collect())
+                    .ok_or("it has a spread or a hole"),
+                _ => continue,
+            };
+            let array = matches!(init, Expression::ArrayExpression(_));
+
+            let members = keys.and_then(|keys| {
+                let members = escape_analysis::members(pass.semantic, dataflow, symbol)?;
+                if keys.is_empty() {
+                    return Err("it has no fields");
+                }
+                let mut found = vec![];
+                for (member, access) in members {
+                    let assigned = matches!(
+                        pass.semantic.nodes().parent_kind(member),
+                        Some(AstKind::SimpleAssignmentTarget(_) | AstKind::AssignmentTarget(_))
+                    );
+                    let key = match access {
+                        MemberExpression::StaticMemberExpression(field) if !array => {
+                            Some(field.property.name.to_string())
+                        }
+                        MemberExpression::StaticMemberExpression(field)
+                            if field.property.name == "length" =>
+                        {
+                            if assigned {
+                                return Err("its length is assigned");
+                            }
+                            found.push((NodeAddress::of(&**field), None));
+                            continue;
+                        }
+                        MemberExpression::ComputedMemberExpression(element) if array => constants
+                            .value(&element.expression, member)
+                            .filter(|index| index.fract() == 0.0 && *index >= 0.0)
+                            .map(|index| (index as usize).to_string()),
+                        _ => None,
+                    };
+                    let Some(key) = key.filter(|key| keys.contains(key)) else {
+                        return Err(if array {
+                            "it is indexed with something other than a constant in bounds"
+                        } else {
+                            "a field is read that it does not have"
+                        });
+                    };
+                    let address = match access {
+                        MemberExpression::StaticMemberExpression(field) => {
+                            NodeAddress::of(&**field)
+                        }
+                        MemberExpression::ComputedMemberExpression(element) => {
+                            NodeAddress::of(&**element)
+                        }
+                        MemberExpression::PrivateFieldExpression(_) => unreachable!(),
+                    };
+                    if assigned {
+                        pass.written.insert(symbol);
+                    }
+                    found.push((address, Some(key)));
+                }
+                Ok((keys, found))
+            });
+
+            let source = id.name.as_str();
+            match members {
+                Ok((keys, found)) => {
+                    let variables: Vec<_> = keys
+                        .iter()
+                        .map(|key| names.fresh(&format!("{source}_{key}")))
+                        .collect();
+                    for (address, key) in found {
+                        let replacement = match key {
+                            Some(key) => {
+                                let field = keys.iter().position(|other| *other == key).unwrap();
+                                Replacement::Field(variables[field].clone())
+                            }
+                            None => Replacement::Length(keys.len()),
+                        };
+                        pass.members.insert(address, replacement);
+                    }
+                    pass.remarks.push(Remark::applied(
+                        declarator.span,
+                        format!(
+                            "`{source}` replaced by {} local{}",
+                            variables.len(),
+                            if variables.len() == 1 { "" } else { "s" }
+                        ),
+                    ));
+                    pass.fields.insert(symbol, variables);
+                }
+                Err(reason) => {
+                    pass.remarks.push(Remark::missed(
+                        declarator.span,
+                        format!("`{source}` kept: {reason}"),
+                    ));
+                }
+            }
+        }
+        pass
+    }
+}
+
+/// The names of the fields an object literal defines, when it only has `key: value` fields
+/// with static and distinct names.
+fn keys<'e, 'a: 'e>(
+    properties: impl Iterator<Item = &'e ObjectPropertyKind<'a>>,
+) -> Result<Vec<String>, &'static str> {
+    let mut keys = vec![];
+    for property in properties {
+        let ObjectPropertyKind::ObjectProperty(property) = property else {
+            return Err("it has a spread");
+        };
+        if property.kind != PropertyKind::Init || property.method {
+            return Err("it has a method or an accessor");
+        }
+        let key = match property.key.static_name() {
+            Some(key) if !property.computed => key.to_string(),
+            _ => return Err("it has a computed key"),
+        };
+        if keys.contains(&key) {
+            return Err("it defines a field twice");
+        }
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+impl<'a> ScalarReplacementOptimization<'_, 'a> {
+    fn replacement(&self, member: &MemberExpression) -> Option<&Replacement> {
+        let address = match member {
+            MemberExpression::StaticMemberExpression(field) => NodeAddress::of(&**field),
+            MemberExpression::ComputedMemberExpression(element) => NodeAddress::of(&**element),
+            MemberExpression::PrivateFieldExpression(_) => return None,
+        };
+        self.members.get(&address)
+    }
+}
+
+impl<'a> VisitMut<'a> for ScalarReplacementOptimization<'_, 'a> {
+    fn visit_variable_declaration(&mut self, declaration: &mut VariableDeclaration<'a>) {
+        let mut declarators = self.ast.vec();
+        for mut declarator in declaration.declarations.drain(..) {
+            let symbol = match &declarator.id.kind {
+                BindingPatternKind::BindingIdentifier(id) => id.symbol_id.get(),
+                _ => None,
+            };
+            let Some(variables) = symbol.and_then(|symbol| self.fields.get(&symbol)) else {
+                declarators.push(declarator);
+                continue;
+            };
+            if self.written.contains(&symbol.unwrap())
+                && declaration.kind == VariableDeclarationKind::Const
+            {
+                declaration.kind = VariableDeclarationKind::Let;
+            }
+            let mut values = vec![];
+            match declarator.init.as_mut() {
+                Some(Expression::ObjectExpression(object)) => {
+                    for property in object.properties.iter_mut() {
+                        if let ObjectPropertyKind::ObjectProperty(property) = property {
+                            values.push(self.ast.move_expression(&mut property.value));
+                        }
+                    }
+                }
+                Some(Expression::ArrayExpression(array)) => {
+                    for element in array.elements.iter_mut() {
+                        let element = element.to_expression_mut();
+                        values.push(self.ast.move_expression(element));
+                    }
+                }
+                _ => unreachable!(),
+            }
+            for (name, value) in variables.iter().zip(values) {
+                declarators.push(
+                    self.ast.variable_declarator(
+                        value.span(),
+                        declaration.kind,
+                        self.ast.binding_pattern(
+                            self.ast
+                                .binding_pattern_kind_binding_identifier(SPAN, name.as_str()),
+                            None::<TSTypeAnnotation>,
+                            false,
+                        ),
+                        Some(value),
+                        false,
+                    ),
+                );
+            }
+        }
+        for declarator in declarators.iter_mut() {
+            declarator.kind = declaration.kind;
+        }
+        declaration.declarations = declarators;
+        walk_mut::walk_variable_declaration(self, declaration);
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'a>) {
+        let replacement = expr
+            .as_member_expression()
+            .and_then(|member| self.replacement(member));
+        match replacement {
+            Some(Replacement::Field(name)) => {
+                *expr = self
+                    .ast
+                    .expression_identifier_reference(expr.span(), name.as_str());
+            }
+            Some(Replacement::Length(length)) => {
+                let length = *length;
+                *expr = self.ast.expression_numeric_literal(
+                    expr.span(),
+                    length as f64,
+                    None,
+                    NumberBase::Decimal,
+                );
+            }
+            None => walk_mut::walk_expression(self, expr),
+        }
+    }
+
+    fn visit_simple_assignment_target(&mut self, target: &mut SimpleAssignmentTarget<'a>) {
+        let replacement = target
+            .as_member_expression()
+            .and_then(|member| self.replacement(member));
+        if let Some(Replacement::Field(name)) = replacement {
+            *target = self
+                .ast
+                .simple_assignment_target_identifier_reference(target.span(), name.as_str());
+            return;
+        }
+        walk_mut::walk_simple_assignment_target(self, target);
+    }
+}
@@ -1,174 +1,508 @@
+use std::collections::HashMap;
+
 use oxc::{
     allocator::Allocator,
     ast::{
         ast::{
-            BinaryOperator, Expression, ForStatement, ForStatementInit, MemberExpression, Program,
+            AssignmentOperator, AssignmentTarget, BinaryOperator, BindingPatternKind, Expression,
+            ForStatement, ForStatementInit, LogicalOperator, MemberExpression, NumberBase, Program,
+            SimpleAssignmentTarget, Statement, UpdateOperator, VariableDeclarationKind,
         },
-        visit::{walk, walk_mut},
-        AstBuilder, Visit, VisitMut,
+        visit::walk_mut,
+        AstBuilder, AstKind, VisitMut,
     },
-    span::SPAN,
+    semantic::{NodeId, ReferenceId, Semantic, SymbolId},
+    span::{GetSpan, SPAN},
+};
+
+use crate::{
+    dataflow::{Dataflow, RangeAnalysis, ReachingDefinitions, Results},
+    purity::is_pure,
+    remarks::Remark,
 };
 
-pub struct SlottedArrayReadOptimization<'a> {
+/// A loop that fills a typed array of which only one element is read afterwards only needs
+/// to run the iteration that writes that element:
+///
+/// ```js
+/// for (let i = 0; i < m; i++) a[i + 1] = f(i);   // and later only a[r] is read
+/// for (let i = r - 1; i == r - 1 && i >= 0 && i < m; i++) a[i + 1] = f(i);
+/// ```
+pub struct SlottedArrayReadOptimization<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    dataflow: &'s Dataflow<'s, 'a>,
+    ranges: Results<'s, 's, 'a, RangeAnalysis<'s>>,
+    reaching: Results<'s, 's, 'a, ReachingDefinitions>,
     ast: AstBuilder<'a>,
-    replacements: Vec<SlottedReadCandidate>,
+    /// Fill loops to shrink, by the symbol of their counter.
+    rewrites: HashMap<SymbolId, Rewrite>,
+    pub remarks: Vec<Remark>,
+}
+
+/// An index of the form `base + offset`; constant indices have no base.
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    base: Option<(SymbolId, ReferenceId)>,
+    offset: f64,
+}
+
+impl Slot {
+    fn same(&self, other: &Slot) -> bool {
+        self.base.map(|(symbol, _)| symbol) == other.base.map(|(symbol, _)| symbol)
+            && self.offset == other.offset
+    }
+}
+
+/// The only iteration a fill loop still has to run: the one where the counter is `target`.
+struct Rewrite {
+    array: SymbolId,
+    target: Slot,
+    /// Reused by the counter identifiers the rewrite builds.
+    counter: ReferenceId,
 }
 
-impl<'a> SlottedArrayReadOptimization<'a> {
-    pub fn new(allocator: &'a Allocator) -> Self {
+impl<'s, 'a> SlottedArrayReadOptimization<'s, 'a> {
+    pub fn new(dataflow: &'s Dataflow<'s, 'a>, allocator: &'a Allocator) -> Self {
         let ast = AstBuilder::new(allocator);
         Self {
+            semantic: dataflow.semantic(),
+            dataflow,
+            ranges: dataflow.solve(RangeAnalysis::default()),
+            reaching: dataflow.solve(ReachingDefinitions),
             ast,
-            replacements: Vec::new(),
+            rewrites: HashMap::new(),
+            remarks: vec![],
         }
     }
-}
 
-impl<'a> VisitMut<'a> for SlottedArrayReadOptimization<'a> {
-    fn visit_program(&mut self, program: &mut Program<'a>) {
-        let mut find_slotted_read = FindSlottedRead::new();
-        find_slotted_read.visit_program(program);
+    /// Whether `array` is bound to `new Int32Array(n)` or `new Float64Array(n)`.
+    fn is_typed_array(&self, array: SymbolId) -> bool {
+        let declaration = self.semantic.symbols().get_declaration(array);
+        let AstKind::VariableDeclarator(declarator) = self.semantic.nodes().kind(declaration)
+        else {
+            return false;
+        };
+        let Some(Expression::NewExpression(new)) = &declarator.init else {
+            return false;
+        };
+        // a local class named Int32Array would resolve to a symbol
+        matches!(&new.callee, Expression::Identifier(callee)
+            if matches!(callee.name.as_str(), "Int32Array" | "Float64Array")
+                && self.reference_symbol(&new.callee).is_none())
+    }
 
-        //println!("candidates: {:?}", find_slotted_read.candidates);
+    /// Proves that every use of `array` indexes it, that one loop fills it and that every
+    /// read afterwards hits the same element. Returns the loop counter and the rewrite.
+    fn analyze(&self, array: SymbolId) -> Result<(SymbolId, Rewrite), &'static str> {
+        let symbols = self.semantic.symbols();
+        let nodes = self.semantic.nodes();
 
-        self.replacements = find_slotted_read
-            .candidates
-            .iter_mut()
-            .filter(|candidate| candidate.valid)
-            .map(|candidate| candidate.clone())
-            .collect();
+        let mut write = None;
+        let mut reads = vec![];
+        for reference_id in symbols.get_resolved_reference_ids(array) {
+            let reference = symbols.get_reference(*reference_id);
+            if reference.is_write() {
+                return Err("array variable is reassigned");
+            }
 
-        walk_mut::walk_program(self, program);
-    }
+            // the array is only ever indexed, so no alias can write it behind our back
+            let member = nodes
+                .parent_id(reference.node_id())
+                .ok_or("array escapes")?;
+            let AstKind::MemberExpression(MemberExpression::ComputedMemberExpression(access)) =
+                nodes.kind(member)
+            else {
+                return Err("array escapes");
+            };
+            if !matches!(&access.object, Expression::Identifier(id) if id.reference_id.get() == Some(*reference_id))
+            {
+                return Err("array escapes");
+            }
 
-    fn visit_for_statement(&mut self, for_: &mut ForStatement<'a>) {
-        let mut loop_var = None;
-        if let Some(init) = &for_.init {
-            if let ForStatementInit::VariableDeclaration(var) = init {
-                if let Some(init) = &var.declarations.first() {
-                    if let oxc::ast::ast::BindingPatternKind::BindingIdentifier(x) = &init.id.kind {
-                        loop_var = Some(x.name.to_string());
-                    }
-                }
+            let is_target = |id| {
+                matches!(
+                    nodes.kind(id),
+                    AstKind::SimpleAssignmentTarget(_) | AstKind::AssignmentTarget(_)
+                )
             };
+            let mut parent = nodes.parent_id(member).ok_or("array escapes")?;
+            if !is_target(parent) {
+                let slot = self
+                    .slot(&access.expression)
+                    .ok_or("read index is not a variable plus a constant")?;
+                reads.push((slot, reference.node_id()));
+                continue;
+            }
+
+            while is_target(parent) {
+                parent = nodes.parent_id(parent).ok_or("array escapes")?;
+            }
+            match nodes.kind(parent) {
+                AstKind::AssignmentExpression(assignment)
+                    if assignment.operator == AssignmentOperator::Assign => {}
+                _ => return Err("array element is updated in place"),
+            }
+            if write.replace(parent).is_some() {
+                return Err("array is written in more than one place");
+            }
         }
 
-        if let Some(loop_var) = loop_var {
-            let c = self
-                .replacements
-                .iter_mut()
-                .find(|candidate| candidate.loop_index == loop_var);
+        let write = write.ok_or("array is never written")?;
+        let (for_, for_node) = self.fill_loop(write)?;
+        let (counter, written) = self.check_loop(for_, for_node, write)?;
 
-            if let Some(c) = c {
-                if let Some(ForStatementInit::VariableDeclaration(var)) = &mut for_.init {
-                    var.declarations.first_mut().unwrap().init = Some(Expression::Identifier(
-                        self.ast
-                            .alloc_identifier_reference(SPAN, c.read_index.clone().unwrap()),
-                    ));
-                }
+        let (read, _) = *reads.first().ok_or("array is never read")?;
+        for (slot, node) in &reads {
+            if !slot.same(&read) {
+                return Err("reads hit different elements");
+            }
+            if nodes.ancestor_ids(*node).any(|id| id == for_node) {
+                return Err("array is read inside the fill loop");
+            }
+        }
 
-                for_.test = Some(Expression::BinaryExpression(
-                    self.ast.alloc_binary_expression(
-                        SPAN,
-                        Expression::Identifier(
-                            self.ast.alloc_identifier_reference(SPAN, loop_var.clone()),
-                        ),
-                        BinaryOperator::Inequality,
-                        Expression::Identifier(
-                            self.ast
-                                .alloc_identifier_reference(SPAN, c.read_index.clone().unwrap()),
-                        ),
-                    ),
-                ));
+        // the read index holds the same integer from before the fill loop until every read
+        if let Some((base, _)) = read.base {
+            let [definition] = self.dataflow.definitions_of(base) else {
+                return Err("read index is assigned more than once");
+            };
+            for node in reads.iter().map(|(_, node)| *node).chain([for_node]) {
+                if self.reaching.reaching(base, node) != Some(vec![*definition]) {
+                    return Err("read index is not set before the fill loop");
+                }
+            }
+            if !self.ranges.symbol(base, for_node).integer {
+                return Err("read index is not known to be an integer");
             }
         }
 
-        walk_mut::walk_for_statement(self, for_);
+        let Some(Expression::BinaryExpression(test)) = &for_.test else {
+            unreachable!();
+        };
+        let Expression::Identifier(test_counter) = &test.left else {
+            unreachable!();
+        };
+        let rewrite = Rewrite {
+            array,
+            target: Slot {
+                base: read.base,
+                offset: read.offset - written,
+            },
+            counter: test_counter
+                .reference_id
+                .get()
+                .ok_or("counter is unresolved")?,
+        };
+        Ok((counter, rewrite))
     }
-}
 
-#[derive(Debug, Clone)]
-struct SlottedReadCandidate {
-    array: String,
-    loop_index: String,
-    read_index: Option<String>,
-    valid: bool,
-}
+    /// The top-level `for` statement whose body holds the assignment `write`.
+    fn fill_loop(&self, write: NodeId) -> Result<(&'a ForStatement<'a>, NodeId), &'static str> {
+        let nodes = self.semantic.nodes();
+        let statement = nodes
+            .parent_id(write)
+            .ok_or("array is written outside a loop")?;
+        if !matches!(nodes.kind(statement), AstKind::ExpressionStatement(_)) {
+            return Err("array is written inside an expression");
+        }
+        let mut parent = nodes
+            .parent_id(statement)
+            .ok_or("array is written outside a loop")?;
+        if matches!(nodes.kind(parent), AstKind::BlockStatement(_)) {
+            parent = nodes
+                .parent_id(parent)
+                .ok_or("array is written outside a loop")?;
+        }
+        let AstKind::ForStatement(for_) = nodes.kind(parent) else {
+            return Err("array is written outside a for loop");
+        };
+        // so the loop runs once, after every read index is set
+        if !matches!(nodes.parent_kind(parent), Some(AstKind::Program(_))) {
+            return Err("fill loop is nested");
+        }
+        Ok((for_, parent))
+    }
 
-struct FindSlottedRead {
-    current_loop_vars: Vec<String>,
-    candidates: Vec<SlottedReadCandidate>,
-}
+    /// `for (let i = start; i < end; i++) { const t = ...; a[i + c] = value; }` where the
+    /// skipped iterations have no effect besides writing their own element. Returns the
+    /// counter and `c`.
+    fn check_loop(
+        &self,
+        for_: &ForStatement<'a>,
+        for_node: NodeId,
+        write: NodeId,
+    ) -> Result<(SymbolId, f64), &'static str> {
+        let Some(ForStatementInit::VariableDeclaration(init)) = &for_.init else {
+            return Err("loop does not declare its counter");
+        };
+        // a `var` counter would still be visible, with a different value, after the loop
+        if init.kind != VariableDeclarationKind::Let {
+            return Err("counter is not declared with let");
+        }
+        let [declarator] = init.declarations.as_slice() else {
+            return Err("loop declares more than its counter");
+        };
+        let BindingPatternKind::BindingIdentifier(counter) = &declarator.id.kind else {
+            return Err("loop does not declare its counter");
+        };
+        let counter = counter.symbol_id.get().ok_or("counter is unresolved")?;
+        let start = declarator
+            .init
+            .as_ref()
+            .ok_or("counter has no initial value")?;
+        if !is_pure(self.semantic, start) || !self.ranges.range(start, for_node).integer {
+            return Err("counter does not start at an integer");
+        }
 
-impl FindSlottedRead {
-    fn new() -> Self {
-        Self {
-            current_loop_vars: vec![],
-            candidates: vec![],
+        match &for_.test {
+            Some(Expression::BinaryExpression(test))
+                if matches!(
+                    test.operator,
+                    BinaryOperator::LessThan | BinaryOperator::LessEqualThan
+                ) && self.reference_symbol(&test.left) == Some(counter)
+                    && is_pure(self.semantic, &test.right) => {}
+            _ => return Err("loop test is not counter < end"),
         }
-    }
-}
 
-impl<'a> Visit<'a> for FindSlottedRead {
-    fn visit_for_statement(&mut self, for_: &ForStatement<'a>) {
-        let mut loop_var = None;
-        if let Some(init) = &for_.init {
-            if let ForStatementInit::VariableDeclaration(var) = init {
-                if let Some(init) = &var.declarations.first() {
-                    if let oxc::ast::ast::BindingPatternKind::BindingIdentifier(x) = &init.id.kind {
-                        loop_var = Some(x.name.to_string());
-                    }
+        let updated = match &for_.update {
+            Some(Expression::UpdateExpression(update))
+                if update.operator == UpdateOperator::Increment =>
+            {
+                match &update.argument {
+                    SimpleAssignmentTarget::AssignmentTargetIdentifier(id) => Some(id),
+                    _ => None,
                 }
+            }
+            Some(Expression::AssignmentExpression(assign))
+                if assign.operator == AssignmentOperator::Addition
+                    && matches!(&assign.right, Expression::NumericLiteral(one) if one.value == 1.0) =>
+            {
+                match &assign.left {
+                    AssignmentTarget::AssignmentTargetIdentifier(id) => Some(id),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let updated = updated.and_then(|id| {
+            self.semantic
+                .symbols()
+                .get_reference(id.reference_id.get()?)
+                .symbol_id()
+        });
+        if updated != Some(counter) {
+            return Err("loop update is not counter++");
+        }
+
+        let AstKind::AssignmentExpression(assignment) = self.semantic.nodes().kind(write) else {
+            unreachable!();
+        };
+        let body = match &for_.body {
+            Statement::BlockStatement(block) => block.body.iter().collect(),
+            statement => vec![statement],
+        };
+        for statement in body {
+            match statement {
+                Statement::ExpressionStatement(statement) if matches!(&statement.expression, Expression::AssignmentExpression(expr) if std::ptr::eq(&**expr, assignment)) =>
+                    {}
+                Statement::VariableDeclaration(var)
+                    if var.kind != VariableDeclarationKind::Var
+                        && var.declarations.iter().all(|declarator| {
+                            matches!(declarator.id.kind, BindingPatternKind::BindingIdentifier(_))
+                                && declarator
+                                    .init
+                                    .as_ref()
+                                    .is_some_and(|init| is_pure(self.semantic, init))
+                        }) => {}
+                _ => return Err("loop body does more than fill the array"),
+            }
+        }
+
+        let AssignmentTarget::ComputedMemberExpression(target) = &assignment.left else {
+            unreachable!();
+        };
+        if !is_pure(self.semantic, &assignment.right) {
+            return Err("stored value has side effects");
+        }
+        match self.slot(&target.expression) {
+            Some(Slot {
+                base: Some((base, _)),
+                offset,
+            }) if base == counter => Ok((counter, offset)),
+            _ => Err("write index is not the counter plus a constant"),
+        }
+    }
+
+    /// `x`, `x + c`, `c + x`, `x - c` or `c`, for an integer constant `c`.
+    fn slot(&self, index: &Expression) -> Option<Slot> {
+        let base = |expr: &Expression| {
+            let Expression::Identifier(id) = expr else {
+                return None;
             };
+            Some((self.reference_symbol(expr)?, id.reference_id.get()?))
+        };
+        let constant = |expr: &Expression| match expr {
+            Expression::NumericLiteral(literal) if literal.value.fract() == 0.0 => {
+                Some(literal.value)
+            }
+            _ => None,
+        };
+
+        match index {
+            Expression::ParenthesizedExpression(expr) => self.slot(&expr.expression),
+            Expression::NumericLiteral(_) => Some(Slot {
+                base: None,
+                offset: constant(index)?,
+            }),
+            Expression::Identifier(_) => Some(Slot {
+                base: Some(base(index)?),
+                offset: 0.0,
+            }),
+            Expression::BinaryExpression(expr) => {
+                let (variable, offset) = match expr.operator {
+                    BinaryOperator::Addition => match constant(&expr.right) {
+                        Some(offset) => (&expr.left, offset),
+                        None => (&expr.right, constant(&expr.left)?),
+                    },
+                    BinaryOperator::Subtraction => (&expr.left, -constant(&expr.right)?),
+                    _ => return None,
+                };
+                Some(Slot {
+                    base: Some(base(variable)?),
+                    offset,
+                })
+            }
+            _ => None,
         }
+    }
+
+    fn reference_symbol(&self, expr: &Expression) -> Option<SymbolId> {
+        self.dataflow.reference_symbol(expr)
+    }
 
-        if let Some(loop_var) = &loop_var {
-            self.current_loop_vars.push(loop_var.to_string());
+    /// `slot` as an expression: `base`, `base + offset`, `base - offset` or `offset`.
+    fn slot_expression(&self, slot: &Slot) -> Expression<'a> {
+        let literal = |value: f64| {
+            Expression::NumericLiteral(self.ast.alloc_numeric_literal(
+                SPAN,
+                value,
+                None,
+                NumberBase::Decimal,
+            ))
+        };
+        let Some((base, reference)) = slot.base else {
+            return literal(slot.offset);
+        };
+        let base = Expression::Identifier(self.ast.alloc_identifier_reference_with_reference_id(
+            SPAN,
+            self.semantic.symbols().get_name(base),
+            reference,
+        ));
+        if slot.offset == 0.0 {
+            return base;
         }
-        walk::walk_for_statement(self, for_);
-        if let Some(_) = &loop_var {
-            self.current_loop_vars.pop();
+        let operator = if slot.offset > 0.0 {
+            BinaryOperator::Addition
+        } else {
+            BinaryOperator::Subtraction
+        };
+        Expression::BinaryExpression(self.ast.alloc_binary_expression(
+            SPAN,
+            base,
+            operator,
+            literal(slot.offset.abs()),
+        ))
+    }
+}
+
+impl<'a> VisitMut<'a> for SlottedArrayReadOptimization<'_, 'a> {
+    fn visit_program(&mut self, program: &mut Program<'a>) {
+        for symbol in self.semantic.symbols().symbol_ids() {
+            if !self.is_typed_array(symbol) {
+                continue;
+            }
+            match self.analyze(symbol) {
+                Ok((counter, rewrite)) => {
+                    self.rewrites.insert(counter, rewrite);
+                }
+                Err(reason) => {
+                    let declaration = self.semantic.symbols().get_declaration(symbol);
+                    self.remarks.push(Remark::missed(
+                        self.semantic.nodes().kind(declaration).span(),
+                        format!(
+                            "`{}` filled in full: {reason}",
+                            self.semantic.symbols().get_name(symbol)
+                        ),
+                    ));
+                }
+            }
         }
+
+        walk_mut::walk_program(self, program);
     }
 
-    fn visit_member_expression(&mut self, expr: &MemberExpression<'a>) {
-        if let MemberExpression::ComputedMemberExpression(x) = expr {
-            if let Expression::Identifier(a) = &x.object {
-                if let Expression::Identifier(i) = &x.expression {
-                    let c = self
-                        .candidates
-                        .iter_mut()
-                        .find(|candidate| candidate.array == a.name.to_string());
-
-                    if self.current_loop_vars.contains(&i.name.to_string()) {
-                        if let Some(c) = c {
-                            if c.loop_index != i.name.to_string() {
-                                c.valid = false;
-                            }
-                        } else {
-                            self.candidates.push(SlottedReadCandidate {
-                                array: a.name.to_string(),
-                                loop_index: i.name.to_string(),
-                                read_index: None,
-                                valid: true,
-                            });
-                        }
-                    } else {
-                        if let Some(c) = c {
-                            if c.read_index.is_none() {
-                                c.read_index = Some(i.name.to_string());
-                            } else if *c.read_index.as_ref().unwrap() != i.name.to_string() {
-                                c.valid = false;
-                            }
-                        }
-                    }
-
-                    //println!("expr: {}[{}]", a.name, i.name);
+    fn visit_for_statement(&mut self, for_: &mut ForStatement<'a>) {
+        let mut loop_var = None;
+        if let Some(ForStatementInit::VariableDeclaration(var)) = &for_.init {
+            if let Some(init) = &var.declarations.first() {
+                if let BindingPatternKind::BindingIdentifier(x) = &init.id.kind {
+                    loop_var = x.symbol_id.get();
                 }
             }
         }
 
-        walk::walk_member_expression(self, expr);
+        let rewrite = loop_var.and_then(|loop_var| Some((loop_var, self.rewrites.get(&loop_var)?)));
+        if let Some((loop_var, rewrite)) = rewrite {
+            let counter = || {
+                Expression::Identifier(self.ast.alloc_identifier_reference_with_reference_id(
+                    SPAN,
+                    self.semantic.symbols().get_name(loop_var),
+                    rewrite.counter,
+                ))
+            };
+
+            // let i = target; i == target && i >= start && <original test>
+            let Some(ForStatementInit::VariableDeclaration(var)) = &mut for_.init else {
+                unreachable!();
+            };
+            let init = var.declarations[0].init.as_mut().unwrap();
+            let start = self.ast.move_expression(init);
+            *init = self.slot_expression(&rewrite.target);
+
+            let is_target = Expression::BinaryExpression(self.ast.alloc_binary_expression(
+                SPAN,
+                counter(),
+                BinaryOperator::Equality,
+                self.slot_expression(&rewrite.target),
+            ));
+            let after_start = Expression::BinaryExpression(self.ast.alloc_binary_expression(
+                SPAN,
+                counter(),
+                BinaryOperator::GreaterEqualThan,
+                start,
+            ));
+            let test = self.ast.move_expression(for_.test.as_mut().unwrap());
+            let guard = Expression::LogicalExpression(self.ast.alloc_logical_expression(
+                SPAN,
+                is_target,
+                LogicalOperator::And,
+                after_start,
+            ));
+            for_.test = Some(Expression::LogicalExpression(
+                self.ast
+                    .alloc_logical_expression(SPAN, guard, LogicalOperator::And, test),
+            ));
+            // reported at the array, like the reasons it is left alone
+            let declaration = self.semantic.symbols().get_declaration(rewrite.array);
+            self.remarks.push(Remark::applied(
+                self.semantic.nodes().kind(declaration).span(),
+                format!(
+                    "`{}` filled only at the element read afterwards",
+                    self.semantic.symbols().get_name(rewrite.array)
+                ),
+            ));
+        }
+
+        walk_mut::walk_for_statement(self, for_);
     }
 }
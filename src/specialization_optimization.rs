@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+
+use oxc::{
+    allocator::{Allocator, Box, CloneIn, Vec as ArenaVec},
+    ast::{
+        ast::{
+            Argument, BinaryOperator, BindingPatternKind, CallExpression, Declaration, Expression,
+            Function, FunctionType, LogicalOperator, NumberBase, Statement,
+        },
+        visit::walk_mut,
+        AstBuilder, AstKind, VisitMut,
+    },
+    semantic::{Semantic, SymbolId},
+    span::SPAN,
+};
+
+use crate::{
+    dataflow::Dataflow, fresh_names::FreshNames, profile::ModuleProfile, purity::is_pure,
+    remarks::Remark,
+};
+
+/// Under `--profile-use`, copies a function declaration whose double parameters only ever
+/// received int32 values in the profile, and calls the copy when the arguments are int32:
+///
+/// ```js
+/// function scale(x, k) { return x * k + 1; }
+/// s += scale(x, 3);
+/// // becomes
+/// function scale(x, k) { return x * k + 1; }
+/// function scale_int32(x, k) { return x * k + 1; }
+/// s += x === (x | 0) ? scale_int32(x | 0, 3) : scale(x, 3);
+/// ```
+///
+/// Type inference then gives the parameters of the copy the type of `x | 0`, so its body
+/// computes on int32 values. The check keeps the call right whatever the arguments are, except
+/// that a `-0` reaches the copy as `0`.
+///
+/// A function is specialized for the parameters whose arguments were int32 in every call the
+/// profile counted, when it is not reassigned, async or a generator and its parameters are plain
+/// names. The check evaluates those arguments again, so every counted call must pass them
+/// without side effects. Only the counted calls are rewritten, which leaves none for the next
+/// round of the pipeline to specialize again.
+pub struct SpecializationOptimization<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    allocator: &'a Allocator,
+    ast: AstBuilder<'a>,
+    /// The position of every call whose arguments for a specialized function the profile
+    /// counted.
+    counted: HashSet<u32>,
+    /// The copy of every specialized function.
+    specialized: HashMap<SymbolId, Specialized>,
+    pub remarks: Vec<Remark>,
+}
+
+struct Specialized {
+    name: String,
+    /// The positions of the parameters the copy takes as int32.
+    params: Vec<usize>,
+}
+
+impl<'s, 'a> SpecializationOptimization<'s, 'a> {
+    pub fn new(
+        dataflow: &'s Dataflow<'s, 'a>,
+        allocator: &'a Allocator,
+        profile: Option<&ModuleProfile>,
+    ) -> Self {
+        let semantic = dataflow.semantic();
+        let mut pass = Self {
+            semantic,
+            allocator,
+            ast: AstBuilder::new(allocator),
+            counted: HashSet::new(),
+            specialized: HashMap::new(),
+            remarks: vec![],
+        };
+        let Some(profile) = profile else {
+            return pass;
+        };
+
+        let mut calls: HashMap<SymbolId, Vec<&'s CallExpression<'a>>> = HashMap::new();
+        for node in semantic.nodes().iter() {
+            if let AstKind::CallExpression(call) = node.kind() {
+                if let Some(symbol) = pass.callee(call) {
+                    calls.entry(symbol).or_default().push(call);
+                }
+            }
+        }
+
+        let mut names = FreshNames::new(semantic);
+        for node in semantic.nodes().iter() {
+            let AstKind::Function(function) = node.kind() else {
+                continue;
+            };
+            let Some(symbol) = function.id.as_ref().and_then(|id| id.symbol_id.get()) else {
+                continue;
+            };
+            let Some(calls) = calls.get(&symbol) else {
+                continue;
+            };
+            let name = semantic.symbols().get_name(symbol);
+
+            // the int32 and other arguments of each parameter over every counted call
+            let mut counts = vec![(0, 0); function.params.items.len()];
+            for call in calls {
+                for (index, (int32, other)) in counts.iter_mut().enumerate() {
+                    if let Some(counts) = profile.argument(call.span, index) {
+                        *int32 += counts.0;
+                        *other += counts.1;
+                    }
+                }
+            }
+            let params: Vec<usize> = (0..counts.len())
+                .filter(|&index| counts[index].0 > 0 && counts[index].1 == 0)
+                .collect();
+            if params.is_empty() {
+                let mixed = (0..counts.len())
+                    .find(|&index| counts[index].0 > 0 && counts[index].1 > 0)
+                    .and_then(|index| param_name(function, index));
+                if let Some(param) = mixed {
+                    pass.remarks.push(Remark::missed(
+                        function.span,
+                        format!("`{name}` not specialized: `{param}` also received other numbers"),
+                    ));
+                }
+                continue;
+            }
+
+            let counted: Vec<_> = calls
+                .iter()
+                .copied()
+                .filter(|call| {
+                    !call.span.is_unspanned()
+                        && params
+                            .iter()
+                            .any(|&index| profile.argument(call.span, index).is_some())
+                })
+                .collect();
+            if let Err(reason) = pass.check(symbol, function, &params, &counted) {
+                pass.remarks.push(Remark::missed(
+                    function.span,
+                    format!("`{name}` not specialized: {reason}"),
+                ));
+                continue;
+            }
+            let copy = names.fresh(&format!("{name}_int32"));
+            let specialized_params = params
+                .iter()
+                .filter_map(|&index| param_name(function, index))
+                .map(|param| format!("`{param}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            pass.remarks.push(Remark::applied(
+                function.span,
+                format!("`{name}` specialized for int32 {specialized_params} as `{copy}`"),
+            ));
+            pass.counted
+                .extend(counted.iter().map(|call| call.span.start));
+            pass.specialized
+                .insert(symbol, Specialized { name: copy, params });
+        }
+        pass
+    }
+
+    /// Whether `function` can be specialized for `params`, given its counted `calls`.
+    fn check(
+        &self,
+        symbol: SymbolId,
+        function: &Function<'a>,
+        params: &[usize],
+        calls: &[&CallExpression<'a>],
+    ) -> Result<(), &'static str> {
+        if function.r#type != FunctionType::FunctionDeclaration {
+            return Err("it is not a function declaration");
+        }
+        if function.r#async || function.generator {
+            return Err("it is async or a generator");
+        }
+        let plain =
+            function.params.items.iter().all(|param| {
+                matches!(param.pattern.kind, BindingPatternKind::BindingIdentifier(_))
+            });
+        if !plain || function.params.rest.is_some() {
+            return Err("its parameters are not plain names");
+        }
+        let symbols = self.semantic.symbols();
+        if symbols
+            .get_resolved_references(symbol)
+            .any(|reference| reference.is_write())
+        {
+            return Err("it is reassigned");
+        }
+        for call in calls {
+            if self.arguments(call, params).is_none() {
+                return Err("a call passes an argument with side effects");
+            }
+        }
+        Ok(())
+    }
+
+    /// The function declaration `call` calls directly, if it is one.
+    fn callee(&self, call: &CallExpression<'a>) -> Option<SymbolId> {
+        let Expression::Identifier(callee) = &call.callee else {
+            return None;
+        };
+        let symbol = self
+            .semantic
+            .symbols()
+            .get_reference(callee.reference_id.get()?)
+            .symbol_id()?;
+        matches!(
+            self.semantic.symbol_declaration(symbol).kind(),
+            AstKind::Function(_)
+        )
+        .then_some(symbol)
+    }
+
+    /// The arguments of `call` for `params`, when they can be evaluated again.
+    fn arguments<'c>(
+        &self,
+        call: &'c CallExpression<'a>,
+        params: &[usize],
+    ) -> Option<Vec<&'c Expression<'a>>> {
+        params
+            .iter()
+            .map(|&index| {
+                let argument = call.arguments.get(index)?.as_expression()?;
+                is_pure(self.semantic, argument).then_some(argument)
+            })
+            .collect()
+    }
+
+    /// The call `original` checking its specialized arguments and calling the copy when they are
+    /// int32.
+    fn specialize(&self, mut original: Expression<'a>) -> Expression<'a> {
+        let Expression::CallExpression(call) = &mut original else {
+            unreachable!();
+        };
+        let symbol = self.callee(call).unwrap();
+        let specialized = &self.specialized[&symbol];
+
+        let mut test = None;
+        for argument in self.arguments(call, &specialized.params).unwrap() {
+            let int32 = self.ast.expression_binary(
+                SPAN,
+                argument.clone_in(self.allocator),
+                BinaryOperator::StrictEquality,
+                self.int32(argument),
+            );
+            test = Some(match test {
+                Some(test) => self
+                    .ast
+                    .expression_logical(SPAN, test, LogicalOperator::And, int32),
+                None => int32,
+            });
+        }
+
+        let mut arguments = self.ast.vec();
+        for (index, argument) in call.arguments.iter().enumerate() {
+            arguments.push(match argument.as_expression() {
+                Some(argument) if specialized.params.contains(&index) => {
+                    Argument::from(self.int32(argument))
+                }
+                _ => argument.clone_in(self.allocator),
+            });
+        }
+        let copy = self.ast.expression_call(
+            SPAN,
+            self.ast
+                .expression_identifier_reference(SPAN, self.ast.atom(&specialized.name)),
+            None::<Box<_>>,
+            arguments,
+            false,
+        );
+
+        let span = call.span;
+        call.span = SPAN;
+        self.ast
+            .expression_conditional(span, test.unwrap(), copy, original)
+    }
+
+    /// `argument | 0`.
+    fn int32(&self, argument: &Expression<'a>) -> Expression<'a> {
+        self.ast.expression_binary(
+            SPAN,
+            argument.clone_in(self.allocator),
+            BinaryOperator::BitwiseOR,
+            self.ast
+                .expression_numeric_literal(SPAN, 0.0, None, NumberBase::Decimal),
+        )
+    }
+}
+
+/// The name of parameter `index` of `function`.
+fn param_name(function: &Function, index: usize) -> Option<String> {
+    match &function.params.items.get(index)?.pattern.kind {
+        BindingPatternKind::BindingIdentifier(id) => Some(id.name.to_string()),
+        _ => None,
+    }
+}
+
+impl<'a> VisitMut<'a> for SpecializationOptimization<'_, 'a> {
+    fn visit_statements(&mut self, statements: &mut ArenaVec<'a, Statement<'a>>) {
+        walk_mut::walk_statements(self, statements);
+
+        // each copy follows its function, which codegen declares ahead of the statements anyway
+        let mut index = 0;
+        while index < statements.len() {
+            index += 1;
+            let function = match &statements[index - 1] {
+                Statement::FunctionDeclaration(function) => function,
+                Statement::ExportNamedDeclaration(export) => match &export.declaration {
+                    Some(Declaration::FunctionDeclaration(function)) => function,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let specialized = function
+                .id
+                .as_ref()
+                .and_then(|id| id.symbol_id.get())
+                .and_then(|symbol| self.specialized.get(&symbol));
+            let Some(specialized) = specialized else {
+                continue;
+            };
+            let mut copy = function.clone_in(self.allocator);
+            copy.id = Some(
+                self.ast
+                    .binding_identifier(SPAN, self.ast.atom(&specialized.name)),
+            );
+            statements.insert(index, Statement::FunctionDeclaration(copy));
+            index += 1;
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'a>) {
+        walk_mut::walk_expression(self, expr);
+
+        let Expression::CallExpression(call) = expr else {
+            return;
+        };
+        let specialized = self
+            .callee(call)
+            .is_some_and(|symbol| self.specialized.contains_key(&symbol));
+        if specialized && !call.span.is_unspanned() && self.counted.contains(&call.span.start) {
+            let original = self.ast.move_expression(expr);
+            *expr = self.specialize(original);
+        }
+    }
+}
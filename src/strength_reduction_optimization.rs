@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+
+use oxc::{
+    allocator::{Allocator, Box},
+    ast::{
+        ast::{
+            ArrowFunctionExpression, AssignmentOperator, BinaryOperator, Expression, ForStatement,
+            ForStatementInit, Function, NumberBase, Statement, TSTypeAnnotation,
+            VariableDeclarationKind,
+        },
+        visit::walk_mut,
+        AstBuilder, AstKind, Visit, VisitMut,
+    },
+    semantic::{NodeId, ScopeFlags, ScopeId, Semantic, SymbolId},
+    span::{GetSpan, Span, SPAN},
+};
+
+use crate::{
+    dataflow::{ConstantPropagation, Dataflow, Range, RangeAnalysis, Results},
+    fresh_names::FreshNames,
+    induction_variables::{Continues, Loop},
+    remarks::Remark,
+    type_inference::Types,
+};
+
+/// Running values are only exact while everything they add up stays within this.
+const LIMIT: f64 = (1u64 << 52) as f64;
+
+/// Replaces products, remainders and quotients of a loop's induction variables by variables
+/// that the end of the body updates with additions, where `i` steps by `s` each iteration:
+///
+/// ```js
+/// for (let i = 1; i < n; i += 2) s += a[i * 3] + i % 4;
+/// // becomes
+/// for (let i = 1, i_times_3 = i * 3, i_mod_4 = i % 4; i < n; i += 2) {
+///     s += a[i_times_3] + i_mod_4;
+///     i_times_3 = i_times_3 + 6;
+///     i_mod_4 = i_mod_4 + 2;
+///     if (i_mod_4 >= 4) { i_mod_4 = i_mod_4 - 4; }
+/// }
+/// ```
+///
+/// `i * k` takes any `k` fixed in the loop, `E % n` an `E` of `i` or `i * k` with `k` a positive
+/// constant, and `Math.floor(i / n)` or `(i / n) | 0` become a running quotient, carried by the
+/// running `i % n`. Remainders and quotients need `i` to count up from 0 or more and `n` to be
+/// a positive integer, since JS `%` keeps the sign of the dividend. What the running values
+/// add must also stay below 2^52, where doubles are exact.
+pub struct StrengthReductionOptimization<'t, 's, 'a> {
+    semantic: &'s Semantic<'a>,
+    dataflow: &'s Dataflow<'s, 'a>,
+    constants: Results<'s, 's, 'a, ConstantPropagation>,
+    ranges: Results<'s, 's, 'a, RangeAnalysis<'t>>,
+    ast: AstBuilder<'a>,
+    names: FreshNames,
+    pub remarks: Vec<Remark>,
+}
+
+/// A factor or divisor fixed in the loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Operand {
+    Constant(i64),
+    Variable(SymbolId),
+}
+
+/// A value kept up to date with additions, named by what it equals.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Running {
+    /// `i * k`
+    Product(SymbolId, Operand),
+    /// `(i * k) % n`, a constant `k` of 1 being plain `i % n`.
+    Remainder(SymbolId, i64, Operand),
+    /// `Math.floor(i / n)`
+    Quotient(SymbolId, Operand),
+}
+
+/// An expression a running value can replace.
+struct Candidate {
+    running: Running,
+    /// It is `(i / n) | 0`, which only equals the quotient in the int32 range.
+    truncated: bool,
+}
+
+/// What the checks of one loop need to know about it.
+struct Context<'l, 's, 'a> {
+    loop_: &'l Loop<'s, 'a>,
+    /// Before the initializer.
+    entry: NodeId,
+    /// At the start of the body, where the ranges cover every iteration.
+    body: NodeId,
+    scopes: [ScopeId; 2],
+    /// Why the loop cannot take running values at all.
+    unsupported: Option<&'static str>,
+}
+
+impl<'t, 's, 'a> StrengthReductionOptimization<'t, 's, 'a> {
+    pub fn new(
+        dataflow: &'s Dataflow<'s, 'a>,
+        allocator: &'a Allocator,
+        types: Option<&'t Types>,
+    ) -> Self {
+        Self {
+            semantic: dataflow.semantic(),
+            dataflow,
+            constants: dataflow.solve(ConstantPropagation),
+            ranges: dataflow
+                .solve(types.map_or_else(RangeAnalysis::default, RangeAnalysis::with_types)),
+            ast: AstBuilder::new(allocator),
+            names: FreshNames::new(dataflow.semantic()),
+            remarks: vec![],
+        }
+    }
+
+    fn source(&self, span: Span) -> &'a str {
+        let text = span.source_text(self.semantic.source_text());
+        text.lines().next().unwrap_or_default()
+    }
+
+    fn describe(&self, operand: Operand) -> String {
+        match operand {
+            Operand::Constant(value) if value < 0 => format!("minus_{}", -value),
+            Operand::Constant(value) => value.to_string(),
+            Operand::Variable(symbol) => self.semantic.symbols().get_name(symbol).to_string(),
+        }
+    }
+
+    /// What to call the variable holding `running`.
+    fn name(&mut self, running: Running) -> String {
+        let symbols = self.semantic.symbols();
+        let base = match running {
+            Running::Product(i, k) => {
+                format!("{}_times_{}", symbols.get_name(i), self.describe(k))
+            }
+            Running::Remainder(i, 1, n) => {
+                format!("{}_mod_{}", symbols.get_name(i), self.describe(n))
+            }
+            Running::Remainder(i, k, n) => {
+                format!("{}_times_{k}_mod_{}", symbols.get_name(i), self.describe(n))
+            }
+            Running::Quotient(i, n) => {
+                format!("{}_div_{}", symbols.get_name(i), self.describe(n))
+            }
+        };
+        self.names.fresh(&base)
+    }
+
+    /// Why the loop cannot keep running values, if it cannot.
+    fn unsupported(&self, for_: &ForStatement<'a>) -> Option<&'static str> {
+        if !matches!(for_.body, Statement::BlockStatement(_)) {
+            return Some("the loop body is not a block");
+        }
+        // the running values are updated at the end of the body, which `continue` skips
+        let mut continues = Continues(false);
+        continues.visit_statement(&for_.body);
+        if continues.0 {
+            return Some("the loop body contains continue");
+        }
+        match &for_.init {
+            Some(ForStatementInit::VariableDeclaration(init))
+                if init.kind == VariableDeclarationKind::Const =>
+            {
+                Some("the loop initializer declares constants")
+            }
+            Some(ForStatementInit::VariableDeclaration(_)) | None => None,
+            Some(_) => Some("the loop initializer is not a declaration"),
+        }
+    }
+
+    /// The `Running` value `expr` computes, when it is one with the operands of this loop.
+    fn candidate(&self, context: &Context, expr: &Expression<'a>) -> Option<Candidate> {
+        let counter = |expr: &Expression| {
+            let symbol = self.dataflow.reference_symbol(expr.without_parentheses())?;
+            context.loop_.step(symbol).map(|_| symbol)
+        };
+        let operand = |expr: &Expression| match self.constants.value(expr, context.body) {
+            Some(value) if value.fract() == 0.0 && value.abs() <= i32::MAX as f64 => {
+                Some(Operand::Constant(value as i64))
+            }
+            Some(_) => None,
+            None => self
+                .dataflow
+                .reference_symbol(expr.without_parentheses())
+                .filter(|symbol| context.loop_.is_fixed(*symbol))
+                .map(Operand::Variable),
+        };
+        // `i * k` or `k * i`
+        let product = |expr: &Expression| match expr.without_parentheses() {
+            Expression::BinaryExpression(expr)
+                if expr.operator == BinaryOperator::Multiplication =>
+            {
+                if let Some(i) = counter(&expr.left) {
+                    operand(&expr.right).map(|k| (i, k))
+                } else {
+                    let i = counter(&expr.right)?;
+                    operand(&expr.left).map(|k| (i, k))
+                }
+            }
+            _ => None,
+        };
+        // `i / n`
+        let quotient = |expr: &Expression| match expr.without_parentheses() {
+            Expression::BinaryExpression(expr) if expr.operator == BinaryOperator::Division => {
+                let i = counter(&expr.left)?;
+                operand(&expr.right).map(|n| Running::Quotient(i, n))
+            }
+            _ => None,
+        };
+
+        let running = |running| {
+            Some(Candidate {
+                running,
+                truncated: false,
+            })
+        };
+        match expr {
+            Expression::CallExpression(call) => {
+                let floor = matches!(&call.callee, Expression::StaticMemberExpression(member)
+                    if member.property.name == "floor"
+                        && matches!(&member.object, Expression::Identifier(id) if id.name == "Math"));
+                match call
+                    .arguments
+                    .first()
+                    .and_then(|argument| argument.as_expression())
+                {
+                    Some(argument) if floor && call.arguments.len() == 1 => {
+                        running(quotient(argument)?)
+                    }
+                    _ => None,
+                }
+            }
+            Expression::BinaryExpression(binary) => match binary.operator {
+                BinaryOperator::BitwiseOR if matches!(&binary.right, Expression::NumericLiteral(zero) if zero.value == 0.0) => {
+                    Some(Candidate {
+                        running: quotient(&binary.left)?,
+                        truncated: true,
+                    })
+                }
+                BinaryOperator::Remainder => {
+                    let (i, k) = match counter(&binary.left) {
+                        Some(i) => (i, 1),
+                        None => match product(&binary.left)? {
+                            (i, Operand::Constant(k)) if k >= 1 => (i, k),
+                            _ => return None,
+                        },
+                    };
+                    running(Running::Remainder(i, k, operand(&binary.right)?))
+                }
+                BinaryOperator::Multiplication => {
+                    let (i, k) = product(expr)?;
+                    running(Running::Product(i, k))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Why `candidate` cannot become a running value, if it cannot.
+    fn check(&self, context: &Context, candidate: &Candidate) -> Result<(), String> {
+        let symbols = self.semantic.symbols();
+        let (i, k, n) = match candidate.running {
+            Running::Product(i, k) => (i, k, None),
+            Running::Remainder(i, k, n) => (i, Operand::Constant(k), Some(n)),
+            Running::Quotient(i, n) => (i, Operand::Constant(1), Some(n)),
+        };
+        let step = context.loop_.step(i).unwrap();
+        let name = symbols.get_name(i);
+
+        let range = |operand, node| match operand {
+            Operand::Constant(value) => Range::point(value as f64),
+            Operand::Variable(symbol) => self.ranges.symbol(symbol, node),
+        };
+        let known = |range: Range| {
+            range.integer && !range.nan && range.min.abs().max(range.max.abs()) <= LIMIT
+        };
+        // the values in every iteration, and the one after the last
+        let counter = self.ranges.symbol(i, context.body);
+        if !known(counter) {
+            return Err(format!("`{name}` is not known to be an integer below 2^52"));
+        }
+        let factor = range(k, context.body);
+        if !known(factor) {
+            return Err(format!(
+                "`{}` is not known to be an integer",
+                self.describe(k)
+            ));
+        }
+        // the running value is one step ahead once the body is done
+        let reached = Range {
+            min: counter.min - step.abs() as f64,
+            max: counter.max + step.abs() as f64,
+            ..counter
+        };
+        if !known(reached.binary(BinaryOperator::Multiplication, &factor)) {
+            return Err(format!("`{name} * {}` may exceed 2^52", self.describe(k)));
+        }
+
+        for operand in [Some(k), n].into_iter().flatten() {
+            if let Operand::Variable(symbol) = operand {
+                let visible = context.scopes.iter().all(|scope| {
+                    self.semantic
+                        .scopes()
+                        .find_binding(*scope, symbols.get_name(symbol))
+                        == Some(symbol)
+                });
+                if !visible {
+                    return Err(format!(
+                        "`{}` is shadowed in the loop",
+                        self.describe(operand)
+                    ));
+                }
+            }
+        }
+
+        let Some(n) = n else {
+            return Ok(());
+        };
+        if step < 0 {
+            return Err(format!("`{name}` counts down"));
+        }
+        if counter.min < 0.0 {
+            return Err(format!("`{name}` may be negative"));
+        }
+        if candidate.truncated && counter.max > i32::MAX as f64 {
+            return Err(format!("`{name}` may exceed the int32 range"));
+        }
+        let positive = |range: Range| known(range) && range.min >= 1.0;
+        if !positive(range(n, context.body)) {
+            return Err(format!(
+                "`{}` is not known to be a positive integer",
+                self.describe(n)
+            ));
+        }
+        if let Operand::Variable(_) = n {
+            let increment = match candidate.running {
+                Running::Remainder(_, k, _) => step * k,
+                _ => step,
+            };
+            if matches!(candidate.running, Running::Quotient(..)) && step != 1 {
+                return Err(format!("`{name}` steps by more than 1"));
+            }
+            // the initializer then divides by `n`, which C++ does not allow for 0
+            if (increment != 1 || !self.starts_at_zero(context, i))
+                && !positive(range(n, context.entry))
+            {
+                return Err(format!(
+                    "`{}` is not known to be positive before the loop",
+                    self.describe(n)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// `i` is 0 when the loop starts.
+    fn starts_at_zero(&self, context: &Context, i: SymbolId) -> bool {
+        let declaration = self.semantic.symbols().get_declaration(i);
+        let range = match self.semantic.nodes().kind(declaration) {
+            AstKind::VariableDeclarator(declarator) if context.loop_.contains(declaration) => {
+                match &declarator.init {
+                    Some(init) => self.ranges.range(init, declaration),
+                    None => Range::UNKNOWN,
+                }
+            }
+            _ => self.ranges.symbol(i, context.entry),
+        };
+        range == Range::point(0.0)
+    }
+
+    fn number(&self, value: f64) -> Expression<'a> {
+        self.ast
+            .expression_numeric_literal(SPAN, value, None, NumberBase::Decimal)
+    }
+
+    fn variable(&self, name: &str) -> Expression<'a> {
+        self.ast
+            .expression_identifier_reference(SPAN, self.ast.atom(name))
+    }
+
+    fn operand(&self, operand: Operand) -> Expression<'a> {
+        match operand {
+            Operand::Constant(value) => self.number(value as f64),
+            Operand::Variable(symbol) => self.variable(self.semantic.symbols().get_name(symbol)),
+        }
+    }
+
+    fn binary(
+        &self,
+        left: Expression<'a>,
+        operator: BinaryOperator,
+        right: Expression<'a>,
+    ) -> Expression<'a> {
+        self.ast.expression_binary(SPAN, left, operator, right)
+    }
+
+    /// `name = name + step`, or `name = name - step` for a negative `step`.
+    fn add(&self, name: &str, step: Expression<'a>, negative: bool) -> Statement<'a> {
+        let operator = if negative {
+            BinaryOperator::Subtraction
+        } else {
+            BinaryOperator::Addition
+        };
+        self.ast.statement_expression(
+            SPAN,
+            self.ast.expression_assignment(
+                SPAN,
+                AssignmentOperator::Assign,
+                self.ast
+                    .simple_assignment_target_identifier_reference(SPAN, self.ast.atom(name))
+                    .into(),
+                self.binary(self.variable(name), operator, step),
+            ),
+        )
+    }
+
+    /// Declares the running values of one loop and appends their updates to its body.
+    fn emit(
+        &mut self,
+        for_: &mut ForStatement<'a>,
+        context: &Context,
+        found: Vec<(Running, String)>,
+    ) {
+        let mut declarations = vec![];
+        let mut updates = vec![];
+        for (running, name) in &found {
+            let (i, step) = match running {
+                Running::Product(i, _) | Running::Remainder(i, ..) | Running::Quotient(i, _) => {
+                    (*i, context.loop_.step(*i).unwrap())
+                }
+            };
+            let zero = self.starts_at_zero(context, i);
+            let counter = self.variable(self.semantic.symbols().get_name(i));
+            match *running {
+                Running::Product(_, k) => {
+                    let initial = if zero {
+                        self.number(0.0)
+                    } else {
+                        self.binary(counter, BinaryOperator::Multiplication, self.operand(k))
+                    };
+                    declarations.push((name.clone(), initial));
+                    let increment = match k {
+                        Operand::Constant(k) => self.number((step * k).abs() as f64),
+                        Operand::Variable(_) if step.abs() == 1 => self.operand(k),
+                        Operand::Variable(_) => {
+                            let increment = self.names.fresh(&format!("{name}_step"));
+                            let value = self.binary(
+                                self.operand(k),
+                                BinaryOperator::Multiplication,
+                                self.number(step.abs() as f64),
+                            );
+                            declarations.push((increment.clone(), value));
+                            self.variable(&increment)
+                        }
+                    };
+                    let negative = match k {
+                        Operand::Constant(k) => step * k < 0,
+                        Operand::Variable(_) => step < 0,
+                    };
+                    updates.push(self.add(name, increment, negative));
+                }
+                Running::Remainder(_, k, n) => {
+                    let initial = if zero {
+                        self.number(0.0)
+                    } else {
+                        let dividend = if k == 1 {
+                            counter
+                        } else {
+                            self.ast.expression_parenthesized(
+                                SPAN,
+                                self.binary(
+                                    counter,
+                                    BinaryOperator::Multiplication,
+                                    self.number(k as f64),
+                                ),
+                            )
+                        };
+                        self.binary(dividend, BinaryOperator::Remainder, self.operand(n))
+                    };
+                    declarations.push((name.clone(), initial));
+                    let quotient = found.iter().find_map(|(other, name)| {
+                        (k == 1 && *other == Running::Quotient(i, n)).then_some(name)
+                    });
+                    let (increment, carry) = match n {
+                        Operand::Constant(n) => {
+                            let increment = step * k % n;
+                            let carry = step * k / n;
+                            (
+                                (increment > 0).then(|| self.number(increment as f64)),
+                                carry,
+                            )
+                        }
+                        Operand::Variable(_) if step * k == 1 => (Some(self.number(1.0)), 0),
+                        Operand::Variable(_) => {
+                            let increment = self.names.fresh(&format!("{name}_step"));
+                            let value = self.binary(
+                                self.number((step * k) as f64),
+                                BinaryOperator::Remainder,
+                                self.operand(n),
+                            );
+                            declarations.push((increment.clone(), value));
+                            (Some(self.variable(&increment)), 0)
+                        }
+                    };
+                    if let Some(increment) = increment {
+                        updates.push(self.add(name, increment, false));
+                        let mut wrap = self.ast.vec1(self.add(name, self.operand(n), true));
+                        if let Some(quotient) = quotient {
+                            wrap.push(self.add(quotient, self.number(1.0), false));
+                        }
+                        updates.push(self.ast.statement_if(
+                            SPAN,
+                            self.binary(
+                                self.variable(name),
+                                BinaryOperator::GreaterEqualThan,
+                                self.operand(n),
+                            ),
+                            self.ast.statement_block(SPAN, wrap),
+                            None,
+                        ));
+                    }
+                    if let (Some(quotient), true) = (quotient, carry > 0) {
+                        updates.push(self.add(quotient, self.number(carry as f64), false));
+                    }
+                }
+                Running::Quotient(_, n) => {
+                    let initial = if zero {
+                        self.number(0.0)
+                    } else {
+                        let floor = self.ast.member_expression_static(
+                            SPAN,
+                            self.variable("Math"),
+                            self.ast.identifier_name(SPAN, "floor"),
+                            false,
+                        );
+                        self.ast.expression_call(
+                            SPAN,
+                            floor.into(),
+                            None::<Box<_>>,
+                            self.ast.vec1(
+                                self.binary(counter, BinaryOperator::Division, self.operand(n))
+                                    .into(),
+                            ),
+                            false,
+                        )
+                    };
+                    declarations.push((name.clone(), initial));
+                }
+            }
+        }
+
+        let init = for_.init.get_or_insert_with(|| {
+            ForStatementInit::VariableDeclaration(self.ast.alloc_variable_declaration(
+                SPAN,
+                VariableDeclarationKind::Let,
+                self.ast.vec(),
+                false,
+            ))
+        });
+        let ForStatementInit::VariableDeclaration(init) = init else {
+            unreachable!();
+        };
+        let kind = init.kind;
+        for (name, initial) in declarations {
+            init.declarations.push(self.ast.variable_declarator(
+                SPAN,
+                kind,
+                self.ast.binding_pattern(
+                    self.ast.binding_pattern_kind_binding_identifier(SPAN, name),
+                    None::<Box<TSTypeAnnotation>>,
+                    false,
+                ),
+                Some(initial),
+                false,
+            ));
+        }
+        if let Statement::BlockStatement(body) = &mut for_.body {
+            body.body.extend(updates);
+        }
+    }
+}
+
+impl<'a> VisitMut<'a> for StrengthReductionOptimization<'_, '_, 'a> {
+    fn visit_for_statement(&mut self, for_: &mut ForStatement<'a>) {
+        let loop_ = Loop::new(self.dataflow, for_).with_induction_variables(for_, &self.constants);
+        let scopes = self.semantic.scopes();
+        let body = match &for_.body {
+            Statement::BlockStatement(body) => body.scope_id(),
+            _ => for_.scope_id(),
+        };
+        let context = Context {
+            loop_: &loop_,
+            entry: scopes.get_node_id(for_.scope_id()),
+            body: scopes.get_node_id(body),
+            scopes: [for_.scope_id(), body],
+            unsupported: self.unsupported(for_),
+        };
+
+        let mut reductions = Reductions {
+            pass: self,
+            context: &context,
+            names: HashMap::new(),
+            found: vec![],
+        };
+        if let Some(test) = &mut for_.test {
+            reductions.visit_expression(test);
+        }
+        reductions.visit_statement(&mut for_.body);
+        let mut found = reductions.found;
+
+        if !found.is_empty() {
+            // a quotient is carried by the remainder of the same division
+            for index in 0..found.len() {
+                if let Running::Quotient(i, n) = found[index].0 {
+                    let remainder = Running::Remainder(i, 1, n);
+                    if !found.iter().any(|(running, _)| *running == remainder) {
+                        let name = self.name(remainder);
+                        found.push((remainder, name));
+                    }
+                }
+            }
+            self.emit(for_, &context, found);
+        }
+
+        walk_mut::walk_for_statement(self, for_);
+    }
+}
+
+/// Replaces the candidates of one loop that pass the checks with their running variables.
+struct Reductions<'p, 'c, 't, 's, 'a> {
+    pass: &'p mut StrengthReductionOptimization<'t, 's, 'a>,
+    context: &'c Context<'c, 's, 'a>,
+    /// The variable of each running value.
+    names: HashMap<Running, String>,
+    /// The running values, in the order they were first used.
+    found: Vec<(Running, String)>,
+}
+
+impl<'a> VisitMut<'a> for Reductions<'_, '_, '_, '_, 'a> {
+    fn visit_expression(&mut self, expr: &mut Expression<'a>) {
+        let Some(candidate) = self.pass.candidate(self.context, expr) else {
+            walk_mut::walk_expression(self, expr);
+            return;
+        };
+        let checked = match self.context.unsupported {
+            Some(reason) => Err(reason.to_string()),
+            None => self.pass.check(self.context, &candidate),
+        };
+        let source = self.pass.source(expr.span());
+        if let Err(reason) = checked {
+            self.pass.remarks.push(Remark::missed(
+                expr.span(),
+                format!("`{source}` kept: {reason}"),
+            ));
+            walk_mut::walk_expression(self, expr);
+            return;
+        }
+
+        let name = match self.names.get(&candidate.running) {
+            Some(name) => name.clone(),
+            None => {
+                let name = self.pass.name(candidate.running);
+                self.names.insert(candidate.running, name.clone());
+                self.found.push((candidate.running, name.clone()));
+                name
+            }
+        };
+        self.pass.remarks.push(Remark::applied(
+            expr.span(),
+            format!("`{source}` replaced by the running `{name}`"),
+        ));
+        *expr = self.pass.variable(&name);
+    }
+
+    // a nested function runs whenever it is called
+    fn visit_function(&mut self, _function: &mut Function<'a>, _flags: ScopeFlags) {}
+
+    fn visit_arrow_function_expression(&mut self, _arrow: &mut ArrowFunctionExpression<'a>) {}
+}
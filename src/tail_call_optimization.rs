@@ -0,0 +1,844 @@
+use std::collections::{HashMap, HashSet};
+
+use oxc::{
+    allocator::{self, Allocator},
+    ast::{
+        ast::{
+            Argument, ArrowFunctionExpression, AssignmentOperator, BinaryOperator,
+            BindingPatternKind, CallExpression, DoWhileStatement, Expression, ForInStatement,
+            ForOfStatement, ForStatement, Function, FunctionBody, FunctionType,
+            IdentifierReference, LabeledStatement, NumberBase, ReturnStatement, Statement,
+            SwitchStatement, TSTypeAnnotation, ThisExpression, TryStatement, VariableDeclaration,
+            VariableDeclarationKind, WhileStatement,
+        },
+        visit::{walk, walk_mut},
+        AstBuilder, AstKind, Visit, VisitMut,
+    },
+    semantic::{NodeId, ScopeFlags, Semantic, SymbolId},
+    span::SPAN,
+};
+
+use crate::{
+    dataflow::Dataflow, fresh_names::FreshNames, overflow_analysis::NodeAddress, purity::is_pure,
+    remarks::Remark, type_inference::Types,
+};
+
+/// Turns a function declaration whose calls to itself are all in tail position into a loop
+/// that assigns the arguments to the parameters and starts over, so recursion as deep as the
+/// input no longer grows the C++ stack:
+///
+/// ```js
+/// function sum(k, acc) {           function sum(k, acc) {
+///     if (k == 0) return acc;          for (;;) {
+///     return sum(k - 1, acc + k);          if (k == 0) return acc;
+/// }                                        else { const k_next = k - 1; acc = acc + k; k = k_next; }
+///                                      }
+///                                  }
+/// ```
+///
+/// With [`PassOptions::tail_call_accumulator`](crate::pass_manager::PassOptions), calls that
+/// are an operand of `+` or `*` in a `return`, like `return n * fact(n - 1)`, become tail calls
+/// first by carrying the other operands in an accumulator. That multiplies or adds them in the
+/// opposite order, which rounds differently once the values are not exact integers.
+///
+/// Statements after an `if` that returns on one branch move into the other branch, so every
+/// tail call ends the body of the loop.
+pub struct TailCallOptimization<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    ast: AstBuilder<'a>,
+    functions: HashMap<SymbolId, Rewrite>,
+    pub remarks: Vec<Remark>,
+}
+
+/// How to turn one function into a loop.
+struct Rewrite {
+    function: SymbolId,
+    params: Vec<SymbolId>,
+    /// The name of the temporary holding the next value of each parameter.
+    next: Vec<String>,
+    /// The variable carrying the operands of calls under `+` or `*`, with the operator.
+    accumulator: Option<(String, BinaryOperator)>,
+    /// The function only returns `undefined`: paths falling off its end get a `return`.
+    void: bool,
+}
+
+/// What the body of a function does with the function itself.
+struct Scan<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    function: SymbolId,
+    params: usize,
+    /// The calls to the function in tail position or under an accumulated operator.
+    tail: HashSet<NodeAddress>,
+    /// The operators of `return` expressions with a call to the function as an operand.
+    operators: Vec<BinaryOperator>,
+    /// Loops, `switch`, `try` and labels around the current node.
+    nesting: usize,
+    value_returns: bool,
+    error: Option<&'static str>,
+}
+
+impl Scan<'_, '_> {
+    fn fail(&mut self, reason: &'static str) {
+        self.error.get_or_insert(reason);
+    }
+
+    /// `expr` is a direct call to the function being scanned.
+    fn is_self_call(&self, expr: &Expression) -> bool {
+        matches!(expr.without_parentheses(), Expression::CallExpression(call)
+            if is_call_to(self.semantic, call, self.function))
+    }
+}
+
+fn without_parentheses(expr: Expression) -> Expression {
+    match expr {
+        Expression::ParenthesizedExpression(inner) => without_parentheses(inner.unbox().expression),
+        expr => expr,
+    }
+}
+
+fn is_call_to(semantic: &Semantic, call: &CallExpression, function: SymbolId) -> bool {
+    matches!(&call.callee, Expression::Identifier(id) if id.reference_id.get().is_some_and(
+        |reference| semantic.symbols().get_reference(reference).symbol_id() == Some(function)))
+}
+
+/// The call to `function` of a `return function(...)`, or of `return e + function(...)` and
+/// `return function(...) * e` with the operator and `e`.
+fn tail_call<'e, 'a>(
+    semantic: &Semantic,
+    expr: &'e Expression<'a>,
+    function: SymbolId,
+) -> Option<(
+    &'e CallExpression<'a>,
+    Option<(BinaryOperator, &'e Expression<'a>)>,
+)> {
+    let call = |expr: &'e Expression<'a>| match expr.without_parentheses() {
+        Expression::CallExpression(call) if is_call_to(semantic, call, function) => Some(&**call),
+        _ => None,
+    };
+    match expr.without_parentheses() {
+        Expression::BinaryExpression(binary)
+            if matches!(
+                binary.operator,
+                BinaryOperator::Addition | BinaryOperator::Multiplication
+            ) =>
+        {
+            match (call(&binary.left), call(&binary.right)) {
+                (Some(call), None) => Some((call, Some((binary.operator, &binary.right)))),
+                (None, Some(call)) => Some((call, Some((binary.operator, &binary.left)))),
+                _ => None,
+            }
+        }
+        expr => call(expr).map(|call| (call, None)),
+    }
+}
+
+impl<'a> Visit<'a> for Scan<'_, 'a> {
+    fn visit_return_statement(&mut self, statement: &ReturnStatement<'a>) {
+        let Some(argument) = &statement.argument else {
+            return;
+        };
+        let Some((call, operand)) = tail_call(self.semantic, argument, self.function) else {
+            self.value_returns = true;
+            walk::walk_return_statement(self, statement);
+            return;
+        };
+        if let Some((operator, operand)) = operand {
+            if !is_pure(self.semantic, operand) {
+                self.fail("the other operand of a call to itself has side effects");
+            }
+            self.operators.push(operator);
+        }
+        if self.nesting > 0 {
+            self.fail("a call to itself is inside a loop");
+        }
+        self.tail.insert(NodeAddress::of(call));
+        walk::walk_return_statement(self, statement);
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression<'a>) {
+        if is_call_to(self.semantic, call, self.function) {
+            if !self.tail.contains(&NodeAddress::of(call)) {
+                self.fail("a call to itself is not in tail position");
+            }
+            if call.arguments.len() != self.params || call.arguments.iter().any(Argument::is_spread)
+            {
+                self.fail("it calls itself with a different number of arguments");
+            }
+        }
+        walk::walk_call_expression(self, call);
+    }
+
+    fn visit_identifier_reference(&mut self, id: &IdentifierReference<'a>) {
+        let resolved = id
+            .reference_id
+            .get()
+            .and_then(|reference| self.semantic.symbols().get_reference(reference).symbol_id());
+        if resolved.is_none() && id.name == "arguments" {
+            self.fail("it uses `arguments`");
+        }
+    }
+
+    fn visit_this_expression(&mut self, _this: &ThisExpression) {
+        self.fail("it uses `this`");
+    }
+
+    fn visit_variable_declaration(&mut self, declaration: &VariableDeclaration<'a>) {
+        if declaration.kind.is_var() {
+            self.fail("it declares `var` variables");
+        }
+        walk::walk_variable_declaration(self, declaration);
+    }
+
+    // every call starts with fresh parameters a closure may have kept
+    fn visit_function(&mut self, _function: &Function<'a>, _flags: ScopeFlags) {
+        self.fail("it defines closures");
+    }
+
+    fn visit_arrow_function_expression(&mut self, _function: &ArrowFunctionExpression<'a>) {
+        self.fail("it defines closures");
+    }
+
+    fn visit_for_statement(&mut self, statement: &ForStatement<'a>) {
+        self.nesting += 1;
+        walk::walk_for_statement(self, statement);
+        self.nesting -= 1;
+    }
+
+    fn visit_for_in_statement(&mut self, statement: &ForInStatement<'a>) {
+        self.nesting += 1;
+        walk::walk_for_in_statement(self, statement);
+        self.nesting -= 1;
+    }
+
+    fn visit_for_of_statement(&mut self, statement: &ForOfStatement<'a>) {
+        self.nesting += 1;
+        walk::walk_for_of_statement(self, statement);
+        self.nesting -= 1;
+    }
+
+    fn visit_while_statement(&mut self, statement: &WhileStatement<'a>) {
+        self.nesting += 1;
+        walk::walk_while_statement(self, statement);
+        self.nesting -= 1;
+    }
+
+    fn visit_do_while_statement(&mut self, statement: &DoWhileStatement<'a>) {
+        self.nesting += 1;
+        walk::walk_do_while_statement(self, statement);
+        self.nesting -= 1;
+    }
+
+    fn visit_switch_statement(&mut self, statement: &SwitchStatement<'a>) {
+        self.nesting += 1;
+        walk::walk_switch_statement(self, statement);
+        self.nesting -= 1;
+    }
+
+    fn visit_try_statement(&mut self, statement: &TryStatement<'a>) {
+        self.nesting += 1;
+        walk::walk_try_statement(self, statement);
+        self.nesting -= 1;
+    }
+
+    fn visit_labeled_statement(&mut self, statement: &LabeledStatement<'a>) {
+        self.nesting += 1;
+        walk::walk_labeled_statement(self, statement);
+        self.nesting -= 1;
+    }
+}
+
+/// Every path through `statements` ends in a `return` or a `throw`.
+fn terminates(statements: &[&Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::ReturnStatement(_) | Statement::ThrowStatement(_) => true,
+        Statement::BlockStatement(block) => terminates(&block.body.iter().collect::<Vec<_>>()),
+        Statement::IfStatement(if_) => {
+            terminates(&branch(Some(&if_.consequent)))
+                && terminates(&branch(if_.alternate.as_ref()))
+        }
+        _ => false,
+    })
+}
+
+/// The statements of the branch of an `if`.
+fn branch<'e, 'a>(statement: Option<&'e Statement<'a>>) -> Vec<&'e Statement<'a>> {
+    match statement {
+        Some(Statement::BlockStatement(block)) => block.body.iter().collect(),
+        Some(statement) => vec![statement],
+        None => vec![],
+    }
+}
+
+/// Whether the statements after an `if` or a block can move into it so that no tail call
+/// inside is followed by other code, as [`TailCallOptimization::place`] does.
+fn placeable(statements: &[&Statement], tail: &HashSet<NodeAddress>) -> bool {
+    for (i, statement) in statements.iter().enumerate() {
+        let rest = &statements[i + 1..];
+        match statement {
+            Statement::ReturnStatement(_) | Statement::ThrowStatement(_) => return true,
+            Statement::BlockStatement(block) => {
+                let body: Vec<_> = block.body.iter().collect();
+                if terminates(&body) {
+                    return placeable(&body, tail);
+                }
+                if !rest.is_empty() && contains_tail_call(statement, tail) {
+                    return false;
+                }
+                if !placeable(&body, tail) {
+                    return false;
+                }
+            }
+            Statement::IfStatement(if_) => {
+                let mut consequent = branch(Some(&if_.consequent));
+                let mut alternate = branch(if_.alternate.as_ref());
+                match (terminates(&consequent), terminates(&alternate)) {
+                    (true, true) => {}
+                    (true, false) => alternate.extend(rest),
+                    (false, true) => consequent.extend(rest),
+                    (false, false) => {
+                        if !rest.is_empty() && contains_tail_call(statement, tail) {
+                            return false;
+                        }
+                        if !placeable(&consequent, tail) || !placeable(&alternate, tail) {
+                            return false;
+                        }
+                        continue;
+                    }
+                }
+                return placeable(&consequent, tail) && placeable(&alternate, tail);
+            }
+            _ => {}
+        }
+    }
+    true
+}
+
+fn contains_tail_call(statement: &Statement, tail: &HashSet<NodeAddress>) -> bool {
+    struct Find<'t> {
+        tail: &'t HashSet<NodeAddress>,
+        found: bool,
+    }
+    impl<'a> Visit<'a> for Find<'_> {
+        fn visit_call_expression(&mut self, call: &CallExpression<'a>) {
+            self.found |= self.tail.contains(&NodeAddress::of(call));
+            walk::walk_call_expression(self, call);
+        }
+    }
+    let mut find = Find { tail, found: false };
+    find.visit_statement(statement);
+    find.found
+}
+
+/// The symbols `expr` reads.
+fn reads<'a>(semantic: &Semantic<'a>, expr: &Expression<'a>) -> HashSet<SymbolId> {
+    struct Reads<'s, 'a> {
+        semantic: &'s Semantic<'a>,
+        symbols: HashSet<SymbolId>,
+    }
+    impl<'a> Visit<'a> for Reads<'_, 'a> {
+        fn visit_identifier_reference(&mut self, id: &IdentifierReference<'a>) {
+            let symbol = id
+                .reference_id
+                .get()
+                .and_then(|reference| self.semantic.symbols().get_reference(reference).symbol_id());
+            self.symbols.extend(symbol);
+        }
+    }
+    let mut reads = Reads {
+        semantic,
+        symbols: HashSet::new(),
+    };
+    reads.visit_expression(expr);
+    reads.symbols
+}
+
+impl<'s, 'a> TailCallOptimization<'s, 'a> {
+    pub fn new(
+        dataflow: &'s Dataflow<'s, 'a>,
+        allocator: &'a Allocator,
+        types: Option<&Types>,
+        accumulate: bool,
+    ) -> Self {
+        let semantic = dataflow.semantic();
+        let mut pass = Self {
+            semantic,
+            ast: AstBuilder::new(allocator),
+            functions: HashMap::new(),
+            remarks: vec![],
+        };
+        let mut names = FreshNames::new(semantic);
+        for node in semantic.nodes().iter() {
+            let AstKind::Function(function) = node.kind() else {
+                continue;
+            };
+            let Some(id) = &function.id else {
+                continue;
+            };
+            let Some(symbol) = id.symbol_id.get() else {
+                continue;
+            };
+            if function.r#type != FunctionType::FunctionDeclaration {
+                continue;
+            }
+            // only functions calling themselves
+            let recursive = semantic
+                .symbols()
+                .get_resolved_references(symbol)
+                .any(|reference| {
+                    semantic
+                        .nodes()
+                        .ancestor_ids(reference.node_id())
+                        .any(|ancestor| ancestor == node.id())
+                });
+            if !recursive {
+                continue;
+            }
+            match pass.rewrite(node.id(), function, symbol, types, accumulate, &mut names) {
+                Ok(rewrite) => {
+                    let message = match &rewrite.accumulator {
+                        Some((name, operator)) => format!(
+                            "`{}` turned into a loop, accumulating `{}` in `{name}`",
+                            id.name,
+                            operator.as_str()
+                        ),
+                        None => format!("`{}` turned into a loop", id.name),
+                    };
+                    pass.remarks.push(Remark::applied(function.span, message));
+                    pass.functions.insert(symbol, rewrite);
+                }
+                Err(reason) => pass.remarks.push(Remark::missed(
+                    function.span,
+                    format!("`{}` kept recursive: {reason}", id.name),
+                )),
+            }
+        }
+        pass
+    }
+
+    /// How to turn `function`, declared at `node` as `symbol`, into a loop.
+    fn rewrite(
+        &self,
+        node: NodeId,
+        function: &Function<'a>,
+        symbol: SymbolId,
+        types: Option<&Types>,
+        accumulate: bool,
+        names: &mut FreshNames,
+    ) -> Result<Rewrite, &'static str> {
+        if function.r#async || function.generator {
+            return Err("it is async or a generator");
+        }
+        let symbols = self.semantic.symbols();
+        if symbols
+            .get_resolved_references(symbol)
+            .any(|reference| reference.is_write())
+        {
+            return Err("it is assigned");
+        }
+        let mut params = vec![];
+        for param in &function.params.items {
+            match &param.pattern.kind {
+                BindingPatternKind::BindingIdentifier(id) => params.push(
+                    id.symbol_id
+                        .get()
+                        .ok_or("its parameters are not plain names")?,
+                ),
+                _ => return Err("its parameters are not plain names"),
+            }
+        }
+        if function.params.rest.is_some() {
+            return Err("its parameters are not plain names");
+        }
+        // a `@param {int32} n` hint converts the argument, an assignment would not
+        let hinted = self
+            .semantic
+            .jsdoc()
+            .get_all_by_node(self.semantic.nodes().get_node(node))
+            .unwrap_or_default()
+            .iter()
+            .any(|jsdoc| jsdoc.tags().iter().any(|tag| tag.kind.parsed() == "param"));
+        if hinted {
+            return Err("its parameters have type hints");
+        }
+        let body = function.body.as_ref().ok_or("it has no body")?;
+
+        let mut scan = Scan {
+            semantic: self.semantic,
+            function: symbol,
+            params: params.len(),
+            tail: HashSet::new(),
+            operators: vec![],
+            nesting: 0,
+            value_returns: false,
+            error: None,
+        };
+        // a call ending a function that returns nothing is a tail call too
+        if let Some(Statement::ExpressionStatement(statement)) = body.statements.last() {
+            if let Expression::CallExpression(call) = statement.expression.without_parentheses() {
+                if scan.is_self_call(&statement.expression) {
+                    scan.tail.insert(NodeAddress::of(&**call));
+                }
+            }
+        }
+        let trailing = !scan.tail.is_empty();
+        scan.visit_function_body(body);
+        if let Some(reason) = scan.error {
+            return Err(reason);
+        }
+
+        let statements: Vec<_> = body.statements.iter().collect();
+        let void = !scan.value_returns;
+        if trailing && !void {
+            return Err("it returns a value but ends with a call to itself");
+        }
+        if !void && !trailing && !terminates(&statements) {
+            return Err("it may return without a value");
+        }
+        if !placeable(&statements, &scan.tail) {
+            return Err("code after a tail call would have to be copied");
+        }
+
+        let accumulator = match scan.operators.first() {
+            None => None,
+            Some(_) if !accumulate => {
+                return Err("a call to itself is an operand of `+` or `*`, which \
+                            --tail-call-accumulator rewrites")
+            }
+            Some(_) if void => return Err("it returns no value to accumulate"),
+            Some(operator) if scan.operators.iter().any(|other| other != operator) => {
+                return Err("calls to itself are operands of both `+` and `*`")
+            }
+            // `+` concatenates strings
+            Some(BinaryOperator::Addition)
+                if !types
+                    .and_then(|types| types.returns(symbol))
+                    .is_some_and(|t| t.is_numeric()) =>
+            {
+                return Err("it may not return a number")
+            }
+            Some(operator) => {
+                let name = names.fresh(&format!("{}_acc", symbols.get_name(symbol)));
+                Some((name, *operator))
+            }
+        };
+        let next = params
+            .iter()
+            .map(|param| names.fresh(&format!("{}_next", symbols.get_name(*param))))
+            .collect();
+        Ok(Rewrite {
+            function: symbol,
+            params,
+            next,
+            accumulator,
+            void,
+        })
+    }
+}
+
+impl<'a> TailCallOptimization<'_, 'a> {
+    fn variable(&self, name: &str) -> Expression<'a> {
+        self.ast
+            .expression_identifier_reference(SPAN, self.ast.atom(name))
+    }
+
+    fn assign(&self, name: &str, value: Expression<'a>) -> Statement<'a> {
+        self.ast.statement_expression(
+            SPAN,
+            self.ast.expression_assignment(
+                SPAN,
+                AssignmentOperator::Assign,
+                self.ast
+                    .simple_assignment_target_identifier_reference(SPAN, self.ast.atom(name))
+                    .into(),
+                value,
+            ),
+        )
+    }
+
+    fn declare(
+        &self,
+        kind: VariableDeclarationKind,
+        name: &str,
+        value: Expression<'a>,
+    ) -> Statement<'a> {
+        Statement::VariableDeclaration(
+            self.ast.alloc_variable_declaration(
+                SPAN,
+                kind,
+                self.ast.vec1(
+                    self.ast.variable_declarator(
+                        SPAN,
+                        kind,
+                        self.ast.binding_pattern(
+                            self.ast
+                                .binding_pattern_kind_binding_identifier(SPAN, self.ast.atom(name)),
+                            None::<TSTypeAnnotation>,
+                            false,
+                        ),
+                        Some(value),
+                        false,
+                    ),
+                ),
+                false,
+            ),
+        )
+    }
+
+    /// Makes the branch of an `if` a block, so statements can be appended to it.
+    fn block<'b>(
+        &self,
+        statement: &'b mut Statement<'a>,
+    ) -> &'b mut allocator::Vec<'a, Statement<'a>> {
+        if !matches!(statement, Statement::BlockStatement(_)) {
+            let inner = self.ast.move_statement(statement);
+            *statement = self.ast.statement_block(SPAN, self.ast.vec1(inner));
+        }
+        let Statement::BlockStatement(block) = statement else {
+            unreachable!();
+        };
+        &mut block.body
+    }
+
+    /// Moves the statements following an `if` with a branch that always returns into its other
+    /// branch, so that reaching the end of a branch means reaching the end of the function.
+    fn place(&self, statements: &mut allocator::Vec<'a, Statement<'a>>) {
+        let mut i = 0;
+        while i < statements.len() {
+            let done = match &mut statements[i] {
+                Statement::ReturnStatement(_) | Statement::ThrowStatement(_) => true,
+                Statement::BlockStatement(block) => {
+                    let done = terminates(&block.body.iter().collect::<Vec<_>>());
+                    self.place(&mut block.body);
+                    done
+                }
+                Statement::IfStatement(_) => {
+                    let rest: Vec<_> = statements.drain(i + 1..).collect();
+                    let Statement::IfStatement(if_) = &mut statements[i] else {
+                        unreachable!();
+                    };
+                    let consequent = terminates(&branch(Some(&if_.consequent)));
+                    let alternate = terminates(&branch(if_.alternate.as_ref()));
+                    let moved = match (consequent, alternate) {
+                        (true, true) => true,
+                        (true, false) => {
+                            let alternate = if_.alternate.get_or_insert_with(|| {
+                                self.ast.statement_block(SPAN, self.ast.vec())
+                            });
+                            self.block(alternate).extend(rest);
+                            true
+                        }
+                        (false, true) => {
+                            self.block(&mut if_.consequent).extend(rest);
+                            true
+                        }
+                        (false, false) => {
+                            statements.extend(rest);
+                            false
+                        }
+                    };
+                    let Statement::IfStatement(if_) = &mut statements[i] else {
+                        unreachable!();
+                    };
+                    self.place(self.block(&mut if_.consequent));
+                    if let Some(alternate) = &mut if_.alternate {
+                        self.place(self.block(alternate));
+                    }
+                    moved
+                }
+                _ => false,
+            };
+            if done {
+                statements.truncate(i + 1);
+            }
+            i += 1;
+        }
+    }
+
+    /// Ends every path of a function returning nothing with a `return`, since falling off the
+    /// end of the loop body would start over.
+    fn close(&self, statements: &mut allocator::Vec<'a, Statement<'a>>) {
+        if terminates(&statements.iter().collect::<Vec<_>>()) {
+            return;
+        }
+        match statements.last_mut() {
+            Some(Statement::IfStatement(if_)) => {
+                self.close(self.block(&mut if_.consequent));
+                let alternate = if_
+                    .alternate
+                    .get_or_insert_with(|| self.ast.statement_block(SPAN, self.ast.vec()));
+                self.close(self.block(alternate));
+            }
+            Some(Statement::BlockStatement(block)) => self.close(&mut block.body),
+            _ => statements.push(self.ast.statement_return(SPAN, None)),
+        }
+    }
+
+    /// Replaces the `return`s of the function with assignments to the parameters, for calls
+    /// to itself, or with the result combined with the accumulator.
+    fn replace(
+        &self,
+        statements: &mut allocator::Vec<'a, Statement<'a>>,
+        function: SymbolId,
+        rewrite: &Rewrite,
+    ) {
+        for statement in statements.iter_mut() {
+            match statement {
+                Statement::BlockStatement(block) => {
+                    self.replace(&mut block.body, function, rewrite)
+                }
+                Statement::IfStatement(if_) => {
+                    self.replace(self.block(&mut if_.consequent), function, rewrite);
+                    if let Some(alternate) = &mut if_.alternate {
+                        self.replace(self.block(alternate), function, rewrite);
+                    }
+                }
+                Statement::ReturnStatement(return_) => {
+                    let Some(argument) = &mut return_.argument else {
+                        continue;
+                    };
+                    let call = tail_call(self.semantic, argument, function)
+                        .map(|(_, operand)| operand.is_none());
+                    if let Some(plain) = call {
+                        let argument = self.ast.move_expression(argument);
+                        *statement = self.jump(argument, plain, rewrite);
+                    } else if let Some((name, operator)) = &rewrite.accumulator {
+                        let value = self.ast.move_expression(argument);
+                        *argument =
+                            self.ast
+                                .expression_binary(SPAN, self.variable(name), *operator, value);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The statements standing for `expr`, a tail call, or with `plain` false the operation
+    /// of the accumulated operator on one: the other operand goes into the accumulator and the
+    /// arguments into the parameters, through temporaries where a later argument still reads
+    /// a parameter or may have side effects.
+    fn jump(&self, expr: Expression<'a>, plain: bool, rewrite: &Rewrite) -> Statement<'a> {
+        let mut statements = self.ast.vec();
+        let mut expr = without_parentheses(expr);
+        if !plain {
+            let Expression::BinaryExpression(binary) = expr else {
+                unreachable!();
+            };
+            let binary = binary.unbox();
+            let (call, operand) = if self.is_tail_call(&binary.left, rewrite) {
+                (binary.left, binary.right)
+            } else {
+                (binary.right, binary.left)
+            };
+            let (name, operator) = rewrite.accumulator.as_ref().unwrap();
+            statements.push(
+                self.assign(
+                    name,
+                    self.ast
+                        .expression_binary(SPAN, self.variable(name), *operator, operand),
+                ),
+            );
+            expr = without_parentheses(call);
+        }
+        let Expression::CallExpression(mut call) = expr else {
+            unreachable!();
+        };
+
+        let arguments: Vec<_> = call
+            .arguments
+            .iter_mut()
+            .map(|argument| self.ast.move_expression(argument.to_expression_mut()))
+            .collect();
+        let read: Vec<_> = arguments
+            .iter()
+            .map(|argument| reads(self.semantic, argument))
+            .collect();
+        let pure = arguments
+            .iter()
+            .all(|argument| is_pure(self.semantic, argument));
+        // temporaries go in last, after the arguments evaluated in place read the parameters
+        let mut updates = vec![];
+        let mut last = vec![];
+        for (i, argument) in arguments.into_iter().enumerate() {
+            let param = rewrite.params[i];
+            let name = self.semantic.symbols().get_name(param);
+            // passing a parameter on unchanged
+            let unchanged = matches!(&argument, Expression::Identifier(id)
+                if id.reference_id.get().and_then(|reference| {
+                    self.semantic.symbols().get_reference(reference).symbol_id()
+                }) == Some(param));
+            if unchanged {
+                continue;
+            }
+            if !pure || read[i + 1..].iter().any(|read| read.contains(&param)) {
+                let next = &rewrite.next[i];
+                statements.push(self.declare(VariableDeclarationKind::Const, next, argument));
+                last.push(self.assign(name, self.variable(next)));
+            } else {
+                updates.push(self.assign(name, argument));
+            }
+        }
+        statements.extend(updates);
+        statements.extend(last);
+        self.ast.statement_block(SPAN, statements)
+    }
+
+    /// `expr` is a call to the function `rewrite` is for.
+    fn is_tail_call(&self, expr: &Expression, rewrite: &Rewrite) -> bool {
+        matches!(expr.without_parentheses(), Expression::CallExpression(call)
+            if is_call_to(self.semantic, call, rewrite.function))
+    }
+
+    fn loop_body(&self, body: &mut FunctionBody<'a>, function: SymbolId, rewrite: &Rewrite) {
+        // `f(x);` ending a function that returns nothing is `return f(x);`
+        if let Some(statement @ Statement::ExpressionStatement(_)) = body.statements.last_mut() {
+            let Statement::ExpressionStatement(expr) = statement else {
+                unreachable!();
+            };
+            if self.is_tail_call(&expr.expression, rewrite) {
+                let call = self.ast.move_expression(&mut expr.expression);
+                *statement = self.ast.statement_return(SPAN, Some(call));
+            }
+        }
+        self.place(&mut body.statements);
+        if rewrite.void {
+            self.close(&mut body.statements);
+        }
+        self.replace(&mut body.statements, function, rewrite);
+
+        let statements = std::mem::replace(&mut body.statements, self.ast.vec());
+        if let Some((name, operator)) = &rewrite.accumulator {
+            let identity = match operator {
+                BinaryOperator::Multiplication => 1.0,
+                _ => 0.0,
+            };
+            body.statements.push(
+                self.declare(
+                    VariableDeclarationKind::Let,
+                    name,
+                    self.ast
+                        .expression_numeric_literal(SPAN, identity, None, NumberBase::Decimal),
+                ),
+            );
+        }
+        body.statements.push(self.ast.statement_for(
+            SPAN,
+            None,
+            None,
+            None,
+            self.ast.statement_block(SPAN, statements),
+        ));
+    }
+}
+
+impl<'a> VisitMut<'a> for TailCallOptimization<'_, 'a> {
+    fn visit_function(&mut self, function: &mut Function<'a>, flags: ScopeFlags) {
+        let symbol = function.id.as_ref().and_then(|id| id.symbol_id.get());
+        if let Some(rewrite) = symbol.and_then(|symbol| self.functions.get(&symbol)) {
+            if let Some(body) = &mut function.body {
+                self.loop_body(body, symbol.unwrap(), rewrite);
+            }
+        }
+        walk_mut::walk_function(self, function, flags);
+    }
+}
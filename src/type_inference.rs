@@ -0,0 +1,783 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use oxc::{
+    ast::{
+        ast::{
+            ArrowFunctionExpression, AssignmentExpression, AssignmentOperator, AssignmentTarget,
+            BinaryOperator, BindingIdentifier, BindingPatternKind, CallExpression, Expression,
+            FormalParameter, Function, FunctionType, IdentifierReference, LogicalExpression,
+            NumericLiteral, Program, ReturnStatement, SimpleAssignmentTarget,
+            TSInterfaceDeclaration, TSSignature, TSType, TSTypeName, UnaryOperator,
+            UpdateExpression, VariableDeclarator,
+        },
+        visit::walk,
+        AstKind, Visit,
+    },
+    semantic::{ScopeFlags, Semantic, SymbolId},
+};
+
+use crate::{
+    dataflow::Dataflow,
+    overflow_analysis::{self, NodeAddress},
+};
+
+/// The C++ representation chosen for a JS value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    Int32,
+    Double,
+    /// Only from a `{uint8}` JSDoc hint.
+    Uint8,
+    /// Only from a `{f32}` JSDoc hint.
+    Float32,
+    Boolean,
+    String,
+    Int32Array,
+    Float64Array,
+    /// An interface, lowered to a C++ struct.
+    Object(SymbolId),
+    /// Left to C++ `auto`.
+    Unknown,
+}
+
+impl Type {
+    /// The C++ spelling of this type, `None` for structs which are named after
+    /// their interface's symbol.
+    pub fn cpp_name(&self) -> Option<&'static str> {
+        match self {
+            Type::Int32 => Some("int32_t"),
+            Type::Double => Some("double"),
+            Type::Uint8 => Some("uint8_t"),
+            Type::Float32 => Some("float"),
+            Type::Boolean => Some("bool"),
+            Type::String => Some("const char*"),
+            Type::Int32Array => Some("int32_t*"),
+            Type::Float64Array => Some("double*"),
+            Type::Object(_) => None,
+            Type::Unknown => Some("auto"),
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Type::Int32 | Type::Double | Type::Uint8 | Type::Float32
+        )
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Type::Int32 | Type::Uint8)
+    }
+
+    pub fn is_typed_array(&self) -> bool {
+        matches!(self, Type::Int32Array | Type::Float64Array)
+    }
+
+    fn join(&self, other: &Type) -> Type {
+        match (self, other) {
+            (a, b) if a == b => a.clone(),
+            (a, b) if a.is_integer() && b.is_integer() => Type::Int32,
+            (a, b) if a.is_numeric() && b.is_numeric() => Type::Double,
+            _ => Type::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int32 => write!(f, "int32"),
+            Type::Uint8 => write!(f, "uint8"),
+            Type::Float32 => write!(f, "f32"),
+            Type::Double => write!(f, "number"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::String => write!(f, "string"),
+            Type::Int32Array => write!(f, "Int32Array"),
+            Type::Float64Array => write!(f, "Float64Array"),
+            Type::Object(_) => write!(f, "object"),
+            Type::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// A declared type: TypeScript `number` only says the value is numeric and
+/// leaves int32 vs double to inference.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Annotation {
+    Number,
+    Exact(Type),
+}
+
+impl std::fmt::Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Annotation::Number => write!(f, "number"),
+            Annotation::Exact(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+impl Annotation {
+    /// The type of a symbol declared with this annotation whose values have type `evidence`.
+    fn refine(&self, evidence: Option<&Type>) -> Type {
+        match (self, evidence) {
+            (Annotation::Number, Some(Type::Int32)) => Type::Int32,
+            (Annotation::Number, _) => Type::Double,
+            (Annotation::Exact(t), _) => t.clone(),
+        }
+    }
+
+    /// Whether a value of type `t` may be stored in a symbol declared with this annotation.
+    fn accepts(&self, t: &Type) -> bool {
+        match self {
+            _ if *t == Type::Unknown => true,
+            Annotation::Number => t.is_numeric(),
+            Annotation::Exact(expected) => {
+                expected == t || (expected.is_numeric() && t.is_numeric())
+            }
+        }
+    }
+}
+
+/// Result of [`TypeInference`]: the type of every declared symbol of a module.
+#[derive(Default)]
+pub struct Types {
+    symbols: HashMap<SymbolId, Type>,
+    /// Symbols whose type comes from a JSDoc numeric hint.
+    hints: HashMap<SymbolId, Type>,
+    /// Fields of the interfaces declared in the module.
+    interfaces: HashMap<SymbolId, Vec<(String, Type)>>,
+    /// Join of the types of the values each function declaration returns.
+    returns: HashMap<SymbolId, Type>,
+    /// The int32 arithmetic whose result may not fit an int32, computed on doubles instead.
+    overflows: HashSet<NodeAddress>,
+}
+
+impl Types {
+    pub fn symbol(&self, symbol_id: SymbolId) -> Type {
+        self.symbols
+            .get(&symbol_id)
+            .cloned()
+            .unwrap_or(Type::Unknown)
+    }
+
+    pub fn expression(&self, semantic: &Semantic, expr: &Expression) -> Type {
+        self.infer(semantic, expr).unwrap_or(Type::Unknown)
+    }
+
+    /// The type promised by a JSDoc hint on `symbol_id`.
+    pub fn hint(&self, symbol_id: SymbolId) -> Option<&Type> {
+        self.hints.get(&symbol_id)
+    }
+
+    /// The type of the values the function declared as `symbol_id` returns, `None` when it
+    /// returns none.
+    pub fn returns(&self, symbol_id: SymbolId) -> Option<&Type> {
+        self.returns.get(&symbol_id)
+    }
+
+    /// Whether `expr` is int32 arithmetic whose result may leave the int32 range, so it has to
+    /// be computed with overflow checks.
+    pub fn overflows(&self, expr: &Expression) -> bool {
+        let address = match expr {
+            Expression::BinaryExpression(expr) => NodeAddress::of(&**expr),
+            Expression::UnaryExpression(expr) => NodeAddress::of(&**expr),
+            Expression::AssignmentExpression(expr) => NodeAddress::of(&**expr),
+            Expression::UpdateExpression(expr) => NodeAddress::of(&**expr),
+            _ => return false,
+        };
+        self.overflows.contains(&address)
+    }
+
+    pub fn interface_fields(&self, symbol_id: SymbolId) -> Option<&[(String, Type)]> {
+        self.interfaces
+            .get(&symbol_id)
+            .map(|fields| fields.as_slice())
+    }
+
+    /// `None` while an identifier in `expr` has no type yet.
+    fn infer(&self, semantic: &Semantic, expr: &Expression) -> Option<Type> {
+        let t = match expr {
+            Expression::NumericLiteral(x) => {
+                if is_int32_literal(x) {
+                    Type::Int32
+                } else {
+                    Type::Double
+                }
+            }
+            Expression::BooleanLiteral(_) => Type::Boolean,
+            Expression::StringLiteral(_) => Type::String,
+            Expression::Identifier(id) => match reference_symbol(semantic, id) {
+                Some(symbol_id) => self.symbols.get(&symbol_id)?.clone(),
+                None => Type::Unknown,
+            },
+            Expression::ParenthesizedExpression(expr) => self.infer(semantic, &expr.expression)?,
+            Expression::UnaryExpression(expr) => match expr.operator {
+                UnaryOperator::LogicalNot => Type::Boolean,
+                UnaryOperator::BitwiseNot => Type::Int32,
+                UnaryOperator::UnaryNegation
+                    if self.overflows.contains(&NodeAddress::of(&**expr)) =>
+                {
+                    Type::Double
+                }
+                UnaryOperator::UnaryNegation | UnaryOperator::UnaryPlus => {
+                    match self.infer(semantic, &expr.argument)? {
+                        t if t.is_numeric() => t,
+                        _ => Type::Double,
+                    }
+                }
+                _ => Type::Unknown,
+            },
+            Expression::BinaryExpression(expr) => {
+                let left = self.infer(semantic, &expr.left)?;
+                let right = self.infer(semantic, &expr.right)?;
+                match binary_type(expr.operator, &left, &right) {
+                    Type::Int32 if self.overflows.contains(&NodeAddress::of(&**expr)) => {
+                        Type::Double
+                    }
+                    t => t,
+                }
+            }
+            Expression::LogicalExpression(expr) => self.logical(semantic, expr)?,
+            Expression::ConditionalExpression(expr) => known_join(
+                self.infer(semantic, &expr.consequent),
+                self.infer(semantic, &expr.alternate),
+            )?,
+            Expression::AssignmentExpression(expr) => self.assigned(semantic, expr)?,
+            Expression::UpdateExpression(expr) => match &expr.argument {
+                SimpleAssignmentTarget::AssignmentTargetIdentifier(id) => {
+                    match reference_symbol(semantic, id) {
+                        Some(symbol_id) => self.symbols.get(&symbol_id)?.clone(),
+                        None => Type::Unknown,
+                    }
+                }
+                _ => Type::Unknown,
+            },
+            Expression::CallExpression(call) => match &call.callee {
+                Expression::Identifier(id) if id.name == "Number" => Type::Int32,
+                Expression::Identifier(id) => match reference_symbol(semantic, id) {
+                    // a function that has returned nothing yet has no type yet
+                    Some(symbol_id)
+                        if matches!(
+                            semantic.symbol_declaration(symbol_id).kind(),
+                            AstKind::Function(_)
+                        ) =>
+                    {
+                        self.returns.get(&symbol_id)?.clone()
+                    }
+                    _ => Type::Unknown,
+                },
+                Expression::StaticMemberExpression(callee) => {
+                    match (&callee.object, callee.property.name.as_str()) {
+                        (Expression::Identifier(object), "floor") if object.name == "Math" => {
+                            if self.overflows.contains(&NodeAddress::of(&**call)) {
+                                Type::Double
+                            } else {
+                                Type::Int32
+                            }
+                        }
+                        (Expression::Identifier(object), "random" | "sqrt")
+                            if object.name == "Math" =>
+                        {
+                            Type::Double
+                        }
+                        _ => Type::Unknown,
+                    }
+                }
+                _ => Type::Unknown,
+            },
+            Expression::NewExpression(expr) => match &expr.callee {
+                Expression::Identifier(id) if id.name == "Int32Array" => Type::Int32Array,
+                Expression::Identifier(id) if id.name == "Float64Array" => Type::Float64Array,
+                _ => Type::Unknown,
+            },
+            Expression::ComputedMemberExpression(expr) => match &expr.object {
+                Expression::StaticMemberExpression(object)
+                    if matches!(&object.object, Expression::Identifier(id) if id.name == "process")
+                        && object.property.name == "argv" =>
+                {
+                    Type::String
+                }
+                object => match self.infer(semantic, object)? {
+                    Type::Int32Array => Type::Int32,
+                    Type::Float64Array => Type::Double,
+                    _ => Type::Unknown,
+                },
+            },
+            Expression::StaticMemberExpression(expr) => match self.infer(semantic, &expr.object)? {
+                Type::Object(symbol_id) => self
+                    .interface_fields(symbol_id)
+                    .and_then(|fields| {
+                        fields
+                            .iter()
+                            .find(|(field, _)| field == expr.property.name.as_str())
+                    })
+                    .map_or(Type::Unknown, |(_, t)| t.clone()),
+                t if t.is_typed_array() && expr.property.name == "length" => Type::Int32,
+                _ => Type::Unknown,
+            },
+            Expression::TSAsExpression(expr) => {
+                self.cast(semantic, &expr.expression, &expr.type_annotation)?
+            }
+            Expression::TSSatisfiesExpression(expr) => self.infer(semantic, &expr.expression)?,
+            Expression::TSTypeAssertion(expr) => {
+                self.cast(semantic, &expr.expression, &expr.type_annotation)?
+            }
+            Expression::TSNonNullExpression(expr) => self.infer(semantic, &expr.expression)?,
+            _ => Type::Unknown,
+        };
+
+        Some(t)
+    }
+
+    fn logical(&self, semantic: &Semantic, expr: &LogicalExpression) -> Option<Type> {
+        known_join(
+            self.infer(semantic, &expr.left),
+            self.infer(semantic, &expr.right),
+        )
+    }
+
+    fn cast(&self, semantic: &Semantic, expr: &Expression, ts_type: &TSType) -> Option<Type> {
+        let inner = self.infer(semantic, expr)?;
+        Some(match annotation(semantic, ts_type) {
+            Some(annotation) => annotation.refine(Some(&inner)),
+            None => inner,
+        })
+    }
+
+    /// Type of the value stored by an assignment.
+    fn assigned(&self, semantic: &Semantic, expr: &AssignmentExpression) -> Option<Type> {
+        let right = self.infer(semantic, &expr.right)?;
+        let Some(operator) = expr.operator.to_binary_operator() else {
+            return Some(right);
+        };
+        let left = match &expr.left {
+            AssignmentTarget::AssignmentTargetIdentifier(id) => {
+                match reference_symbol(semantic, id) {
+                    Some(symbol_id) => self.symbols.get(&symbol_id)?.clone(),
+                    None => Type::Unknown,
+                }
+            }
+            _ => Type::Unknown,
+        };
+        match binary_type(operator, &left, &right) {
+            Type::Int32 if self.overflows.contains(&NodeAddress::of(expr)) => Some(Type::Double),
+            t => Some(t),
+        }
+    }
+}
+
+/// The join of the types of two branches, of the known one while the other has no type yet, as a
+/// recursive call before its function returned anything.
+fn known_join(a: Option<Type>, b: Option<Type>) -> Option<Type> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.join(&b)),
+        (Some(t), None) | (None, Some(t)) => Some(t),
+        (None, None) => None,
+    }
+}
+
+fn binary_type(operator: BinaryOperator, left: &Type, right: &Type) -> Type {
+    match operator {
+        BinaryOperator::Addition
+        | BinaryOperator::Subtraction
+        | BinaryOperator::Multiplication
+        | BinaryOperator::Remainder => {
+            if left.is_integer() && right.is_integer() {
+                Type::Int32
+            } else if left.is_numeric() && right.is_numeric() {
+                Type::Double
+            } else {
+                Type::Unknown
+            }
+        }
+        BinaryOperator::Division | BinaryOperator::Exponential => {
+            if left.is_numeric() && right.is_numeric() {
+                Type::Double
+            } else {
+                Type::Unknown
+            }
+        }
+        BinaryOperator::ShiftLeft
+        | BinaryOperator::ShiftRight
+        | BinaryOperator::BitwiseAnd
+        | BinaryOperator::BitwiseOR
+        | BinaryOperator::BitwiseXOR => Type::Int32,
+        BinaryOperator::ShiftRightZeroFill => Type::Double,
+        _ => Type::Boolean,
+    }
+}
+
+/// Whether `literal` is a whole number in the int32 range written without a decimal point;
+/// `1.0` asks for a double.
+pub fn is_int32_literal(literal: &NumericLiteral) -> bool {
+    literal.value.fract() == 0.0
+        && literal.value >= i32::MIN as f64
+        && literal.value <= i32::MAX as f64
+        && !literal.raw.as_ref().is_some_and(|raw| raw.contains('.'))
+}
+
+fn reference_symbol(semantic: &Semantic, id: &IdentifierReference) -> Option<SymbolId> {
+    semantic
+        .symbols()
+        .get_reference(id.reference_id.get()?)
+        .symbol_id()
+}
+
+/// Maps a TypeScript type to the representation it asks for, or `None` when it
+/// says nothing about the C++ type.
+pub fn annotation(semantic: &Semantic, ts_type: &TSType) -> Option<Annotation> {
+    match ts_type {
+        TSType::TSNumberKeyword(_) => Some(Annotation::Number),
+        TSType::TSBooleanKeyword(_) => Some(Annotation::Exact(Type::Boolean)),
+        TSType::TSStringKeyword(_) => Some(Annotation::Exact(Type::String)),
+        TSType::TSParenthesizedType(t) => annotation(semantic, &t.type_annotation),
+        TSType::TSTypeReference(reference) => {
+            let TSTypeName::IdentifierReference(id) = &reference.type_name else {
+                return None;
+            };
+            let Some(symbol_id) = reference_symbol(semantic, id) else {
+                return match id.name.as_str() {
+                    "Int32Array" => Some(Annotation::Exact(Type::Int32Array)),
+                    "Float64Array" => Some(Annotation::Exact(Type::Float64Array)),
+                    _ => None,
+                };
+            };
+            match semantic.symbol_declaration(symbol_id).kind() {
+                AstKind::TSTypeAliasDeclaration(alias) => {
+                    annotation(semantic, &alias.type_annotation)
+                }
+                AstKind::TSInterfaceDeclaration(_) => {
+                    Some(Annotation::Exact(Type::Object(symbol_id)))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Infers a [`Type`] for every variable of a module from its initializer, the
+/// values assigned to it, and its type annotation. Annotations are also checked:
+/// assigning a value of a different type is an error.
+pub struct TypeInference<'s, 'a> {
+    semantic: &'s Semantic<'a>,
+    types: Types,
+    /// Join of the types of all values stored in each symbol.
+    evidence: HashMap<SymbolId, Type>,
+    annotations: HashMap<SymbolId, Annotation>,
+    /// The function declarations around the node being visited, `None` for other functions.
+    functions: Vec<Option<SymbolId>>,
+    changed: bool,
+    errors: Vec<String>,
+}
+
+impl<'s, 'a> TypeInference<'s, 'a> {
+    pub fn new(semantic: &'s Semantic<'a>) -> Self {
+        Self {
+            semantic,
+            types: Types::default(),
+            evidence: HashMap::new(),
+            annotations: HashMap::new(),
+            functions: vec![],
+            changed: false,
+            errors: vec![],
+        }
+    }
+
+    /// Gives the imported bindings the types of the bindings they import, from the modules
+    /// already inferred.
+    pub fn with_imports(mut self, imports: HashMap<SymbolId, Type>) -> Self {
+        self.types.symbols.extend(imports);
+        self
+    }
+
+    pub fn infer(mut self, program: &Program<'a>) -> Result<Types, String> {
+        // arithmetic that may overflow makes doubles, which may make more arithmetic overflow
+        let dataflow = Dataflow::new(self.semantic);
+        loop {
+            loop {
+                self.changed = false;
+                self.visit_program(program);
+                if !self.changed {
+                    break;
+                }
+            }
+            let overflows = overflow_analysis::overflows(&dataflow, &self.types);
+            if overflows.is_subset(&self.types.overflows) {
+                break;
+            }
+            self.types.overflows.extend(overflows);
+        }
+
+        // check annotations once every type is final
+        self.visit_program(program);
+        match self.errors.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(self.types),
+        }
+    }
+
+    fn record(&mut self, symbol_id: SymbolId, value: Option<Type>, name: &str) {
+        let Some(value) = value else {
+            return;
+        };
+
+        if let Some(annotation) = self.annotations.get(&symbol_id) {
+            if !annotation.accepts(&value) {
+                let error = format!("'{name}' is declared as {annotation} but assigned a {value}");
+                if !self.errors.contains(&error) {
+                    self.errors.push(error);
+                }
+            }
+        }
+
+        let evidence = match self.evidence.get(&symbol_id) {
+            Some(old) => old.join(&value),
+            None => value,
+        };
+        if self.evidence.get(&symbol_id) != Some(&evidence) {
+            self.evidence.insert(symbol_id, evidence);
+            self.changed = true;
+        }
+
+        let t = match self.annotations.get(&symbol_id) {
+            Some(annotation) => annotation.refine(self.evidence.get(&symbol_id)),
+            None => self.evidence[&symbol_id].clone(),
+        };
+        self.types.symbols.insert(symbol_id, t);
+    }
+
+    fn record_return(&mut self, function: SymbolId, value: Option<Type>) {
+        let Some(value) = value else {
+            return;
+        };
+        let returns = match self.types.returns.get(&function) {
+            Some(old) => old.join(&value),
+            None => value,
+        };
+        if self.types.returns.get(&function) != Some(&returns) {
+            self.types.returns.insert(function, returns);
+            self.changed = true;
+        }
+    }
+
+    /// A `@type {int32}`, `@param {int32} name` or `@crush-int` tag on the
+    /// declaration of `symbol_id`.
+    fn jsdoc_hint(&self, symbol_id: SymbolId) -> Option<Type> {
+        let name = self.semantic.symbols().get_name(symbol_id);
+        let declaration = self.semantic.symbols().get_declaration(symbol_id);
+
+        for node in self.semantic.nodes().ancestors(declaration) {
+            let parameter = match node.kind() {
+                AstKind::VariableDeclarator(_)
+                | AstKind::VariableDeclaration(_)
+                | AstKind::ExportNamedDeclaration(_)
+                | AstKind::FormalParameter(_) => false,
+                AstKind::FormalParameters(_) => continue,
+                AstKind::Function(_) | AstKind::ArrowFunctionExpression(_) => true,
+                _ => break,
+            };
+
+            for jsdoc in self
+                .semantic
+                .jsdoc()
+                .get_all_by_node(node)
+                .unwrap_or_default()
+            {
+                for tag in jsdoc.tags() {
+                    let hint = match tag.kind.parsed() {
+                        "crush-int" if !parameter => Some("int32"),
+                        "type" if !parameter => tag.r#type().map(|t| t.parsed()),
+                        "param" if parameter => match tag.type_name_comment() {
+                            (Some(t), Some(param), _) if param.parsed() == name => Some(t.parsed()),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    match hint {
+                        Some("int32") => return Some(Type::Int32),
+                        Some("uint8") => return Some(Type::Uint8),
+                        Some("f32") => return Some(Type::Float32),
+                        _ => {}
+                    }
+                }
+            }
+
+            if parameter {
+                break;
+            }
+        }
+
+        None
+    }
+
+    fn declare(&mut self, id: &BindingIdentifier, annotation: Option<Annotation>) {
+        let Some(symbol_id) = id.symbol_id.get() else {
+            return;
+        };
+        let annotation = match self.jsdoc_hint(symbol_id) {
+            Some(hint) => {
+                self.types.hints.insert(symbol_id, hint.clone());
+                Some(Annotation::Exact(hint))
+            }
+            None => annotation,
+        };
+        if let Some(annotation) = annotation {
+            if let Entry::Vacant(entry) = self.types.symbols.entry(symbol_id) {
+                entry.insert(annotation.refine(None));
+                self.changed = true;
+            }
+            self.annotations.insert(symbol_id, annotation);
+        }
+    }
+}
+
+impl<'a> Visit<'a> for TypeInference<'_, 'a> {
+    fn visit_variable_declarator(&mut self, decl: &VariableDeclarator<'a>) {
+        if let BindingPatternKind::BindingIdentifier(id) = &decl.id.kind {
+            let annotation = decl
+                .id
+                .type_annotation
+                .as_ref()
+                .and_then(|t| annotation(self.semantic, &t.type_annotation));
+            self.declare(id, annotation);
+
+            if let (Some(symbol_id), Some(init)) = (id.symbol_id.get(), &decl.init) {
+                let value = match init {
+                    Expression::ObjectExpression(_) => match self.types.symbols.get(&symbol_id) {
+                        Some(t @ Type::Object(_)) => Some(t.clone()),
+                        _ => Some(Type::Unknown),
+                    },
+                    init => self.types.infer(self.semantic, init),
+                };
+                self.record(symbol_id, value, &id.name);
+            }
+        }
+
+        walk::walk_variable_declarator(self, decl);
+    }
+
+    fn visit_formal_parameter(&mut self, param: &FormalParameter<'a>) {
+        if let BindingPatternKind::BindingIdentifier(id) = &param.pattern.kind {
+            let annotation = param
+                .pattern
+                .type_annotation
+                .as_ref()
+                .and_then(|t| annotation(self.semantic, &t.type_annotation));
+            self.declare(id, annotation);
+        }
+
+        walk::walk_formal_parameter(self, param);
+    }
+
+    fn visit_function(&mut self, function: &Function<'a>, flags: ScopeFlags) {
+        let symbol_id = match function.r#type {
+            FunctionType::FunctionDeclaration => {
+                function.id.as_ref().and_then(|id| id.symbol_id.get())
+            }
+            _ => None,
+        };
+        self.functions.push(symbol_id);
+        walk::walk_function(self, function, flags);
+        self.functions.pop();
+    }
+
+    fn visit_arrow_function_expression(&mut self, function: &ArrowFunctionExpression<'a>) {
+        self.functions.push(None);
+        walk::walk_arrow_function_expression(self, function);
+        self.functions.pop();
+    }
+
+    fn visit_return_statement(&mut self, statement: &ReturnStatement<'a>) {
+        if let (Some(Some(function)), Some(argument)) = (self.functions.last(), &statement.argument)
+        {
+            let value = self.types.infer(self.semantic, argument);
+            self.record_return(*function, value);
+        }
+
+        walk::walk_return_statement(self, statement);
+    }
+
+    /// Parameters take the types of the arguments of direct calls.
+    fn visit_call_expression(&mut self, call: &CallExpression<'a>) {
+        if let Expression::Identifier(callee) = &call.callee {
+            let declaration = reference_symbol(self.semantic, callee)
+                .map(|symbol_id| self.semantic.symbol_declaration(symbol_id).kind());
+            if let Some(AstKind::Function(function)) = declaration {
+                for (param, argument) in function.params.items.iter().zip(&call.arguments) {
+                    let (BindingPatternKind::BindingIdentifier(id), Some(argument)) =
+                        (&param.pattern.kind, argument.as_expression())
+                    else {
+                        continue;
+                    };
+                    if let Some(symbol_id) = id.symbol_id.get() {
+                        let value = self.types.infer(self.semantic, argument);
+                        self.record(symbol_id, value, &id.name);
+                    }
+                }
+            }
+        }
+
+        walk::walk_call_expression(self, call);
+    }
+
+    fn visit_assignment_expression(&mut self, expr: &AssignmentExpression<'a>) {
+        if let AssignmentTarget::AssignmentTargetIdentifier(id) = &expr.left {
+            if let Some(symbol_id) = reference_symbol(self.semantic, id) {
+                let value = match (&expr.right, expr.operator) {
+                    (Expression::ObjectExpression(_), AssignmentOperator::Assign) => {
+                        match self.types.symbols.get(&symbol_id) {
+                            Some(t @ Type::Object(_)) => Some(t.clone()),
+                            _ => Some(Type::Unknown),
+                        }
+                    }
+                    _ => self.types.assigned(self.semantic, expr),
+                };
+                self.record(symbol_id, value, &id.name);
+            }
+        }
+
+        walk::walk_assignment_expression(self, expr);
+    }
+
+    /// `x++` stores a double once `x` may leave the int32 range.
+    fn visit_update_expression(&mut self, expr: &UpdateExpression<'a>) {
+        if let SimpleAssignmentTarget::AssignmentTargetIdentifier(id) = &expr.argument {
+            if let Some(symbol_id) = reference_symbol(self.semantic, id) {
+                if self.types.overflows.contains(&NodeAddress::of(expr)) {
+                    self.record(symbol_id, Some(Type::Double), &id.name);
+                }
+            }
+        }
+
+        walk::walk_update_expression(self, expr);
+    }
+
+    fn visit_ts_interface_declaration(&mut self, decl: &TSInterfaceDeclaration<'a>) {
+        let Some(symbol_id) = decl.id.symbol_id.get() else {
+            return;
+        };
+        if self.types.interfaces.contains_key(&symbol_id) {
+            return;
+        }
+
+        let mut fields = vec![];
+        for signature in &decl.body.body {
+            let TSSignature::TSPropertySignature(property) = signature else {
+                continue;
+            };
+            let Some(key) = property.key.static_name() else {
+                continue;
+            };
+            let t = property
+                .type_annotation
+                .as_ref()
+                .and_then(|t| annotation(self.semantic, &t.type_annotation))
+                .map_or(Type::Unknown, |annotation| annotation.refine(None));
+            fields.push((key.to_string(), t));
+        }
+        self.types.interfaces.insert(symbol_id, fields);
+        self.changed = true;
+    }
+}
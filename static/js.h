@@ -1,15 +1,37 @@
 #include <stdint.h>
 #include <time.h>
 
-int32_t Number(char* str) {
+// before a loop whose iterations never touch memory that another one writes
+#if defined(_MSC_VER)
+#define JS_IVDEP __pragma(loop(ivdep))
+#elif defined(__clang__)
+#define JS_IVDEP _Pragma("clang loop vectorize(assume_safety)")
+#else
+#define JS_IVDEP _Pragma("GCC ivdep")
+#endif
+
+// around an if test that a profile saw go mostly one way
+#if defined(__GNUC__) || defined(__clang__)
+#define JS_LIKELY(x) __builtin_expect(!!(x), 1)
+#define JS_UNLIKELY(x) __builtin_expect(!!(x), 0)
+#else
+#define JS_LIKELY(x) (x)
+#define JS_UNLIKELY(x) (x)
+#endif
+
+int32_t Number(const char* str) {
     return atoi(str);
 }
 
 namespace Math {
-    int32_t floor(double x) {
+    double floor(double x) {
         return ::floor(x);
     }
 
+    double sqrt(double x) {
+        return ::sqrt(x);
+    }
+
     double random() {
         double r = ((double)rand() / (double)(RAND_MAX));
         return r;
@@ -21,13 +43,190 @@ namespace console {
         printf("%d\n", x);
     }
 
-    void log(char* x) {
+    void log(double x) {
+        if (isnan(x)) {
+            printf("NaN\n");
+            return;
+        }
+        if (isinf(x)) {
+            printf(x < 0 ? "-Infinity\n" : "Infinity\n");
+            return;
+        }
+        // shortest representation that reads back as the same double, like JS
+        char buffer[32];
+        int precision = 1;
+        for (; precision <= 17; precision++) {
+            snprintf(buffer, sizeof(buffer), "%.*e", precision - 1, x);
+            if (strtod(buffer, NULL) == x)
+                break;
+        }
+        if (x == trunc(x) && fabs(x) < 1e21) {
+            // whole numbers print without exponent: the shortest digits, padded with zeros
+            if (x == 0) {
+                printf(signbit(x) ? "-0\n" : "0\n");
+                return;
+            }
+            int exponent = atoi(strchr(buffer, 'e') + 1);
+            char digits[24];
+            int length = 0;
+            for (char* c = buffer; *c != 'e'; c++)
+                if (*c >= '0' && *c <= '9')
+                    digits[length++] = *c;
+            while (length <= exponent)
+                digits[length++] = '0';
+            digits[length] = 0;
+            printf("%s%s\n", x < 0 ? "-" : "", digits);
+            return;
+        }
+        snprintf(buffer, sizeof(buffer), "%.*g", precision, x);
+        printf("%s\n", buffer);
+    }
+
+    void log(bool x) {
+        printf("%s\n", x ? "true" : "false");
+    }
+
+    void log(const char* x) {
         printf("%s\n", x);
     }
 }
 
+// typed arrays keep their length in a header just before the elements, which start zeroed
+template <typename T>
+T* js_typed_array(int32_t size) {
+    if (size < 0) {
+        fprintf(stderr, "invalid typed array length %d\n", size);
+        abort();
+    }
+    char* header = (char*)calloc(1, 8 + (size_t)size * sizeof(T));
+    *(int32_t*)header = size;
+    return (T*)(header + 8);
+}
+
+// a typed array of a constant length n on the stack, laid out like js_typed_array
+template <typename T, int32_t n>
+struct js_stack_array {
+    int32_t header[2];
+    T elements[n];
+};
+
+template <typename T>
+int32_t js_length(const T* array) {
+    return *(const int32_t*)((const char*)array - 8);
+}
+
+template <typename T>
+bool js_in_bounds(const T* array, double index) {
+    return index >= 0 && index < js_length(array) && index == trunc(index);
+}
+
+// element reads the compiler could not prove in bounds: past the end JS reads undefined, which
+// arithmetic sees as NaN
+double js_checked(const double* array, double index) {
+    return js_in_bounds(array, index) ? array[(int32_t)index] : NAN;
+}
+
+// an int32_t cannot hold NaN, so reading past the end of an Int32Array aborts instead
+int32_t js_checked(const int32_t* array, double index) {
+    if (!js_in_bounds(array, index)) {
+        fflush(stdout);
+        fprintf(stderr, "index %.17g out of bounds for length %d\n", index, js_length(array));
+        abort();
+    }
+    return array[(int32_t)index];
+}
+
+// element stores the compiler could not prove in bounds: JS ignores those past the end
+template <typename T>
+T& js_checked_store(T* array, double index) {
+    static T ignored;
+    return js_in_bounds(array, index) ? array[(int32_t)index] : ignored;
+}
+
 int32_t* js_constructor_Int32Array(int32_t size) {
-    return new int[size];
+    return js_typed_array<int32_t>(size);
+}
+
+double* js_constructor_Float64Array(int32_t size) {
+    return js_typed_array<double>(size);
+}
+
+// --checked-hints: stores into JSDoc-hinted variables abort when the value does not fit
+static void js_hint_failed(const char* name, const char* type, double x) {
+    fprintf(stderr, "hint violated: %s is declared %s but was assigned %.17g\n", name, type, x);
+    abort();
+}
+
+int32_t js_check_int32(double x, const char* name) {
+    if (!(x >= INT32_MIN && x <= INT32_MAX) || x != (double)(int32_t)x)
+        js_hint_failed(name, "int32", x);
+    return (int32_t)x;
+}
+
+uint8_t js_check_uint8(double x, const char* name) {
+    if (!(x >= 0 && x <= 255) || x != (double)(uint8_t)x)
+        js_hint_failed(name, "uint8", x);
+    return (uint8_t)x;
+}
+
+float js_check_f32(double x, const char* name) {
+    if (!isinf(x) && isinf((float)x))
+        js_hint_failed(name, "f32", x);
+    return (float)x;
+}
+
+// int32 arithmetic the compiler cannot prove to stay in the int32 range: computed exactly in
+// 64 bits, then rounded to the double JS would have produced
+double js_int32_add(int32_t a, int32_t b) {
+    return (double)((int64_t)a + b);
+}
+
+double js_int32_sub(int32_t a, int32_t b) {
+    return (double)((int64_t)a - b);
+}
+
+double js_int32_mul(int32_t a, int32_t b) {
+    int64_t product = (int64_t)a * b;
+    // a zero times a negative number is -0
+    if (product == 0 && (a < 0 || b < 0)) {
+        return -0.0;
+    }
+    return (double)product;
+}
+
+// a % b is NaN for a zero b and -0 for a negative multiple a of b; INT32_MIN % -1 would trap
+double js_int32_mod(int32_t a, int32_t b) {
+    if (b == 0) {
+        return NAN;
+    }
+    int32_t r = b == -1 ? 0 : a % b;
+    return r == 0 && a < 0 ? -0.0 : r;
+}
+
+// x / d rounded down and x % d for a whole number x in 0..2^31 and a constant d, with the
+// multiplier m and the shift s the compiler picked for d
+int32_t js_udiv(uint32_t x, uint64_t m, int s) {
+    return (int32_t)((x * m) >> s);
+}
+
+int32_t js_umod(uint32_t x, uint32_t d, uint64_t m, int s) {
+    return (int32_t)(x - (uint32_t)((x * m) >> s) * d);
+}
+
+// Math.floor(x / d) for a positive d: the C++ division rounds toward zero
+int32_t js_floor_div(int32_t x, int32_t d) {
+    int32_t q = x / d;
+    return x % d < 0 ? q - 1 : q;
+}
+
+// ECMAScript ToInt32, for doubles used as operands of bitwise operators
+int32_t js_to_int32(double x) {
+    if (!isfinite(x))
+        return 0;
+    double m = fmod(trunc(x), 4294967296.0);
+    if (m < 0)
+        m += 4294967296.0;
+    return (int32_t)(uint32_t)m;
 }
 
 namespace process {
@@ -45,4 +244,56 @@ namespace process {
 
         srand(time(NULL));
     }
-}
\ No newline at end of file
+}
+
+// --profile-generate: one counter per site, written to the profile file when the program exits
+struct js_profile_counter {
+    const char* kind;
+    const char* site;
+    uint64_t counts[2];
+    js_profile_counter* next;
+    js_profile_counter(const char* kind, const char* site);
+};
+
+static struct js_profile_file {
+    const char* path;
+    js_profile_counter* counters;
+
+    ~js_profile_file() {
+        if (!path)
+            return;
+        FILE* file = fopen(path, "w");
+        if (!file) {
+            fprintf(stderr, "cannot write the profile %s\n", path);
+            return;
+        }
+        for (js_profile_counter* counter = counters; counter; counter = counter->next)
+            fprintf(file, "%s %s %llu %llu\n", counter->kind, counter->site,
+                (unsigned long long)counter->counts[0], (unsigned long long)counter->counts[1]);
+        fclose(file);
+    }
+} js_profile;
+
+js_profile_counter::js_profile_counter(const char* kind, const char* site)
+    : kind(kind), site(site), counts{0, 0}, next(js_profile.counters) {
+    js_profile.counters = this;
+}
+
+static void js_profile_open(const char* path) {
+    js_profile.path = path;
+}
+
+// the counter of a site, registered the first time the site runs
+#define JS_PROFILE_SITE(kind, site) \
+    ([]() -> js_profile_counter& { static js_profile_counter counter(kind, site); return counter; }())
+
+bool js_profile_branch(js_profile_counter& counter, bool taken) {
+    counter.counts[taken ? 0 : 1]++;
+    return taken;
+}
+
+double js_profile_argument(js_profile_counter& counter, double x) {
+    bool int32 = x >= INT32_MIN && x <= INT32_MAX && x == (double)(int32_t)x;
+    counter.counts[int32 ? 0 : 1]++;
+    return x;
+}
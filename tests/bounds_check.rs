@@ -0,0 +1,191 @@
+mod common;
+
+fn compile(name: &str, source: &str) -> String {
+    common::compile(&format!("bounds_check_{name}"), source)
+}
+
+#[test]
+fn length_loop_is_unchecked() {
+    let cpp = compile(
+        "length_loop_is_unchecked",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Float64Array(n);
+        for (let i = 0; i < a.length; i++) {
+            a[i] = i / 2;
+        }
+        console.log(a.length);
+        ",
+    );
+    assert!(cpp.contains("console::log(js_length(a));"), "{cpp}");
+    assert!(cpp.contains("a_restrict[i] = "), "{cpp}");
+    assert!(!cpp.contains("js_checked"), "{cpp}");
+}
+
+#[test]
+fn allocation_size_bounds_the_loop() {
+    let cpp = compile(
+        "allocation_size_bounds_the_loop",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(n);
+        const b = new Int32Array(n + 1);
+        for (let i = 0; i < n; i++) {
+            b[i + 1] = a[i] + 1;
+        }
+        console.log(b[n]);
+        ",
+    );
+    assert!(
+        cpp.contains("b_restrict[(i+1)] = js_to_int32(js_int32_add(a_restrict[i], 1));"),
+        "{cpp}"
+    );
+    assert!(cpp.contains("console::log(js_checked(b, n));"), "{cpp}");
+}
+
+#[test]
+fn running_modulo_is_unchecked() {
+    let cpp = compile(
+        "running_modulo_is_unchecked",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(7);
+        for (let i = 0; i < n; i++) {
+            a[i % 7] += i;
+        }
+        console.log(a[3]);
+        ",
+    );
+    assert!(cpp.contains("a_restrict[i_modulo] += i;"), "{cpp}");
+    assert!(cpp.contains("console::log(a[3]);"), "{cpp}");
+}
+
+#[test]
+fn reset_counter_is_unchecked() {
+    let cpp = compile(
+        "reset_counter_is_unchecked",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(n);
+        let j = 0;
+        for (let i = 0; i < a.length; i++) {
+            a[j] += i;
+            j++;
+            if (j >= a.length) {
+                j = 0;
+            }
+        }
+        console.log(a[0]);
+        ",
+    );
+    assert!(cpp.contains("a_restrict[(int32_t)j] += i;"), "{cpp}");
+}
+
+#[test]
+fn unproven_accesses_are_checked() {
+    let cpp = compile(
+        "unproven_accesses_are_checked",
+        "
+        const n = Number(process.argv[2]);
+        const k = Number(process.argv[3]);
+        const a = new Int32Array(n);
+        for (let i = 0; i <= a.length; i++) {
+            a[i] = i;
+        }
+        a[k] = 1;
+        console.log(a[k - 1]);
+        ",
+    );
+    assert!(
+        cpp.contains("js_checked_store(a, i) = js_to_int32(i);"),
+        "{cpp}"
+    );
+    assert!(cpp.contains("js_checked_store(a, k) = 1;"), "{cpp}");
+    assert!(
+        cpp.contains("console::log(js_checked(a, js_int32_sub(k, 1)));"),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn reassigned_array_is_checked() {
+    let cpp = compile(
+        "reassigned_array_is_checked",
+        "
+        const n = Number(process.argv[2]);
+        let a = new Int32Array(n);
+        for (let i = 0; i < n; i++) {
+            a = new Int32Array(i);
+            a[i] = 1;
+        }
+        console.log(a[0]);
+        ",
+    );
+    assert!(cpp.contains("js_checked_store(a, i) = 1;"), "{cpp}");
+}
+
+#[test]
+fn out_of_bounds_accesses_behave_like_js() {
+    let (dir, _) = common::run(
+        "bounds_check_out_of_bounds_accesses_behave_like_js",
+        &[(
+            "main.js",
+            "
+            const n = Number(process.argv[2]);
+            const a = new Float64Array(2);
+            a[n] = 1;
+            a[0.5] = 2;
+            console.log(a[0] + a[1]);
+            console.log(a[n]);
+            const b = new Int32Array(2);
+            b[n] = 3;
+            console.log(b[n - 1]);
+            console.log(b[n]);
+            ",
+        )],
+        &[],
+    );
+    if let Some(error) = common::execute_failing(&dir, &["2"]) {
+        assert_eq!(error, "index 2 out of bounds for length 2\n");
+    }
+}
+
+#[test]
+fn unchecked_loops_match_js() {
+    let (dir, _) = common::run(
+        "bounds_check_unchecked_loops_match_js",
+        &[(
+            "main.js",
+            "
+            const n = Number(process.argv[2]);
+            const a = new Int32Array(n);
+            const b = new Int32Array(n + 1);
+            for (let i = 0; i < n; i++) {
+                a[i] = i * i - 3;
+            }
+            for (let i = 0; i < n; i++) {
+                b[i + 1] = a[i] + b[i];
+            }
+            for (let i = 0; i < a.length; i++) {
+                a[i] = b[i + 1] - a[i];
+            }
+            console.log(b[n]);
+            console.log(a[n - 1]);
+            ",
+        )],
+        &[],
+    );
+    let cpp = std::fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+    assert!(
+        cpp.contains(
+            "b_restrict[(i+1)] = js_to_int32(js_int32_add(a_restrict_2[i], b_restrict[i]));"
+        ),
+        "{cpp}"
+    );
+    // what node prints for each `n`
+    for (n, expected) in [("10", "255\n177\n"), ("7", "70\n37\n")] {
+        if let Some(output) = common::execute(&dir, &[n]) {
+            assert_eq!(output, expected, "{n}");
+        }
+    }
+}
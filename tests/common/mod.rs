@@ -0,0 +1,113 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Compiles `source` as the entry module of a fresh directory named `name` and returns the
+/// generated C++.
+#[allow(dead_code)]
+pub fn compile(name: &str, source: &str) -> String {
+    compile_modules(name, &[("main.js", source)], &[])
+}
+
+/// Like [`compile`], with the first file, usually `main.js`, importing the others and extra
+/// command line `args`.
+pub fn compile_modules(name: &str, files: &[(&str, &str)], args: &[&str]) -> String {
+    let dir = run(name, files, args).0;
+    fs::read_to_string(dir.join("tmp/out.cpp")).unwrap()
+}
+
+/// Compiles `source` like [`compile`], with `--opt-report` and the extra `args`, and returns the
+/// report.
+#[allow(dead_code)]
+pub fn report(name: &str, source: &str, args: &[&str]) -> String {
+    let args = [&["--opt-report"], args].concat();
+    run(name, &[("main.js", source)], &args).1
+}
+
+fn compile_files(name: &str, files: &[(&str, &str)], args: &[&str]) -> (PathBuf, Output) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::create_dir_all(dir.join("tmp")).unwrap();
+    for (file, source) in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_crushscript"))
+        .current_dir(&dir)
+        .args([files[0].0, "--no-build"])
+        .args(args)
+        .output()
+        .unwrap();
+    (dir, output)
+}
+
+/// Compiles the `files`, the first one being the entry module, in a fresh directory named `name`
+/// and returns that directory and what the compiler printed to stdout.
+pub fn run(name: &str, files: &[(&str, &str)], args: &[&str]) -> (PathBuf, String) {
+    let (dir, output) = compile_files(name, files, args);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    (dir, stdout)
+}
+
+/// Compiles the `files` in a fresh directory named `name`, expecting the compiler to fail, and
+/// returns what it printed to stderr.
+#[allow(dead_code)]
+pub fn fail(name: &str, files: &[(&str, &str)], args: &[&str]) -> String {
+    let (_, output) = compile_files(name, files, args);
+    assert!(!output.status.success(), "compiled");
+    String::from_utf8(output.stderr).unwrap()
+}
+
+/// Builds the C++ that [`run`] wrote into `dir` with the C++ compiler in `$CXX`, `c++` by
+/// default, runs it with `args` and returns what it printed; `None` without a C++ compiler.
+#[allow(dead_code)]
+pub fn execute(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = build_and_run(dir, args)?;
+    assert!(output.status.success(), "{:?}", output.status);
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+/// Like [`execute`], expecting the program to fail, and returns what it printed to stderr.
+#[allow(dead_code)]
+pub fn execute_failing(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = build_and_run(dir, args)?;
+    assert!(!output.status.success(), "succeeded");
+    Some(String::from_utf8(output.stderr).unwrap())
+}
+
+#[allow(dead_code)]
+fn build_and_run(dir: &Path, args: &[&str]) -> Option<Output> {
+    let compiler = std::env::var("CXX").unwrap_or_else(|_| String::from("c++"));
+    let output = match Command::new(compiler)
+        .current_dir(dir)
+        .args(["-x", "c++", "-O1", "-I"])
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/static"))
+        .args(["tmp/out.cpp", "-o", "tmp/program"])
+        .output()
+    {
+        Err(error) if error.kind() == ErrorKind::NotFound => return None,
+        output => output.unwrap(),
+    };
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Some(
+        Command::new(dir.join("tmp/program"))
+            .args(args)
+            .output()
+            .unwrap(),
+    )
+}
@@ -0,0 +1,180 @@
+mod common;
+
+/// Compiles `files` as CommonJS, then builds and runs them with `args` when there is a C++
+/// compiler.
+fn execute(name: &str, files: &[(&str, &str)], args: &[&str]) -> Option<String> {
+    let (dir, _) = common::run(
+        &format!("commonjs_{name}"),
+        files,
+        &["--module-format", "commonjs"],
+    );
+    common::execute(&dir, args)
+}
+
+#[test]
+fn require_bindings_and_exports() {
+    let output = execute(
+        "require_bindings_and_exports",
+        &[
+            (
+                "main.js",
+                "
+                const util = require('./util');
+                const { size, twice: double } = require('./config.js');
+                const seed = require('./seed');
+                require('./config.js');
+                console.log(util.offset + size + double + seed);
+                ",
+            ),
+            (
+                "config.js",
+                "const size = 10;\nmodule.exports = { size, twice: size * 2 };\n",
+            ),
+            (
+                "util.js",
+                "
+                const { size } = require('./config');
+                exports.offset = size + 100;
+                module.exports.extra = exports.offset + 1;
+                ",
+            ),
+            ("seed.js", "module.exports = 7;\n"),
+        ],
+        &[],
+    );
+    if let Some(output) = output {
+        assert_eq!(output, "147\n");
+    }
+}
+
+#[test]
+fn require_members_read_the_export() {
+    let files = [
+        (
+            "main.js",
+            "
+            console.log(require('./util').twice);
+            function f() {
+                return require('./util.js').size + 1;
+            }
+            console.log(f());
+            ",
+        ),
+        (
+            "util.js",
+            "const size = 10;\nmodule.exports = { size, twice: size * 2 };\n",
+        ),
+    ];
+    let cpp = common::compile_modules(
+        "commonjs_require_members_read_the_export",
+        &files,
+        &["--module-format", "commonjs", "-O0"],
+    );
+    assert!(cpp.contains("console::log(m1_exports_twice);"), "{cpp}");
+    assert!(cpp.contains("(m1_size+1)"), "{cpp}");
+
+    if let Some(output) = execute("require_members_read_the_export", &files, &[]) {
+        assert_eq!(output, "20\n11\n");
+    }
+}
+
+#[test]
+fn other_uses_of_require_are_rejected() {
+    let error = common::fail(
+        "commonjs_other_uses_of_require_are_rejected",
+        &[
+            (
+                "main.js",
+                "let util;\nutil = require('./util');\nconsole.log(util.size);\n",
+            ),
+            ("util.js", "exports.size = 1;\n"),
+        ],
+        &["--module-format", "commonjs"],
+    );
+    assert!(
+        error.contains("require('./util') is only supported as a statement"),
+        "{error}"
+    );
+}
+
+#[test]
+fn missing_require_members_are_reported() {
+    let error = common::fail(
+        "commonjs_missing_require_members_are_reported",
+        &[
+            ("main.js", "console.log(require('./util').nope);\n"),
+            ("util.js", "exports.size = 1;\n"),
+        ],
+        &["--module-format", "commonjs"],
+    );
+    assert!(error.contains("'./util' does not export 'nope'"), "{error}");
+}
+
+#[test]
+fn export_variables_do_not_collide_with_bindings() {
+    let output = execute(
+        "export_variables_do_not_collide_with_bindings",
+        &[
+            ("main.js", "console.log(require('./config').size);\n"),
+            (
+                "config.js",
+                "const exports_size = 3;\nexports.size = exports_size + 7;\nconsole.log(exports_size);\n",
+            ),
+        ],
+        &[],
+    );
+    if let Some(output) = output {
+        assert_eq!(output, "3\n10\n");
+    }
+}
+
+#[test]
+fn required_bindings_are_re_exported() {
+    let output = execute(
+        "required_bindings_are_re_exported",
+        &[
+            (
+                "main.js",
+                "
+                const { seed, size, total } = require('./index');
+                console.log(seed + size);
+                console.log(total);
+                ",
+            ),
+            (
+                "index.js",
+                "
+                const seed = require('./seed');
+                const { size } = require('./config');
+                module.exports = { seed, size, total: seed * size };
+                ",
+            ),
+            ("seed.js", "module.exports = 7;\n"),
+            ("config.js", "exports.size = 10;\n"),
+        ],
+        &[],
+    );
+    if let Some(output) = output {
+        assert_eq!(output, "17\n70\n");
+    }
+}
+
+#[test]
+fn local_bindings_shadow_require_and_exports() {
+    let output = execute(
+        "local_bindings_shadow_require_and_exports",
+        &[(
+            "main.js",
+            "
+            function require(x) { return x * 2; }
+            const exports = [1, 2, 3];
+            console.log(require(21));
+            console.log(exports.length);
+            ",
+        )],
+        &[],
+    );
+    if let Some(output) = output {
+        assert_eq!(output, "42\n3\n");
+    }
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use std::path::PathBuf;
+
+const ARGS: &[&str] = &[
+    "--disable-pass",
+    "running-modulo",
+    "--disable-pass",
+    "strength-reduction",
+];
+
+fn compile(name: &str, source: &str) -> String {
+    common::compile_modules(
+        &format!("constant_division_{name}"),
+        &[("main.js", source)],
+        ARGS,
+    )
+}
+
+fn run(name: &str, source: &str) -> PathBuf {
+    common::run(
+        &format!("constant_division_{name}"),
+        &[("main.js", source)],
+        ARGS,
+    )
+    .0
+}
+
+#[test]
+fn counters_multiply_and_shift() {
+    let cpp = compile(
+        "counters_multiply_and_shift",
+        "
+        const n = Number(process.argv[2]);
+        let s = 0;
+        for (let i = 0; i < n; i++) {
+            s += i % 10 + Math.floor(i / 7) + ((i / 3) | 0);
+        }
+        console.log(s);
+        ",
+    );
+    assert!(cpp.contains("js_umod(i, 10, 3435973837ull, 35)"), "{cpp}");
+    assert!(cpp.contains("js_udiv(i, 2454267027ull, 34)"), "{cpp}");
+    assert!(cpp.contains("js_udiv(i, 2863311531ull, 33)"), "{cpp}");
+}
+
+#[test]
+fn conditions_prove_the_sign() {
+    let cpp = compile(
+        "conditions_prove_the_sign",
+        "
+        const k = Number(process.argv[2]);
+        const m = k & 1023;
+        console.log(m % 1000);
+        if (k >= 0) {
+            console.log(Math.floor(k / 2147483647));
+        }
+        ",
+    );
+    assert!(cpp.contains("js_umod(m, 1000, "), "{cpp}");
+    assert!(cpp.contains("js_udiv(k, 2147483650ull, 62)"), "{cpp}");
+}
+
+#[test]
+fn signed_dividends_round_down() {
+    let cpp = compile(
+        "signed_dividends_round_down",
+        "
+        const k = Number(process.argv[2]);
+        console.log(k % 10);
+        console.log(Math.floor(k / 7));
+        console.log((k / 7) | 0);
+        ",
+    );
+    // a negative multiple of 10 leaves -0
+    assert!(cpp.contains("console::log(js_int32_mod(k, 10));"), "{cpp}");
+    assert!(cpp.contains("console::log(js_floor_div(k, 7));"), "{cpp}");
+    assert!(cpp.contains("console::log((k/7));"), "{cpp}");
+}
+
+#[test]
+fn doubles_keep_fmod() {
+    let cpp = compile(
+        "doubles_keep_fmod",
+        "
+        const k = Number(process.argv[2]);
+        const z = k * 1.5;
+        console.log(z % 4);
+        console.log(Math.floor(z / 4));
+        console.log(k % 2.5);
+        ",
+    );
+    assert!(cpp.contains("console::log(fmod(z, 4));"), "{cpp}");
+    // z / 4 may reach into (-1, 0], where the floor could be -0
+    assert!(cpp.contains("console::log(Math::floor((z/4)));"), "{cpp}");
+    assert!(cpp.contains("console::log(fmod(k, 2.5));"), "{cpp}");
+}
+
+#[test]
+fn results_match_js_for_every_dividend() {
+    let dir = run(
+        "results_match_js_for_every_dividend",
+        "
+        const k = Number(process.argv[2]);
+        const d = Number(process.argv[3]);
+        const m = k & 1023;
+        console.log(k % 10);
+        console.log(Math.floor(k / 7));
+        console.log((k / 7) | 0);
+        console.log(m % 1000);
+        if (k >= 0) {
+            console.log(Math.floor(k / 2147483647));
+        }
+        console.log(k % d);
+        ",
+    );
+    // what node prints for each `k` and `d`
+    let cases = [
+        (["-17", "5"], "-7\n-3\n-2\n7\n-2\n"),
+        (["-10", "5"], "-0\n-2\n-1\n14\n-0\n"),
+        (["0", "0"], "0\n0\n0\n0\n0\nNaN\n"),
+        (["2147483647", "-1"], "7\n306783378\n306783378\n23\n1\n0\n"),
+        (["-2147483648", "-1"], "-8\n-306783379\n-306783378\n0\n-0\n"),
+    ];
+    for (args, expected) in cases {
+        if let Some(output) = common::execute(&dir, &args) {
+            assert_eq!(output, expected, "{args:?}");
+        }
+    }
+}
@@ -0,0 +1,147 @@
+mod common;
+
+fn compile(name: &str, source: &str, args: &[&str]) -> String {
+    common::compile_modules(
+        &format!("constant_folding_{name}"),
+        &[("main.js", source)],
+        args,
+    )
+}
+
+#[test]
+fn folds_and_propagates() {
+    let cpp = compile(
+        "folds_and_propagates",
+        "
+        const N = 1024;
+        const a = new Int32Array(N * 4);
+        a[0] = 1 << 20;
+        console.log(Math.floor(10 / 3));
+        console.log(a[0] + N);
+        ",
+        &[],
+    );
+    assert!(cpp.contains("js_constructor_Int32Array(4096)"), "{cpp}");
+    assert!(cpp.contains("a[0] = 1048576"), "{cpp}");
+    assert!(cpp.contains("console::log(3)"), "{cpp}");
+    assert!(cpp.contains("js_int32_add(a[0], 1024)"), "{cpp}");
+}
+
+#[test]
+fn wraps_like_int32() {
+    let cpp = compile(
+        "wraps_like_int32",
+        "
+        console.log((2147483647 + 1) | 0);
+        console.log(1 << 33);
+        console.log(-1 >>> 28);
+        ",
+        &[],
+    );
+    assert!(cpp.contains("console::log((~2147483647))"), "{cpp}");
+    assert!(cpp.contains("console::log(2)"), "{cpp}");
+    assert!(cpp.contains("console::log(15.0)"), "{cpp}");
+}
+
+#[test]
+fn whole_doubles_stay_doubles() {
+    let cpp = compile(
+        "whole_doubles_stay_doubles",
+        "
+        const half = 10 / 2;
+        console.log(half * 1000000000);
+        console.log(2 ** 10);
+        ",
+        &["--disable-pass", "dead-code"],
+    );
+    assert!(cpp.contains("double half = 5.0"), "{cpp}");
+    assert!(cpp.contains("console::log(5000000000.0)"), "{cpp}");
+    assert!(cpp.contains("console::log(1024.0)"), "{cpp}");
+}
+
+#[test]
+fn keeps_values_without_literal() {
+    let report = common::run(
+        "constant_folding_keeps_values_without_literal",
+        &[(
+            "main.js",
+            "let nan = 0 / 0;
+let inf = -1 / 0;
+let zero = 0 * -1;
+let big = 2147483647 + 1;
+",
+        )],
+        &["--opt-report", "--disable-pass", "dead-code"],
+    )
+    .1;
+    let lines: Vec<_> = report.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "main.js:1:11: constant-folding: missed: `0 / 0` kept: its value NaN has no C++ literal",
+            "main.js:2:11: constant-folding: missed: `-1 / 0` kept: its value -Infinity has no C++ literal",
+            "main.js:3:12: constant-folding: `0 * -1` folded to -0",
+            "main.js:4:11: constant-folding: `2147483647 + 1` folded to 2147483648",
+        ],
+        "{report}"
+    );
+}
+
+#[test]
+fn varying_variables_are_kept() {
+    let cpp = compile(
+        "varying_variables_are_kept",
+        "
+        let k = 1;
+        if (Number(process.argv[2]) > 0) {
+            k = 2;
+        }
+        console.log(k);
+        ",
+        &[],
+    );
+    assert!(cpp.contains("console::log(k)"), "{cpp}");
+}
+
+#[test]
+fn not_at_level_zero() {
+    let cpp = compile(
+        "not_at_level_zero",
+        "
+        const N = 1024;
+        console.log(N * 4);
+        ",
+        &["-O0"],
+    );
+    assert!(cpp.contains("(N*4)"), "{cpp}");
+}
+
+#[test]
+fn folded_values_match_js() {
+    let (dir, _) = common::run(
+        "constant_folding_folded_values_match_js",
+        &[(
+            "main.js",
+            "
+            const x = Number(process.argv[2]);
+            console.log((2147483647 + 1) | 0);
+            console.log(4294967296 + 5 | 0);
+            console.log(-1 >>> 0);
+            console.log(0 / 0);
+            console.log(-1 / 0);
+            console.log(0 * -1);
+            console.log(1 / (0 * -1));
+            console.log(x * (0 * -1.5));
+            ",
+        )],
+        &[],
+    );
+    let cpp = std::fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+    assert!(cpp.contains("console::log((-0.0));"), "{cpp}");
+    assert!(cpp.contains("console::log((x*((-0.0))));"), "{cpp}");
+    // what node prints for `x` = 3
+    let expected = "-2147483648\n5\n4294967295\nNaN\n-Infinity\n-0\n-Infinity\n-0\n";
+    if let Some(output) = common::execute(&dir, &["3"]) {
+        assert_eq!(output, expected);
+    }
+}
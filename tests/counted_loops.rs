@@ -0,0 +1,143 @@
+mod common;
+
+fn compile(name: &str, source: &str) -> String {
+    common::compile(&format!("counted_loops_{name}"), source)
+}
+
+#[test]
+fn kernels_get_restricted_pointers() {
+    let cpp = compile(
+        "kernels_get_restricted_pointers",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Float64Array(n);
+        const b = new Float64Array(n);
+        for (let i = 0; i < n; i++) {
+            a[i] = a[i] * 2 + b[i];
+        }
+        console.log(a[0]);
+        ",
+    );
+    assert!(
+        cpp.contains("double* __restrict a_restrict = a;\ndouble* __restrict b_restrict = b;\nint32_t i = 0;\nJS_IVDEP\nfor (; (i<n); i++) {\n    a_restrict[i] = ((a_restrict[i]*2)+b_restrict[i]);\n}"),
+        "{cpp}"
+    );
+    assert!(cpp.contains("console::log(js_checked(a, 0));"), "{cpp}");
+}
+
+#[test]
+fn carried_dependences_keep_the_pragma_out() {
+    let cpp = compile(
+        "carried_dependences_keep_the_pragma_out",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(n + 1);
+        for (let i = 10; i > 0; i = i - 1) {
+            a[i] = a[i - 1] + 1;
+        }
+        console.log(a[1]);
+        ",
+    );
+    assert!(cpp.contains("for (; (i>0); i = (i-1)) {"), "{cpp}");
+    assert!(cpp.contains("int32_t* __restrict a_restrict = a;"), "{cpp}");
+    assert!(!cpp.contains("JS_IVDEP"), "{cpp}");
+}
+
+#[test]
+fn parameters_may_alias() {
+    let cpp = common::compile_modules(
+        "counted_loops_parameters_may_alias",
+        &[(
+            "main.js",
+            "
+            const n = Number(process.argv[2]);
+            const a = new Int32Array(n);
+            function fill(b, c) {
+                for (let i = 0; i < b.length; i++) {
+                    b[i] = c[i] + 1;
+                }
+            }
+            fill(a, a);
+            console.log(a[0]);
+            ",
+        )],
+        &["--disable-pass", "inline"],
+    );
+    assert!(cpp.contains("for (; (i<b_length); i++) {"), "{cpp}");
+    assert!(!cpp.contains("__restrict"), "{cpp}");
+}
+
+#[test]
+fn moving_bounds_stay_while_loops() {
+    let cpp = compile(
+        "moving_bounds_stay_while_loops",
+        "
+        let n = Number(process.argv[2]);
+        let s = 0;
+        for (let i = 0; i < n; i++) {
+            n = n - 1;
+            s += i;
+        }
+        for (let j = 0; j < 10; j++) {
+            j = j + 1;
+            s += j;
+        }
+        console.log(s);
+        ",
+    );
+    assert!(cpp.contains("while ((i<n)) {"), "{cpp}");
+    assert!(cpp.contains("while ((j<10)) {"), "{cpp}");
+    assert!(!cpp.contains("for (;"), "{cpp}");
+}
+
+#[test]
+fn unrolled_loops_finish_the_remainder() {
+    let cpp = common::compile_modules(
+        "counted_loops_unrolled_loops_finish_the_remainder",
+        &[(
+            "main.js",
+            "
+            const n = Number(process.argv[2]);
+            const a = new Float64Array(n);
+            for (let i = 0; i < a.length; i++) {
+                a[i] = i / 2;
+            }
+            console.log(a[0]);
+            ",
+        )],
+        &["--unroll", "3"],
+    );
+    assert!(
+        cpp.contains("JS_IVDEP\nfor (; (((int64_t)i+2)<a_length); i++) {\n    {\n        a_restrict[i] = ((double)i/2);\n    }\n    i++;\n    {\n        a_restrict[i] = ((double)i/2);\n    }\n    i++;\n    {\n        a_restrict[i] = ((double)i/2);\n    }\n}\nJS_IVDEP\nfor (; (i<a_length); i++) {\n    a_restrict[i] = ((double)i/2);\n}"),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn arrays_allocated_in_the_body_are_not_restricted() {
+    let source = "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(3);
+        let s = 0;
+        for (let i = 0; i < 3; i++) {
+            const c = new Int32Array(n);
+            c[i] = c[i] + i + 1;
+            a[i] = c[i] * 2;
+            s += c[i];
+        }
+        console.log(s);
+        console.log(a[2]);
+        ";
+    for (name, args) in [("O2", &[][..]), ("O0", &["-O0"][..])] {
+        let (dir, _) = common::run(
+            &format!("counted_loops_arrays_allocated_in_the_body_are_not_restricted_{name}"),
+            &[("main.js", source)],
+            args,
+        );
+        let cpp = std::fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+        assert!(!cpp.contains("c_restrict"), "{cpp}");
+        if let Some(output) = common::execute(&dir, &["4"]) {
+            assert_eq!(output, "6\n6\n");
+        }
+    }
+}
@@ -0,0 +1,122 @@
+mod common;
+
+#[test]
+fn repeated_loads_are_computed_once() {
+    let cpp = common::compile(
+        "cse_repeated_loads_are_computed_once",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(n);
+        const b = new Int32Array(n);
+        for (let i = 0; i < n; i++) {
+            b[i] = i;
+        }
+        for (let i = 0; i < n; i++) {
+            a[i] = a[i] + b[i] * b[i];
+        }
+        console.log(a[n - 1]);
+        ",
+    );
+    assert!(cpp.contains("int32_t b_i = b_restrict_2[i];"), "{cpp}");
+    assert!(cpp.contains("js_int32_mul(b_i, b_i)"), "{cpp}");
+}
+
+#[test]
+fn stores_in_between_compute_again() {
+    let cpp = common::compile(
+        "cse_stores_in_between_compute_again",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(4);
+        const x = a[1] + n;
+        a[1] = 7;
+        const y = a[1] + n;
+        console.log(x + y);
+        ",
+    );
+    assert!(
+        cpp.contains("a[1] = 7;\ndouble y = js_int32_add(a[1], n);"),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn expressions_before_an_if_are_reused_in_its_branches() {
+    let cpp = common::compile(
+        "cse_expressions_before_an_if_are_reused_in_its_branches",
+        "
+        const n = Number(process.argv[2]);
+        const m = n * n + 1;
+        if (n > 3) {
+            console.log(n * n + 1);
+        } else {
+            console.log(n * n);
+        }
+        console.log(m);
+        ",
+    );
+    assert!(cpp.contains("console::log(m);"), "{cpp}");
+    assert!(cpp.contains("console::log(common);"), "{cpp}");
+}
+
+#[test]
+fn reuses_and_recomputations_are_reported() {
+    let report = common::report(
+        "cse_reuses_and_recomputations_are_reported",
+        "const n = Number(process.argv[2]);
+const a = new Int32Array(4);
+let s = n % 3 + a[2];
+a[0] = s;
+s += n % 3 + a[2];
+console.log(s);
+",
+        &[],
+    );
+    assert!(
+        report.contains("main.js:3:9: cse: `n % 3` computed once and reused 1 time"),
+        "{report}"
+    );
+    assert!(
+        report.contains(
+            "main.js:5:14: cse: missed: `a[2]` computed again: an element is stored in between"
+        ),
+        "{report}"
+    );
+}
+
+#[test]
+fn results_match_js() {
+    let (dir, _) = common::run(
+        "cse_results_match_js",
+        &[(
+            "main.js",
+            "
+            const n = Number(process.argv[2]);
+            const a = new Int32Array(n);
+            const b = new Int32Array(n);
+            let s = 0;
+            for (let i = 0; i < n; i++) {
+                a[i] = i * 7 - 20;
+            }
+            for (let i = 0; i < n; i++) {
+                b[i] = a[i] % 5 + (a[i] % 5) * 3;
+                s += n % 3 + b[i];
+            }
+            console.log(s);
+            console.log(b[n - 1]);
+            ",
+        )],
+        &[],
+    );
+    let cpp = std::fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+    assert!(
+        cpp.contains("double common = js_int32_mod(a_restrict_2[i], 5);"),
+        "{cpp}"
+    );
+    // what node prints for each `n`
+    for (n, expected) in [("10", "50\n12\n"), ("7", "15\n8\n")] {
+        if let Some(output) = common::execute(&dir, &[n]) {
+            assert_eq!(output, expected, "{n}");
+        }
+    }
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+/// The analyses only show through the passes and types they feed, so each test compiles a program
+/// and looks at what a pass made of it.
+fn run(name: &str, source: &str, args: &[&str]) -> (PathBuf, String) {
+    let (dir, _) = common::run(&format!("dataflow_{name}"), &[("main.js", source)], args);
+    let cpp = fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+    (dir, cpp)
+}
+
+#[test]
+fn constants_meet_where_branches_join() {
+    let (dir, cpp) = run(
+        "constants_meet_where_branches_join",
+        "
+        const n = Number(process.argv[2]);
+        let a = 4;
+        let b = 4;
+        if (n > 2) {
+            a = 4;
+            b = 5;
+        } else {
+            b = 6;
+        }
+        console.log(a * 10);
+        console.log(b * 10);
+        ",
+        &[],
+    );
+    // `a` is 4 on both paths, `b` is not
+    assert!(cpp.contains("console::log(40);"), "{cpp}");
+    assert!(cpp.contains("console::log((b*10));"), "{cpp}");
+    if let Some(output) = common::execute(&dir, &["5"]) {
+        assert_eq!(output, "40\n50\n");
+    }
+}
+
+#[test]
+fn constants_flow_around_loops() {
+    let (dir, cpp) = run(
+        "constants_flow_around_loops",
+        "
+        const n = Number(process.argv[2]);
+        let k = 3;
+        let m = 3;
+        for (let i = 0; i < n; i++) {
+            m = k;
+            k = k + 1;
+        }
+        console.log(k);
+        console.log(m);
+        ",
+        &[],
+    );
+    assert!(cpp.contains("console::log(k);"), "{cpp}");
+    assert!(cpp.contains("console::log(m);"), "{cpp}");
+    if let Some(output) = common::execute(&dir, &["5"]) {
+        assert_eq!(output, "8\n7\n");
+    }
+}
+
+#[test]
+fn variables_written_by_functions_are_not_tracked() {
+    let (dir, cpp) = run(
+        "variables_written_by_functions_are_not_tracked",
+        "
+        let c = 2;
+        function bump() {
+            c = c + 1;
+        }
+        bump();
+        console.log(c * 3);
+        ",
+        &["--disable-pass", "inline"],
+    );
+    assert!(cpp.contains("console::log((c*3));"), "{cpp}");
+    if let Some(output) = common::execute(&dir, &[]) {
+        assert_eq!(output, "9\n");
+    }
+}
+
+#[test]
+fn liveness_follows_loop_back_edges() {
+    let (dir, cpp) = run(
+        "liveness_follows_loop_back_edges",
+        "
+        const n = Number(process.argv[2]);
+        let x = 0;
+        x = n * 2;
+        x = n + 1;
+        let y = 0;
+        for (let i = 0; i < n; i++) {
+            console.log(y);
+            y = i;
+        }
+        console.log(x);
+        ",
+        &[],
+    );
+    assert!(!cpp.contains("js_int32_mul(n, 2)"), "{cpp}");
+    // read by the next iteration
+    assert!(cpp.contains("y = i;"), "{cpp}");
+    if let Some(output) = common::execute(&dir, &["3"]) {
+        assert_eq!(output, "0\n0\n1\n4\n");
+    }
+}
+
+#[test]
+fn ranges_keep_bounded_arithmetic_int32() {
+    let (dir, cpp) = run(
+        "ranges_keep_bounded_arithmetic_int32",
+        "
+        const n = Number(process.argv[2]);
+        const h = n & 255;
+        const q = h + h;
+        const r = n + n;
+        console.log(q);
+        console.log(r);
+        ",
+        &[],
+    );
+    assert!(cpp.contains("int32_t q = (h+h);"), "{cpp}");
+    assert!(cpp.contains("double r = js_int32_add(n, n);"), "{cpp}");
+    if let Some(output) = common::execute(&dir, &["2000000000"]) {
+        assert_eq!(output, "0\n4000000000\n");
+    }
+}
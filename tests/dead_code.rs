@@ -0,0 +1,148 @@
+mod common;
+
+fn compile(name: &str, source: &str, args: &[&str]) -> String {
+    common::compile_modules(&format!("dead_code_{name}"), &[("main.js", source)], args)
+}
+
+#[test]
+fn unused_variables_are_removed() {
+    let cpp = compile(
+        "unused_variables_are_removed",
+        "
+        let unused = Number(process.argv[2]) * 2;
+        unused = 7;
+        let used = Number(process.argv[2]);
+        console.log(used);
+        ",
+        &[],
+    );
+    assert!(!cpp.contains("unused"), "{cpp}");
+    assert!(cpp.contains("console::log(used)"), "{cpp}");
+}
+
+#[test]
+fn overwritten_stores_are_removed() {
+    let cpp = compile(
+        "overwritten_stores_are_removed",
+        "
+        let x = Number(process.argv[2]);
+        x = x + 1;
+        x = Math.random();
+        console.log(x);
+        ",
+        &[],
+    );
+    assert!(!cpp.contains("x = (x+1)"), "{cpp}");
+    assert!(cpp.contains("x = Math::random()"), "{cpp}");
+}
+
+#[test]
+fn stores_read_in_loops_are_kept() {
+    let cpp = compile(
+        "stores_read_in_loops_are_kept",
+        "
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += i;
+        }
+        console.log(s);
+        ",
+        &[],
+    );
+    assert!(cpp.contains("s += i"), "{cpp}");
+}
+
+#[test]
+fn side_effects_are_kept() {
+    let report = common::run(
+        "dead_code_side_effects_are_kept",
+        &[(
+            "main.js",
+            "let r = Math.random();
+Math.floor(2.5 * Number(process.argv[2]));
+",
+        )],
+        &["--opt-report"],
+    )
+    .1;
+    let lines: Vec<_> = report.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "main.js:1:5: dead-code: missed: `r` kept: its initializer may have side effects",
+            "main.js:2:1: dead-code: `Math.floor(2.5 * Number(process.argv[2]));` removed: no side effects",
+        ],
+        "{report}"
+    );
+}
+
+#[test]
+fn unreachable_code_is_removed() {
+    let (_, report) = common::run(
+        "dead_code_unreachable_code_is_removed",
+        &[(
+            "main.js",
+            "function f(n) {
+    return n;
+    console.log(n);
+    console.log(n + 1);
+}
+console.log(f(2));
+",
+        )],
+        &["--opt-report"],
+    );
+    assert!(
+        report.contains("main.js:3:5: dead-code: unreachable code removed\n"),
+        "{report}"
+    );
+    assert!(!report.contains("main.js:4:"), "{report}");
+}
+
+#[test]
+fn constant_conditions_pick_a_branch() {
+    let cpp = compile(
+        "constant_conditions_pick_a_branch",
+        "
+        if (2 > 3) {
+            console.log(1);
+        } else {
+            console.log(2);
+        }
+        while (1 < 0) {
+            console.log(3);
+        }
+        ",
+        &[],
+    );
+    assert!(!cpp.contains("console::log(1)"), "{cpp}");
+    assert!(cpp.contains("console::log(2)"), "{cpp}");
+    assert!(!cpp.contains("console::log(3)"), "{cpp}");
+    assert!(!cpp.contains("if"), "{cpp}");
+}
+
+#[test]
+fn exports_are_kept() {
+    let cpp = compile(
+        "exports_are_kept",
+        "
+        export const size = 4;
+        export let count = 0;
+        count = 1;
+        ",
+        &[],
+    );
+    assert!(cpp.contains("count = 1"), "{cpp}");
+}
+
+#[test]
+fn not_at_level_zero() {
+    let cpp = compile(
+        "not_at_level_zero",
+        "
+        let unused = 1;
+        ",
+        &["-O0"],
+    );
+    assert!(cpp.contains("unused"), "{cpp}");
+}
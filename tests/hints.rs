@@ -0,0 +1,99 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+const PARAM: &str = "/** @param {int32} n */
+function f(n) {
+    return n * 2;
+}
+const c = Number(process.argv[2]);
+console.log(f(c / 7));
+";
+
+const VARIABLES: &str = "/** @type {int32} */
+let total = 0;
+/** @type {uint8} */
+let byte = 0;
+/** @type {f32} */
+let ratio = 0;
+const n = Number(process.argv[2]);
+for (let i = 0; i < n; i++) {
+    total = total + i / 2;
+    byte = byte + 100;
+    ratio = ratio + 0.1;
+}
+console.log(total);
+console.log(byte);
+console.log(ratio);
+";
+
+fn run(name: &str, source: &str, args: &[&str]) -> (PathBuf, String) {
+    let (dir, _) = common::run(&format!("hints_{name}"), &[("main.js", source)], args);
+    let cpp = fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+    (dir, cpp)
+}
+
+#[test]
+fn hints_pick_narrower_types() {
+    let (dir, cpp) = run("hints_pick_narrower_types", VARIABLES, &["-O0"]);
+    assert!(cpp.contains("int32_t total = 0;"), "{cpp}");
+    assert!(cpp.contains("uint8_t byte = 0;"), "{cpp}");
+    assert!(cpp.contains("float ratio = 0;"), "{cpp}");
+    // trusted: the stores convert like C++ does
+    if let Some(output) = common::execute(&dir, &["3"]) {
+        assert_eq!(output, "1\n44\n0.30000001192092896\n");
+    }
+}
+
+#[test]
+fn checked_hints_guard_stores() {
+    let (dir, cpp) = run(
+        "checked_hints_guard_stores",
+        VARIABLES,
+        &["-O0", "--checked-hints"],
+    );
+    assert!(
+        cpp.contains("total = js_check_int32((total+((double)i/2)), \"total\");"),
+        "{cpp}"
+    );
+    if let Some(error) = common::execute_failing(&dir, &["3"]) {
+        assert_eq!(
+            error,
+            "hint violated: total is declared int32 but was assigned 0.5\n"
+        );
+    }
+}
+
+#[test]
+fn hinted_parameters_convert_their_arguments() {
+    let (dir, cpp) = run("hinted_parameters_convert_their_arguments", PARAM, &[]);
+    assert!(cpp.contains("f = [&](int32_t n) -> double {"), "{cpp}");
+    if let Some(output) = common::execute(&dir, &["10"]) {
+        assert_eq!(output, "2\n");
+    }
+}
+
+#[test]
+fn checked_hints_guard_parameters_on_entry() {
+    let (dir, cpp) = run(
+        "checked_hints_guard_parameters_on_entry",
+        PARAM,
+        &["--checked-hints"],
+    );
+    assert!(cpp.contains("std::function<double(double)> f;"), "{cpp}");
+    assert!(
+        cpp.contains(
+            "f = [&](double n_unchecked) -> double {\n    int32_t n = js_check_int32(n_unchecked, \"n\");"
+        ),
+        "{cpp}"
+    );
+    if let Some(output) = common::execute(&dir, &["14"]) {
+        assert_eq!(output, "4\n");
+    }
+    if let Some(error) = common::execute_failing(&dir, &["10"]) {
+        assert_eq!(
+            error,
+            "hint violated: n is declared int32 but was assigned 1.4285714285714286\n"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+mod common;
+
+#[test]
+fn functions_compile_to_lambdas() {
+    let cpp = common::compile_modules(
+        "inline_functions_compile_to_lambdas",
+        &[(
+            "main.js",
+            "
+        let total = 0;
+        console.log(half(7));
+        function half(x) {
+            return x / 2;
+        }
+        function bump(k) {
+            total += k;
+        }
+        bump(3);
+            ",
+        )],
+        &["-O0"],
+    );
+    // callable before its declaration, like JS hoists it
+    assert!(
+        cpp.contains(
+            "std::function<double(int32_t)> half;
+std::function<void(int32_t)> bump;
+half = [&](int32_t x) -> double {
+    return ((double)x/2);
+};"
+        ),
+        "{cpp}"
+    );
+    // captures `total`, so it is defined where it is declared
+    assert!(
+        cpp.contains(
+            "double total = 0;
+console::log(half(7));
+bump = [&](int32_t k) -> void {"
+        ),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn loop_passes_see_through_calls() {
+    let cpp = common::compile(
+        "inline_loop_passes_see_through_calls",
+        "
+        function wrap(i, n) {
+            return i % n;
+        }
+        let s = 0;
+        for (let i = 0; i < 100; i++) {
+            s += wrap(i, 7);
+        }
+        console.log(s);
+        ",
+    );
+    assert!(cpp.contains("s += i_modulo"), "{cpp}");
+    assert!(!cpp.contains("wrap"), "{cpp}");
+}
+
+#[test]
+fn statements_become_blocks() {
+    let cpp = common::compile(
+        "inline_statements_become_blocks",
+        "
+        const a = new Int32Array(10);
+        function set(arr, i) {
+            let v = i * 2;
+            arr[i] = v;
+        }
+        const i = Number(process.argv[2]);
+        set(a, i);
+        console.log(a[1]);
+        ",
+    );
+    assert!(cpp.contains("int32_t* arr_2 = a;\nint32_t i_2 = i;\n    {\ndouble v = js_int32_mul(i_2, 2);\n        js_checked_store(arr_2, i_2) = js_to_int32(v);"), "{cpp}");
+}
+
+#[test]
+fn rejected_functions() {
+    let report = common::report(
+        "inline_rejected_functions",
+        "let t = Number(process.argv[2]);
+function fib(n) {
+    if (n < 2) return n;
+    return fib(n - 1) + fib(n - 2);
+}
+function outer() {
+    function inner() { return 1; }
+    return inner();
+}
+function count(a) {
+    return arguments.length;
+}
+function scale(v) {
+    return v * t;
+}
+function run(n) {
+    let t = Number(process.argv[3]);
+    return scale(n) + t;
+}
+console.log(fib(10) + outer() + count(1) + run(2) + scale(Math.random()));
+",
+        &[],
+    );
+    for line in [
+        "main.js:4:12: inline: missed: call to `fib` not inlined: it is recursive",
+        "main.js:18:12: inline: missed: call to `scale` not inlined: `t` means something else here",
+        "main.js:20:13: inline: missed: call to `fib` not inlined: it is recursive",
+        "main.js:20:23: inline: missed: call to `outer` not inlined: it defines closures",
+        "main.js:20:33: inline: missed: call to `count` not inlined: it uses `arguments`",
+        "main.js:20:44: inline: missed: call to `run` not inlined: it has statements besides its `return`",
+        "main.js:20:53: inline: missed: call to `scale` not inlined: its arguments would be evaluated out of order",
+    ] {
+        assert!(report.contains(line), "{line}\n{report}");
+    }
+}
+
+#[test]
+fn threshold() {
+    let source = "function area(w, h) {
+    return w * h;
+}
+console.log(area(Number(process.argv[2]), 3));
+";
+    assert!(common::report("inline_threshold_default", source, &[])
+        .contains("main.js:4:13: inline: call to `area` inlined"));
+    assert!(common::report("inline_threshold_low", source, &["--inline-threshold", "3"]).contains(
+        "main.js:4:13: inline: missed: call to `area` not inlined: its cost 5 is above the threshold of 3"
+    ));
+}
+
+#[test]
+fn not_at_level_one() {
+    let cpp = common::compile_modules(
+        "inline_not_at_level_one",
+        &[(
+            "main.js",
+            "
+        function square(n) {
+            return n * n;
+        }
+        console.log(square(Number(process.argv[2])));
+            ",
+        )],
+        &["-O1"],
+    );
+    assert!(cpp.contains("console::log(square("), "{cpp}");
+}
+
+#[test]
+fn recursion_in_a_conditional_keeps_the_known_branch() {
+    let source = "
+        function fact(n) {
+            return n <= 1 ? 1 : n * fact(n - 1);
+        }
+        console.log(fact(Number(process.argv[2])));
+        ";
+    let cpp = common::compile_modules(
+        "inline_recursion_in_a_conditional_keeps_the_known_branch",
+        &[("main.js", source)],
+        &["-O0"],
+    );
+    assert!(cpp.contains("std::function<double(double)> fact;"), "{cpp}");
+    let (dir, _) = common::run(
+        "inline_recursion_in_a_conditional_keeps_the_known_branch_run",
+        &[("main.js", source)],
+        &[],
+    );
+    if let Some(output) = common::execute(&dir, &["10"]) {
+        assert_eq!(output, "3628800\n");
+    }
+}
+
+#[test]
+fn unknown_return_types_are_errors() {
+    let error = common::fail(
+        "inline_unknown_return_types_are_errors",
+        &[(
+            "main.js",
+            "function f(n) {\n    return f(n);\n}\nconsole.log(f(1));\n",
+        )],
+        &["-O0"],
+    );
+    assert!(
+        error.contains("'f' returns a value of unknown type"),
+        "{error}"
+    );
+}
+
+#[test]
+fn inlined_calls_match_js() {
+    let (dir, _) = common::run(
+        "inline_inlined_calls_match_js",
+        &[(
+            "main.js",
+            "
+            function clamp(x, lo, hi) {
+                return x < lo ? lo : x > hi ? hi : x;
+            }
+            function add(a, b) {
+                return a + b;
+            }
+            const n = Number(process.argv[2]);
+            let s = 0;
+            for (let i = 0; i < n; i++) {
+                s = add(s, clamp(i * 3 - 10, 0, 12));
+            }
+            console.log(s);
+            ",
+        )],
+        &[],
+    );
+    let cpp = std::fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+    assert!(
+        cpp.contains("s = (s+((common<0) ? 0 : ((common>12) ? 12 : common)));"),
+        "{cpp}"
+    );
+    // what node prints for each `n`
+    for (n, expected) in [("10", "50\n"), ("6", "7\n")] {
+        if let Some(output) = common::execute(&dir, &[n]) {
+            assert_eq!(output, expected, "{n}");
+        }
+    }
+}
@@ -0,0 +1,220 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+fn compile(name: &str, source: &str) -> String {
+    common::compile(&format!("int32_ranges_{name}"), source)
+}
+
+fn run(name: &str, source: &str) -> (PathBuf, String) {
+    let (dir, _) = common::run(&format!("int32_ranges_{name}"), &[("main.js", source)], &[]);
+    let cpp = fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+    (dir, cpp)
+}
+
+#[test]
+fn loop_counter_stays_int32() {
+    let cpp = compile(
+        "loop_counter_stays_int32",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(n);
+        for (let i = 0; i < n; i++) {
+            a[i] = i;
+        }
+        console.log(a[0]);
+        ",
+    );
+    assert!(cpp.contains("int32_t i = 0;"), "{cpp}");
+    assert!(cpp.contains("a[i] = i;"), "{cpp}");
+    assert!(!cpp.contains("js_int32_add"), "{cpp}");
+}
+
+#[test]
+fn bounded_arithmetic_stays_int32() {
+    let cpp = compile(
+        "bounded_arithmetic_stays_int32",
+        "
+        const a = new Int32Array(100);
+        for (let i = 0; i < 100; i++) {
+            a[i] = i * i - 3;
+        }
+        console.log(a[7]);
+        ",
+    );
+    assert!(cpp.contains("a[i] = ((i*i)-3);"), "{cpp}");
+}
+
+#[test]
+fn element_sum_becomes_double() {
+    let cpp = compile(
+        "element_sum_becomes_double",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(n);
+        let sum = 0;
+        for (let i = 0; i < n; i++) {
+            sum += a[i];
+        }
+        console.log(sum);
+        ",
+    );
+    assert!(cpp.contains("double sum = 0;"), "{cpp}");
+    assert!(cpp.contains("int32_t i = 0;"), "{cpp}");
+}
+
+#[test]
+fn products_promote_to_double() {
+    let cpp = compile(
+        "products_promote_to_double",
+        "
+        const a = new Int32Array(2);
+        a[0] = Number(process.argv[2]);
+        a[1] = Number(process.argv[3]);
+        const p = a[0] * a[1];
+        console.log(-a[0]);
+        console.log(p);
+        ",
+    );
+    assert!(cpp.contains("double p = js_int32_mul(a_0, a[1]);"), "{cpp}");
+    assert!(cpp.contains("console::log((-(double)a_0));"), "{cpp}");
+}
+
+#[test]
+fn bitwise_operands_convert_with_to_int32() {
+    let cpp = compile(
+        "bitwise_operands_convert_with_to_int32",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(n);
+        let h = 0;
+        for (let i = 0; i < n; i++) {
+            h = (h * 31 + a[i]) | 0;
+        }
+        console.log(h);
+        ",
+    );
+    assert!(cpp.contains("int32_t h = 0;"), "{cpp}");
+    assert!(
+        cpp.contains("h = (js_to_int32(((js_int32_mul(h, 31)+a_restrict[i])))|0);"),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn running_variables_bounded_by_the_loop_stay_int32() {
+    let (dir, cpp) = run(
+        "running_variables_bounded_by_the_loop_stay_int32",
+        "
+        const out = new Int32Array(64);
+        for (let i = 0; i < 64; i++) {
+            out[i] = i * 7 + i % 5;
+        }
+        let big = 0;
+        for (let i = 0; i < 100000; i++) {
+            big = i * 100000 + i % 3;
+        }
+        let sum = 0;
+        for (let i = 0; i < 64; i++) {
+            sum += out[i];
+        }
+        console.log(sum);
+        console.log(big);
+        ",
+    );
+    // reset at the denominator, or in lock step with a counter below 64
+    assert!(cpp.contains("int32_t i_modulo = 0;"), "{cpp}");
+    assert!(cpp.contains("int32_t i_times_7 = 0;"), "{cpp}");
+    assert!(
+        cpp.contains("out_restrict[i] = (i_times_7+i_modulo);"),
+        "{cpp}"
+    );
+    // 99999 * 100000 does not fit
+    assert!(cpp.contains("int32_t i_modulo_2 = 0;"), "{cpp}");
+    assert!(cpp.contains("double i_times_100000 = 0;"), "{cpp}");
+    if let Some(output) = common::execute(&dir, &[]) {
+        assert_eq!(output, "14238\n9999900000\n");
+    }
+}
+
+#[test]
+fn floor_is_int32_only_when_bounded() {
+    let (dir, cpp) = run(
+        "floor_is_int32_only_when_bounded",
+        "
+        const n = Number(process.argv[2]);
+        const h = n & 1023;
+        console.log(Math.floor(n * 1000000 / 7));
+        console.log(Math.floor(h * 0.5) % 5);
+        console.log(Math.floor(n / 0));
+        ",
+    );
+    assert!(
+        cpp.contains("console::log(Math::floor((js_int32_mul(n, 1000000)/7)));"),
+        "{cpp}"
+    );
+    assert!(cpp.contains("((int32_t)Math::floor((h*0.5)))"), "{cpp}");
+    if let Some(output) = common::execute(&dir, &["70000"]) {
+        assert_eq!(output, "10000000000\n4\nInfinity\n");
+    }
+}
+
+#[test]
+fn products_that_may_be_negative_zero_are_doubles() {
+    let (dir, cpp) = run(
+        "products_that_may_be_negative_zero_are_doubles",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Int32Array(2);
+        a[0] = n - 5;
+        const y = a[0] * -2;
+        console.log(y);
+        console.log(1 / y);
+        ",
+    );
+    assert!(
+        cpp.contains("double y = js_int32_mul(a[0], (-2));"),
+        "{cpp}"
+    );
+    if let Some(output) = common::execute(&dir, &["5"]) {
+        assert_eq!(output, "-0\n-Infinity\n");
+    }
+    if let Some(output) = common::execute(&dir, &["12"]) {
+        assert_eq!(output, "-14\n-0.07142857142857142\n");
+    }
+}
+
+#[test]
+fn zero_times_a_negative_literal_is_negative_zero() {
+    let (dir, _) = run(
+        "zero_times_a_negative_literal_is_negative_zero",
+        "
+        const d = 0 * -1;
+        console.log(d);
+        console.log(1 / d);
+        ",
+    );
+    if let Some(output) = common::execute(&dir, &[]) {
+        assert_eq!(output, "-0\n-Infinity\n");
+    }
+}
+
+#[test]
+fn floor_of_negative_zero_is_a_double() {
+    let (dir, cpp) = run(
+        "floor_of_negative_zero_is_a_double",
+        "
+        const n = Number(process.argv[2]);
+        const x = n - 5;
+        console.log(Math.floor(x / -7));
+        console.log(1 / Math.floor(x / -7));
+        ",
+    );
+    assert!(!cpp.contains("(int32_t)Math::floor"), "{cpp}");
+    if let Some(output) = common::execute(&dir, &["5"]) {
+        assert_eq!(output, "-0\n-Infinity\n");
+    }
+    if let Some(output) = common::execute(&dir, &["12"]) {
+        assert_eq!(output, "-1\n-1\n");
+    }
+}
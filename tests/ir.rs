@@ -0,0 +1,85 @@
+mod common;
+
+fn emit_ir(name: &str, source: &str) -> String {
+    common::run(name, &[("main.js", source)], &["--emit-ir", "-O0"]).1
+}
+
+#[test]
+fn loops_merge_variables_with_phis() {
+    let ir = emit_ir(
+        "ir_loops",
+        "let sum = 0;
+        for (let i = 0; i < 10; i++) {
+            if (i % 3 == 0) continue;
+            sum += i;
+        }
+        console.log(sum);",
+    );
+    assert!(ir.contains("function main() {"), "{ir}");
+    assert!(ir.contains("int32 = phi [bb0: "), "{ir}");
+    assert!(ir.contains("call console.log("), "{ir}");
+    assert!(!ir.contains("load_var sum"), "{ir}");
+}
+
+#[test]
+fn function_declarations_are_lowered_separately() {
+    let ir = emit_ir(
+        "ir_functions",
+        "function square(n) {
+            return n * n;
+        }
+        console.log(square(4));",
+    );
+    assert!(ir.contains("function square(n) {"), "{ir}");
+    assert!(ir.contains("param 0"), "{ir}");
+    assert!(ir.contains("call square("), "{ir}");
+}
+
+#[test]
+fn captured_variables_stay_in_memory() {
+    let ir = emit_ir(
+        "ir_captured",
+        "let total = 0;
+        function bump() {
+            total++;
+        }
+        bump();
+        console.log(total);",
+    );
+    assert!(ir.contains("store_var total"), "{ir}");
+    assert!(ir.contains("load_var total"), "{ir}");
+}
+
+#[test]
+fn unsupported_constructs_are_reported() {
+    let ir = emit_ir(
+        "ir_unsupported",
+        "let n = 2;
+        switch (n) {
+            case 1:
+                console.log(1);
+        }",
+    );
+    assert!(
+        ir.contains(": not lowered: line 2: switch statements are not supported"),
+        "{ir}"
+    );
+}
+
+#[test]
+fn code_after_jumps_is_dropped() {
+    let ir = emit_ir(
+        "ir_jumps",
+        "function first(n) {
+            while (n > 0 && n < 100) {
+                if (n % 7 == 0) break;
+                n++;
+            }
+            return n;
+            console.log(n);
+        }
+        console.log(first(3));",
+    );
+    assert!(ir.contains("boolean = phi ["), "{ir}");
+    assert_eq!(ir.matches("call console.log(").count(), 1, "{ir}");
+}
@@ -0,0 +1,199 @@
+mod common;
+
+#[test]
+fn invariants_move_to_the_initializer() {
+    let cpp = common::compile(
+        "licm_invariants_move_to_the_initializer",
+        "
+        const n = Number(process.argv[2]);
+        const k = Number(process.argv[3]);
+        const a = new Float64Array(n);
+        for (let i = 0; i < a.length; i++) {
+            a[i] = Math.sqrt(k) * i + n * k;
+        }
+        console.log(a[1]);
+        ",
+    );
+    assert!(cpp.contains("int32_t i = 0;\nint32_t a_length = js_length(a_restrict);\ndouble sqrt_value = Math::sqrt(k);\ndouble invariant = js_int32_mul(n, k);\nJS_IVDEP\nfor (; (i<a_length); i++) {"), "{cpp}");
+    assert!(
+        cpp.contains("a_restrict[i] = ((sqrt_value*i)+invariant);"),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn repeated_expressions_share_a_variable() {
+    let cpp = common::compile(
+        "licm_repeated_expressions_share_a_variable",
+        "
+        const n = Number(process.argv[2]);
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += n * 3 - i;
+            s = s + n * 3;
+        }
+        console.log(s);
+        ",
+    );
+    assert!(
+        cpp.contains("double invariant = js_int32_mul(n, 3);"),
+        "{cpp}"
+    );
+    assert!(cpp.contains("s = (s+invariant);"), "{cpp}");
+    assert!(!cpp.contains("invariant_2"), "{cpp}");
+}
+
+#[test]
+fn inner_loop_invariants_leave_both_loops() {
+    let cpp = common::compile(
+        "licm_inner_loop_invariants_leave_both_loops",
+        "
+        const n = Number(process.argv[2]);
+        let s = 0;
+        for (let i = 0; i < n; i++) {
+            for (let j = 0; j < n; j++) {
+                s += (n >> 1) + (i << 1);
+            }
+        }
+        console.log(s);
+        ",
+    );
+    assert!(
+        cpp.contains("int32_t invariant = (n>>1);\nwhile ((i<n)) {"),
+        "{cpp}"
+    );
+    assert!(
+        cpp.contains("double invariant_2 = js_int32_add((invariant), "),
+        "{cpp}"
+    );
+    assert!(cpp.contains("s += invariant_2;"), "{cpp}");
+}
+
+#[test]
+fn variants_stay() {
+    let cpp = common::compile(
+        "licm_variants_stay",
+        "
+        const n = Number(process.argv[2]);
+        let m = n;
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += m * 2 + (n % m);
+            m++;
+            const c = n * 4;
+            s += c + 1;
+        }
+        console.log(s);
+        ",
+    );
+    assert!(cpp.contains("(m*2)"), "{cpp}");
+    assert!(cpp.contains("fmod(n, m)"), "{cpp}");
+    assert!(cpp.contains("(c+1)"), "{cpp}");
+    assert!(
+        cpp.contains("double invariant = js_int32_mul(n, 4);"),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn calls_keep_lengths() {
+    let cpp = common::compile_modules(
+        "licm_calls_keep_lengths",
+        &[(
+            "main.js",
+            "
+            const n = Number(process.argv[2]);
+            const a = new Int32Array(n);
+            function touch(b) {
+                b[0] = 1;
+            }
+            for (let i = 0; i < a.length; i++) {
+                touch(a);
+            }
+            console.log(a[0]);
+            ",
+        )],
+        &["--disable-pass", "inline"],
+    );
+    assert!(cpp.contains("for (; (i<js_length(a)); i++)"), "{cpp}");
+}
+
+#[test]
+fn nested_functions_are_left_alone() {
+    let cpp = common::compile_modules(
+        "licm_nested_functions_are_left_alone",
+        &[(
+            "main.js",
+            "
+            const n = Number(process.argv[2]);
+            let s = 0;
+            for (let i = 0; i < 3; i++) {
+                function f() {
+                    return n * 5;
+                }
+                s += f();
+            }
+            console.log(s);
+            ",
+        )],
+        &["--disable-pass", "inline"],
+    );
+    assert!(!cpp.contains("invariant"), "{cpp}");
+}
+
+#[test]
+fn expression_initializer_is_reported() {
+    let report = common::report(
+        "licm_expression_initializer_is_reported",
+        "const n = Number(process.argv[2]);
+let i;
+let s = 0;
+for (i = 0; i < n * n; i++) {
+    s += n + n;
+}
+console.log(s);
+",
+        &[],
+    );
+    assert!(
+        report.contains(
+            "main.js:4:1: licm: missed: 2 invariant expressions kept: the loop initializer is not a declaration"
+        ),
+        "{report}"
+    );
+}
+
+#[test]
+fn results_match_js() {
+    let (dir, _) = common::run(
+        "licm_results_match_js",
+        &[(
+            "main.js",
+            "
+            const n = Number(process.argv[2]);
+            const k = Number(process.argv[3]);
+            const a = new Float64Array(n);
+            let s = 0;
+            for (let i = 0; i < a.length; i++) {
+                a[i] = Math.sqrt(k) * i + n * k;
+                s += a[i];
+            }
+            console.log(s);
+            console.log(a[n - 1]);
+            ",
+        )],
+        &[],
+    );
+    let cpp = std::fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+    assert!(cpp.contains("double sqrt_value = Math::sqrt(k);"), "{cpp}");
+    // what node prints for each `n` and `k`
+    let cases = [
+        (["10", "3"], "377.9422863405995\n45.588457268119896\n"),
+        (["7", "2"], "127.69848480983501\n22.48528137423857\n"),
+    ];
+    for (args, expected) in cases {
+        if let Some(output) = common::execute(&dir, &args) {
+            assert_eq!(output, expected, "{args:?}");
+        }
+    }
+}
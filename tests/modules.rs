@@ -0,0 +1,198 @@
+mod common;
+
+/// Compiles `files`, then builds and runs them with `args` when there is a C++ compiler.
+fn execute(name: &str, files: &[(&str, &str)], args: &[&str]) -> Option<String> {
+    let (dir, _) = common::run(&format!("modules_{name}"), files, &[]);
+    common::execute(&dir, args)
+}
+
+#[test]
+fn named_default_and_namespace_imports() {
+    let output = execute(
+        "named_default_and_namespace_imports",
+        &[
+            (
+                "main.js",
+                "
+                import { size, scale as s } from './lib/config';
+                import * as util from './lib/util.js';
+                import total from './lib/util.js';
+                const a = new Int32Array(size);
+                for (let i = 0; i < size; i++) {
+                    a[i] = i * s;
+                }
+                console.log(a[3] + util.offset + total);
+                ",
+            ),
+            (
+                "lib/config.js",
+                "
+                export const size = 8;
+                export const scale = 3;
+                ",
+            ),
+            (
+                "lib/util.js",
+                "
+                import { size } from './config.js';
+                export const offset = 100;
+                export default size * 20;
+                ",
+            ),
+        ],
+        &[],
+    );
+    if let Some(output) = output {
+        assert_eq!(output, "269\n");
+    }
+}
+
+#[test]
+fn dependencies_are_evaluated_first() {
+    let cpp = common::compile_modules(
+        "modules_dependencies_are_evaluated_first",
+        &[
+            (
+                "main.js",
+                "
+                import { a } from './a.js';
+                console.log(a);
+                ",
+            ),
+            (
+                "a.js",
+                "import { b } from './b.js';\nexport const a = b + 1;\n",
+            ),
+            ("b.js", "export const b = 41;\n"),
+        ],
+        &[],
+    );
+    let b = cpp.find("int32_t m2_b = 41;").expect(&cpp);
+    let a = cpp.find("m1_a = ").expect(&cpp);
+    let log = cpp.find("console::log(m1_a);").expect(&cpp);
+    assert!(b < a && a < log, "{cpp}");
+}
+
+#[test]
+fn re_exports_resolve_to_the_original_binding() {
+    let cpp = common::compile_modules(
+        "modules_re_exports_resolve_to_the_original_binding",
+        &[
+            (
+                "main.js",
+                "
+                import { value, renamed } from './index.js';
+                console.log(value + renamed);
+                ",
+            ),
+            (
+                "index.js",
+                "export * from './value.js';\nexport { value as renamed } from './value.js';\n",
+            ),
+            ("value.js", "export const value = 21;\n"),
+        ],
+        &[],
+    );
+    assert!(
+        cpp.contains("console::log(js_int32_add(m2_value, m2_value));"),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn functions_read_bindings_of_circular_imports() {
+    let output = execute(
+        "functions_read_bindings_of_circular_imports",
+        &[
+            (
+                "main.js",
+                "import { f } from './lib.js';\nexport let x = 3;\nconsole.log(f());\n",
+            ),
+            (
+                "lib.js",
+                "import { x } from './main.js';\nexport function f() { return x + 1; }\n",
+            ),
+        ],
+        &[],
+    );
+    if let Some(output) = output {
+        assert_eq!(output, "4\n");
+    }
+}
+
+#[test]
+fn top_level_reads_of_circular_imports_are_rejected() {
+    let error = common::fail(
+        "modules_top_level_reads_of_circular_imports_are_rejected",
+        &[
+            (
+                "main.js",
+                "import { y } from './lib.js';\nexport let x = 3;\nconsole.log(y);\n",
+            ),
+            (
+                "lib.js",
+                "import { x } from './main.js';\nexport const y = x + 1;\n",
+            ),
+        ],
+        &[],
+    );
+    assert!(
+        error.contains("cannot access 'x' before initialization (circular import of './main.js')"),
+        "{error}"
+    );
+}
+
+#[test]
+fn missing_exports_are_reported() {
+    let error = common::fail(
+        "modules_missing_exports_are_reported",
+        &[
+            (
+                "main.js",
+                "import { nope } from './lib.js';\nconsole.log(nope);\n",
+            ),
+            ("lib.js", "export const yes = 1;\n"),
+        ],
+        &[],
+    );
+    assert!(
+        error.contains("'./lib.js' does not provide an export named 'nope'"),
+        "{error}"
+    );
+}
+
+#[test]
+fn exported_classes_are_reported() {
+    let error = common::fail(
+        "modules_exported_classes_are_reported",
+        &[
+            (
+                "main.js",
+                "import { Point } from './point.js';\nconsole.log(1);\n",
+            ),
+            ("point.js", "export class Point {}\n"),
+        ],
+        &[],
+    );
+    assert!(error.contains("exported class is not supported"), "{error}");
+}
+
+#[test]
+fn missing_namespace_members_are_reported() {
+    let error = common::fail(
+        "modules_missing_namespace_members_are_reported",
+        &[
+            (
+                "main.js",
+                "import * as lib from './lib.js';\nconsole.log(lib.nope);\n",
+            ),
+            ("lib.js", "export const yes = 1;\n"),
+        ],
+        &[],
+    );
+    assert!(
+        error.contains("main.js: 'lib' has no export 'nope'"),
+        "{error}"
+    );
+    assert!(!error.contains("panicked"), "{error}");
+}
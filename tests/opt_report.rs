@@ -0,0 +1,56 @@
+mod common;
+
+const PROGRAM: &str = "const k = 3;
+const a = new Int32Array(10);
+let s = 0;
+for (let i = 0; i < 10; i++) {
+    s += i % k;
+}
+for (let i = 1; i < 10; i++) {
+    s += i % k;
+    a[i] = i;
+}
+console.log(s + a[1] + a[2]);
+";
+
+fn report(name: &str, args: &[&str]) -> String {
+    common::run(&format!("opt_report_{name}"), &[("main.js", PROGRAM)], args).1
+}
+
+#[test]
+fn human() {
+    let report = report("human", &["--opt-report"]);
+    let lines: Vec<_> = report.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "main.js:5:14: constant-folding: `k` folded to 3",
+            "main.js:8:14: constant-folding: `k` folded to 3",
+            "main.js:1:7: dead-code: `k` removed: never read",
+            "main.js:2:7: slotted-array-read: missed: `a` filled in full: loop body does more than fill the array",
+            "main.js:4:1: running-modulo: `i % 3` replaced by the running `i_modulo`",
+            "main.js:7:1: running-modulo: missed: `i % 3` kept: counter does not start at 0",
+            "main.js:8:10: strength-reduction: `i % k` replaced by the running `i_mod_3`",
+        ],
+        "{report}"
+    );
+}
+
+#[test]
+fn json() {
+    let report = report("json", &["--opt-report", "json"]);
+    let remarks: serde_json::Value = serde_json::from_str(&report).unwrap();
+    let remarks = remarks.as_array().unwrap();
+    assert_eq!(remarks.len(), 7, "{report}");
+    assert_eq!(remarks[4]["pass"], "running-modulo");
+    assert_eq!(remarks[4]["file"], "main.js");
+    assert_eq!(remarks[4]["line"], 4);
+    assert_eq!(remarks[4]["column"], 1);
+    assert_eq!(remarks[4]["applied"], true);
+    assert_eq!(remarks[5]["applied"], false);
+}
+
+#[test]
+fn no_report_by_default() {
+    assert_eq!(report("no_report_by_default", &[]), "");
+}
@@ -0,0 +1,58 @@
+mod common;
+
+const PROGRAM: &str = "
+    const k = 7;
+    const a = new Int32Array(100);
+    const r = Math.floor(Math.random() * 100);
+    for (let i = 0; i < 100; i++) {
+        a[i] = i % k;
+    }
+    console.log(a[r]);
+";
+
+/// The generated C++ for [`PROGRAM`] compiled with `args`.
+fn compile(name: &str, args: &[&str]) -> String {
+    common::compile_modules(
+        &format!("pass_manager_{name}"),
+        &[("main.js", PROGRAM)],
+        args,
+    )
+}
+
+#[test]
+fn default_level() {
+    let output = compile("default_level", &[]);
+    assert!(output.contains("(i==r)"), "{output}");
+}
+
+#[test]
+fn no_optimization() {
+    let output = compile("no_optimization", &["-O0"]);
+    assert!(output.contains("(i%k)"), "{output}");
+    assert!(!output.contains("(i==r)"), "{output}");
+}
+
+#[test]
+fn level_one() {
+    let output = compile("level_one", &["-O1"]);
+    assert!(output.contains("i_modulo"), "{output}");
+    assert!(!output.contains("(i==r)"), "{output}");
+}
+
+#[test]
+fn toggled_passes() {
+    let output = compile(
+        "toggled_passes",
+        &[
+            "-O0",
+            "--enable-pass",
+            "running-modulo",
+            "--enable-pass",
+            "slotted-array-read",
+            "--disable-pass",
+            "slotted-array-read",
+        ],
+    );
+    assert!(output.contains("i_modulo"), "{output}");
+    assert!(!output.contains("(i==r)"), "{output}");
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+const SOURCE: &str = "const n = Number(process.argv[2]);
+function mix(h, x) {
+    let r = h;
+    for (let k = 0; k < 3; k++) {
+        r = (r * 31 + x + k) % 1000003;
+    }
+    return r;
+}
+let h = 7;
+for (let i = 0; i < n; i++) {
+    if (i % 10 != 0) {
+        h = mix(h, i * 2);
+    } else {
+        h = h + 1;
+    }
+}
+for (let j = 0; j < 0; j++) {
+    h += Math.sqrt(n);
+}
+console.log(h);
+";
+
+/// The counts of [`SOURCE`] run with 5000.
+const PROFILE: &str = "loop main.js:10:1 1 5000
+branch main.js:11:5 4500 500
+call main.js:12:13 4500 0
+argument main.js:12:13 0 4500 0
+argument main.js:12:13 1 4500 0
+loop main.js:4:5 4500 13500
+loop main.js:17:1 1 0
+";
+
+fn run(name: &str, args: &[&str]) -> (PathBuf, String) {
+    common::run(
+        &format!("profile_{name}"),
+        &[("main.js", SOURCE), ("main.profile", PROFILE)],
+        args,
+    )
+}
+
+fn compile(name: &str, args: &[&str]) -> String {
+    let dir = run(name, args).0;
+    fs::read_to_string(dir.join("tmp/out.cpp")).unwrap()
+}
+
+#[test]
+fn generated_programs_count_branches_loops_calls_and_arguments() {
+    let cpp = compile(
+        "generated_programs_count_branches_loops_calls_and_arguments",
+        &["--profile-generate"],
+    );
+    assert!(
+        cpp.contains("js_profile_open(\"crushscript.profile\");"),
+        "{cpp}"
+    );
+    assert!(
+        cpp.contains("js_profile_branch(JS_PROFILE_SITE(\"branch\", \"main.js:11:5\"), "),
+        "{cpp}"
+    );
+    assert!(
+        cpp.contains("JS_PROFILE_SITE(\"loop\", \"main.js:10:1\");"),
+        "{cpp}"
+    );
+    assert!(
+        cpp.contains("(JS_PROFILE_SITE(\"call\", \"main.js:12:13\").counts[0]++, mix("),
+        "{cpp}"
+    );
+    assert!(
+        cpp.contains("js_profile_argument(JS_PROFILE_SITE(\"argument\", \"main.js:12:13 1\"), "),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn profiles_hint_branches_and_unroll_long_loops() {
+    let cpp = compile(
+        "profiles_hint_branches_and_unroll_long_loops",
+        &["--profile-use", "main.profile"],
+    );
+    assert!(cpp.contains("if (JS_LIKELY("), "{cpp}");
+    assert!(cpp.contains("for (; (((int64_t)i+3)<n); i++) {"), "{cpp}");
+    assert!(!cpp.contains("k+3"), "{cpp}");
+}
+
+#[test]
+fn int32_arguments_specialize_functions() {
+    let cpp = compile(
+        "int32_arguments_specialize_functions",
+        &["--profile-use", "main.profile"],
+    );
+    assert!(
+        cpp.contains("mix_int32 = [&](int32_t h, int32_t x) -> double {"),
+        "{cpp}"
+    );
+    assert!(cpp.contains("? mix_int32((js_to_int32(h)|0), "), "{cpp}");
+}
+
+#[test]
+fn profile_decisions_are_reported() {
+    let report = run(
+        "profile_decisions_are_reported",
+        &["--profile-use", "main.profile", "--opt-report"],
+    )
+    .1;
+    assert!(
+        report.contains(
+            "main.js:2:1: specialize: `mix` specialized for int32 `h`, `x` as `mix_int32`"
+        ),
+        "{report}"
+    );
+    assert!(
+        report.contains(
+            "main.js:17:1: licm: missed: 1 invariant expression kept: the loop never iterated in the profile"
+        ),
+        "{report}"
+    );
+}
+
+#[test]
+fn generated_profiles_are_used() {
+    let name = "profile_generated_profiles_are_used";
+    // the program writes its profile relative to where it runs, not to its directory
+    let profile = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.profile"));
+    let _ = fs::remove_file(&profile);
+
+    let (dir, _) = common::run(
+        name,
+        &[("main.js", SOURCE)],
+        &["--profile-generate", profile.to_str().unwrap()],
+    );
+    let Some(output) = common::execute(&dir, &["5000"]) else {
+        return;
+    };
+    assert_eq!(output, "704104\n");
+    let counts = fs::read_to_string(&profile).unwrap();
+    assert!(
+        counts.contains("branch main.js:11:5 4500 500\n"),
+        "{counts}"
+    );
+    assert!(
+        counts.contains("argument main.js:12:13 1 4500 0\n"),
+        "{counts}"
+    );
+
+    let cpp = common::compile_modules(
+        name,
+        &[("main.js", SOURCE)],
+        &["--profile-use", profile.to_str().unwrap()],
+    );
+    assert!(cpp.contains("mix_int32"), "{cpp}");
+    assert_eq!(common::execute(&dir, &["5000"]).unwrap(), output);
+}
@@ -0,0 +1,291 @@
+mod common;
+
+/// Whether the pass replaced `i % ...` with a running `i_modulo` variable.
+fn fires(name: &str, source: &str) -> bool {
+    common::compile(&format!("running_modulo_{name}"), source).contains("i_modulo")
+}
+
+#[test]
+fn constant_denominator() {
+    assert!(fires(
+        "constant_denominator",
+        "
+        const n = Number(process.argv[2]);
+        const k = 3;
+        let s = 0;
+        for (let i = 0; i < n; i++) {
+            s += i % k;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn prefix_increment() {
+    assert!(fires(
+        "prefix_increment",
+        "
+        const k = 3;
+        let s = 0;
+        for (let i = 0; i < 10; ++i) {
+            s += i % k;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn add_one_update() {
+    assert!(fires(
+        "add_one_update",
+        "
+        const k = 3;
+        let s = 0;
+        for (let i = 0; i < 10; i += 1) {
+            s += i % k;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn denominator_reassigned_before_loop() {
+    assert!(fires(
+        "denominator_reassigned_before_loop",
+        "
+        let k = 4;
+        k = k + 1;
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += i % k;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn counter_starts_elsewhere() {
+    assert!(!fires(
+        "counter_starts_elsewhere",
+        "
+        const k = 3;
+        let s = 0;
+        for (let i = 5; i < 10; i++) {
+            s += i % k;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn counter_steps_by_two() {
+    assert!(!fires(
+        "counter_steps_by_two",
+        "
+        const k = 3;
+        let s = 0;
+        for (let i = 0; i < 10; i += 2) {
+            s += i % k;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn counter_written_in_body() {
+    assert!(!fires(
+        "counter_written_in_body",
+        "
+        const k = 3;
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += i % k;
+            i++;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn denominator_written_in_body() {
+    assert!(!fires(
+        "denominator_written_in_body",
+        "
+        let k = 3;
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += i % k;
+            k = 4;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn unknown_denominator() {
+    assert!(!fires(
+        "unknown_denominator",
+        "
+        const n = Number(process.argv[2]);
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += i % n;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn negative_denominator() {
+    assert!(!fires(
+        "negative_denominator",
+        "
+        const k = -3;
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += i % k;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn fractional_denominator() {
+    assert!(!fires(
+        "fractional_denominator",
+        "
+        const k = 2.5;
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += i % k;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn continue_in_body() {
+    assert!(!fires(
+        "continue_in_body",
+        "
+        const k = 3;
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            if (s > 5) continue;
+            s += i % k;
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn shadowed_counter() {
+    assert!(!fires(
+        "shadowed_counter",
+        "
+        const k = 3;
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            {
+                let i = 7;
+                s += i % k;
+            }
+        }
+        console.log(s);
+        "
+    ));
+}
+
+#[test]
+fn fresh_name_avoids_user_variable() {
+    let cpp = common::compile(
+        "running_modulo_fresh_name_avoids_user_variable",
+        "
+        const k = 3;
+        let i_modulo = 1;
+        for (let i = 0; i < 10; i++) {
+            i_modulo += i % k;
+        }
+        console.log(i_modulo);
+        ",
+    );
+    assert!(cpp.contains("i_modulo_2 = 0"));
+    assert!(cpp.contains("i_modulo += i_modulo_2"));
+}
+
+#[test]
+fn same_names_in_rejected_loop() {
+    let cpp = common::compile(
+        "running_modulo_same_names_in_rejected_loop",
+        "
+        const k = 3;
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += i % k;
+        }
+        for (let i = 1; i < 10; i++) {
+            s += i % k;
+        }
+        console.log(s);
+        ",
+    );
+    assert!(cpp.contains("js_umod(i, 3, "), "{cpp}");
+}
+
+#[test]
+fn synthesized_references_are_linked() {
+    // the reset compares against `k`, which codegen prefixes with the module it belongs to
+    let output = common::compile_modules(
+        "running_modulo_synthesized_references_are_linked",
+        &[
+            (
+                "main.js",
+                "
+                import { s } from './lib.js';
+                console.log(s);
+                ",
+            ),
+            (
+                "lib.js",
+                "
+                const k = 3;
+                export let s = 0;
+                for (let i = 0; i < 10; i++) {
+                    s += i % k;
+                }
+                ",
+            ),
+        ],
+        // folding would turn `k` into a literal
+        &["--disable-pass", "constant-folding"],
+    );
+    assert!(output.contains("(i_modulo>=m1_k)"), "{output}");
+}
+
+#[test]
+fn folded_denominator() {
+    let cpp = common::compile(
+        "running_modulo_folded_denominator",
+        "
+        const k = 3 * 2;
+        let s = 0;
+        for (let i = 0; i < 10; i++) {
+            s += i % k;
+        }
+        console.log(s);
+        ",
+    );
+    assert!(cpp.contains("(i_modulo>=6)"), "{cpp}");
+}
@@ -0,0 +1,119 @@
+mod common;
+
+#[test]
+fn object_fields_become_locals() {
+    let cpp = common::compile(
+        "scalar_replacement_object_fields_become_locals",
+        "
+        const n = Number(process.argv[2]);
+        let s = 0;
+        for (let i = 0; i < n; i++) {
+            const p = { x: i, y: n - i };
+            p.x = p.x + 1;
+            s += p.x * p.y;
+        }
+        console.log(s);
+        ",
+    );
+    assert!(cpp.contains("p_x = (p_x+1);"), "{cpp}");
+    assert!(cpp.contains("s += js_int32_mul(p_x, p_y);"), "{cpp}");
+    assert!(!cpp.contains("struct"), "{cpp}");
+}
+
+#[test]
+fn array_elements_and_length() {
+    let cpp = common::compile(
+        "scalar_replacement_array_elements_and_length",
+        "
+        const n = Number(process.argv[2]);
+        const v = [n, n * 2, n * 3];
+        console.log(v[0] + v[2] * v.length);
+        ",
+    );
+    assert!(cpp.contains("v_0 = n"), "{cpp}");
+    assert!(cpp.contains("(v_0+(v_2*3))"), "{cpp}");
+    assert!(!cpp.contains("v_1"), "{cpp}");
+}
+
+#[test]
+fn escaping_literals_are_reported() {
+    let report = common::report(
+        "scalar_replacement_escaping_literals_are_reported",
+        "const n = Number(process.argv[2]);
+function pick(k) {
+    const v = [k, 2];
+    return v[k % 2];
+}
+function keep(k) {
+    const p = { k: k };
+    return p;
+}
+const w = [n];
+console.log(w[0]);
+",
+        &[],
+    );
+    assert!(
+        report.contains("main.js:3:11: scalar-replacement: missed: `v` kept: it is indexed with something other than a constant in bounds"),
+        "{report}"
+    );
+    assert!(
+        report.contains("main.js:7:11: scalar-replacement: missed: `p` kept: it escapes"),
+        "{report}"
+    );
+    assert!(
+        report.contains("main.js:10:7: scalar-replacement: `w` replaced by 1 local"),
+        "{report}"
+    );
+}
+
+#[test]
+fn small_typed_arrays_live_on_the_stack() {
+    let cpp = common::compile(
+        "scalar_replacement_small_typed_arrays_live_on_the_stack",
+        "
+        const n = Number(process.argv[2]);
+        const counts = new Int32Array(10);
+        const big = new Float64Array(100000);
+        for (let i = 0; i < n; i++) {
+            counts[i % 10] += 1;
+            big[i % 100000] = i;
+        }
+        console.log(counts[3] + counts.length + big[1]);
+        ",
+    );
+    assert!(
+        cpp.contains("js_stack_array<int32_t, 10> counts_storage = {{10}};\nint32_t* counts = counts_storage.elements;"),
+        "{cpp}"
+    );
+    assert!(!cpp.contains("big_storage"), "{cpp}");
+}
+
+#[test]
+fn results_match_js() {
+    let (dir, _) = common::run(
+        "scalar_replacement_results_match_js",
+        &[(
+            "main.js",
+            "
+            const n = Number(process.argv[2]);
+            let s = 0;
+            for (let i = 0; i < n; i++) {
+                const q = { x: i, y: n - i };
+                q.x = q.x * 2 + 1;
+                s += q.x - q.y;
+            }
+            console.log(s);
+            ",
+        )],
+        &[],
+    );
+    let cpp = std::fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+    assert!(cpp.contains("q_x = ((q_x*2)+1);"), "{cpp}");
+    // what node prints for each `n`
+    for (n, expected) in [("10", "45\n"), ("7", "21\n")] {
+        if let Some(output) = common::execute(&dir, &[n]) {
+            assert_eq!(output, expected, "{n}");
+        }
+    }
+}
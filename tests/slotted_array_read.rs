@@ -0,0 +1,200 @@
+mod common;
+
+/// Whether the pass shrank the fill loop to the iteration `i == ...`.
+fn fires(name: &str, source: &str) -> bool {
+    common::compile(&format!("slotted_array_read_{name}"), source).contains("(i==")
+}
+
+#[test]
+fn single_read() {
+    assert!(fires(
+        "single_read",
+        "
+        const n = Number(process.argv[2]);
+        const m = Number(process.argv[3]);
+        const a = new Int32Array(m);
+        const r = Math.floor(Math.random() * m);
+        for (let i = 0; i < m; i++) {
+            a[i] = i % n;
+        }
+        let sum = 0;
+        for (let j = 0; j < m; j++) {
+            sum += a[r];
+        }
+        console.log(sum);
+        "
+    ));
+}
+
+#[test]
+fn offset_indices() {
+    let output = common::compile(
+        "slotted_array_read_offset_indices",
+        "
+        const m = Number(process.argv[2]);
+        const a = new Float64Array(m + 1);
+        const r = Math.floor(m / 3);
+        for (let i = 0; i < m; i++) {
+            const t = i * 3;
+            a[i + 1] = t / 2;
+        }
+        console.log(a[r - 1] + a[r - 1]);
+        ",
+    );
+    assert!(output.contains("i = (r-2)"), "{output}");
+}
+
+#[test]
+fn constant_read() {
+    assert!(fires(
+        "constant_read",
+        "
+        const a = new Int32Array(100);
+        for (let i = 0; i < 100; i++) {
+            a[i] = i * i;
+        }
+        console.log(a[7]);
+        "
+    ));
+}
+
+#[test]
+fn second_write() {
+    assert!(!fires(
+        "second_write",
+        "
+        const a = new Int32Array(100);
+        const r = Math.floor(Math.random() * 100);
+        for (let i = 0; i < 100; i++) {
+            a[i] = i;
+        }
+        a[0] = 5;
+        console.log(a[r]);
+        "
+    ));
+}
+
+#[test]
+fn aliased_array() {
+    assert!(!fires(
+        "aliased_array",
+        "
+        const a = new Int32Array(100);
+        const b = a;
+        const r = Math.floor(Math.random() * 100);
+        for (let i = 0; i < 100; i++) {
+            a[i] = i;
+        }
+        console.log(b[r + 1]);
+        "
+    ));
+}
+
+#[test]
+fn different_reads() {
+    assert!(!fires(
+        "different_reads",
+        "
+        const a = new Int32Array(100);
+        const r = Math.floor(Math.random() * 100);
+        for (let i = 0; i < 100; i++) {
+            a[i] = i;
+        }
+        console.log(a[r] + a[r + 1]);
+        "
+    ));
+}
+
+#[test]
+fn read_in_fill_loop() {
+    assert!(!fires(
+        "read_in_fill_loop",
+        "
+        const a = new Int32Array(100);
+        for (let i = 1; i < 100; i++) {
+            a[i] = a[i - 1] + 1;
+        }
+        console.log(a[50]);
+        "
+    ));
+}
+
+#[test]
+fn nested_fill_loop() {
+    assert!(!fires(
+        "nested_fill_loop",
+        "
+        const a = new Int32Array(100);
+        const r = Math.floor(Math.random() * 100);
+        for (let k = 0; k < 3; k++) {
+            for (let i = 0; i < 100; i++) {
+                a[i] = i + k;
+            }
+        }
+        console.log(a[r]);
+        "
+    ));
+}
+
+#[test]
+fn read_index_reassigned() {
+    assert!(!fires(
+        "read_index_reassigned",
+        "
+        const a = new Int32Array(100);
+        let r = Math.floor(Math.random() * 100);
+        for (let i = 0; i < 100; i++) {
+            a[i] = i;
+        }
+        r = Math.floor(Math.random() * 50);
+        console.log(a[r]);
+        "
+    ));
+}
+
+#[test]
+fn fractional_read_index() {
+    assert!(!fires(
+        "fractional_read_index",
+        "
+        const a = new Int32Array(100);
+        const r = Math.random() * 100;
+        for (let i = 0; i < 100; i++) {
+            a[i] = i;
+        }
+        console.log(a[r]);
+        "
+    ));
+}
+
+#[test]
+fn impure_body() {
+    assert!(!fires(
+        "impure_body",
+        "
+        const a = new Int32Array(100);
+        const r = Math.floor(Math.random() * 100);
+        let s = 0;
+        for (let i = 0; i < 100; i++) {
+            a[i] = i;
+            s += i;
+        }
+        console.log(a[r] + s);
+        "
+    ));
+}
+
+#[test]
+fn random_stored_value() {
+    assert!(!fires(
+        "random_stored_value",
+        "
+        const a = new Float64Array(100);
+        const r = Math.floor(Math.random() * 100);
+        for (let i = 0; i < 100; i++) {
+            a[i] = Math.random();
+        }
+        console.log(a[r]);
+        "
+    ));
+}
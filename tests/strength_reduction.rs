@@ -0,0 +1,140 @@
+mod common;
+
+#[test]
+fn products_run_with_the_counter() {
+    let cpp = common::compile(
+        "strength_reduction_products_run_with_the_counter",
+        "
+        const n = Number(process.argv[2]);
+        const a = new Float64Array(3 * n + 3);
+        for (let i = 1; i < n; i += 2) {
+            a[i * 3] = i;
+        }
+        console.log(a[3]);
+        ",
+    );
+    assert!(cpp.contains("double i_times_3 = (i*3);"), "{cpp}");
+    assert!(cpp.contains("i_times_3 = (i_times_3+6);"), "{cpp}");
+    assert!(!cpp.contains("(i*3)]"), "{cpp}");
+}
+
+#[test]
+fn countdown_products() {
+    let cpp = common::compile(
+        "strength_reduction_countdown_products",
+        "
+        let s = 0;
+        for (let k = 10; k > 0; k = k - 1) {
+            s += k * 6;
+        }
+        console.log(s);
+        ",
+    );
+    assert!(cpp.contains("k_times_6 = (k_times_6-6);"), "{cpp}");
+}
+
+#[test]
+fn quotient_is_carried_by_the_remainder() {
+    let cpp = common::compile(
+        "strength_reduction_quotient_is_carried_by_the_remainder",
+        "
+        const n = Number(process.argv[2]);
+        let s = 0;
+        for (let i = 0; i < n; i++) {
+            s += Math.floor(i / 7) + ((i / 3) | 0);
+        }
+        console.log(s);
+        ",
+    );
+    assert!(cpp.contains("s += (i_div_7+(i_div_3));"), "{cpp}");
+    assert!(
+        cpp.contains("if ((i_mod_7>=7)) {\n        i_mod_7 = (i_mod_7-7);\n        i_div_7 = (i_div_7+1);\n    }"),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn scaled_remainder_with_a_const_denominator() {
+    let cpp = common::compile(
+        "strength_reduction_scaled_remainder_with_a_const_denominator",
+        "
+        const n = Number(process.argv[2]);
+        const k = 7;
+        let s = 0;
+        for (let i = 2; i < n; i += 3) {
+            s += (i * 4) % k + i % 10;
+        }
+        console.log(s);
+        ",
+    );
+    assert!(
+        cpp.contains("double i_times_4_mod_7 = js_umod(((i*4)), 7, "),
+        "{cpp}"
+    );
+    assert!(
+        cpp.contains("i_times_4_mod_7 = (i_times_4_mod_7+5);"),
+        "{cpp}"
+    );
+    assert!(cpp.contains("double i_mod_10 = js_umod(i, 10, "), "{cpp}");
+    assert!(cpp.contains("s += (i_times_4_mod_7+i_mod_10);"), "{cpp}");
+}
+
+#[test]
+fn variable_operands_step_with_the_loop() {
+    let cpp = common::compile(
+        "strength_reduction_variable_operands_step_with_the_loop",
+        "
+        const n = Number(process.argv[2]);
+        let s = 0;
+        for (let j = 0; j < n; j++) {
+            s += Math.floor(j / n) + j % n;
+        }
+        console.log(s);
+        ",
+    );
+    assert!(cpp.contains("s += (j_div_n+j_mod_n);"), "{cpp}");
+    assert!(cpp.contains("j_mod_n = (j_mod_n-n);"), "{cpp}");
+}
+
+#[test]
+fn large_products_are_reported() {
+    let report = common::report(
+        "strength_reduction_large_products_are_reported",
+        "const n = Number(process.argv[2]);
+let s = 0;
+for (let j = 0; j < n; j++) {
+    s += j * n;
+}
+console.log(s);
+",
+        &[],
+    );
+    assert!(
+        report.contains(
+            "main.js:4:10: strength-reduction: missed: `j * n` kept: `j * n` may exceed 2^52"
+        ),
+        "{report}"
+    );
+}
+
+#[test]
+fn continue_keeps_the_expressions() {
+    let report = common::report(
+        "strength_reduction_continue_keeps_the_expressions",
+        "const n = Number(process.argv[2]);
+let s = 0;
+for (let i = 0; i < n; i++) {
+    if (i == 3) continue;
+    s += i * 3;
+}
+console.log(s);
+",
+        &[],
+    );
+    assert!(
+        report.contains(
+            "main.js:5:10: strength-reduction: missed: `i * 3` kept: the loop body contains continue"
+        ),
+        "{report}"
+    );
+}
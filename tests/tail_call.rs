@@ -0,0 +1,143 @@
+mod common;
+
+/// Compiles `source`, then builds and runs it with `input` when there is a C++ compiler.
+fn execute(name: &str, source: &str, args: &[&str], input: &str) -> Option<String> {
+    let (dir, _) = common::run(&format!("tail_call_{name}"), &[("main.js", source)], args);
+    common::execute(&dir, &[input])
+}
+
+#[test]
+fn self_tail_calls_become_loops() {
+    let cpp = common::compile(
+        "tail_call_self_tail_calls_become_loops",
+        "
+        function gcd(a, b) {
+            if (b == 0) return a;
+            return gcd(b, a % b);
+        }
+        console.log(gcd(Number(process.argv[2]), 84));
+        ",
+    );
+    assert!(cpp.contains("while (true) {"), "{cpp}");
+    // `a % b` is -0 for a negative `a`, so the parameters are doubles
+    assert!(
+        cpp.contains(
+            "double a_next = b;\n                b = fmod(a, b);\n                a = a_next;"
+        ),
+        "{cpp}"
+    );
+    assert!(!cpp.contains("return gcd("), "{cpp}");
+}
+
+#[test]
+fn statements_after_a_return_move_into_the_else() {
+    let cpp = common::compile(
+        "tail_call_statements_after_a_return_move_into_the_else",
+        "
+        let count = 0;
+        function down(k) {
+            if (k == 0) return;
+            count += 1;
+            down(k - 1);
+        }
+        down(Number(process.argv[2]));
+        console.log(count);
+        ",
+    );
+    assert!(
+        cpp.contains(
+            "if ((k==0)) {\n            return;\n        } else {\n            count += 1;"
+        ),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn non_tail_calls_are_reported() {
+    let report = common::report(
+        "tail_call_non_tail_calls_are_reported",
+        "function fib(k) {
+    if (k < 2) return k;
+    return fib(k - 1) + fib(k - 2);
+}
+function fact(k) {
+    if (k <= 1) return 1;
+    return k * fact(k - 1);
+}
+console.log(fib(20) + fact(10));
+",
+        &[],
+    );
+    assert!(
+        report.contains("main.js:1:1: tail-call: missed: `fib` kept recursive: a call to itself is not in tail position"),
+        "{report}"
+    );
+    assert!(
+        report.contains("main.js:5:1: tail-call: missed: `fact` kept recursive: a call to itself is an operand of `+` or `*`, which --tail-call-accumulator rewrites"),
+        "{report}"
+    );
+}
+
+#[test]
+fn accumulator_carries_the_other_operand() {
+    let source = "function fact(k) {
+    if (k <= 1) {
+        return 1;
+    } else {
+        return k * fact(k - 1);
+    }
+}
+console.log(fact(Number(process.argv[2])));
+";
+    let report = common::report(
+        "tail_call_accumulator_carries_the_other_operand",
+        source,
+        &["--tail-call-accumulator"],
+    );
+    assert!(
+        report.contains(
+            "main.js:1:1: tail-call: `fact` turned into a loop, accumulating `*` in `fact_acc`"
+        ),
+        "{report}"
+    );
+    let cpp = common::compile("tail_call_accumulator_carries_the_other_operand", source);
+    assert!(!cpp.contains("fact_acc"), "{cpp}");
+}
+
+#[test]
+fn deep_recursion_runs_in_constant_stack() {
+    let output = execute(
+        "deep_recursion_runs_in_constant_stack",
+        "
+        function sum(k, acc) {
+            if (k == 0) return acc;
+            return sum(k - 1, acc + k);
+        }
+        console.log(sum(Number(process.argv[2]), 0));
+        ",
+        &[],
+        "100000000",
+    );
+    if let Some(output) = output {
+        assert_eq!(output, "5000000050000000\n");
+    }
+}
+
+#[test]
+fn deep_linear_recursion_with_an_accumulator() {
+    let output = execute(
+        "deep_linear_recursion_with_an_accumulator",
+        "
+        function tri(k) {
+            if (k == 0) return 0;
+            return k + tri(k - 1);
+        }
+        console.log(tri(Number(process.argv[2])));
+        ",
+        &["--tail-call-accumulator"],
+        "100000000",
+    );
+    if let Some(output) = output {
+        assert_eq!(output, "5000000050000000\n");
+    }
+}
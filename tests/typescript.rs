@@ -0,0 +1,115 @@
+mod common;
+
+const SOURCE: &str = "interface Point { x: number; y: number }
+function dist(p: Point, scale: number): number {
+    return (p.x * p.x + p.y * p.y) * scale;
+}
+const n: number = Number(process.argv[2]);
+let count: number = 0;
+let small: number = 3;
+small = small & 7;
+let total = 0.5;
+let ok: boolean = n > 2;
+const name: string = \"pts\";
+const a: Int32Array = new Int32Array(n);
+for (let i: number = 0; i < n; i++) {
+    a[i] = i * 2;
+    count += a[i];
+    const p: Point = { x: i, y: 1 };
+    total += dist(p, 0.5);
+}
+console.log(count);
+console.log(small);
+console.log(total);
+console.log(ok);
+console.log(name);
+";
+
+#[test]
+fn annotations_pick_the_cpp_types() {
+    let cpp = common::compile_modules(
+        "typescript_annotations_pick_the_cpp_types",
+        &[("main.ts", SOURCE)],
+        &["-O0"],
+    );
+    assert!(
+        cpp.contains("struct Point {\n    double x;\n    double y;\n};"),
+        "{cpp}"
+    );
+    assert!(
+        cpp.contains("std::function<double(Point, double)> dist;"),
+        "{cpp}"
+    );
+    // `count += a[i]` may leave the int32 range
+    assert!(cpp.contains("double count = 0;"), "{cpp}");
+    assert!(cpp.contains("int32_t small = 3;"), "{cpp}");
+    assert!(cpp.contains("bool ok = (n>2);"), "{cpp}");
+    assert!(cpp.contains("const char* name = \"pts\";"), "{cpp}");
+    assert!(
+        cpp.contains("int32_t* a = js_constructor_Int32Array(n);"),
+        "{cpp}"
+    );
+}
+
+#[test]
+fn annotated_programs_run() {
+    let (dir, _) = common::run(
+        "typescript_annotated_programs_run",
+        &[("main.ts", SOURCE)],
+        &[],
+    );
+    if let Some(output) = common::execute(&dir, &["4"]) {
+        assert_eq!(output, "12\n3\n9.5\ntrue\npts\n");
+    }
+}
+
+#[test]
+fn annotations_must_accept_the_assigned_values() {
+    let error = common::fail(
+        "typescript_annotations_must_accept_the_assigned_values",
+        &[(
+            "main.ts",
+            "let flag: boolean = true;\nflag = 3;\nconsole.log(flag);\n",
+        )],
+        &[],
+    );
+    assert!(
+        error.contains("'flag' is declared as boolean but assigned a int32"),
+        "{error}"
+    );
+}
+
+#[test]
+fn negating_an_int32_that_may_be_zero_makes_a_double() {
+    let source = "let x: number = -0;
+console.log(x);
+console.log(1 / x);
+let y: number = 7;
+console.log(-y);
+console.log(0 / 0);
+";
+    let (dir, _) = common::run(
+        "typescript_negating_an_int32_that_may_be_zero_makes_a_double",
+        &[("main.ts", source)],
+        &["-O0"],
+    );
+    let cpp = std::fs::read_to_string(dir.join("tmp/out.cpp")).unwrap();
+    assert!(cpp.contains("double x = (-(double)0);"), "{cpp}");
+    assert!(cpp.contains("int32_t y = 7;"), "{cpp}");
+    if let Some(output) = common::execute(&dir, &[]) {
+        assert_eq!(output, "-0\n-Infinity\n-7\nNaN\n");
+    }
+}
+
+#[test]
+fn enums_are_reported() {
+    let error = common::fail(
+        "typescript_enums_are_reported",
+        &[(
+            "main.ts",
+            "enum Color { Red, Green }\nconsole.log(Color.Green);\n",
+        )],
+        &[],
+    );
+    assert!(error.contains("enum 'Color' is not supported"), "{error}");
+}